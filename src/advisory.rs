@@ -0,0 +1,1463 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    path::PathBuf,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    db::{try_utc_from_str, utc_to_str},
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    package::PackageFormat,
+    sbom::get_sboms_from_db,
+    version::compare,
+    waiver::active_waivers,
+};
+
+/// File format of vulnerability advisories
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum AdvisoryFormat {
+    /// OSV json, a single advisory or an array of advisories (Debian, Ubuntu, Alpine, AlmaLinux, Rocky Linux, ...)
+    Osv,
+    /// json of the Debian security tracker
+    Debian,
+    /// Alpine secdb json
+    Alpine,
+    /// json of the CISA known exploited vulnerabilities catalog, flags CVEs as exploited
+    Kev,
+}
+
+impl Display for AdvisoryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self {
+            AdvisoryFormat::Osv => "osv",
+            AdvisoryFormat::Debian => "debian",
+            AdvisoryFormat::Alpine => "alpine",
+            AdvisoryFormat::Kev => "kev",
+        };
+        write!(f, "{format}")
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{severity}")
+    }
+}
+
+impl Severity {
+    fn from_db(s: &str) -> Severity {
+        Severity::from_word(s).unwrap_or_default()
+    }
+
+    /// severity or urgency of the distributions, e.g. `important` of Red Hat or `low**` of Debian
+    fn from_word(s: &str) -> Option<Severity> {
+        match s.trim_end_matches('*').to_lowercase().as_str() {
+            "critical" => Some(Severity::Critical),
+            "high" | "important" => Some(Severity::High),
+            "medium" | "moderate" => Some(Severity::Medium),
+            "low" | "negligible" | "unimportant" => Some(Severity::Low),
+            _ => None,
+        }
+    }
+
+    /// severity of a CVSS v3 vector
+    fn from_cvss3(vector: &str) -> Option<Severity> {
+        cvss3_score(vector).map(Severity::from_score)
+    }
+
+    /// qualitative rating of a CVSS base score
+    fn from_score(score: f64) -> Severity {
+        match score {
+            s if s >= 9.0 => Severity::Critical,
+            s if s >= 7.0 => Severity::High,
+            s if s >= 4.0 => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+/// base score of a CVSS v3 vector, e.g. `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+fn cvss3_score(vector: &str) -> Option<f64> {
+    let metrics: BTreeMap<&str, &str> = vector
+        .split('/')
+        .filter_map(|m| m.split_once(':'))
+        .collect();
+    let changed = *metrics.get("S")? == "C";
+    let av = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        _ => 0.2,
+    };
+    let ac = match *metrics.get("AC")? {
+        "L" => 0.77,
+        _ => 0.44,
+    };
+    let pr = match (*metrics.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        (_, false) => 0.27,
+        (_, true) => 0.5,
+    };
+    let ui = match *metrics.get("UI")? {
+        "N" => 0.85,
+        _ => 0.62,
+    };
+    let cia = |m: &str| match metrics.get(m) {
+        Some(&"H") => Some(0.56),
+        Some(&"L") => Some(0.22),
+        Some(_) => Some(0.0),
+        None => None,
+    };
+    let iss: f64 = 1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?);
+    let impact = match changed {
+        true => 7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15),
+        false => 6.42 * iss,
+    };
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let score = match (impact <= 0.0, changed) {
+        (true, _) => 0.0,
+        (false, true) => (1.08 * (impact + exploitability)).min(10.0),
+        (false, false) => (impact + exploitability).min(10.0),
+    };
+    Some((score * 10.0).ceil() / 10.0)
+}
+
+/// Version range of a package affected by an advisory
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Affected {
+    /// format the advisory was imported from
+    pub source: AdvisoryFormat,
+    pub format: PackageFormat,
+    /// os-release id, e.g. `debian`, empty for all distributions
+    pub distro: String,
+    /// version or codename of the distribution, e.g. `12` or `bookworm`, empty for all releases
+    pub release: String,
+    /// binary or source package
+    pub name: String,
+    /// first affected version, empty for all versions before `fixed`
+    pub introduced: String,
+    pub fixed: Option<String>,
+    pub last_affected: Option<String>,
+}
+
+impl Affected {
+    /// whether `version` is in the range
+    fn contains(&self, version: &str) -> bool {
+        if !self.introduced.is_empty()
+            && compare(self.format, version, &self.introduced) == Ordering::Less
+        {
+            return false;
+        }
+        match (&self.fixed, &self.last_affected) {
+            (Some(fixed), _) => compare(self.format, version, fixed) == Ordering::Less,
+            (None, Some(last)) => compare(self.format, version, last) != Ordering::Greater,
+            (None, None) => true,
+        }
+    }
+
+    /// whether the range applies to a host with `facts`, unknown facts match all distributions
+    fn applies_to(&self, facts: &HashMap<String, String>) -> bool {
+        if let Some(os) = facts.get("os") {
+            if !self.distro.is_empty() && !self.distro.eq_ignore_ascii_case(os) {
+                return false;
+            }
+        }
+        let version = facts.get("os_version");
+        let codename = facts.get("os_codename");
+        if self.release.is_empty() || (version.is_none() && codename.is_none()) {
+            return true;
+        }
+        codename.is_some_and(|c| c.eq_ignore_ascii_case(&self.release))
+            || version
+                .is_some_and(|v| *v == self.release || v.starts_with(&format!("{}.", self.release)))
+    }
+}
+
+/// Vulnerability advisory, e.g. a CVE
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Advisory {
+    pub id: String,
+    /// other ids of the advisory, e.g. the CVE of a distribution advisory
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// highest CVSS v3 base score
+    #[serde(default)]
+    pub cvss: Option<f64>,
+    pub published: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub affected: Vec<Affected>,
+}
+
+impl From<SqliteRow> for Advisory {
+    fn from(s: SqliteRow) -> Self {
+        Advisory {
+            id: s.get::<String, _>("id"),
+            aliases: serde_json::from_str(&s.get::<String, _>("aliases")).unwrap_or_default(),
+            summary: s.get::<String, _>("summary"),
+            severity: Severity::from_db(&s.get::<String, _>("severity")),
+            cvss: s.get::<Option<f64>, _>("cvss"),
+            published: s
+                .get::<Option<String>, _>("published")
+                .and_then(|ts| try_utc_from_str(&ts).ok()),
+            modified: s
+                .get::<Option<String>, _>("modified")
+                .and_then(|ts| try_utc_from_str(&ts).ok()),
+            affected: vec![],
+        }
+    }
+}
+
+impl Advisory {
+    /// the CVE of the advisory, the id if it has none
+    pub fn cve(&self) -> &str {
+        std::iter::once(&self.id)
+            .chain(&self.aliases)
+            .find(|id| id.starts_with("CVE-"))
+            .unwrap_or(&self.id)
+    }
+}
+
+fn affected_from_row(s: SqliteRow) -> Affected {
+    Affected {
+        source: serde_json::from_value(s.get::<String, _>("source").into())
+            .unwrap_or(AdvisoryFormat::Osv),
+        format: PackageFormat::from_db(&s.get::<String, _>("format"))
+            .unwrap_or(PackageFormat::Dpkg),
+        distro: s.get::<String, _>("distro"),
+        release: s.get::<String, _>("release"),
+        name: s.get::<String, _>("name"),
+        introduced: s.get::<String, _>("introduced"),
+        fixed: s.get::<Option<String>, _>("fixed"),
+        last_affected: s.get::<Option<String>, _>("last_affected"),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OsvFile {
+    Many(Vec<OsvEntry>),
+    One(Box<OsvEntry>),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OsvEntry {
+    id: String,
+    aliases: Vec<String>,
+    /// used by Ubuntu instead of aliases
+    upstream: Vec<String>,
+    summary: String,
+    details: String,
+    published: Option<DateTime<Utc>>,
+    modified: Option<DateTime<Utc>>,
+    severity: Vec<OsvSeverity>,
+    affected: Vec<OsvAffected>,
+    database_specific: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OsvSeverity {
+    #[serde(rename = "type")]
+    kind: String,
+    score: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OsvAffected {
+    package: OsvPackage,
+    ranges: Vec<OsvRange>,
+    versions: Vec<String>,
+    severity: Vec<OsvSeverity>,
+    ecosystem_specific: Value,
+    database_specific: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    kind: String,
+    events: Vec<BTreeMap<String, String>>,
+}
+
+/// package format, distribution and release of an OSV ecosystem like `Ubuntu:22.04:LTS`
+fn osv_ecosystem(ecosystem: &str) -> Option<(PackageFormat, &'static str, String)> {
+    let mut parts = ecosystem.split(':');
+    let (format, distro) = match parts.next()? {
+        "Debian" => (PackageFormat::Dpkg, "debian"),
+        "Ubuntu" => (PackageFormat::Dpkg, "ubuntu"),
+        "Alpine" => (PackageFormat::Apk, "alpine"),
+        "Wolfi" => (PackageFormat::Apk, "wolfi"),
+        "Chainguard" => (PackageFormat::Apk, "chainguard"),
+        "Red Hat" => (PackageFormat::Rpm, "rhel"),
+        "AlmaLinux" => (PackageFormat::Rpm, "almalinux"),
+        "Rocky Linux" => (PackageFormat::Rpm, "rocky"),
+        "openEuler" => (PackageFormat::Rpm, "openeuler"),
+        "Mageia" => (PackageFormat::Rpm, "mageia"),
+        "SUSE" => (PackageFormat::Rpm, "sles"),
+        "openSUSE" => (PackageFormat::Rpm, "opensuse-leap"),
+        // language ecosystems apply to all distributions
+        "npm" => (PackageFormat::Npm, ""),
+        "PyPI" => (PackageFormat::Pypi, ""),
+        "Maven" => (PackageFormat::Maven, ""),
+        "Go" => (PackageFormat::Golang, ""),
+        "crates.io" => (PackageFormat::Cargo, ""),
+        "RubyGems" => (PackageFormat::Gem, ""),
+        "NuGet" => (PackageFormat::Nuget, ""),
+        _ => return None,
+    };
+    let release = parts
+        .map(|p| p.trim_start_matches('v'))
+        .find(|p| p.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or_default();
+    Some((format, distro, release.to_string()))
+}
+
+fn severity_of(value: &Value) -> Option<Severity> {
+    ["severity", "urgency"]
+        .iter()
+        .filter_map(|key| value.get(key)?.as_str())
+        .find_map(Severity::from_word)
+}
+
+fn parse_osv(input: &str) -> Result<Vec<Advisory>, String> {
+    let entries = match serde_json::from_str(input).map_err(|e| e.to_string())? {
+        OsvFile::Many(entries) => entries,
+        OsvFile::One(entry) => vec![*entry],
+    };
+    let mut advisories = vec![];
+    for entry in entries {
+        if entry.id.is_empty() {
+            return Err("advisory without id".into());
+        }
+        let mut severities: Vec<Severity> =
+            severity_of(&entry.database_specific).into_iter().collect();
+        let mut scores: Vec<f64> = vec![];
+        let mut affected = vec![];
+        for a in &entry.affected {
+            let Some((format, distro, release)) = osv_ecosystem(&a.package.ecosystem) else {
+                continue;
+            };
+            severities.extend(severity_of(&a.ecosystem_specific));
+            severities.extend(severity_of(&a.database_specific));
+            severities.extend(
+                a.severity
+                    .iter()
+                    .filter_map(|s| Severity::from_word(&s.score)),
+            );
+            scores.extend(
+                a.severity
+                    .iter()
+                    .filter(|s| s.kind == "CVSS_V3")
+                    .filter_map(|s| cvss3_score(&s.score)),
+            );
+            let range = |introduced: Option<String>, fixed, last_affected| Affected {
+                source: AdvisoryFormat::Osv,
+                format,
+                distro: distro.to_string(),
+                release: release.clone(),
+                name: format.normalize_name(&a.package.name),
+                introduced: introduced.filter(|i| i != "0").unwrap_or_default(),
+                fixed,
+                last_affected,
+            };
+            let ranges: Vec<&OsvRange> = a
+                .ranges
+                .iter()
+                .filter(|r| r.kind == "ECOSYSTEM" || r.kind == "SEMVER")
+                .collect();
+            for r in &ranges {
+                let mut introduced = None;
+                for event in &r.events {
+                    if let Some(v) = event.get("introduced") {
+                        if let Some(open) = introduced.replace(v.clone()) {
+                            affected.push(range(Some(open), None, None));
+                        }
+                    } else if let Some(v) = event.get("fixed") {
+                        affected.push(range(introduced.take(), Some(v.clone()), None));
+                    } else if let Some(v) = event.get("last_affected") {
+                        affected.push(range(introduced.take(), None, Some(v.clone())));
+                    }
+                }
+                if let Some(open) = introduced {
+                    affected.push(range(Some(open), None, None));
+                }
+            }
+            // explicitly listed versions only
+            if ranges.is_empty() {
+                for v in &a.versions {
+                    affected.push(range(Some(v.clone()), None, Some(v.clone())));
+                }
+            }
+        }
+        severities.extend(entry.severity.iter().filter_map(|s| match s.kind.as_str() {
+            "CVSS_V3" => Severity::from_cvss3(&s.score),
+            _ => Severity::from_word(&s.score),
+        }));
+        scores.extend(
+            entry
+                .severity
+                .iter()
+                .filter(|s| s.kind == "CVSS_V3")
+                .filter_map(|s| cvss3_score(&s.score)),
+        );
+        let mut aliases = entry.aliases;
+        for upstream in entry.upstream {
+            if !aliases.contains(&upstream) {
+                aliases.push(upstream);
+            }
+        }
+        advisories.push(Advisory {
+            id: entry.id,
+            aliases,
+            summary: match entry.summary.is_empty() {
+                true => entry.details,
+                false => entry.summary,
+            },
+            severity: severities.into_iter().max().unwrap_or_default(),
+            cvss: scores.into_iter().reduce(f64::max),
+            published: entry.published,
+            modified: entry.modified,
+            affected,
+        });
+    }
+    Ok(advisories)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DebianIssue {
+    description: String,
+    releases: BTreeMap<String, DebianRelease>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DebianRelease {
+    status: String,
+    fixed_version: Option<String>,
+    urgency: String,
+}
+
+fn parse_debian(input: &str) -> Result<Vec<Advisory>, String> {
+    let packages: BTreeMap<String, BTreeMap<String, DebianIssue>> =
+        serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let mut advisories: BTreeMap<String, Advisory> = BTreeMap::new();
+    for (name, issues) in packages {
+        for (id, issue) in issues {
+            let advisory = advisories.entry(id.clone()).or_insert_with(|| Advisory {
+                id,
+                aliases: vec![],
+                summary: "".into(),
+                severity: Severity::Unknown,
+                cvss: None,
+                published: None,
+                modified: None,
+                affected: vec![],
+            });
+            if advisory.summary.is_empty() {
+                advisory.summary = issue.description;
+            }
+            for (release, r) in issue.releases {
+                let fixed = match (r.status.as_str(), r.fixed_version) {
+                    // version 0 means the release was never affected
+                    ("resolved", Some(v)) if v != "0" => Some(v),
+                    ("open", _) => None,
+                    _ => continue,
+                };
+                advisory.severity = advisory
+                    .severity
+                    .max(Severity::from_word(&r.urgency).unwrap_or_default());
+                advisory.affected.push(Affected {
+                    source: AdvisoryFormat::Debian,
+                    format: PackageFormat::Dpkg,
+                    distro: "debian".into(),
+                    release,
+                    name: name.clone(),
+                    introduced: "".into(),
+                    fixed,
+                    last_affected: None,
+                });
+            }
+        }
+    }
+    Ok(advisories.into_values().collect())
+}
+
+#[derive(Deserialize)]
+struct Secdb {
+    distroversion: String,
+    packages: Vec<SecdbPackage>,
+}
+
+#[derive(Deserialize)]
+struct SecdbPackage {
+    pkg: SecdbPkg,
+}
+
+#[derive(Deserialize)]
+struct SecdbPkg {
+    name: String,
+    #[serde(default)]
+    secfixes: BTreeMap<String, Option<Vec<String>>>,
+}
+
+fn parse_alpine(input: &str) -> Result<Vec<Advisory>, String> {
+    let secdb: Secdb = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let release = secdb.distroversion.trim_start_matches('v').to_string();
+    let mut advisories: BTreeMap<String, Advisory> = BTreeMap::new();
+    for package in secdb.packages {
+        for (fixed, ids) in package.pkg.secfixes {
+            // version 0 means the package was never affected
+            if fixed == "0" {
+                continue;
+            }
+            for id in ids.iter().flatten().flat_map(|i| i.split_whitespace()) {
+                let advisory = advisories
+                    .entry(id.to_string())
+                    .or_insert_with(|| Advisory {
+                        id: id.to_string(),
+                        aliases: vec![],
+                        summary: "".into(),
+                        severity: Severity::Unknown,
+                        cvss: None,
+                        published: None,
+                        modified: None,
+                        affected: vec![],
+                    });
+                advisory.affected.push(Affected {
+                    source: AdvisoryFormat::Alpine,
+                    format: PackageFormat::Apk,
+                    distro: "alpine".into(),
+                    release: release.clone(),
+                    name: package.pkg.name.clone(),
+                    introduced: "".into(),
+                    fixed: Some(fixed.clone()),
+                    last_affected: None,
+                });
+            }
+        }
+    }
+    Ok(advisories.into_values().collect())
+}
+
+/// parse advisories of `format`, ranges of unsupported package ecosystems are skipped
+///
+/// the KEV catalog has no advisories, it is parsed by [parse_kev]
+pub fn parse_advisories(format: AdvisoryFormat, input: &str) -> Result<Vec<Advisory>, String> {
+    match format {
+        AdvisoryFormat::Osv => parse_osv(input),
+        AdvisoryFormat::Debian => parse_debian(input),
+        AdvisoryFormat::Alpine => parse_alpine(input),
+        AdvisoryFormat::Kev => Err("the KEV catalog contains no advisories".into()),
+    }
+}
+
+/// CVE of the known exploited vulnerabilities catalog
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct KnownExploited {
+    pub cve: String,
+    pub name: String,
+    /// date added to the catalog
+    pub added: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KevCatalog {
+    vulnerabilities: Vec<KevEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KevEntry {
+    #[serde(rename = "cveID")]
+    cve_id: String,
+    #[serde(default)]
+    vulnerability_name: String,
+    date_added: Option<String>,
+}
+
+/// parse the json of the CISA known exploited vulnerabilities catalog
+pub fn parse_kev(input: &str) -> Result<Vec<KnownExploited>, String> {
+    let catalog: KevCatalog = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    Ok(catalog
+        .vulnerabilities
+        .into_iter()
+        .map(|e| KnownExploited {
+            cve: e.cve_id,
+            name: e.vulnerability_name,
+            added: e.date_added,
+        })
+        .collect())
+}
+
+/// Result of an advisory import
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub advisories: usize,
+    pub affected: usize,
+    /// CVEs of the KEV catalog
+    #[serde(default)]
+    pub known_exploited: usize,
+}
+
+/// insert or update the known exploited CVEs of the KEV catalog, the catalog only grows
+pub async fn store_known_exploited(
+    cves: Vec<KnownExploited>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let count = cves.len();
+    for cve in cves {
+        let q = r#"INSERT INTO known_exploited(cve, name, added, imported) VALUES(?, ?, ?, ?)
+        ON CONFLICT(cve) DO UPDATE SET name=excluded.name, added=excluded.added, imported=excluded.imported"#;
+        query(q)
+            .bind(cve.cve)
+            .bind(cve.name)
+            .bind(cve.added)
+            .bind(utc_to_str(now))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    debug!("Imported {count} known exploited vulnerabilities");
+    Ok(ImportSummary {
+        known_exploited: count,
+        ..Default::default()
+    })
+}
+
+/// parse and store `input` of `format`
+pub async fn import(
+    format: AdvisoryFormat,
+    input: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<ImportSummary, String> {
+    let stored = match format {
+        AdvisoryFormat::Kev => store_known_exploited(parse_kev(input)?, now, pool).await,
+        _ => store_advisories(parse_advisories(format, input)?, now, pool).await,
+    };
+    stored.map_err(|e| e.to_string())
+}
+
+/// insert or update `advisories`
+///
+/// the affected ranges of a package in a distribution release are replaced by the ones
+/// of the same source, other sources of the same advisory are kept
+pub async fn store_advisories(
+    advisories: Vec<Advisory>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut summary = ImportSummary::default();
+    let mut tx = pool.begin().await?;
+    for advisory in advisories {
+        let q = r#"INSERT INTO advisories(id, aliases, summary, severity, published, modified, imported, cvss) VALUES(?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            aliases=CASE WHEN excluded.aliases = '[]' THEN aliases ELSE excluded.aliases END,
+            summary=CASE WHEN excluded.summary = '' THEN summary ELSE excluded.summary END,
+            severity=CASE WHEN excluded.severity = 'unknown' THEN severity ELSE excluded.severity END,
+            published=COALESCE(excluded.published, published),
+            modified=COALESCE(excluded.modified, modified),
+            imported=excluded.imported,
+            cvss=COALESCE(excluded.cvss, cvss)"#;
+        query(q)
+            .bind(&advisory.id)
+            .bind(serde_json::to_string(&advisory.aliases).unwrap())
+            .bind(&advisory.summary)
+            .bind(advisory.severity.to_string())
+            .bind(advisory.published.map(utc_to_str))
+            .bind(advisory.modified.map(utc_to_str))
+            .bind(utc_to_str(now))
+            .bind(advisory.cvss)
+            .execute(&mut *tx)
+            .await?;
+        let scopes: BTreeSet<(String, String, String, String, String)> = advisory
+            .affected
+            .iter()
+            .map(|a| {
+                (
+                    a.source.to_string(),
+                    a.format.to_string(),
+                    a.distro.clone(),
+                    a.release.clone(),
+                    a.name.clone(),
+                )
+            })
+            .collect();
+        for (source, format, distro, release, name) in scopes {
+            let q = "DELETE FROM affected_packages WHERE advisory_id = ? AND source = ? AND format = ? AND distro = ? AND release = ? AND name = ?";
+            query(q)
+                .bind(&advisory.id)
+                .bind(source)
+                .bind(format)
+                .bind(distro)
+                .bind(release)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for a in &advisory.affected {
+            let q = r#"INSERT INTO affected_packages(advisory_id, source, format, distro, release, name, introduced, fixed, last_affected) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+            query(q)
+                .bind(&advisory.id)
+                .bind(a.source.to_string())
+                .bind(a.format.to_string())
+                .bind(&a.distro)
+                .bind(&a.release)
+                .bind(&a.name)
+                .bind(&a.introduced)
+                .bind(&a.fixed)
+                .bind(&a.last_affected)
+                .execute(&mut *tx)
+                .await?;
+        }
+        summary.advisories += 1;
+        summary.affected += advisory.affected.len();
+    }
+    tx.commit().await?;
+    debug!(
+        "Imported {} advisories with {} affected ranges",
+        summary.advisories, summary.affected
+    );
+    Ok(summary)
+}
+
+/// import advisory files, directories are searched for `.json` files
+pub async fn import_files(
+    format: AdvisoryFormat,
+    paths: &[PathBuf],
+    pool: &SqlitePool,
+) -> Result<ImportSummary, String> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let entries =
+                std::fs::read_dir(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let mut json: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|e| e == "json"))
+                .collect();
+            json.sort();
+            files.extend(json);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    let mut summary = ImportSummary::default();
+    for file in files {
+        let input =
+            std::fs::read_to_string(&file).map_err(|e| format!("{}: {e}", file.display()))?;
+        let imported = import(format, &input, Utc::now(), pool)
+            .await
+            .map_err(|e| format!("{}: {e}", file.display()))?;
+        info!(
+            "Imported {} advisories and {} known exploited from {}",
+            imported.advisories,
+            imported.known_exploited,
+            file.display()
+        );
+        summary.advisories += imported.advisories;
+        summary.affected += imported.affected;
+        summary.known_exploited += imported.known_exploited;
+    }
+    Ok(summary)
+}
+
+/// Vulnerable package installed on a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Vulnerability {
+    pub host_id: Uuid,
+    /// CVE of the advisory, the advisory id if it has none
+    pub cve: String,
+    pub advisory_id: String,
+    pub severity: Severity,
+    /// CVSS v3 base score, None if unknown
+    pub cvss: Option<f64>,
+    /// listed in the CISA known exploited vulnerabilities catalog
+    pub known_exploited: bool,
+    pub summary: String,
+    pub format: PackageFormat,
+    pub package: String,
+    /// SBOM component of the package, empty for the host inventory
+    pub component: String,
+    pub installed_version: String,
+    /// None if no fix is available
+    pub fixed_version: Option<String>,
+    /// since when the fix is available, the first pending update of the package or the
+    /// publication of the advisory
+    pub fixed_since: Option<DateTime<Utc>>,
+    /// active waiver accepting the risk, waived vulnerabilities stay listed
+    pub waiver_id: Option<Uuid>,
+}
+
+/// match the inventory of `host` against the advisories, most severe first
+///
+/// packages match affected ranges by binary or source name, releases are matched against the
+/// `os`, `os_version` and `os_codename` facts of the host or the operating system of the SBOM
+/// component, vulnerabilities with an active waiver get its id
+pub async fn host_vulnerabilities(host: &Host, pool: &SqlitePool) -> Vec<Vulnerability> {
+    let q = r#"SELECT p.name AS package, p.component, p.version AS installed_version, a.*, v.aliases, v.summary, v.severity, v.cvss, v.published,
+            (SELECT MIN(u.first_seen) FROM pending_updates u WHERE u.host_id = p.host_id AND u.format = p.format AND u.name = p.name) AS update_seen,
+            EXISTS(SELECT 1 FROM known_exploited k WHERE k.cve = v.id OR k.cve IN (SELECT value FROM json_each(v.aliases))) AS known_exploited
+        FROM packages p
+        JOIN affected_packages a ON a.format = p.format AND a.name IN (p.name, p.source)
+        JOIN advisories v ON v.id = a.advisory_id
+        WHERE p.host_id = ?"#;
+    let rows = query(q)
+        .bind(host.id.to_string())
+        .fetch_all(&mut *pool.acquire().await.unwrap())
+        .await
+        .unwrap_or_default();
+    let filter = format!("host_id='{}'", host.id);
+    let component_facts: HashMap<String, HashMap<String, String>> =
+        get_sboms_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .filter_map(|sbom| Some((sbom.component.clone(), sbom.facts()?)))
+            .collect();
+    let mut found: BTreeMap<(String, String, String), Vulnerability> = BTreeMap::new();
+    for row in rows {
+        let package = row.get::<String, _>("package");
+        let component = row.get::<String, _>("component");
+        let installed_version = row.get::<String, _>("installed_version");
+        let known_exploited = row.get::<bool, _>("known_exploited");
+        let update_seen = row
+            .get::<Option<String>, _>("update_seen")
+            .and_then(|ts| try_utc_from_str(&ts).ok());
+        let advisory = Advisory {
+            id: row.get::<String, _>("advisory_id"),
+            aliases: serde_json::from_str(&row.get::<String, _>("aliases")).unwrap_or_default(),
+            summary: row.get::<String, _>("summary"),
+            severity: Severity::from_db(&row.get::<String, _>("severity")),
+            cvss: row.get::<Option<f64>, _>("cvss"),
+            published: row
+                .get::<Option<String>, _>("published")
+                .and_then(|ts| try_utc_from_str(&ts).ok()),
+            modified: None,
+            affected: vec![],
+        };
+        let affected = affected_from_row(row);
+        let facts = component_facts.get(&component).unwrap_or(&host.facts);
+        if !affected.applies_to(facts) || !affected.contains(&installed_version) {
+            continue;
+        }
+        let cve = advisory.cve().to_string();
+        let fixed_since = match affected.fixed {
+            Some(_) => update_seen.or(advisory.published),
+            None => None,
+        };
+        found
+            .entry((cve.clone(), component.clone(), package.clone()))
+            .and_modify(|v| {
+                v.severity = v.severity.max(advisory.severity);
+                v.cvss = v.cvss.into_iter().chain(advisory.cvss).reduce(f64::max);
+                v.known_exploited |= known_exploited;
+            })
+            .or_insert(Vulnerability {
+                host_id: host.id,
+                cve,
+                advisory_id: advisory.id,
+                severity: advisory.severity,
+                cvss: advisory.cvss,
+                known_exploited,
+                summary: advisory.summary,
+                format: affected.format,
+                package,
+                component,
+                installed_version,
+                fixed_version: affected.fixed,
+                fixed_since,
+                waiver_id: None,
+            });
+    }
+    let waivers = active_waivers(Utc::now(), pool).await;
+    for v in found.values_mut() {
+        v.waiver_id = waivers.iter().find(|w| w.matches(host, v)).map(|w| w.id);
+    }
+    let mut vulnerabilities: Vec<Vulnerability> = found.into_values().collect();
+    vulnerabilities.sort_by_key(|v| std::cmp::Reverse(v.severity));
+    vulnerabilities
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    format: AdvisoryFormat,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct VulnerabilityQueryParams {
+    /// minimum severity
+    severity: Option<Severity>,
+    cve: Option<String>,
+    /// only waived (true) or open (false) vulnerabilities
+    waived: Option<bool>,
+}
+
+impl VulnerabilityQueryParams {
+    fn matches(&self, v: &Vulnerability) -> bool {
+        self.severity.is_none_or(|s| v.severity >= s)
+            && self.cve.as_ref().is_none_or(|cve| v.cve == *cve)
+            && self.waived.is_none_or(|w| w == v.waiver_id.is_some())
+    }
+}
+
+/// API to import advisories from the request body
+pub async fn post_import_api(
+    _claims: Claims,
+    Query(params): Query<ImportParams>,
+    State(pool): State<SqlitePool>,
+    body: String,
+) -> Response {
+    match import(params.format, &body, Utc::now(), &pool).await {
+        Ok(summary) => (StatusCode::CREATED, Json(summary)).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
+/// API to get an advisory with its affected packages by id or alias
+pub async fn get_one_advisory_api(
+    _claims: Claims,
+    Path(id): Path<String>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let id = id.replace('\'', "''");
+    let filter =
+        format!("id = '{id}' OR EXISTS (SELECT 1 FROM json_each(aliases) WHERE value = '{id}')");
+    let advisories = get_advisories_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match advisories.into_iter().next() {
+        Some(advisory) => Json(advisory).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// API to get the vulnerable packages of a host, most severe first
+pub async fn get_host_vulnerabilities_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<VulnerabilityQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let hosts = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(host) = hosts.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mut vulnerabilities = host_vulnerabilities(host, &pool).await;
+    vulnerabilities.retain(|v| params.matches(v));
+    Json(vulnerabilities).into_response()
+}
+
+/// API to get the vulnerable packages of all hosts, e.g. the hosts affected by a CVE
+pub async fn get_vulnerabilities_api(
+    _claims: Claims,
+    Query(params): Query<VulnerabilityQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut vulnerabilities = vec![];
+    for host in &hosts {
+        let mut found = host_vulnerabilities(host, &pool).await;
+        found.retain(|v| params.matches(v));
+        vulnerabilities.extend(found);
+    }
+    Json(vulnerabilities)
+}
+
+/// advisories matching `filter` with their affected packages
+pub async fn get_advisories_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Advisory> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM advisories WHERE {f}"),
+        None => "SELECT * FROM advisories".into(),
+    };
+    let mut advisories: Vec<Advisory> = query(&q)
+        .map(|row: SqliteRow| Advisory::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default();
+    for advisory in advisories.iter_mut() {
+        advisory.affected = query("SELECT * FROM affected_packages WHERE advisory_id = ?")
+            .bind(&advisory.id)
+            .map(affected_from_row)
+            .fetch_all(&mut *connection)
+            .await
+            .unwrap_or_default();
+    }
+    advisories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        package::{store_inventory, Package},
+    };
+    use std::collections::HashMap;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    const OSV: &str = r#"[{
+        "id": "UBUNTU-CVE-2024-0727",
+        "upstream": ["CVE-2024-0727"],
+        "summary": "openssl vulnerability",
+        "published": "2024-01-26T09:15:00Z",
+        "severity": [{"type": "Ubuntu", "score": "low"}, {"type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:U/C:N/I:N/A:H"}],
+        "affected": [
+            {"package": {"ecosystem": "Ubuntu:22.04:LTS", "name": "openssl"},
+             "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "3.0.2-0ubuntu1.14"}]}]},
+            {"package": {"ecosystem": "PyPI", "name": "cryptography"},
+             "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "42.0.2"}]}]}
+        ]
+    }, {
+        "id": "ALSA-2024:1234",
+        "aliases": ["CVE-2024-5535"],
+        "details": "openssl: SSL_select_next_proto buffer overread",
+        "affected": [
+            {"package": {"ecosystem": "AlmaLinux:9", "name": "openssl-libs"},
+             "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "1:3.0.7-28.el9"}]}],
+             "ecosystem_specific": {"severity": "important"}}
+        ]
+    }]"#;
+
+    const DEBIAN: &str = r#"{
+        "openssl": {
+            "CVE-2023-5678": {
+                "description": "Generating excessively long X9.42 DH keys",
+                "releases": {
+                    "bookworm": {"status": "resolved", "fixed_version": "3.0.11-1~deb12u2", "urgency": "not yet assigned"},
+                    "bullseye": {"status": "open", "urgency": "low**"},
+                    "trixie": {"status": "resolved", "fixed_version": "0", "urgency": "medium"}
+                }
+            }
+        },
+        "curl": {
+            "CVE-2023-5678": {"releases": {"bookworm": {"status": "undetermined", "urgency": "low"}}},
+            "CVE-2023-38545": {
+                "description": "SOCKS5 heap buffer overflow",
+                "releases": {"bookworm": {"status": "resolved", "fixed_version": "7.88.1-10+deb12u4", "urgency": "high"}}
+            }
+        }
+    }"#;
+
+    const ALPINE: &str = r#"{
+        "distroversion": "v3.19",
+        "packages": [
+            {"pkg": {"name": "musl", "secfixes": {"1.2.4-r2": ["CVE-2025-26519 GHSA-xxxx"], "0": ["CVE-2020-28928"]}}},
+            {"pkg": {"name": "zlib", "secfixes": {"1.3.1-r0": null}}}
+        ]
+    }"#;
+
+    const KEV: &str = r#"{
+        "title": "CISA Catalog of Known Exploited Vulnerabilities",
+        "vulnerabilities": [
+            {"cveID": "CVE-2024-5535", "vendorProject": "OpenSSL", "vulnerabilityName": "OpenSSL Buffer Over-read", "dateAdded": "2024-07-01"}
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_advisories() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        assert_eq!(
+            Severity::from_cvss3("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(Severity::Critical)
+        );
+        assert_eq!(
+            Severity::from_cvss3("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:N/A:N"),
+            Some(Severity::Medium)
+        );
+        assert_eq!(
+            Severity::from_cvss3("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:L/I:L/A:N"),
+            Some(Severity::High)
+        );
+        assert_eq!(
+            Severity::from_cvss3("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"),
+            Some(Severity::Low)
+        );
+        assert_eq!(Severity::from_cvss3("AV:N"), None);
+
+        let osv = parse_advisories(AdvisoryFormat::Osv, OSV).unwrap();
+        assert_eq!(osv.len(), 2);
+        assert_eq!(osv[0].cve(), "CVE-2024-0727");
+        // the CVSS vector (6.5) is more severe than the Ubuntu priority
+        assert_eq!(osv[0].severity, Severity::Medium);
+        assert_eq!(osv[0].cvss, Some(6.5));
+        assert_eq!(
+            osv[0].affected,
+            vec![
+                Affected {
+                    source: AdvisoryFormat::Osv,
+                    format: PackageFormat::Dpkg,
+                    distro: "ubuntu".into(),
+                    release: "22.04".into(),
+                    name: "openssl".into(),
+                    introduced: "".into(),
+                    fixed: Some("3.0.2-0ubuntu1.14".into()),
+                    last_affected: None,
+                },
+                // language ecosystems apply to all distributions
+                Affected {
+                    source: AdvisoryFormat::Osv,
+                    format: PackageFormat::Pypi,
+                    distro: "".into(),
+                    release: "".into(),
+                    name: "cryptography".into(),
+                    introduced: "".into(),
+                    fixed: Some("42.0.2".into()),
+                    last_affected: None,
+                }
+            ]
+        );
+        assert_eq!(
+            osv[1].summary,
+            "openssl: SSL_select_next_proto buffer overread"
+        );
+        assert_eq!(osv[1].severity, Severity::High);
+        assert_eq!(osv[1].affected[0].release, "9");
+        let single = parse_advisories(
+            AdvisoryFormat::Osv,
+            r#"{"id": "DSA-1", "affected": [{"package": {"ecosystem": "Debian:12", "name": "bash"}, "versions": ["5.2.15-2"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            single[0].affected[0].last_affected.as_deref(),
+            Some("5.2.15-2")
+        );
+        assert!(parse_advisories(AdvisoryFormat::Osv, r#"{"summary": "no id"}"#).is_err());
+        assert!(parse_advisories(AdvisoryFormat::Osv, "<oval_definitions>").is_err());
+
+        let debian = parse_advisories(AdvisoryFormat::Debian, DEBIAN).unwrap();
+        assert_eq!(debian.len(), 2);
+        assert_eq!(debian[0].id, "CVE-2023-38545");
+        assert_eq!(debian[0].severity, Severity::High);
+        // undetermined and never affected releases are skipped
+        assert_eq!(debian[1].affected.len(), 2);
+        assert_eq!(debian[1].affected[0].release, "bookworm");
+        assert_eq!(debian[1].affected[1].fixed, None);
+        assert_eq!(debian[1].severity, Severity::Low);
+
+        let alpine = parse_advisories(AdvisoryFormat::Alpine, ALPINE).unwrap();
+        assert_eq!(alpine.len(), 2);
+        assert_eq!(alpine[0].id, "CVE-2025-26519");
+        assert_eq!(alpine[0].affected[0].release, "3.19");
+        assert_eq!(alpine[1].id, "GHSA-xxxx");
+        assert!(parse_advisories(AdvisoryFormat::Alpine, "{}").is_err());
+        let kev = parse_kev(KEV).unwrap();
+        assert_eq!(kev[0].cve, "CVE-2024-5535");
+        assert_eq!(kev[0].added.as_deref(), Some("2024-07-01"));
+        assert!(parse_advisories(AdvisoryFormat::Kev, KEV).is_err());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let summary = store_advisories(debian, Utc::now(), &pool).await.unwrap();
+        assert_eq!(summary.advisories, 2);
+        assert_eq!(summary.affected, 3);
+        let _s = store_advisories(osv, Utc::now(), &pool).await.unwrap();
+        let _s = store_advisories(alpine, Utc::now(), &pool).await.unwrap();
+        let advisories = get_advisories_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(advisories.len(), 6);
+
+        // re-import replaces the ranges of the same source and keeps the others
+        let osv_debian = Advisory {
+            id: "CVE-2023-5678".into(),
+            aliases: vec![],
+            summary: "".into(),
+            severity: Severity::Unknown,
+            cvss: None,
+            published: None,
+            modified: None,
+            affected: vec![Affected {
+                source: AdvisoryFormat::Osv,
+                format: PackageFormat::Dpkg,
+                distro: "debian".into(),
+                release: "12".into(),
+                name: "openssl".into(),
+                introduced: "".into(),
+                fixed: Some("3.0.11-1~deb12u2".into()),
+                last_affected: None,
+            }],
+        };
+        let _s = store_advisories(vec![osv_debian.clone()], Utc::now(), &pool)
+            .await
+            .unwrap();
+        let _s = store_advisories(vec![osv_debian], Utc::now(), &pool)
+            .await
+            .unwrap();
+        let filter = "id = 'CVE-2023-5678'";
+        let advisory = get_advisories_from_db(Some(filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(advisory[0].affected.len(), 3);
+        // empty fields do not overwrite imported ones
+        assert_eq!(advisory[0].severity, Severity::Low);
+        assert_eq!(
+            advisory[0].summary,
+            "Generating excessively long X9.42 DH keys"
+        );
+
+        // matching against the inventory
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "db-1".into(),
+            facts: HashMap::from([
+                ("os".to_string(), "debian".to_string()),
+                ("os_version".to_string(), "12".to_string()),
+                ("os_codename".to_string(), "bookworm".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let package = |name: &str, version: &str, source: &str| Package {
+            format: PackageFormat::Dpkg,
+            name: name.into(),
+            version: version.into(),
+            arch: "amd64".into(),
+            source: source.into(),
+        };
+        let _c = store_inventory(
+            host.id,
+            vec![
+                package("libssl3", "3.0.11-1~deb12u1", "openssl"),
+                package("openssl", "3.0.11-1~deb12u1", "openssl"),
+                package("curl", "7.88.1-10+deb12u4", "curl"),
+            ],
+            Utc::now(),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let vulnerabilities = host_vulnerabilities(&host, &pool).await;
+        let found: Vec<(&str, &str, Option<&str>)> = vulnerabilities
+            .iter()
+            .map(|v| {
+                (
+                    v.cve.as_str(),
+                    v.package.as_str(),
+                    v.fixed_version.as_deref(),
+                )
+            })
+            .collect();
+        // binary package by source name, fixed curl and other distributions are skipped
+        assert_eq!(
+            found,
+            vec![
+                ("CVE-2023-5678", "libssl3", Some("3.0.11-1~deb12u2")),
+                ("CVE-2023-5678", "openssl", Some("3.0.11-1~deb12u2")),
+            ]
+        );
+        assert_eq!(vulnerabilities[0].severity, Severity::Low);
+
+        // without facts all releases apply, a CVE is listed once per package
+        let unknown = Host {
+            facts: HashMap::new(),
+            ..host.clone()
+        };
+        let vulnerabilities = host_vulnerabilities(&unknown, &pool).await;
+        assert_eq!(vulnerabilities.len(), 2);
+
+        let ubuntu = Host {
+            facts: HashMap::from([("os".to_string(), "ubuntu".to_string())]),
+            ..host.clone()
+        };
+        assert!(host_vulnerabilities(&ubuntu, &pool).await.is_empty());
+
+        // import from files and directories
+        let dir = std::env::temp_dir().join(format!("advisories-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), OSV).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"id": "DSA-1"}"#).unwrap();
+        std::fs::write(dir.join("README"), "not json").unwrap();
+        let summary = import_files(AdvisoryFormat::Osv, std::slice::from_ref(&dir), &pool)
+            .await
+            .unwrap();
+        assert_eq!(summary.advisories, 3);
+        let missing = import_files(AdvisoryFormat::Osv, &[dir.join("missing.json")], &pool).await;
+        assert!(missing.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let api_import = post_import_api(
+            claims.clone(),
+            axum::extract::Query(ImportParams {
+                format: AdvisoryFormat::Debian,
+            }),
+            axum::extract::State(pool.clone()),
+            DEBIAN.to_string(),
+        )
+        .await;
+        assert_eq!(api_import.status(), StatusCode::CREATED);
+        let body = hyper::body::to_bytes(api_import.into_body()).await.unwrap();
+        let summary: ImportSummary = serde_json::from_slice(&body).unwrap();
+        assert_eq!(summary.advisories, 2);
+
+        let api_invalid = post_import_api(
+            claims.clone(),
+            axum::extract::Query(ImportParams {
+                format: AdvisoryFormat::Alpine,
+            }),
+            axum::extract::State(pool.clone()),
+            DEBIAN.to_string(),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let _s = store_advisories(
+            parse_advisories(AdvisoryFormat::Osv, OSV).unwrap(),
+            Utc::now(),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let api_alias = get_one_advisory_api(
+            claims.clone(),
+            axum::extract::Path("CVE-2024-5535".to_string()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_alias.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_alias.into_body()).await.unwrap();
+        let advisory: Advisory = serde_json::from_slice(&body).unwrap();
+        assert_eq!(advisory.id, "ALSA-2024:1234");
+        assert_eq!(advisory.affected.len(), 1);
+
+        let api_missing = get_one_advisory_api(
+            claims.clone(),
+            axum::extract::Path("CVE-1999-0001' OR '1'='1".to_string()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_missing.status(), StatusCode::NOT_FOUND);
+
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let curl = Package {
+            format: PackageFormat::Dpkg,
+            name: "curl".into(),
+            version: "7.88.1-10+deb12u3".into(),
+            arch: "amd64".into(),
+            source: "curl".into(),
+        };
+        let _c = store_inventory(host_id, vec![curl], Utc::now(), &pool)
+            .await
+            .unwrap();
+
+        let api_host = get_host_vulnerabilities_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            axum::extract::Query(VulnerabilityQueryParams {
+                severity: Some(Severity::High),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_host.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_host.into_body()).await.unwrap();
+        let found: Vec<Vulnerability> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].cve, "CVE-2023-38545");
+
+        let api_unknown = get_host_vulnerabilities_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::Query(VulnerabilityQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_fleet = get_vulnerabilities_api(
+            claims.clone(),
+            axum::extract::Query(VulnerabilityQueryParams {
+                cve: Some("CVE-2023-38545".into()),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_fleet.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_fleet.into_body()).await.unwrap();
+        let found: Vec<Vulnerability> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(found[0].host_id, host_id);
+    }
+}
@@ -0,0 +1,1437 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    host::{get_hosts_from_db, Host},
+    jwt::{Claims, KEYS},
+    lifecycle::{get_lifecycles_from_db, host_lifecycle, LifecycleStatus},
+    parser::OutputParser,
+    reboot::{get_reboots_from_db, RebootState},
+    schedule::{get_schedules_from_db, Schedule},
+    silence::{suppressed_by, Subject},
+    CRON, EXTERNAL_URL,
+};
+
+/// Validity of the acknowledge links sent with notifications
+pub const ACK_LINK_TTL: Duration = Duration::days(7);
+const ACK_AUDIENCE: &str = "unpatched-alert-ack";
+
+/// Token of a signed acknowledge link, signed with the JWT secret
+#[derive(Debug, Serialize, Deserialize)]
+struct AckClaims {
+    aud: String,
+    sub: Uuid,
+    exp: usize,
+}
+
+/// Rule evaluated periodically against executions or metrics of every host and schedule
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct AlertRule {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    pub condition: AlertCondition,
+    #[serde(default)]
+    pub severity: Severity,
+    /// copied to every alert of this rule
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// seconds the condition has to hold before a pending alert fires
+    #[serde(default)]
+    pub for_secs: i64,
+    /// only evaluate this schedule, all schedules if `None`
+    #[serde(default)]
+    pub sched_id: Option<Uuid>,
+    /// only evaluate hosts with all of these attributes
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// hex encoded sha256 of the sorted labels, first 16 characters
+pub fn fingerprint(labels: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    for (k, v) in labels {
+        hasher.update(k.as_bytes());
+        hasher.update([0xff]);
+        hasher.update(v.as_bytes());
+        hasher.update([0xfe]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// the verdicts of the last `count` executions are not success, agents report no exit code
+    /// so a script fails by its output regex or parser
+    ConsecutiveFailures { count: i64 },
+    /// the latest value of a metric compared to `value`
+    MetricThreshold {
+        metric: String,
+        operator: Operator,
+        value: f64,
+    },
+    /// no result within `factor` times the schedule interval
+    NoResult { factor: f64 },
+    /// the os release of the host reaches its end of life within `within_days`, reported on
+    /// the schedule with the `os_release` parser
+    OsEndOfLife { within_days: i64 },
+    /// the host waits for a reboot for more than `after_hours`, reported on the schedule with
+    /// the `reboot_status` parser
+    RebootRequired { after_hours: i64 },
+    /// the latest reboot step of a schedule on the host timed out or failed its post-check
+    RebootFailed {},
+    /// the output of a schedule with `track_drift` changed on the host within `within_minutes`
+    OutputDrift { within_minutes: i64 },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Operator {
+    pub(crate) fn compare(&self, left: f64, right: f64) -> bool {
+        match self {
+            Operator::Gt => left > right,
+            Operator::Ge => left >= right,
+            Operator::Lt => left < right,
+            Operator::Le => left <= right,
+            Operator::Eq => left == right,
+            Operator::Ne => left != right,
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{severity}")
+    }
+}
+
+impl Severity {
+    fn from_db(s: &str) -> Severity {
+        match s {
+            "info" => Severity::Info,
+            "critical" => Severity::Critical,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// pending alerts that clear are removed, firing alerts that clear are resolved
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    #[default]
+    Pending,
+    Firing,
+    Resolved,
+}
+
+impl Display for AlertState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            AlertState::Pending => "pending",
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        };
+        write!(f, "{state}")
+    }
+}
+
+impl AlertState {
+    fn from_db(s: &str) -> AlertState {
+        match s {
+            "firing" => AlertState::Firing,
+            "resolved" => AlertState::Resolved,
+            _ => AlertState::Pending,
+        }
+    }
+}
+
+/// One rule matching one host and schedule
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Alert {
+    pub id: Uuid,
+    pub rule_id: Uuid,
+    pub host_id: Uuid,
+    pub sched_id: Uuid,
+    pub state: AlertState,
+    pub severity: Severity,
+    /// rule labels plus `alertname` and `host`
+    pub labels: BTreeMap<String, String>,
+    /// hash of the labels, alerts with the same fingerprint are notified once
+    pub fingerprint: String,
+    pub summary: String,
+    pub value: Option<f64>,
+    pub started: DateTime<Utc>,
+    pub fired: Option<DateTime<Utc>>,
+    pub resolved: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged: Option<DateTime<Utc>>,
+}
+
+/// condition of a rule evaluated for one host and schedule
+#[derive(Debug, Clone, PartialEq)]
+struct Observation {
+    host_id: Uuid,
+    sched_id: Uuid,
+    value: f64,
+    summary: String,
+}
+
+impl AlertRule {
+    /// Insert or Update `AlertRule` in alert_rules table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | condition | TEXT | json
+    /// | severity | TEXT | info, warning or critical
+    /// | labels | TEXT | json map
+    /// | for_secs | INT |
+    /// | sched_id | TEXT | uuid, NULL for all schedules
+    /// | attributes | TEXT | json list
+    /// | active | NUMERIC | bool
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO alert_rules(id, name, condition, severity, labels, for_secs, sched_id, attributes, active, created) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, condition=excluded.condition, severity=excluded.severity, labels=excluded.labels, for_secs=excluded.for_secs, sched_id=excluded.sched_id, attributes=excluded.attributes, active=excluded.active"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(serde_json::to_string(&self.condition).unwrap())
+            .bind(self.severity.to_string())
+            .bind(serde_json::to_string(&self.labels).unwrap())
+            .bind(self.for_secs)
+            .bind(self.sched_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(self.active)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.for_secs < 0 {
+            return Err("for_secs must not be negative".into());
+        }
+        match &self.condition {
+            AlertCondition::ConsecutiveFailures { count } if *count < 1 => {
+                Err("count must be at least 1".into())
+            }
+            AlertCondition::MetricThreshold { metric, .. } if metric.is_empty() => {
+                Err("metric must not be empty".into())
+            }
+            AlertCondition::NoResult { factor } if *factor <= 0.0 => {
+                Err("factor must be positive".into())
+            }
+            AlertCondition::OsEndOfLife { within_days } if *within_days < 0 => {
+                Err("within_days must not be negative".into())
+            }
+            AlertCondition::RebootRequired { after_hours } if *after_hours < 0 => {
+                Err("after_hours must not be negative".into())
+            }
+            AlertCondition::OutputDrift { within_minutes } if *within_minutes < 1 => {
+                Err("within_minutes must be at least 1".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// the condition for every host and schedule it applies to, with the ones that hold
+    async fn observe(
+        &self,
+        now: DateTime<Utc>,
+        schedules: &HashMap<Uuid, Schedule>,
+        pool: &SqlitePool,
+    ) -> Vec<Observation> {
+        let sched_filter = match self.sched_id {
+            Some(id) => format!("AND sched_id = '{id}'"),
+            None => "".into(),
+        };
+        let mut connection = pool.acquire().await.unwrap();
+        match &self.condition {
+            AlertCondition::ConsecutiveFailures { count } => {
+                let q = format!(
+                    r#"SELECT host_id, sched_id, COUNT(*) AS runs, SUM(verdict != 'success') AS failed FROM (
+                        SELECT host_id, sched_id, verdict,
+                            ROW_NUMBER() OVER (PARTITION BY host_id, sched_id ORDER BY response DESC) AS rn
+                        FROM executions WHERE verdict IS NOT NULL {sched_filter}
+                    ) WHERE rn <= {count} GROUP BY host_id, sched_id"#
+                );
+                query(&q)
+                    .fetch_all(&mut *connection)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|row| {
+                        row.get::<i64, _>("runs") == *count && row.get::<i64, _>("failed") == *count
+                    })
+                    .map(|row| Observation {
+                        host_id: row.get::<String, _>("host_id").parse().unwrap(),
+                        sched_id: row.get::<String, _>("sched_id").parse().unwrap(),
+                        value: *count as f64,
+                        summary: format!("{count} consecutive failed runs"),
+                    })
+                    .collect()
+            }
+            AlertCondition::MetricThreshold {
+                metric,
+                operator,
+                value,
+            } => {
+                // sqlite returns the bare columns of the row with MAX(ts)
+                let q = format!(
+                    "SELECT host_id, sched_id, value, MAX(ts) FROM metrics WHERE name = ? {sched_filter} GROUP BY host_id, sched_id"
+                );
+                query(&q)
+                    .bind(metric)
+                    .fetch_all(&mut *connection)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|row| operator.compare(row.get("value"), *value))
+                    .map(|row| {
+                        let latest: f64 = row.get("value");
+                        Observation {
+                            host_id: row.get::<String, _>("host_id").parse().unwrap(),
+                            sched_id: row.get::<String, _>("sched_id").parse().unwrap(),
+                            value: latest,
+                            summary: format!("{metric} is {latest} ({operator} {value})"),
+                        }
+                    })
+                    .collect()
+            }
+            AlertCondition::NoResult { factor } => {
+                let q = format!(
+                    r#"SELECT host_id, sched_id, MAX(response) AS last FROM executions
+                    WHERE response IS NOT NULL AND response != '1970-01-01T00:00:00.000Z' {sched_filter}
+                    GROUP BY host_id, sched_id"#
+                );
+                let seven_part_cron = *CRON.get().unwrap_or(&false);
+                query(&q)
+                    .fetch_all(&mut *connection)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|row| {
+                        let sched_id: Uuid = row.get::<String, _>("sched_id").parse().unwrap();
+                        let schedule = schedules.get(&sched_id).filter(|s| s.active)?;
+                        let interval = schedule.interval(seven_part_cron)?;
+                        let silent = now - utc_from_str(&row.get::<String, _>("last"));
+                        let allowed = Duration::milliseconds(
+                            (interval.num_milliseconds() as f64 * factor) as i64,
+                        );
+                        (silent > allowed).then(|| Observation {
+                            host_id: row.get::<String, _>("host_id").parse().unwrap(),
+                            sched_id,
+                            value: silent.num_seconds() as f64,
+                            summary: format!(
+                                "no result for {}s, expected every {}s",
+                                silent.num_seconds(),
+                                interval.num_seconds()
+                            ),
+                        })
+                    })
+                    .collect()
+            }
+            AlertCondition::OsEndOfLife { within_days } => {
+                let lifecycles = get_lifecycles_from_db(None, pool.acquire().await.unwrap()).await;
+                let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+                let reported =
+                    parser_schedules(&OutputParser::OsRelease, &sched_filter, connection).await;
+                hosts
+                    .iter()
+                    .filter_map(|host| {
+                        let sched_id = *reported.get(&host.id)?;
+                        let lifecycle =
+                            host_lifecycle(host, *within_days, now.date_naive(), &lifecycles);
+                        matches!(
+                            lifecycle.status,
+                            LifecycleStatus::Eol | LifecycleStatus::Expiring
+                        )
+                        .then(|| Observation {
+                            host_id: host.id,
+                            sched_id,
+                            value: lifecycle.days_left.unwrap_or_default() as f64,
+                            summary: lifecycle.summary(),
+                        })
+                    })
+                    .collect()
+            }
+            AlertCondition::RebootFailed {} => {
+                // sqlite returns the bare columns of the row with MAX(response)
+                let q = format!(
+                    r#"SELECT host_id, sched_id, reboot_state, reboot_deadline, MAX(response) FROM executions
+                    WHERE reboot_state IS NOT NULL {sched_filter} GROUP BY host_id, sched_id"#
+                );
+                query(&q)
+                    .fetch_all(&mut *connection)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|row| {
+                        let summary =
+                            match RebootState::from_db(&row.get::<String, _>("reboot_state")) {
+                                Some(RebootState::TimedOut) => {
+                                    "host did not come back after the reboot in time"
+                                }
+                                Some(RebootState::Failed) => "post-check failed after the reboot",
+                                _ => return None,
+                            };
+                        let deadline = utc_from_str(&row.get::<String, _>("reboot_deadline"));
+                        Some(Observation {
+                            host_id: row.get::<String, _>("host_id").parse().unwrap(),
+                            sched_id: row.get::<String, _>("sched_id").parse().unwrap(),
+                            value: (now - deadline).num_minutes().max(0) as f64,
+                            summary: summary.to_string(),
+                        })
+                    })
+                    .collect()
+            }
+            AlertCondition::OutputDrift { within_minutes } => {
+                let q = format!(
+                    r#"SELECT host_id, sched_id, COUNT(*) AS changes FROM drift_events
+                    WHERE detected >= ? {sched_filter} GROUP BY host_id, sched_id"#
+                );
+                query(&q)
+                    .bind(utc_to_str(now - Duration::minutes(*within_minutes)))
+                    .fetch_all(&mut *connection)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|row| {
+                        let changes: i64 = row.get("changes");
+                        Observation {
+                            host_id: row.get::<String, _>("host_id").parse().unwrap(),
+                            sched_id: row.get::<String, _>("sched_id").parse().unwrap(),
+                            value: changes as f64,
+                            summary: format!(
+                                "output changed {changes} times within {within_minutes} minutes"
+                            ),
+                        }
+                    })
+                    .collect()
+            }
+            AlertCondition::RebootRequired { after_hours } => {
+                let filter = format!(
+                    "reboot_required = 1 AND required_since <= '{}'",
+                    utc_to_str(now - Duration::hours(*after_hours))
+                );
+                let reboots =
+                    get_reboots_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+                let reported =
+                    parser_schedules(&OutputParser::RebootStatus, &sched_filter, connection).await;
+                reboots
+                    .iter()
+                    .filter_map(|reboot| {
+                        let sched_id = *reported.get(&reboot.host_id)?;
+                        let pending = now - reboot.required_since?;
+                        Some(Observation {
+                            host_id: reboot.host_id,
+                            sched_id,
+                            value: pending.num_hours() as f64,
+                            summary: reboot.summary(),
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// latest schedule of every host running a script with `parser`, host level conditions are
+/// reported on it
+async fn parser_schedules(
+    parser: &OutputParser,
+    sched_filter: &str,
+    mut connection: PoolConnection<Sqlite>,
+) -> HashMap<Uuid, Uuid> {
+    // sqlite returns the bare columns of the row with MAX(response)
+    let q = format!(
+        r#"SELECT host_id, sched_id, MAX(response) FROM executions
+        WHERE response IS NOT NULL AND response != '1970-01-01T00:00:00.000Z' AND sched_id IN (
+            SELECT sc.id FROM schedules sc JOIN scripts s ON s.id = sc.script_id WHERE s.parser = ?
+        ) {sched_filter} GROUP BY host_id"#
+    );
+    query(&q)
+        .bind(serde_json::to_string(parser).unwrap())
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| {
+            (
+                row.get::<String, _>("host_id").parse().unwrap(),
+                row.get::<String, _>("sched_id").parse().unwrap(),
+            )
+        })
+        .collect()
+}
+
+impl From<SqliteRow> for AlertRule {
+    fn from(s: SqliteRow) -> Self {
+        AlertRule {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            condition: serde_json::from_str(&s.get::<String, _>("condition")).unwrap(),
+            severity: Severity::from_db(&s.get::<String, _>("severity")),
+            labels: serde_json::from_str(&s.get::<String, _>("labels")).unwrap_or_default(),
+            for_secs: s.get::<i64, _>("for_secs"),
+            sched_id: s
+                .get::<Option<String>, _>("sched_id")
+                .and_then(|id| id.parse().ok()),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            active: s.get::<bool, _>("active"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+impl Alert {
+    /// Insert or Update `Alert` in alerts table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | rule_id | TEXT | uuid
+    /// | host_id | TEXT | uuid
+    /// | sched_id | TEXT | uuid
+    /// | state | TEXT | pending, firing or resolved
+    /// | severity | TEXT | info, warning or critical
+    /// | labels | TEXT | json map
+    /// | fingerprint | TEXT | hash of the labels
+    /// | summary | TEXT |
+    /// | value | REAL |
+    /// | started | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | fired | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | resolved | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | acknowledged_by | TEXT | email of the user
+    /// | acknowledged | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        // an upsert, REPLACE would delete the row and cascade to its escalation
+        let q = r#"INSERT INTO alerts(id, rule_id, host_id, sched_id, state, severity, labels, fingerprint, summary, value, started, fired, resolved, acknowledged_by, acknowledged) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET rule_id=excluded.rule_id, host_id=excluded.host_id, sched_id=excluded.sched_id, state=excluded.state, severity=excluded.severity, labels=excluded.labels, fingerprint=excluded.fingerprint, summary=excluded.summary, value=excluded.value, started=excluded.started, fired=excluded.fired, resolved=excluded.resolved, acknowledged_by=excluded.acknowledged_by, acknowledged=excluded.acknowledged"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.rule_id.to_string())
+            .bind(self.host_id.to_string())
+            .bind(self.sched_id.to_string())
+            .bind(self.state.to_string())
+            .bind(self.severity.to_string())
+            .bind(serde_json::to_string(&self.labels).unwrap())
+            .bind(self.fingerprint)
+            .bind(self.summary)
+            .bind(self.value)
+            .bind(utc_to_str(self.started))
+            .bind(self.fired.map(utc_to_str))
+            .bind(self.resolved.map(utc_to_str))
+            .bind(self.acknowledged_by)
+            .bind(self.acknowledged.map(utc_to_str))
+            .execute(&mut *connection)
+            .await
+    }
+}
+
+impl From<SqliteRow> for Alert {
+    fn from(s: SqliteRow) -> Self {
+        let time = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .as_deref()
+                .map(utc_from_str)
+        };
+        Alert {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            rule_id: s.get::<String, _>("rule_id").parse().unwrap(),
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            sched_id: s.get::<String, _>("sched_id").parse().unwrap(),
+            state: AlertState::from_db(&s.get::<String, _>("state")),
+            severity: Severity::from_db(&s.get::<String, _>("severity")),
+            labels: serde_json::from_str(&s.get::<String, _>("labels")).unwrap_or_default(),
+            fingerprint: s.get::<String, _>("fingerprint"),
+            summary: s.get::<String, _>("summary"),
+            value: s.get::<Option<f64>, _>("value"),
+            started: utc_from_str(&s.get::<String, _>("started")),
+            fired: time("fired"),
+            resolved: time("resolved"),
+            acknowledged_by: s.get::<Option<String>, _>("acknowledged_by"),
+            acknowledged: time("acknowledged"),
+        }
+    }
+}
+
+/// Evaluate all active rules, returns the alerts that started firing or got resolved
+pub async fn evaluate_rules(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<Alert> {
+    let rules = get_alert_rules_from_db(Some("active = 1"), pool.acquire().await.unwrap()).await;
+    if rules.is_empty() {
+        return vec![];
+    }
+    let hosts: HashMap<Uuid, Host> = get_hosts_from_db(None, pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .map(|h| (h.id, h))
+        .collect();
+    let schedules: HashMap<Uuid, Schedule> =
+        get_schedules_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .map(|s| (s.id, s))
+            .collect();
+    let mut changed = vec![];
+    for rule in rules {
+        let observations: Vec<Observation> = rule
+            .observe(now, &schedules, pool)
+            .await
+            .into_iter()
+            .filter(|o| {
+                hosts.get(&o.host_id).is_some_and(|h| {
+                    let attributes = h.selector_attributes();
+                    h.active && rule.attributes.iter().all(|a| attributes.contains(a))
+                })
+            })
+            .collect();
+        let filter = format!("rule_id = '{}' AND state != 'resolved'", rule.id);
+        let open = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        for observation in &observations {
+            let existing = open
+                .iter()
+                .find(|a| a.host_id == observation.host_id && a.sched_id == observation.sched_id);
+            let mut alert = match existing {
+                Some(alert) => alert.clone(),
+                None => {
+                    let mut labels = rule.labels.clone();
+                    labels.insert("alertname".into(), rule.name.clone());
+                    labels.insert("host".into(), hosts[&observation.host_id].alias.clone());
+                    Alert {
+                        id: Uuid::new_v4(),
+                        rule_id: rule.id,
+                        host_id: observation.host_id,
+                        sched_id: observation.sched_id,
+                        severity: rule.severity.clone(),
+                        fingerprint: fingerprint(&labels),
+                        labels,
+                        started: now,
+                        ..Default::default()
+                    }
+                }
+            };
+            alert.summary = observation.summary.clone();
+            alert.value = Some(observation.value);
+            if alert.state == AlertState::Pending
+                && now - alert.started >= Duration::seconds(rule.for_secs)
+            {
+                info!("Alert {} firing: {}", rule.name, alert.summary);
+                alert.state = AlertState::Firing;
+                alert.fired = Some(now);
+                changed.push(alert.clone());
+            }
+            let _res = alert.insert_into_db(pool.acquire().await.unwrap()).await;
+        }
+        for mut alert in open {
+            let holds = observations
+                .iter()
+                .any(|o| o.host_id == alert.host_id && o.sched_id == alert.sched_id);
+            if holds {
+                continue;
+            }
+            if alert.state == AlertState::Pending {
+                debug!("Pending alert {} cleared", alert.id);
+                let filter = format!("id = '{}'", alert.id);
+                let _res =
+                    delete_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+            } else {
+                info!("Alert {} resolved: {}", rule.name, alert.summary);
+                alert.state = AlertState::Resolved;
+                alert.resolved = Some(now);
+                changed.push(alert.clone());
+                let _res = alert.insert_into_db(pool.acquire().await.unwrap()).await;
+            }
+        }
+    }
+    changed
+}
+
+/// API to get all alert rules
+pub async fn get_alert_rules_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let rule_vec = get_alert_rules_from_db(None, pool.acquire().await.unwrap()).await;
+    Json(rule_vec)
+}
+
+/// API to get one alert rule
+pub async fn get_one_alert_rule_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let rule_vec = get_alert_rules_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(rule_vec)
+}
+
+/// API to create or replace an alert rule
+pub async fn post_alert_rules_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<AlertRule>,
+) -> Response {
+    debug!("{:?}", payload);
+    if let Err(e) = payload.validate() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    match payload.insert_into_db(pool.acquire().await.unwrap()).await {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(_) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Schedule ID not found, could not add alert rule",
+        )
+            .into_response(),
+    }
+}
+
+/// API to delete an alert rule and its alerts
+pub async fn delete_one_alert_rule_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_alert_rules_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AlertQueryParams {
+    state: Option<AlertState>,
+    severity: Option<Severity>,
+}
+
+/// API to get alerts, newest first
+pub async fn get_alerts_api(
+    _claims: Claims,
+    Query(params): Query<AlertQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec!["1=1".to_string()];
+    if let Some(state) = params.state {
+        conditions.push(format!("state='{state}'"));
+    }
+    if let Some(severity) = params.severity {
+        conditions.push(format!("severity='{severity}'"));
+    }
+    let filter = format!("{} ORDER BY started DESC", conditions.join(" AND "));
+    let alert_vec = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(alert_vec)
+}
+
+/// API to get one alert
+pub async fn get_one_alert_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let alert_vec = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(alert_vec)
+}
+
+/// Open alerts sharing a fingerprint
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct AlertGroup {
+    pub fingerprint: String,
+    pub labels: BTreeMap<String, String>,
+    /// firing if any alert of the group fires
+    pub state: AlertState,
+    /// highest severity of the group
+    pub severity: Severity,
+    pub alerts: Vec<Uuid>,
+    pub started: DateTime<Utc>,
+    /// silence or maintenance window suppressing notifications
+    pub suppressed_by: Option<String>,
+}
+
+/// group all pending and firing alerts by fingerprint
+pub async fn alert_groups(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<AlertGroup> {
+    let open = get_alerts_from_db(
+        Some("state != 'resolved' ORDER BY started"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    let hosts: HashMap<Uuid, Host> = get_hosts_from_db(None, pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .map(|h| (h.id, h))
+        .collect();
+    let mut groups: Vec<AlertGroup> = vec![];
+    for alert in open {
+        if let Some(group) = groups
+            .iter_mut()
+            .find(|g| g.fingerprint == alert.fingerprint)
+        {
+            if alert.state == AlertState::Firing {
+                group.state = AlertState::Firing;
+            }
+            if alert.severity > group.severity {
+                group.severity = alert.severity.clone();
+            }
+            group.alerts.push(alert.id);
+            continue;
+        }
+        let suppressed_by = match hosts.get(&alert.host_id) {
+            Some(host) => {
+                let subject = Subject {
+                    host,
+                    sched_id: Some(alert.sched_id),
+                    labels: &alert.labels,
+                };
+                suppressed_by(&subject, now, pool).await
+            }
+            None => None,
+        };
+        groups.push(AlertGroup {
+            fingerprint: alert.fingerprint,
+            labels: alert.labels,
+            state: alert.state,
+            severity: alert.severity,
+            alerts: vec![alert.id],
+            started: alert.started,
+            suppressed_by,
+        });
+    }
+    groups
+}
+
+/// API to get pending and firing alerts grouped by fingerprint
+pub async fn get_alert_groups_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    Json(alert_groups(Utc::now(), &pool).await)
+}
+
+/// API to acknowledge an alert as the logged in user
+pub async fn acknowledge_alert_api(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    match acknowledge_alert(id, claims.sub.as_str(), &pool).await {
+        Some(alert) => Json(alert).into_response(),
+        None => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+    }
+}
+
+/// link acknowledging alert `id` without login, valid for `ACK_LINK_TTL`
+pub fn ack_url(id: Uuid, now: DateTime<Utc>) -> String {
+    let claims = AckClaims {
+        aud: ACK_AUDIENCE.into(),
+        sub: id,
+        exp: (now + ACK_LINK_TTL).timestamp() as usize,
+    };
+    let token = encode(&Header::default(), &claims, &KEYS.encoding).unwrap_or_default();
+    let base = EXTERNAL_URL.get().map(String::as_str).unwrap_or_default();
+    format!("{base}/api/v1/alerts/ack/{token}")
+}
+
+/// id of the alert a signed acknowledge link was issued for, `None` if invalid or expired
+fn ack_link_alert(token: &str) -> Option<Uuid> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[ACK_AUDIENCE]);
+    decode::<AckClaims>(token, &KEYS.decoding, &validation)
+        .ok()
+        .map(|t| t.claims.sub)
+}
+
+/// escape text for an html page
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// API to show the confirmation page of a signed acknowledge link, no login required
+///
+/// does not change the alert, link scanners of mail and chat services open these links
+pub async fn acknowledge_link_page_api(
+    Path(token): Path<String>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let Some(id) = ack_link_alert(&token) else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired link").into_response();
+    };
+    let filter = format!("id='{id}'");
+    let Some(alert) = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return (StatusCode::NOT_FOUND, "Alert not found").into_response();
+    };
+    let html = format!(
+        r#"<!DOCTYPE html>
+    <html lang="en">
+    <head>
+      <meta charset="utf-8" />
+      <meta name="viewport" content="width=device-width, initial-scale=1" />
+      <title>Unpatched Server - Acknowledge alert</title>
+      <link rel="icon" href="/bandaid.svg">
+    </head>
+    <body>
+    <p>[{}] {}</p>
+    <form method="post"><button type="submit">Acknowledge</button></form>
+    </body>
+    </html>"#,
+        alert.severity,
+        escape_html(&alert.summary)
+    );
+    (StatusCode::OK, Html(html)).into_response()
+}
+
+/// API to acknowledge an alert through a signed link, no login required
+pub async fn acknowledge_link_api(
+    Path(token): Path<String>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let Some(id) = ack_link_alert(&token) else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired link").into_response();
+    };
+    match acknowledge_alert(id, "signed link", &pool).await {
+        Some(_) => (StatusCode::OK, "Alert acknowledged").into_response(),
+        None => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+    }
+}
+
+/// mark an alert as acknowledged by `user`, `None` if the alert does not exist
+pub async fn acknowledge_alert(id: Uuid, user: &str, pool: &SqlitePool) -> Option<Alert> {
+    let filter = format!("id='{id}'");
+    let mut alert = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()?;
+    alert.acknowledged_by = Some(user.to_string());
+    alert.acknowledged = Some(Utc::now());
+    let _res = alert
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+    Some(alert)
+}
+
+pub async fn get_alert_rules_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<AlertRule> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM alert_rules WHERE {f}"),
+        None => "SELECT * FROM alert_rules".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| AlertRule::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_alert_rules_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM alert_rules WHERE {f}"),
+        None => "DELETE FROM alert_rules".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_alerts_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Alert> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM alerts WHERE {f}"),
+        None => "SELECT * FROM alerts".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Alert::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_alerts_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM alerts WHERE {f}"),
+        None => "DELETE FROM alerts".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        fixtures,
+        metric::record_fields,
+        parser::Fields,
+        script::Script,
+        silence::Silence,
+    };
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_alerts() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let host = fixtures::host("web-1", &["prod"], &pool).await;
+        let host_id = host.id;
+        let script = Script {
+            id: Uuid::new_v4(),
+            output_regex: "^ok$".into(),
+            fail_on_no_match: true,
+            ..Default::default()
+        };
+        let sched_id = fixtures::schedule(script, &["prod"], &pool).await.id;
+
+        let failures = AlertRule {
+            id: Uuid::new_v4(),
+            name: "failing".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 2 },
+            severity: Severity::Critical,
+            labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            for_secs: 0,
+            sched_id: Some(sched_id),
+            attributes: vec!["prod".into()],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = failures
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+
+        let start = Utc::now() - Duration::minutes(3);
+        fixtures::run(&host, sched_id, "error", start, &pool).await;
+        assert!(evaluate_rules(Utc::now(), &pool).await.is_empty());
+        fixtures::run(
+            &host,
+            sched_id,
+            "error",
+            start + Duration::minutes(1),
+            &pool,
+        )
+        .await;
+        let changed = evaluate_rules(Utc::now(), &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].state, AlertState::Firing);
+        assert_eq!(changed[0].severity, Severity::Critical);
+        assert_eq!(changed[0].labels["team"], "ops");
+        assert_eq!(changed[0].labels["host"], "web-1");
+        assert_eq!(changed[0].fingerprint, fingerprint(&changed[0].labels));
+        let groups = alert_groups(Utc::now(), &pool).await;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].alerts, vec![changed[0].id]);
+        assert_eq!(groups[0].state, AlertState::Firing);
+        assert_eq!(groups[0].suppressed_by, None);
+        let silence = Silence {
+            id: Uuid::new_v4(),
+            host_id: None,
+            attributes: vec![],
+            sched_id: None,
+            labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            starts: Utc::now() - Duration::minutes(1),
+            ends: Utc::now() + Duration::hours(1),
+            comment: "".into(),
+            created_by: "".into(),
+            created: Utc::now(),
+        };
+        let _s = silence
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let groups = alert_groups(Utc::now(), &pool).await;
+        assert_eq!(
+            groups[0].suppressed_by,
+            Some(format!("silence {}", silence.id))
+        );
+        // still firing, no change
+        assert!(evaluate_rules(Utc::now(), &pool).await.is_empty());
+        fixtures::run(&host, sched_id, "ok", start + Duration::minutes(2), &pool).await;
+        let changed = evaluate_rules(Utc::now(), &pool).await;
+        assert_eq!(changed[0].state, AlertState::Resolved);
+        assert!(changed[0].resolved.is_some());
+
+        // metric threshold with a duration: pending first, firing after for_secs
+        let threshold = AlertRule {
+            id: Uuid::new_v4(),
+            name: "updates pending".into(),
+            condition: AlertCondition::MetricThreshold {
+                metric: "security_updates".into(),
+                operator: Operator::Gt,
+                value: 0.0,
+            },
+            for_secs: 7 * 86400,
+            ..failures.clone()
+        };
+        let _r = threshold
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let start = Utc::now();
+        let fields = Fields::from([("security_updates".to_string(), json!(3))]);
+        record_fields(host_id, sched_id, start, &fields, &pool).await;
+        assert!(evaluate_rules(start, &pool).await.is_empty());
+        let filter = format!("rule_id='{}'", threshold.id);
+        let pending = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(pending[0].state, AlertState::Pending);
+        assert_eq!(pending[0].value, Some(3.0));
+        let changed = evaluate_rules(start + Duration::days(7), &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].summary, "security_updates is 3 (> 0)");
+
+        // pending alerts that clear are dropped
+        let _res = delete_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        let later = |minutes: i64, updates: i64| {
+            let pool = pool.clone();
+            async move {
+                let fields = Fields::from([("security_updates".to_string(), json!(updates))]);
+                let ts = start + Duration::minutes(minutes);
+                record_fields(host_id, sched_id, ts, &fields, &pool).await;
+                evaluate_rules(ts, &pool).await
+            }
+        };
+        assert!(later(1, 4).await.is_empty());
+        let pending = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(pending[0].state, AlertState::Pending);
+        assert!(later(2, 0).await.is_empty());
+        assert!(
+            get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+
+        // no result in twice the schedule interval
+        let silence = AlertRule {
+            id: Uuid::new_v4(),
+            name: "silent".into(),
+            condition: AlertCondition::NoResult { factor: 2.0 },
+            ..failures.clone()
+        };
+        let _r = silence
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let filter = format!("rule_id='{}'", silence.id);
+        let _changed = evaluate_rules(Utc::now() + Duration::minutes(90), &pool).await;
+        assert!(
+            get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+        let _changed = evaluate_rules(Utc::now() + Duration::hours(3), &pool).await;
+        let silent = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(silent[0].state, AlertState::Firing);
+
+        // acknowledged through a signed link
+        let token = |url: String| url.rsplit('/').next().unwrap().to_string();
+        let url = ack_url(silent[0].id, Utc::now());
+        assert!(url.contains("/api/v1/alerts/ack/"));
+        let expired = ack_url(silent[0].id, Utc::now() - ACK_LINK_TTL - Duration::hours(1));
+        let api_expired = acknowledge_link_api(
+            axum::extract::Path(token(expired)),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_expired.status(), StatusCode::UNAUTHORIZED);
+        // opening the link only shows the confirmation page
+        let api_page = acknowledge_link_page_api(
+            axum::extract::Path(token(url.clone())),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_page.status(), StatusCode::OK);
+        let page = hyper::body::to_bytes(api_page.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&page).contains(r#"<form method="post">"#));
+        let opened = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(opened[0].acknowledged_by, None);
+        let api_link = acknowledge_link_api(
+            axum::extract::Path(token(url)),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_link.status(), StatusCode::OK);
+        let linked = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(linked[0].acknowledged_by.as_deref(), Some("signed link"));
+
+        // acknowledged by a user
+        let acked = acknowledge_alert(silent[0].id, "b@test.int", &pool)
+            .await
+            .unwrap();
+        assert_eq!(acked.acknowledged_by.as_deref(), Some("b@test.int"));
+        assert!(acknowledge_alert(Uuid::new_v4(), "b@test.int", &pool)
+            .await
+            .is_none());
+
+        // editing the rule keeps its alerts
+        let _r = AlertRule {
+            severity: Severity::Warning,
+            ..silence.clone()
+        }
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await
+        .unwrap();
+        assert_eq!(
+            get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .len(),
+            1
+        );
+
+        // deleting the rule deletes its alerts
+        let rule_filter = format!("id='{}'", silence.id);
+        let _del =
+            delete_alert_rules_from_db(Some(&rule_filter), pool.acquire().await.unwrap()).await;
+        assert!(
+            get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "failing".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 3 },
+            severity: Severity::Warning,
+            labels: BTreeMap::new(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let api_post = post_alert_rules_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(rule.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let invalid = AlertRule {
+            condition: AlertCondition::ConsecutiveFailures { count: 0 },
+            ..rule.clone()
+        };
+        let api_post_invalid = post_alert_rules_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_post_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let unknown_schedule = AlertRule {
+            id: Uuid::new_v4(),
+            sched_id: Some(Uuid::new_v4()),
+            ..rule.clone()
+        };
+        let api_post_unknown = post_alert_rules_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(unknown_schedule),
+        )
+        .await;
+        assert_eq!(api_post_unknown.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_all = get_alert_rules_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+
+        let api_get_one = get_one_alert_rule_api(
+            claims.clone(),
+            axum::extract::Path(rule.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+
+        let api_alerts = get_alerts_api(
+            claims.clone(),
+            axum::extract::Query(AlertQueryParams {
+                state: Some(AlertState::Firing),
+                severity: Some(Severity::Critical),
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_alerts.status(), StatusCode::OK);
+
+        let api_get_alert = get_one_alert_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_alert.status(), StatusCode::OK);
+
+        let api_ack = acknowledge_alert_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_ack.status(), StatusCode::NOT_FOUND);
+
+        let api_link = acknowledge_link_api(
+            axum::extract::Path("garbage".into()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_link.status(), StatusCode::UNAUTHORIZED);
+        let token = ack_url(Uuid::new_v4(), Utc::now());
+        let api_link_unknown = acknowledge_link_api(
+            axum::extract::Path(token.rsplit('/').next().unwrap().into()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_link_unknown.status(), StatusCode::NOT_FOUND);
+        let api_page_unknown = acknowledge_link_page_api(
+            axum::extract::Path(token.rsplit('/').next().unwrap().into()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_page_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_del = delete_one_alert_rule_api(
+            claims.clone(),
+            axum::extract::Path(rule.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    drift::normalize_output,
+    execution::{get_executions_from_db, Execution},
+    host::{get_hosts_from_db, Host, ScheduleState},
+    jwt::Claims,
+    script::{get_scripts_from_db, Script},
+};
+
+/// Host in a comparison with the run its value is taken from
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ComparedHost {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub execution_id: Option<Uuid>,
+    pub executed: Option<DateTime<Utc>>,
+}
+
+/// Hosts sharing a value
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ValueGroup {
+    pub value: Value,
+    pub count: usize,
+    /// share of the reporting hosts in percent
+    pub share: f64,
+    /// less common than the outlier threshold
+    pub outlier: bool,
+    pub hosts: Vec<ComparedHost>,
+}
+
+/// Host with an uncommon value
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Outlier {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub value: Value,
+}
+
+/// Latest output or parsed field of a script on all hosts it is scheduled on, grouped by value
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FleetComparison {
+    pub script_id: Uuid,
+    pub script_name: String,
+    pub field: Option<String>,
+    /// active hosts with an active schedule of the script
+    pub hosts: usize,
+    /// hosts with a value
+    pub reported: usize,
+    /// most common value first
+    pub groups: Vec<ValueGroup>,
+    pub outliers: Vec<Outlier>,
+    /// hosts without a run, or without the field in their latest run
+    pub missing: Vec<ComparedHost>,
+}
+
+/// Compared value of an execution, the field of the parsed output or the normalized output
+fn compared_value(exe: &Execution, field: Option<&str>) -> Option<Value> {
+    match field {
+        Some(name) => exe.fields.get(name).cloned(),
+        None => Some(Value::String(
+            normalize_output(&exe.output).trim_end().to_string(),
+        )),
+    }
+}
+
+/// Group the latest run of `script` on every targeted host by value, groups holding less than
+/// `outlier_percent` of the reporting hosts are outliers unless all hosts agree
+pub async fn compare(
+    script: &Script,
+    field: Option<&str>,
+    outlier_percent: f64,
+    pool: &SqlitePool,
+) -> FleetComparison {
+    let hosts = get_hosts_from_db(
+        Some("active = 1 ORDER BY alias"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    let mut targeted: Vec<Host> = vec![];
+    for host in hosts {
+        let schedules = host
+            .get_all_schedules(pool.acquire().await.unwrap(), ScheduleState::Active)
+            .await;
+        if schedules.iter().any(|s| s.script_id == script.id) {
+            targeted.push(host);
+        }
+    }
+    // latest run of the script per host
+    let filter = format!(
+        r#"id IN (SELECT id FROM (
+            SELECT id, ROW_NUMBER() OVER (PARTITION BY host_id ORDER BY response DESC) AS rn
+            FROM executions WHERE response IS NOT NULL AND response != '1970-01-01T00:00:00.000Z'
+            AND sched_id IN (SELECT id FROM schedules WHERE script_id = '{}')
+        ) WHERE rn = 1)"#,
+        script.id
+    );
+    let latest: BTreeMap<Uuid, Execution> =
+        get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .map(|exe| (exe.host_id, exe))
+            .collect();
+
+    let mut grouped: BTreeMap<String, (Value, Vec<ComparedHost>)> = BTreeMap::new();
+    let mut missing = vec![];
+    for host in &targeted {
+        let exe = latest.get(&host.id);
+        let compared = ComparedHost {
+            host_id: host.id,
+            alias: host.alias.clone(),
+            execution_id: exe.map(|e| e.id),
+            executed: exe.and_then(|e| e.response),
+        };
+        match exe.and_then(|e| compared_value(e, field)) {
+            Some(value) => grouped
+                .entry(value.to_string())
+                .or_insert_with(|| (value, vec![]))
+                .1
+                .push(compared),
+            None => missing.push(compared),
+        }
+    }
+    let reported: usize = grouped.values().map(|(_, hosts)| hosts.len()).sum();
+    let distinct = grouped.len();
+    let mut groups: Vec<ValueGroup> = grouped
+        .into_values()
+        .map(|(value, hosts)| {
+            let share = hosts.len() as f64 * 100.0 / reported as f64;
+            ValueGroup {
+                value,
+                count: hosts.len(),
+                share: (share * 10.0).round() / 10.0,
+                outlier: distinct > 1 && share < outlier_percent,
+                hosts,
+            }
+        })
+        .collect();
+    // stable sort keeps equally common values ordered by value
+    groups.sort_by_key(|g| std::cmp::Reverse(g.count));
+    let outliers = groups
+        .iter()
+        .filter(|g| g.outlier)
+        .flat_map(|g| {
+            g.hosts.iter().map(|h| Outlier {
+                host_id: h.host_id,
+                alias: h.alias.clone(),
+                value: g.value.clone(),
+            })
+        })
+        .collect();
+    FleetComparison {
+        script_id: script.id,
+        script_name: script.name.clone(),
+        field: field.map(|f| f.to_string()),
+        hosts: targeted.len(),
+        reported,
+        groups,
+        outliers,
+        missing,
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ComparisonQueryParams {
+    /// parsed field to compare instead of the output
+    field: Option<String>,
+    /// groups below this share of the reporting hosts are outliers, 10 without
+    outlier_percent: Option<f64>,
+}
+
+/// API to compare the latest output or a parsed field of a script across the fleet
+pub async fn get_script_comparison_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ComparisonQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let outlier_percent = params.outlier_percent.unwrap_or(10.0);
+    if !(0.0..=100.0).contains(&outlier_percent) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "outlier_percent must be between 0 and 100",
+        )
+            .into_response();
+    }
+    let filter = format!("id='{id}'");
+    let scripts = get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(script) = scripts.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    Json(compare(script, params.field.as_deref(), outlier_percent, &pool).await).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        fixtures,
+        parser::OutputParser,
+    };
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_compare() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let script = Script {
+            id: Uuid::new_v4(),
+            name: "openssl_version".into(),
+            script_content: "echo \"version=$(openssl version | cut -d' ' -f2)\"".into(),
+            output_regex: ".*".into(),
+            parser: OutputParser::KeyValue,
+            ..Default::default()
+        };
+        let sched = fixtures::schedule(script.clone(), &["openssl"], &pool).await;
+        let mut hosts = vec![];
+        for i in 0..10 {
+            hosts.push(fixtures::host(&format!("web-{i}"), &["openssl"], &pool).await);
+        }
+        // not targeted by the schedule
+        let other = fixtures::host("db-1", &["postgres"], &pool).await;
+        fixtures::run(&other, sched.id, "version=1.0.2k", fixtures::at(0), &pool).await;
+        // web-0 upgraded, only its latest run counts
+        fixtures::run(&hosts[0], sched.id, "version=3.0.2", fixtures::at(0), &pool).await;
+        fixtures::run(
+            &hosts[0],
+            sched.id,
+            "version=3.0.13",
+            fixtures::at(1),
+            &pool,
+        )
+        .await;
+        for host in &hosts[1..7] {
+            fixtures::run(host, sched.id, "version=3.0.13\n", fixtures::at(0), &pool).await;
+        }
+        for host in &hosts[7..9] {
+            fixtures::run(host, sched.id, "version=1.1.1w", fixtures::at(0), &pool).await;
+        }
+        // web-9 never reported
+
+        let fields = compare(&script, Some("version"), 25.0, &pool).await;
+        assert_eq!((fields.hosts, fields.reported), (10, 9));
+        let values: Vec<(Value, usize, bool)> = fields
+            .groups
+            .iter()
+            .map(|g| (g.value.clone(), g.count, g.outlier))
+            .collect();
+        assert_eq!(
+            values,
+            vec![(json!("3.0.13"), 7, false), (json!("1.1.1w"), 2, true)]
+        );
+        assert_eq!(fields.groups[1].share, 22.2);
+        let outliers: Vec<&str> = fields.outliers.iter().map(|o| o.alias.as_str()).collect();
+        assert_eq!(outliers, vec!["web-7", "web-8"]);
+        assert_eq!(fields.missing.len(), 1);
+        assert_eq!(fields.missing[0].alias, "web-9");
+        assert_eq!(fields.missing[0].execution_id, None);
+
+        // the whole output, trailing whitespace does not make a difference
+        let outputs = compare(&script, None, 10.0, &pool).await;
+        assert_eq!(outputs.groups[0].value, json!("version=3.0.13"));
+        assert_eq!(outputs.groups[0].count, 7);
+        assert!(outputs.outliers.is_empty());
+
+        // a field the parser did not produce
+        let unknown = compare(&script, Some("build"), 10.0, &pool).await;
+        assert!(unknown.groups.is_empty());
+        assert_eq!(unknown.missing.len(), 10);
+
+        let claims: Claims = Claims::default();
+        let api_compare = get_script_comparison_api(
+            claims.clone(),
+            axum::extract::Path(script.id),
+            axum::extract::Query(ComparisonQueryParams {
+                field: Some("version".into()),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_compare.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_compare.into_body())
+            .await
+            .unwrap();
+        let comparison: FleetComparison = serde_json::from_slice(&body).unwrap();
+        assert_eq!(comparison.field.as_deref(), Some("version"));
+        assert!(comparison.outliers.is_empty());
+        let api_invalid = get_script_comparison_api(
+            claims.clone(),
+            axum::extract::Path(script.id),
+            axum::extract::Query(ComparisonQueryParams {
+                outlier_percent: Some(120.0),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let api_unknown = get_script_comparison_api(
+            claims,
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::Query(ComparisonQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,992 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    alert::Operator,
+    db::{utc_from_str, utc_to_str},
+    execution::{get_executions_from_db, Execution, Verdict},
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    script::{get_scripts_from_db, Script},
+};
+
+/// Result a check of a baseline expects from the latest execution of its script
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// the verdict is success
+    #[default]
+    Success,
+    /// the verdict is failure, e.g. a script finding a forbidden setting
+    Failure,
+    /// a parsed field compared to `value`, strings and booleans only support `eq` and `ne`
+    Field {
+        name: String,
+        #[serde(default = "default_operator")]
+        operator: Operator,
+        value: Value,
+    },
+}
+
+fn default_operator() -> Operator {
+    Operator::Eq
+}
+
+impl Expectation {
+    fn is_met(&self, exe: &Execution) -> bool {
+        match self {
+            Expectation::Success => exe.verdict == Some(Verdict::Success),
+            Expectation::Failure => exe.verdict == Some(Verdict::Failure),
+            Expectation::Field {
+                name,
+                operator,
+                value,
+            } => {
+                let Some(field) = exe.fields.get(name) else {
+                    return false;
+                };
+                match (field.as_f64(), value.as_f64(), operator) {
+                    (Some(left), Some(right), _) => operator.compare(left, right),
+                    (_, _, Operator::Eq) => field == value,
+                    (_, _, Operator::Ne) => field != value,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct BaselineCheck {
+    pub script_id: Uuid,
+    #[serde(default)]
+    pub expected: Expectation,
+    /// share of the check in the score of the baseline
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// how to fix a failing check
+    #[serde(default)]
+    pub remediation: String,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// Named group of checks, e.g. "SSH hardening"
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Baseline {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub checks: Vec<BaselineCheck>,
+    /// only hosts with all of these attributes are scored, all hosts if empty
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for Baseline {
+    fn from(s: SqliteRow) -> Self {
+        Baseline {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            description: s.get::<String, _>("description"),
+            checks: serde_json::from_str(&s.get::<String, _>("checks")).unwrap_or_default(),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+impl Baseline {
+    /// Insert or Update `Baseline` in baselines table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | description | TEXT |
+    /// | checks | TEXT | json list of checks (script_id, expected, weight, remediation)
+    /// | attributes | TEXT | json list
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO baselines(id, name, description, checks, attributes, created) VALUES(?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, description=excluded.description, checks=excluded.checks, attributes=excluded.attributes"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(self.description)
+            .bind(serde_json::to_string(&self.checks).unwrap())
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+            .unwrap()
+    }
+
+    async fn validate(&self, pool: &SqlitePool) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.checks.is_empty() {
+            return Err("at least one check is required".into());
+        }
+        let scripts = get_scripts_from_db(None, pool.acquire().await.unwrap()).await;
+        for (i, check) in self.checks.iter().enumerate() {
+            if !scripts.iter().any(|s| s.id == check.script_id) {
+                return Err(format!("check {i}: script {} not found", check.script_id));
+            }
+            if check.weight.is_nan() || check.weight <= 0.0 {
+                return Err(format!("check {i}: weight must be positive"));
+            }
+            if let Expectation::Field { name, .. } = &check.expected {
+                if name.is_empty() {
+                    return Err(format!("check {i}: field name must not be empty"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn applies_to(&self, host: &Host) -> bool {
+        let attributes = host.selector_attributes();
+        host.active && self.attributes.iter().all(|a| attributes.contains(a))
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// the script has no finished execution on the host
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CheckResult {
+    pub script_id: Uuid,
+    pub script_name: String,
+    pub status: CheckStatus,
+    pub weight: f64,
+    /// remediation of checks not passing
+    pub remediation: Option<String>,
+    /// latest finished execution of the script on the host
+    pub execution_id: Option<Uuid>,
+    pub executed: Option<DateTime<Utc>>,
+}
+
+/// Compliance of a host with a baseline
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostCompliance {
+    pub baseline_id: Uuid,
+    pub baseline: String,
+    pub host_id: Uuid,
+    pub alias: String,
+    /// weight of the passing checks in percent of all checks
+    pub score: f64,
+    /// all checks pass
+    pub passed: bool,
+    pub failed: i64,
+    pub unknown: i64,
+    pub checks: Vec<CheckResult>,
+}
+
+/// Score of a host in a baseline since `ts`, recorded when it changes
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ComplianceScore {
+    pub baseline_id: Uuid,
+    pub host_id: Uuid,
+    pub score: f64,
+    pub passed: bool,
+    pub failed: i64,
+    pub unknown: i64,
+    pub ts: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for ComplianceScore {
+    fn from(s: SqliteRow) -> Self {
+        ComplianceScore {
+            baseline_id: s.get::<String, _>("baseline_id").parse().unwrap(),
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            score: s.get::<f64, _>("score"),
+            passed: s.get::<bool, _>("passed"),
+            failed: s.get::<i64, _>("failed"),
+            unknown: s.get::<i64, _>("unknown"),
+            ts: utc_from_str(&s.get::<String, _>("ts")),
+        }
+    }
+}
+
+/// score `host` against all checks of `baseline`
+async fn host_compliance(
+    baseline: &Baseline,
+    host: &Host,
+    scripts: &HashMap<Uuid, Script>,
+    pool: &SqlitePool,
+) -> HostCompliance {
+    let mut checks = vec![];
+    for check in &baseline.checks {
+        let filter = format!(
+            "host_id='{}' AND verdict IS NOT NULL AND sched_id IN (SELECT id FROM schedules WHERE script_id='{}') ORDER BY response DESC LIMIT 1",
+            host.id, check.script_id
+        );
+        let latest = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .next();
+        let status = match &latest {
+            Some(exe) if check.expected.is_met(exe) => CheckStatus::Pass,
+            Some(_) => CheckStatus::Fail,
+            None => CheckStatus::Unknown,
+        };
+        checks.push(CheckResult {
+            script_id: check.script_id,
+            script_name: scripts
+                .get(&check.script_id)
+                .map(|s| s.name.clone())
+                .unwrap_or_default(),
+            status,
+            weight: check.weight,
+            remediation: (status != CheckStatus::Pass).then(|| check.remediation.clone()),
+            execution_id: latest.as_ref().map(|e| e.id),
+            executed: latest.and_then(|e| e.response),
+        });
+    }
+    let total: f64 = checks.iter().map(|c| c.weight).sum();
+    let passing: f64 = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Pass)
+        .map(|c| c.weight)
+        .sum();
+    let score = match total > 0.0 {
+        true => (passing / total * 1000.0).round() / 10.0,
+        false => 0.0,
+    };
+    let count = |status| checks.iter().filter(|c| c.status == status).count() as i64;
+    HostCompliance {
+        baseline_id: baseline.id,
+        baseline: baseline.name.clone(),
+        host_id: host.id,
+        alias: host.alias.clone(),
+        score,
+        passed: checks.iter().all(|c| c.status == CheckStatus::Pass),
+        failed: count(CheckStatus::Fail),
+        unknown: count(CheckStatus::Unknown),
+        checks,
+    }
+}
+
+async fn scripts_by_id(pool: &SqlitePool) -> HashMap<Uuid, Script> {
+    get_scripts_from_db(None, pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .map(|s| (s.id, s))
+        .collect()
+}
+
+/// compliance of all active hosts `baseline` applies to, lowest score first
+pub async fn evaluate_baseline(baseline: &Baseline, pool: &SqlitePool) -> Vec<HostCompliance> {
+    let scripts = scripts_by_id(pool).await;
+    let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut results = vec![];
+    for host in hosts.iter().filter(|h| baseline.applies_to(h)) {
+        results.push(host_compliance(baseline, host, &scripts, pool).await);
+    }
+    results.sort_by(|a, b| a.score.total_cmp(&b.score).then(a.alias.cmp(&b.alias)));
+    results
+}
+
+/// compliance of `host` with all baselines applying to it
+pub async fn evaluate_host(host: &Host, pool: &SqlitePool) -> Vec<HostCompliance> {
+    let scripts = scripts_by_id(pool).await;
+    let baselines =
+        get_baselines_from_db(Some("1=1 ORDER BY name"), pool.acquire().await.unwrap()).await;
+    let mut results = vec![];
+    for baseline in baselines.iter().filter(|b| b.applies_to(host)) {
+        results.push(host_compliance(baseline, host, &scripts, pool).await);
+    }
+    results
+}
+
+/// score all hosts in all baselines and keep the scores that changed since the last run
+pub async fn record_scores(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<ComplianceScore> {
+    let baselines = get_baselines_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut recorded = vec![];
+    for baseline in &baselines {
+        for result in evaluate_baseline(baseline, pool).await {
+            let filter = format!(
+                "baseline_id='{}' AND host_id='{}' ORDER BY ts DESC LIMIT 1",
+                baseline.id, result.host_id
+            );
+            let last = get_scores_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+            let score = ComplianceScore {
+                baseline_id: baseline.id,
+                host_id: result.host_id,
+                score: result.score,
+                passed: result.passed,
+                failed: result.failed,
+                unknown: result.unknown,
+                ts: now,
+            };
+            let unchanged = last.first().is_some_and(|l| {
+                l.score == score.score
+                    && l.passed == score.passed
+                    && l.failed == score.failed
+                    && l.unknown == score.unknown
+            });
+            if unchanged {
+                continue;
+            }
+            let q = r#"INSERT INTO compliance_scores(baseline_id, host_id, score, passed, failed, unknown, ts) VALUES(?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(baseline_id, host_id, ts) DO NOTHING"#;
+            let _res = query(q)
+                .bind(score.baseline_id.to_string())
+                .bind(score.host_id.to_string())
+                .bind(score.score)
+                .bind(score.passed)
+                .bind(score.failed)
+                .bind(score.unknown)
+                .bind(utc_to_str(now))
+                .execute(&mut *pool.acquire().await.unwrap())
+                .await;
+            recorded.push(score);
+        }
+    }
+    debug!("{} compliance scores changed", recorded.len());
+    recorded
+}
+
+/// Check of a baseline failing on hosts of the fleet
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FailingCheck {
+    pub script_id: Uuid,
+    pub script_name: String,
+    pub remediation: String,
+    pub failed: i64,
+    pub unknown: i64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostScore {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub score: f64,
+    pub passed: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct BaselineReport {
+    pub baseline_id: Uuid,
+    pub name: String,
+    pub hosts: i64,
+    pub passing: i64,
+    pub average_score: f64,
+    /// checks not passing on at least one host, most failures first
+    pub failing_checks: Vec<FailingCheck>,
+    /// lowest score first
+    pub host_scores: Vec<HostScore>,
+}
+
+/// Compliance of the fleet with all baselines
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ComplianceReport {
+    /// average of all host scores in all baselines
+    pub average_score: f64,
+    /// lowest average score first
+    pub baselines: Vec<BaselineReport>,
+    pub generated: DateTime<Utc>,
+}
+
+fn average(scores: impl Iterator<Item = f64>) -> f64 {
+    let scores: Vec<f64> = scores.collect();
+    match scores.is_empty() {
+        true => 0.0,
+        false => (scores.iter().sum::<f64>() / scores.len() as f64 * 10.0).round() / 10.0,
+    }
+}
+
+pub async fn fleet_report(now: DateTime<Utc>, pool: &SqlitePool) -> ComplianceReport {
+    let baselines = get_baselines_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut reports = vec![];
+    let mut all_scores = vec![];
+    for baseline in &baselines {
+        let results = evaluate_baseline(baseline, pool).await;
+        all_scores.extend(results.iter().map(|r| r.score));
+        let mut failing_checks: Vec<FailingCheck> = vec![];
+        for (i, check) in baseline.checks.iter().enumerate() {
+            let statuses: Vec<&CheckResult> = results.iter().map(|r| &r.checks[i]).collect();
+            let count = |status| statuses.iter().filter(|c| c.status == status).count() as i64;
+            let (failed, unknown) = (count(CheckStatus::Fail), count(CheckStatus::Unknown));
+            if failed + unknown == 0 {
+                continue;
+            }
+            failing_checks.push(FailingCheck {
+                script_id: check.script_id,
+                script_name: statuses
+                    .first()
+                    .map(|c| c.script_name.clone())
+                    .unwrap_or_default(),
+                remediation: check.remediation.clone(),
+                failed,
+                unknown,
+            });
+        }
+        failing_checks.sort_by_key(|c| std::cmp::Reverse((c.failed, c.unknown)));
+        reports.push(BaselineReport {
+            baseline_id: baseline.id,
+            name: baseline.name.clone(),
+            hosts: results.len() as i64,
+            passing: results.iter().filter(|r| r.passed).count() as i64,
+            average_score: average(results.iter().map(|r| r.score)),
+            failing_checks,
+            host_scores: results
+                .into_iter()
+                .map(|r| HostScore {
+                    host_id: r.host_id,
+                    alias: r.alias,
+                    score: r.score,
+                    passed: r.passed,
+                })
+                .collect(),
+        });
+    }
+    reports.sort_by(|a, b| {
+        a.average_score
+            .total_cmp(&b.average_score)
+            .then(a.name.cmp(&b.name))
+    });
+    ComplianceReport {
+        average_score: average(all_scores.into_iter()),
+        baselines: reports,
+        generated: now,
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct HistoryQueryParams {
+    host_id: Option<Uuid>,
+    since: Option<DateTime<Utc>>,
+}
+
+/// API to get all compliance baselines
+pub async fn get_baselines_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let baseline_vec =
+        get_baselines_from_db(Some("1=1 ORDER BY name"), pool.acquire().await.unwrap()).await;
+    Json(baseline_vec)
+}
+
+/// API to get one compliance baseline
+pub async fn get_one_baseline_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let baselines = get_baselines_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match baselines.into_iter().next() {
+        Some(baseline) => Json(baseline).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// API to create or replace a compliance baseline
+pub async fn post_baselines_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<Baseline>,
+) -> Response {
+    if let Err(e) = payload.validate(&pool).await {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Nothing added",
+        )
+            .into_response()
+    }
+}
+
+/// API to delete a compliance baseline with its score history
+pub async fn delete_one_baseline_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_baselines_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// API to get the compliance of all hosts with a baseline, lowest score first
+pub async fn get_baseline_compliance_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let baselines = get_baselines_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(baseline) = baselines.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    Json(evaluate_baseline(baseline, &pool).await).into_response()
+}
+
+/// API to get the score history of a baseline, oldest first
+pub async fn get_baseline_history_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<HistoryQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec![format!("baseline_id = '{id}'")];
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    if let Some(since) = params.since {
+        conditions.push(format!("ts >= '{}'", utc_to_str(since)));
+    }
+    conditions.push("1=1 ORDER BY ts, host_id".into());
+    let filter = conditions.join(" AND ");
+    let score_vec = get_scores_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(score_vec)
+}
+
+/// API to get the compliance of a host with all baselines applying to it
+pub async fn get_host_compliance_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let hosts = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(host) = hosts.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    Json(evaluate_host(host, &pool).await).into_response()
+}
+
+/// API to get the compliance report of the fleet
+pub async fn get_compliance_report_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    Json(fleet_report(Utc::now(), &pool).await)
+}
+
+pub async fn get_baselines_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Baseline> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM baselines WHERE {f}"),
+        None => "SELECT * FROM baselines".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Baseline::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_baselines_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM baselines WHERE {f}"),
+        None => "DELETE FROM baselines".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_scores_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<ComplianceScore> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM compliance_scores WHERE {f}"),
+        None => "SELECT * FROM compliance_scores".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| ComplianceScore::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        fixtures,
+        parser::OutputParser,
+        schedule::Schedule,
+    };
+    use chrono::Duration;
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_compliance() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let now = Utc::now();
+        let web = fixtures::host("web-1", &["linux"], &pool).await;
+        let db = fixtures::host("db-1", &["linux"], &pool).await;
+        let _win = fixtures::host("win-1", &["windows"], &pool).await;
+        let Schedule {
+            id: root_sched,
+            script_id: root_login,
+            ..
+        } = fixtures::schedule(
+            Script {
+                id: Uuid::new_v4(),
+                name: "sshd_root_login".into(),
+                script_content: "sshd -T | grep -i permitrootlogin".into(),
+                output_regex: "(?i)permitrootlogin no".into(),
+                fail_on_no_match: true,
+                ..Default::default()
+            },
+            &[],
+            &pool,
+        )
+        .await;
+        // succeeds if telnet is installed, the baseline expects it to fail
+        let Schedule {
+            id: telnet_sched,
+            script_id: telnet,
+            ..
+        } = fixtures::schedule(
+            Script {
+                id: Uuid::new_v4(),
+                name: "telnet_installed".into(),
+                script_content: "command -v telnet".into(),
+                output_regex: "telnet".into(),
+                fail_on_no_match: true,
+                ..Default::default()
+            },
+            &[],
+            &pool,
+        )
+        .await;
+        let Schedule {
+            id: config_sched,
+            script_id: ssh_config,
+            ..
+        } = fixtures::schedule(
+            Script {
+                id: Uuid::new_v4(),
+                name: "ssh_config".into(),
+                script_content: "sshd -T | tr ' ' '='".into(),
+                parser: OutputParser::KeyValue,
+                ..Default::default()
+            },
+            &[],
+            &pool,
+        )
+        .await;
+        let mut baseline = Baseline {
+            id: Uuid::new_v4(),
+            name: "SSH hardening".into(),
+            description: "".into(),
+            checks: vec![
+                BaselineCheck {
+                    script_id: root_login,
+                    expected: Expectation::Success,
+                    weight: 2.0,
+                    remediation: "set PermitRootLogin no".into(),
+                },
+                BaselineCheck {
+                    script_id: telnet,
+                    expected: Expectation::Failure,
+                    weight: 1.0,
+                    remediation: "remove telnet".into(),
+                },
+                BaselineCheck {
+                    script_id: ssh_config,
+                    expected: serde_json::from_value(
+                        json!({"field": {"name": "maxauthtries", "operator": "le", "value": 4}}),
+                    )
+                    .unwrap(),
+                    weight: 1.0,
+                    remediation: "set MaxAuthTries 4".into(),
+                },
+            ],
+            attributes: vec!["linux".into()],
+            created: Utc::now(),
+        };
+        baseline.checks[0].weight = 0.0;
+        assert!(baseline.validate(&pool).await.is_err());
+        baseline.checks[0].weight = 2.0;
+        assert!(baseline.validate(&pool).await.is_ok());
+        let _b = baseline
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        // web-1 passes everything
+        fixtures::run(&web, root_sched, "permitrootlogin no", now, &pool).await;
+        fixtures::run(&web, telnet_sched, "", now, &pool).await;
+        fixtures::run(&web, config_sched, "maxauthtries=3", now, &pool).await;
+        // db-1 fails two checks and never ran the telnet check
+        fixtures::run(
+            &db,
+            root_sched,
+            "permitrootlogin yes",
+            now - Duration::hours(1),
+            &pool,
+        )
+        .await;
+        fixtures::run(&db, config_sched, "maxauthtries=6", now, &pool).await;
+
+        let results = evaluate_baseline(&baseline, &pool).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].alias, "db-1");
+        assert_eq!(results[0].score, 0.0);
+        assert!(!results[0].passed);
+        assert_eq!((results[0].failed, results[0].unknown), (2, 1));
+        let statuses: Vec<CheckStatus> = results[0].checks.iter().map(|c| c.status).collect();
+        assert_eq!(
+            statuses,
+            vec![CheckStatus::Fail, CheckStatus::Unknown, CheckStatus::Fail]
+        );
+        assert_eq!(
+            results[0].checks[0].remediation.as_deref(),
+            Some("set PermitRootLogin no")
+        );
+        assert_eq!(results[1].alias, "web-1");
+        assert_eq!(results[1].score, 100.0);
+        assert!(results[1].passed);
+        assert_eq!(results[1].checks[0].remediation, None);
+
+        let t1 = Utc::now();
+        assert_eq!(record_scores(t1, &pool).await.len(), 2);
+        assert!(record_scores(t1 + Duration::minutes(5), &pool)
+            .await
+            .is_empty());
+
+        // the latest execution counts
+        fixtures::run(&db, root_sched, "PermitRootLogin no", now, &pool).await;
+        let results = evaluate_host(&db, &pool).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].score, 50.0);
+        let t2 = t1 + Duration::minutes(10);
+        let recorded = record_scores(t2, &pool).await;
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].host_id, db.id);
+        let filter = format!("host_id='{}' ORDER BY ts", db.id);
+        let history = get_scores_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        let scores: Vec<f64> = history.iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![0.0, 50.0]);
+
+        let report = fleet_report(t2, &pool).await;
+        assert_eq!(report.average_score, 75.0);
+        assert_eq!(report.baselines.len(), 1);
+        let ssh = &report.baselines[0];
+        assert_eq!((ssh.hosts, ssh.passing), (2, 1));
+        assert_eq!(ssh.host_scores[0].alias, "db-1");
+        let failing: Vec<(&str, i64, i64)> = ssh
+            .failing_checks
+            .iter()
+            .map(|c| (c.script_name.as_str(), c.failed, c.unknown))
+            .collect();
+        assert_eq!(
+            failing,
+            vec![("ssh_config", 1, 0), ("telnet_installed", 0, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let now = Utc::now();
+        let web = fixtures::host("web-1", &[], &pool).await;
+        let Schedule {
+            id: sched_id,
+            script_id,
+            ..
+        } = fixtures::schedule(
+            Script {
+                id: Uuid::new_v4(),
+                name: "firewall_enabled".into(),
+                script_content: "ufw status".into(),
+                output_regex: "Status: active".into(),
+                fail_on_no_match: true,
+                ..Default::default()
+            },
+            &[],
+            &pool,
+        )
+        .await;
+        fixtures::run(&web, sched_id, "Status: active", now, &pool).await;
+
+        let payload = Baseline {
+            id: Uuid::new_v4(),
+            name: "CIS L1 subset".into(),
+            description: "selected CIS level 1 checks".into(),
+            checks: vec![BaselineCheck {
+                script_id,
+                expected: Expectation::Success,
+                weight: 1.0,
+                remediation: "ufw enable".into(),
+            }],
+            attributes: vec![],
+            created: Utc::now(),
+        };
+        let api_post = post_baselines_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::Json(payload.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+        let invalid = Baseline {
+            checks: vec![],
+            ..payload.clone()
+        };
+        let api_invalid = post_baselines_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_one = get_one_baseline_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_one.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_one.into_body()).await.unwrap();
+        let stored: Baseline = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stored.checks, payload.checks);
+
+        let api_compliance = get_baseline_compliance_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_compliance.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_compliance.into_body())
+            .await
+            .unwrap();
+        let results: Vec<HostCompliance> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].passed);
+
+        let api_host = get_host_compliance_api(
+            claims.clone(),
+            axum::extract::Path(web.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_host.status(), StatusCode::OK);
+        let api_unknown = get_host_compliance_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        let _scores = record_scores(Utc::now(), &pool).await;
+        let api_history = get_baseline_history_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::Query(HistoryQueryParams {
+                host_id: Some(web.id),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_history.into_body())
+            .await
+            .unwrap();
+        let history: Vec<ComplianceScore> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].score, 100.0);
+
+        let api_report =
+            get_compliance_report_api(claims.clone(), axum::extract::State(pool.clone()))
+                .await
+                .into_response();
+        assert_eq!(api_report.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_report.into_body()).await.unwrap();
+        let report: ComplianceReport = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report.baselines[0].name, "CIS L1 subset");
+
+        let api_delete = delete_one_baseline_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_delete.status(), StatusCode::OK);
+        // the history goes with the baseline
+        assert!(get_scores_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+        let api_gone = get_one_baseline_api(
+            claims,
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_gone.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,8 +1,14 @@
 use std::{str::FromStr, time::Duration};
 
 use crate::{
+    lifecycle,
+    package::INVENTORY_SCRIPT,
+    parser::OutputParser,
+    reboot::REBOOT_SCRIPT,
+    revision::{save_script, ScriptRevision},
     schedule::{self, Schedule},
-    script::{self, Script},
+    script::{self, Interpreter, Script},
+    update::UPDATES_SCRIPT,
     user::{self, hash_password, User},
 };
 use chrono::{DateTime, ParseError, Utc};
@@ -10,8 +16,8 @@ use email_address::EmailAddress;
 use sqlx::{
     pool::PoolConnection,
     query,
-    sqlite::{SqliteConnectOptions, SqliteQueryResult},
-    Pool, Row, Sqlite, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqliteQueryResult},
+    Connection, Pool, Row, Sqlite, SqlitePool,
 };
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -54,6 +60,37 @@ pub async fn create_database(connection: &str) -> Result<SqlitePool, sqlx::Error
 /// * scripts table
 /// * executions table
 /// * schedules table
+/// * users table
+/// * blacklist table
+/// * variables table
+/// * script revisions table
+/// * signing keys table
+/// * metrics table
+/// * alert rules table
+/// * alerts table
+/// * notification channels table
+/// * notification deliveries table
+/// * silences table
+/// * maintenance windows table
+/// * escalation policies table
+/// * routes table
+/// * escalations table
+/// * packages table
+/// * package changes table
+/// * pending updates table
+/// * advisories table
+/// * affected packages table
+/// * known exploited table
+/// * os lifecycles table, loaded with the bundled dataset
+/// * waivers table
+/// * waiver audit table
+/// * host reboots table
+/// * sboms table
+/// * baselines table
+/// * compliance scores table
+/// * drift events table
+/// * remediation actions table
+/// * remediations table
 /// * sample scripts
 /// * sample schedules
 ///
@@ -72,10 +109,41 @@ pub async fn init_database(
     create_schedules_table(pool.acquire().await?).await?;
     create_users_table(pool.acquire().await?).await?;
     create_blacklist_table(pool.acquire().await?).await?;
+    create_variables_table(pool.acquire().await?).await?;
+    create_script_revisions_table(pool.acquire().await?).await?;
+    create_signing_keys_table(pool.acquire().await?).await?;
+    create_metrics_table(pool.acquire().await?).await?;
+    create_alert_rules_table(pool.acquire().await?).await?;
+    create_alerts_table(pool.acquire().await?).await?;
+    create_notification_channels_table(pool.acquire().await?).await?;
+    create_notification_deliveries_table(pool.acquire().await?).await?;
+    create_silences_table(pool.acquire().await?).await?;
+    create_maintenance_windows_table(pool.acquire().await?).await?;
+    create_escalation_policies_table(pool.acquire().await?).await?;
+    create_routes_table(pool.acquire().await?).await?;
+    create_escalations_table(pool.acquire().await?).await?;
+    create_packages_table(pool.acquire().await?).await?;
+    create_package_changes_table(pool.acquire().await?).await?;
+    create_pending_updates_table(pool.acquire().await?).await?;
+    create_advisories_table(pool.acquire().await?).await?;
+    create_affected_packages_table(pool.acquire().await?).await?;
+    create_known_exploited_table(pool.acquire().await?).await?;
+    create_os_lifecycles_table(pool.acquire().await?).await?;
+    create_waivers_table(pool.acquire().await?).await?;
+    create_waiver_audit_table(pool.acquire().await?).await?;
+    create_host_reboots_table(pool.acquire().await?).await?;
+    create_sboms_table(pool.acquire().await?).await?;
+    create_baselines_table(pool.acquire().await?).await?;
+    create_compliance_scores_table(pool.acquire().await?).await?;
+    create_drift_events_table(pool.acquire().await?).await?;
+    create_remediation_actions_table(pool.acquire().await?).await?;
+    create_remediations_table(pool.acquire().await?).await?;
     let tables = query("PRAGMA table_list;")
         .fetch_all(&mut *pool.acquire().await.unwrap())
         .await?;
     info!("DB Init: created {} tables", tables.len());
+    let lifecycles = lifecycle::load_bundled(pool).await?;
+    debug!("DB init: {lifecycles} bundled os lifecycles loaded");
     let script_count = script::count_rows(pool.acquire().await?).await?;
     let schedule_count = schedule::count_rows(pool.acquire().await?).await?;
     if script_count == 0 && schedule_count == 0 {
@@ -106,6 +174,8 @@ pub async fn init_database(
 /// | active | NUMERIC | bool
 /// | last_checkin | TEXT | last checkin from agent
 /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | facts | TEXT | json map of host facts
+/// | interpreters | TEXT | json list of interpreters the agent supports
 async fn create_hosts_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
@@ -116,7 +186,982 @@ async fn create_hosts_table(mut connection: PoolConnection<Sqlite>) -> Result<()
             ip TEXT,
             active NUMERIC,
             last_checkin TEXT,
-            created TEXT
+            created TEXT,
+            facts TEXT,
+            interpreters TEXT
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing("hosts", "facts", "TEXT", &mut connection).await?;
+    add_column_if_missing("hosts", "interpreters", "TEXT", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Scripts Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | version | TEXT |
+/// | output_regex | TEXT | regex for result parsing
+/// | labels | TEXT | script labels
+/// | timeout_in_s | INT | timeout in seconds
+/// | script_content | TEXT | original script
+/// | parameters | TEXT | json list of declared parameters
+/// | interpreter | TEXT | json
+/// | environment | TEXT | json map of environment variables
+/// | working_dir | TEXT |
+/// | run_as | TEXT |
+/// | resource_limits | TEXT | json
+/// | revision | INT | latest revision of the script
+/// | fail_on_no_match | NUMERIC | bool
+/// | parser | TEXT | json
+/// | reboot | TEXT | json reboot step, NULL without
+/// | track_drift | NUMERIC | bool, record drift events of the output
+async fn create_scripts_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        scripts(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT,
+            version TEXT,
+            output_regex TEXT,
+            labels TEXT,
+            timeout_in_s INT,
+            script_content TEXT,
+            parameters TEXT,
+            interpreter TEXT,
+            environment TEXT,
+            working_dir TEXT,
+            run_as TEXT,
+            resource_limits TEXT,
+            revision INT,
+            fail_on_no_match NUMERIC,
+            parser TEXT,
+            reboot TEXT,
+            track_drift NUMERIC
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    for column in [
+        "parameters",
+        "interpreter",
+        "environment",
+        "working_dir",
+        "run_as",
+        "resource_limits",
+    ] {
+        add_column_if_missing("scripts", column, "TEXT", &mut connection).await?;
+    }
+    add_column_if_missing("scripts", "revision", "INT", &mut connection).await?;
+    add_column_if_missing("scripts", "fail_on_no_match", "NUMERIC", &mut connection).await?;
+    add_column_if_missing("scripts", "parser", "TEXT", &mut connection).await?;
+    add_column_if_missing("scripts", "reboot", "TEXT", &mut connection).await?;
+    add_column_if_missing("scripts", "track_drift", "NUMERIC", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Executions Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | request | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | response | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | host_id | TEXT | uuid
+/// | sched_id | TEXT | uuid
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | output | TEXT |
+/// | rendered_script | TEXT | script content as sent to the agent
+/// | script_revision | INT | revision of the script that was executed
+/// | matched | NUMERIC | bool, output matched the output_regex
+/// | verdict | TEXT | success, failure or parse_error
+/// | extracted | TEXT | json map of named capture groups
+/// | fields | TEXT | json map of typed fields from the output parser
+/// | parse_error | TEXT |
+/// | dispatched | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | reboot_state | TEXT | pending, awaiting_reconnect, post_check, succeeded, failed or timed_out
+/// | reboot_deadline | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | check_id | TEXT | uuid of the post-check sent after the reboot
+/// | check_revision | INT | revision of the post-check script that was sent
+/// | output_hash | TEXT | hex sha256 of the normalized output
+async fn create_executions_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        executions(
+            id TEXT PRIMARY KEY NOT NULL,
+            request TEXT,
+            response TEXT,
+            host_id TEXT,
+            sched_id TEXT,
+            created TEXT,
+            output TEXT,
+            rendered_script TEXT,
+            script_revision INT,
+            matched NUMERIC,
+            verdict TEXT,
+            extracted TEXT,
+            fields TEXT,
+            parse_error TEXT,
+            dispatched TEXT,
+            reboot_state TEXT,
+            reboot_deadline TEXT,
+            check_id TEXT,
+            check_revision INT,
+            output_hash TEXT,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing("executions", "rendered_script", "TEXT", &mut connection).await?;
+    add_column_if_missing("executions", "script_revision", "INT", &mut connection).await?;
+    add_column_if_missing("executions", "matched", "NUMERIC", &mut connection).await?;
+    add_column_if_missing("executions", "verdict", "TEXT", &mut connection).await?;
+    add_column_if_missing("executions", "extracted", "TEXT", &mut connection).await?;
+    add_column_if_missing("executions", "fields", "TEXT", &mut connection).await?;
+    add_column_if_missing("executions", "parse_error", "TEXT", &mut connection).await?;
+    add_column_if_missing("executions", "dispatched", "TEXT", &mut connection).await?;
+    for column in ["reboot_state", "reboot_deadline", "check_id", "output_hash"] {
+        add_column_if_missing("executions", column, "TEXT", &mut connection).await?;
+    }
+    add_column_if_missing("executions", "check_revision", "INT", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Schedules Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | script_id | TEXT | uuid
+/// | target_attributes | TEXT | server label to execute on
+/// | target_host_id | TEXT | server uuid to execute on
+/// | timer_cron | TEXT | cron pattern for execution
+/// | timer_ts | TEXT | timestamp for execution
+/// | active | NUMERIC | boolean
+/// | parameters | TEXT | json map of script parameter values
+/// | script_revision | INT | pinned script revision, NULL for latest
+async fn create_schedules_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        schedules(
+            id TEXT PRIMARY KEY NOT NULL,
+            script_id TEXT,
+            target_attributes TEXT,
+            target_host_id TEXT,
+            timer_cron TEXT,
+            timer_ts TEXT,
+            active NUMERIC,
+            parameters TEXT,
+            script_revision INT,
+            FOREIGN KEY(script_id) REFERENCES scripts(id) ON DELETE CASCADE,
+            FOREIGN KEY(target_host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing("schedules", "parameters", "TEXT", &mut connection).await?;
+    add_column_if_missing("schedules", "script_revision", "INT", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Users Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | email | TEXT |
+/// | password | TEXT |
+/// | roles | TEXT |
+/// | active | NUMERIC |
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_users_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        users(
+            id TEXT NOT NULL,
+            email TEXT PRIMARY KEY NOT NULL,
+            password TEXT NOT NULL,
+            roles TEXT,
+            active NUMERIC NOT NULL,
+            created TEXT NOT NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Blacklist Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | ip | TEXT |
+/// | tries | NUMERIC |
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | blocked | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | blocked_until | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_blacklist_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        blacklist(
+            id TEXT NOT NULL,
+            ip TEXT PRIMARY KEY NOT NULL,
+            tries NUMERIC NOT NULL,
+            created TEXT NOT NULL,
+            blocked TEXT,
+            blocked_until TEXT
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Variables Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | attribute | TEXT | host attribute (group) the variable belongs to
+/// | name | TEXT |
+/// | value | TEXT |
+async fn create_variables_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        variables(
+            id TEXT PRIMARY KEY NOT NULL,
+            attribute TEXT NOT NULL,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            UNIQUE(attribute, name)
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Script Revisions Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | script_id | TEXT | uuid
+/// | revision | INT | auto-incremented per script
+/// | author | TEXT | user who saved the revision
+/// | change_note | TEXT |
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | script | TEXT | json snapshot of the script
+/// | approved_by | TEXT | second user who approved the revision
+/// | approved | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | approval_signature | TEXT | json, server signature over revision and approver
+async fn create_script_revisions_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        script_revisions(
+            id TEXT PRIMARY KEY NOT NULL,
+            script_id TEXT NOT NULL,
+            revision INT NOT NULL,
+            author TEXT,
+            change_note TEXT,
+            created TEXT NOT NULL,
+            script TEXT NOT NULL,
+            approved_by TEXT,
+            approved TEXT,
+            approval_signature TEXT,
+            UNIQUE(script_id, revision),
+            FOREIGN KEY(script_id) REFERENCES scripts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    for column in ["approved_by", "approved", "approval_signature"] {
+        add_column_if_missing("script_revisions", column, "TEXT", &mut connection).await?;
+    }
+    backfill_revisions(&mut connection).await
+}
+
+/// DB migration: scripts saved before revisions existed get their content as revision 1
+async fn backfill_revisions(connection: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let q = "SELECT * FROM scripts WHERE id NOT IN (SELECT script_id FROM script_revisions)";
+    let scripts: Vec<Script> = query(q)
+        .fetch_all(&mut *connection)
+        .await?
+        .into_iter()
+        .map(|r| r.into())
+        .collect();
+    for mut script in scripts {
+        info!("DB migration: adding revision 1 of script {}", script.name);
+        script.revision = 1;
+        let rev = ScriptRevision {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            revision: 1,
+            author: "unpatched-server".into(),
+            change_note: "script saved before revisions".into(),
+            created: Utc::now(),
+            script: script.clone(),
+            ..Default::default()
+        };
+        let mut tx = connection.begin().await?;
+        script.insert_into_db(&mut tx).await?;
+        rev.insert_into_db(&mut tx).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Create Signing Keys Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | algorithm | TEXT | ed25519
+/// | public_key | TEXT | base64
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | retired | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ"), NULL for the active key
+async fn create_signing_keys_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        signing_keys(
+            id TEXT PRIMARY KEY NOT NULL,
+            algorithm TEXT NOT NULL,
+            public_key TEXT NOT NULL UNIQUE,
+            created TEXT NOT NULL,
+            retired TEXT
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Metrics Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | host_id | TEXT | uuid
+/// | sched_id | TEXT | uuid
+/// | name | TEXT | parsed field name
+/// | resolution | INT | 0 for raw samples, 3600 or 86400 for rollups
+/// | ts | INT | unix timestamp, start of the bucket for rollups
+/// | value | REAL | average
+/// | min | REAL |
+/// | max | REAL |
+/// | count | INT | number of raw samples
+async fn create_metrics_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        metrics(
+            host_id TEXT NOT NULL,
+            sched_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            resolution INT NOT NULL,
+            ts INT NOT NULL,
+            value REAL NOT NULL,
+            min REAL NOT NULL,
+            max REAL NOT NULL,
+            count INT NOT NULL,
+            PRIMARY KEY(host_id, sched_id, name, resolution, ts),
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+        ) WITHOUT ROWID"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    let _res = query(r#"CREATE INDEX IF NOT EXISTS metrics_name_ts ON metrics(name, ts)"#)
+        .execute(&mut *connection)
+        .await?;
+    Ok(())
+}
+
+/// Create Alert Rules Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | condition | TEXT | json
+/// | severity | TEXT | info, warning or critical
+/// | labels | TEXT | json map
+/// | for_secs | INT | seconds the condition has to hold before firing
+/// | sched_id | TEXT | uuid, NULL for all schedules
+/// | attributes | TEXT | json list of host attributes
+/// | active | NUMERIC | bool
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_alert_rules_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        alert_rules(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            condition TEXT NOT NULL,
+            severity TEXT NOT NULL,
+            labels TEXT,
+            for_secs INT NOT NULL,
+            sched_id TEXT,
+            attributes TEXT,
+            active NUMERIC,
+            created TEXT,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Alerts Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | rule_id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | sched_id | TEXT | uuid
+/// | state | TEXT | pending, firing or resolved
+/// | severity | TEXT | info, warning or critical
+/// | labels | TEXT | json map
+/// | fingerprint | TEXT | hash of the labels
+/// | summary | TEXT |
+/// | value | REAL | value the condition was evaluated with
+/// | started | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | fired | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | resolved | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | acknowledged_by | TEXT | email of the user
+/// | acknowledged | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_alerts_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        alerts(
+            id TEXT PRIMARY KEY NOT NULL,
+            rule_id TEXT NOT NULL,
+            host_id TEXT NOT NULL,
+            sched_id TEXT NOT NULL,
+            state TEXT NOT NULL,
+            severity TEXT NOT NULL,
+            labels TEXT,
+            fingerprint TEXT,
+            summary TEXT,
+            value REAL,
+            started TEXT NOT NULL,
+            fired TEXT,
+            resolved TEXT,
+            acknowledged_by TEXT,
+            acknowledged TEXT,
+            FOREIGN KEY(rule_id) REFERENCES alert_rules(id) ON DELETE CASCADE,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing("alerts", "fingerprint", "TEXT", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Notification Channels Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | config | TEXT | json, channel type and its settings
+/// | title_template | TEXT |
+/// | body_template | TEXT |
+/// | min_severity | TEXT | info, warning or critical
+/// | events | TEXT | json list of event kinds, all if empty
+/// | active | NUMERIC | bool
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_notification_channels_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        notification_channels(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            config TEXT NOT NULL,
+            title_template TEXT NOT NULL,
+            body_template TEXT NOT NULL,
+            min_severity TEXT NOT NULL,
+            events TEXT,
+            active NUMERIC NOT NULL,
+            created TEXT NOT NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Notification Deliveries Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | channel_id | TEXT | uuid
+/// | event | TEXT | alert_firing, alert_resolved, host_offline or test
+/// | title | TEXT |
+/// | alert_id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | status | TEXT | pending, delivered or failed
+/// | attempts | INT |
+/// | error | TEXT | error of the last attempt
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | delivered | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_notification_deliveries_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        notification_deliveries(
+            id TEXT PRIMARY KEY NOT NULL,
+            channel_id TEXT NOT NULL,
+            event TEXT NOT NULL,
+            title TEXT,
+            alert_id TEXT,
+            host_id TEXT,
+            status TEXT NOT NULL,
+            attempts INT NOT NULL,
+            error TEXT,
+            created TEXT NOT NULL,
+            delivered TEXT,
+            FOREIGN KEY(channel_id) REFERENCES notification_channels(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Silences Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | host_id | TEXT | uuid, NULL for all hosts
+/// | attributes | TEXT | json list of host attributes
+/// | sched_id | TEXT | uuid, NULL for all schedules
+/// | labels | TEXT | json map of alert labels
+/// | starts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | ends | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | comment | TEXT |
+/// | created_by | TEXT | email of the user
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_silences_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        silences(
+            id TEXT PRIMARY KEY NOT NULL,
+            host_id TEXT,
+            attributes TEXT,
+            sched_id TEXT,
+            labels TEXT,
+            starts TEXT NOT NULL,
+            ends TEXT NOT NULL,
+            comment TEXT,
+            created_by TEXT,
+            created TEXT NOT NULL,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Maintenance Windows Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | attributes | TEXT | json list of host attributes (group)
+/// | starts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | ends | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | pause_schedules | NUMERIC | bool, skip executions during the window
+/// | comment | TEXT |
+/// | created_by | TEXT | email of the user
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_maintenance_windows_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        maintenance_windows(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            attributes TEXT NOT NULL,
+            starts TEXT NOT NULL,
+            ends TEXT NOT NULL,
+            pause_schedules NUMERIC NOT NULL,
+            comment TEXT,
+            created_by TEXT,
+            created TEXT NOT NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Escalation Policies Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | steps | TEXT | json list of steps (delay_mins, channels)
+/// | repeat_interval_mins | INT | no repeat if 0
+/// | max_repeats | INT |
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_escalation_policies_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        escalation_policies(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            steps TEXT NOT NULL,
+            repeat_interval_mins INT NOT NULL,
+            max_repeats INT NOT NULL,
+            created TEXT NOT NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Routes Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | parent_id | TEXT | uuid, NULL for top level routes
+/// | position | INT | order among siblings
+/// | matchers | TEXT | json map of labels
+/// | channels | TEXT | json list of channel ids
+/// | escalation_id | TEXT | uuid
+/// | continue_matching | NUMERIC | bool, check later siblings after a match
+/// | active | NUMERIC | bool
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_routes_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        routes(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            parent_id TEXT,
+            position INT NOT NULL,
+            matchers TEXT NOT NULL,
+            channels TEXT NOT NULL,
+            escalation_id TEXT,
+            continue_matching NUMERIC NOT NULL,
+            active NUMERIC NOT NULL,
+            created TEXT NOT NULL,
+            FOREIGN KEY(parent_id) REFERENCES routes(id) ON DELETE CASCADE,
+            FOREIGN KEY(escalation_id) REFERENCES escalation_policies(id) ON DELETE SET NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Escalations Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | alert_id | TEXT | uuid
+/// | policy_id | TEXT | uuid
+/// | step | INT | index of the next step
+/// | repeats | INT |
+/// | next_at | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | started | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_escalations_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        escalations(
+            alert_id TEXT PRIMARY KEY NOT NULL,
+            policy_id TEXT NOT NULL,
+            step INT NOT NULL,
+            repeats INT NOT NULL,
+            next_at TEXT NOT NULL,
+            started TEXT NOT NULL,
+            FOREIGN KEY(alert_id) REFERENCES alerts(id) ON DELETE CASCADE,
+            FOREIGN KEY(policy_id) REFERENCES escalation_policies(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Packages Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | host_id | TEXT | uuid
+/// | component | TEXT | SBOM component, empty for the host inventory
+/// | format | TEXT | dpkg, rpm, apk, pacman, homebrew, npm, pypi, maven, golang, cargo, gem or nuget
+/// | name | TEXT |
+/// | arch | TEXT | empty if unknown
+/// | version | TEXT |
+/// | source | TEXT | source package, empty if unknown
+/// | first_seen | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_packages_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    // the component is part of the primary key, tables of older versions are copied over
+    let columns = query(
+        "SELECT count(*) AS col_count, count(CASE WHEN name = 'component' THEN 1 END) AS component FROM pragma_table_info('packages')",
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+    let migrate = columns.get::<i64, _>("col_count") > 0 && columns.get::<i64, _>("component") == 0;
+    if migrate {
+        info!("DB migration: adding column component to table packages");
+        let _res = query("ALTER TABLE packages RENAME TO packages_old")
+            .execute(&mut *connection)
+            .await?;
+    }
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        packages(
+            host_id TEXT NOT NULL,
+            component TEXT NOT NULL DEFAULT '',
+            format TEXT NOT NULL,
+            name TEXT NOT NULL,
+            arch TEXT NOT NULL,
+            version TEXT NOT NULL,
+            source TEXT NOT NULL,
+            first_seen TEXT NOT NULL,
+            PRIMARY KEY(host_id, component, format, name, arch, version),
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    if migrate {
+        let _res = query(
+            r#"INSERT INTO packages(host_id, format, name, arch, version, source, first_seen)
+            SELECT host_id, format, name, arch, version, source, first_seen FROM packages_old
+            WHERE host_id IN (SELECT id FROM hosts)"#,
+        )
+        .execute(&mut *connection)
+        .await?;
+        let _res = query("DROP TABLE packages_old")
+            .execute(&mut *connection)
+            .await?;
+    }
+    let _res = query(r#"CREATE INDEX IF NOT EXISTS packages_name ON packages(name, version)"#)
+        .execute(&mut *connection)
+        .await?;
+    Ok(())
+}
+
+/// Create Package Changes Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | component | TEXT | SBOM component, empty for the host inventory
+/// | format | TEXT | dpkg, rpm, apk, pacman, homebrew, npm, pypi, maven, golang, cargo, gem or nuget
+/// | name | TEXT |
+/// | arch | TEXT |
+/// | change | TEXT | installed, updated or removed
+/// | old_version | TEXT |
+/// | new_version | TEXT |
+/// | ts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_package_changes_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        package_changes(
+            id TEXT PRIMARY KEY NOT NULL,
+            host_id TEXT NOT NULL,
+            format TEXT NOT NULL,
+            name TEXT NOT NULL,
+            arch TEXT NOT NULL,
+            change TEXT NOT NULL,
+            old_version TEXT,
+            new_version TEXT,
+            ts TEXT NOT NULL,
+            component TEXT NOT NULL DEFAULT '',
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing(
+        "package_changes",
+        "component",
+        "TEXT NOT NULL DEFAULT ''",
+        &mut connection,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Create Pending Updates Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | host_id | TEXT | uuid
+/// | format | TEXT | dpkg, rpm, apk, pacman or homebrew
+/// | name | TEXT |
+/// | arch | TEXT | empty if unknown
+/// | installed_version | TEXT | empty if unknown
+/// | available_version | TEXT |
+/// | security | INTEGER | 1 for security updates
+/// | advisory | TEXT | advisories or repositories, empty if unknown
+/// | first_seen | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | seen | INTEGER | 0 while a report is stored and the update was not reported yet
+async fn create_pending_updates_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        pending_updates(
+            host_id TEXT NOT NULL,
+            format TEXT NOT NULL,
+            name TEXT NOT NULL,
+            arch TEXT NOT NULL,
+            installed_version TEXT NOT NULL,
+            available_version TEXT NOT NULL,
+            security INTEGER NOT NULL,
+            advisory TEXT NOT NULL,
+            first_seen TEXT NOT NULL,
+            seen INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY(host_id, format, name, arch),
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Advisories Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | e.g. CVE-2024-1234 or UBUNTU-CVE-2024-1234
+/// | aliases | TEXT | json array of other ids
+/// | summary | TEXT |
+/// | severity | TEXT | unknown, low, medium, high or critical
+/// | published | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | modified | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | imported | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | cvss | REAL | highest CVSS v3 base score, NULL if unknown
+async fn create_advisories_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        advisories(
+            id TEXT PRIMARY KEY NOT NULL,
+            aliases TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            severity TEXT NOT NULL,
+            published TEXT,
+            modified TEXT,
+            imported TEXT NOT NULL,
+            cvss REAL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    add_column_if_missing("advisories", "cvss", "REAL", &mut connection).await?;
+    Ok(())
+}
+
+/// Create Affected Packages Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | advisory_id | TEXT |
+/// | source | TEXT | osv, debian or alpine
+/// | format | TEXT | dpkg, rpm, apk, pacman, homebrew, npm, pypi, maven, golang, cargo, gem or nuget
+/// | distro | TEXT | os-release id, empty for all distributions
+/// | release | TEXT | version or codename, empty for all releases
+/// | name | TEXT | binary or source package
+/// | introduced | TEXT | empty for all versions
+/// | fixed | TEXT | null if not fixed
+/// | last_affected | TEXT |
+async fn create_affected_packages_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        affected_packages(
+            advisory_id TEXT NOT NULL,
+            source TEXT NOT NULL,
+            format TEXT NOT NULL,
+            distro TEXT NOT NULL,
+            release TEXT NOT NULL,
+            name TEXT NOT NULL,
+            introduced TEXT NOT NULL,
+            fixed TEXT,
+            last_affected TEXT,
+            FOREIGN KEY(advisory_id) REFERENCES advisories(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    let _res = query(
+        r#"CREATE INDEX IF NOT EXISTS affected_packages_name ON affected_packages(format, name)"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    let _res = query(
+        r#"CREATE INDEX IF NOT EXISTS affected_packages_advisory ON affected_packages(advisory_id)"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Known Exploited Table in SQLite Database, imported from the CISA KEV catalog
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | cve | TEXT | e.g. CVE-2024-1234
+/// | name | TEXT | vulnerability name of the catalog
+/// | added | TEXT | date added to the catalog
+/// | imported | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_known_exploited_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        known_exploited(
+            cve TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            added TEXT,
+            imported TEXT NOT NULL
         )"#,
     )
     .execute(&mut *connection)
@@ -124,28 +1169,69 @@ async fn create_hosts_table(mut connection: PoolConnection<Sqlite>) -> Result<()
     Ok(())
 }
 
-/// Create Scripts Table in SQLite Database
+/// Create OS Lifecycles Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | distro | TEXT | os-release id
+/// | release | TEXT | version
+/// | codename | TEXT |
+/// | eol | TEXT | date, end of the standard support
+/// | extended_eol | TEXT | date, end of extended support
+/// | bundled | NUMERIC | bool, shipped with the server
+/// | imported | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_os_lifecycles_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        os_lifecycles(
+            distro TEXT NOT NULL,
+            release TEXT NOT NULL,
+            codename TEXT NOT NULL,
+            eol TEXT NOT NULL,
+            extended_eol TEXT,
+            bundled NUMERIC NOT NULL,
+            imported TEXT NOT NULL,
+            PRIMARY KEY(distro, release)
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Waivers Table in SQLite Database
 ///
 /// | Name | Type | Comment
 /// :--- | :--- | :---
 /// | id | TEXT | uuid
-/// | name | TEXT |
-/// | version | TEXT |
-/// | output_regex | TEXT | regex for result parsing
-/// | labels | TEXT | script labels
-/// | timeout_in_s | INT | timeout in seconds
-/// | script_content | TEXT | original script
-async fn create_scripts_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+/// | cve | TEXT | CVE or advisory id, NULL for all
+/// | package | TEXT | NULL for all packages
+/// | host_id | TEXT | uuid, NULL for all hosts
+/// | attributes | TEXT | json list
+/// | justification | TEXT |
+/// | approver | TEXT |
+/// | expires | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | created_by | TEXT | email of the user
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | notified | TEXT | last expiry notification, empty, expiring or expired
+async fn create_waivers_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
-        scripts(
+        waivers(
             id TEXT PRIMARY KEY NOT NULL,
-            name TEXT,
-            version TEXT,
-            output_regex TEXT,
-            labels TEXT,
-            timeout_in_s INT,
-            script_content TEXT
+            cve TEXT,
+            package TEXT,
+            host_id TEXT,
+            attributes TEXT NOT NULL,
+            justification TEXT NOT NULL,
+            approver TEXT NOT NULL,
+            expires TEXT NOT NULL,
+            created_by TEXT NOT NULL,
+            created TEXT NOT NULL,
+            notified TEXT NOT NULL,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
         )"#,
     )
     .execute(&mut *connection)
@@ -153,32 +1239,122 @@ async fn create_scripts_table(mut connection: PoolConnection<Sqlite>) -> Result<
     Ok(())
 }
 
-/// Create Executions Table in SQLite Database
+/// Create Waiver Audit Table in SQLite Database, entries outlive their waiver
 ///
 /// | Name | Type | Comment
 /// :--- | :--- | :---
 /// | id | TEXT | uuid
-/// | request | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-/// | response | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+/// | waiver_id | TEXT | uuid
+/// | action | TEXT | created, updated, deleted, expiring or expired
+/// | user | TEXT | email of the user
+/// | waiver | TEXT | json of the waiver
+/// | ts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_waiver_audit_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        waiver_audit(
+            id TEXT PRIMARY KEY NOT NULL,
+            waiver_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            user TEXT NOT NULL,
+            waiver TEXT NOT NULL,
+            ts TEXT NOT NULL
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Host Reboots Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
 /// | host_id | TEXT | uuid
-/// | sched_id | TEXT | uuid
-/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-/// | output | TEXT |
-async fn create_executions_table(
+/// | running_kernel | TEXT | `uname -r`
+/// | installed_kernel | TEXT | newest installed kernel, empty if unknown
+/// | reboot_required | NUMERIC | bool
+/// | reasons | TEXT | json list
+/// | required_since | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ"), NULL if no reboot is required
+/// | checked | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_host_reboots_table(
     mut connection: PoolConnection<Sqlite>,
 ) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
-        executions(
+        host_reboots(
+            host_id TEXT PRIMARY KEY NOT NULL,
+            running_kernel TEXT NOT NULL,
+            installed_kernel TEXT NOT NULL,
+            reboot_required NUMERIC NOT NULL,
+            reasons TEXT NOT NULL,
+            required_since TEXT,
+            checked TEXT NOT NULL,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create SBOMs Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | host_id | TEXT | uuid
+/// | component | TEXT | component the SBOM describes, e.g. a container
+/// | format | TEXT | cyclonedx or spdx
+/// | spec_version | TEXT | e.g. `1.5` or `SPDX-2.3`
+/// | os | TEXT | os-release id of the component, NULL if unknown
+/// | os_version | TEXT | NULL if unknown
+/// | packages | INT | packages stored in the inventory
+/// | skipped | INT | components without a supported package url
+/// | uploaded | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_sboms_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        sboms(
+            host_id TEXT NOT NULL,
+            component TEXT NOT NULL,
+            format TEXT NOT NULL,
+            spec_version TEXT NOT NULL,
+            os TEXT,
+            os_version TEXT,
+            packages INT NOT NULL,
+            skipped INT NOT NULL,
+            uploaded TEXT NOT NULL,
+            PRIMARY KEY(host_id, component),
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Baselines Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | name | TEXT |
+/// | description | TEXT |
+/// | checks | TEXT | json list of checks (script_id, expected, weight, remediation)
+/// | attributes | TEXT | json list, only hosts with all of them are scored
+/// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_baselines_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        baselines(
             id TEXT PRIMARY KEY NOT NULL,
-            request TEXT,
-            response TEXT,
-            host_id TEXT,
-            sched_id TEXT,
-            created TEXT,
-            output TEXT,
-            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
-            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            checks TEXT NOT NULL,
+            attributes TEXT NOT NULL,
+            created TEXT NOT NULL
         )"#,
     )
     .execute(&mut *connection)
@@ -186,30 +1362,72 @@ async fn create_executions_table(
     Ok(())
 }
 
-/// Create Schedules Table in SQLite Database
+/// Create Compliance Scores Table in SQLite Database
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | baseline_id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | score | REAL | weight of the passing checks in percent
+/// | passed | NUMERIC | bool, all checks pass
+/// | failed | INT | failing checks
+/// | unknown | INT | checks without execution
+/// | ts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ"), when the score changed
+async fn create_compliance_scores_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let _res = query(
+        r#"CREATE TABLE IF NOT EXISTS 
+        compliance_scores(
+            baseline_id TEXT NOT NULL,
+            host_id TEXT NOT NULL,
+            score REAL NOT NULL,
+            passed NUMERIC NOT NULL,
+            failed INT NOT NULL,
+            unknown INT NOT NULL,
+            ts TEXT NOT NULL,
+            PRIMARY KEY(baseline_id, host_id, ts),
+            FOREIGN KEY(baseline_id) REFERENCES baselines(id) ON DELETE CASCADE,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE
+        )"#,
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+/// Create Drift Events Table in SQLite Database
 ///
 /// | Name | Type | Comment
 /// :--- | :--- | :---
 /// | id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | sched_id | TEXT | uuid
 /// | script_id | TEXT | uuid
-/// | target_attributes | TEXT | server label to execute on
-/// | target_host_id | TEXT | server uuid to execute on
-/// | timer_cron | TEXT | cron pattern for execution
-/// | timer_ts | TEXT | timestamp for execution
-/// | active | NUMERIC | boolean
-async fn create_schedules_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+/// | execution_id | TEXT | uuid of the run with the changed output
+/// | previous_execution_id | TEXT | uuid of the run compared to
+/// | previous_hash | TEXT | hex sha256 of the normalized previous output
+/// | hash | TEXT | hex sha256 of the normalized output
+/// | diff | TEXT | unified diff
+/// | detected | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_drift_events_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
-        schedules(
+        drift_events(
             id TEXT PRIMARY KEY NOT NULL,
-            script_id TEXT,
-            target_attributes TEXT,
-            target_host_id TEXT,
-            timer_cron TEXT,
-            timer_ts TEXT,
-            active NUMERIC,
-            FOREIGN KEY(script_id) REFERENCES scripts(id) ON DELETE CASCADE,
-            FOREIGN KEY(target_host_id) REFERENCES hosts(id) ON DELETE CASCADE
+            host_id TEXT NOT NULL,
+            sched_id TEXT NOT NULL,
+            script_id TEXT NOT NULL,
+            execution_id TEXT NOT NULL,
+            previous_execution_id TEXT NOT NULL,
+            previous_hash TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            diff TEXT NOT NULL,
+            detected TEXT NOT NULL,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
         )"#,
     )
     .execute(&mut *connection)
@@ -217,57 +1435,124 @@ async fn create_schedules_table(mut connection: PoolConnection<Sqlite>) -> Resul
     Ok(())
 }
 
-/// Create Users Table in SQLite Database
+/// Create Remediation Actions Table in SQLite Database
 ///
 /// | Name | Type | Comment
 /// :--- | :--- | :---
 /// | id | TEXT | uuid
-/// | email | TEXT |
-/// | password | TEXT |
-/// | roles | TEXT |
-/// | active | NUMERIC |
+/// | name | TEXT |
+/// | check_script_id | TEXT | uuid, a failed run triggers the remediation
+/// | remediation_script_id | TEXT | uuid
+/// | require_approval | NUMERIC | bool
+/// | max_attempts | INT | remediations of a host since the check last passed after one
+/// | cooldown_secs | INT | time between two remediations of a host
+/// | attributes | TEXT | json list, only hosts with all of them are remediated
+/// | active | NUMERIC | bool
 /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-async fn create_users_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+async fn create_remediation_actions_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
-        users(
-            id TEXT NOT NULL,
-            email TEXT PRIMARY KEY NOT NULL,
-            password TEXT NOT NULL,
-            roles TEXT,
+        remediation_actions(
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            check_script_id TEXT NOT NULL,
+            remediation_script_id TEXT NOT NULL,
+            remediation_script_revision INT,
+            require_approval NUMERIC NOT NULL,
+            max_attempts INT NOT NULL,
+            cooldown_secs INT NOT NULL,
+            attributes TEXT NOT NULL,
             active NUMERIC NOT NULL,
-            created TEXT NOT NULL
+            created TEXT NOT NULL,
+            FOREIGN KEY(check_script_id) REFERENCES scripts(id) ON DELETE CASCADE,
+            FOREIGN KEY(remediation_script_id) REFERENCES scripts(id) ON DELETE CASCADE
         )"#,
     )
     .execute(&mut *connection)
     .await?;
+    add_column_if_missing(
+        "remediation_actions",
+        "remediation_script_revision",
+        "INT",
+        &mut connection,
+    )
+    .await?;
     Ok(())
 }
 
-/// Create Blacklist Table in SQLite Database
+/// Create Remediations Table in SQLite Database
 ///
 /// | Name | Type | Comment
 /// :--- | :--- | :---
-/// | id | TEXT | uuid
-/// | ip | TEXT |
-/// | tries | NUMERIC |
+/// | id | TEXT | uuid, also the id of the remediation script sent to the agent
+/// | action_id | TEXT | uuid
+/// | host_id | TEXT | uuid
+/// | sched_id | TEXT | uuid of the check schedule
+/// | check_execution_id | TEXT | uuid of the failed check
+/// | attempt | INT |
+/// | state | TEXT | awaiting_approval, pending, running, verifying, resolved, failed or rejected
+/// | output | TEXT | output of the remediation script
+/// | remediation_verdict | TEXT | success, failure or parse_error
+/// | script_revision | INT | revision of the remediation script that was sent
+/// | verification_execution_id | TEXT | uuid of the check run after the remediation
+/// | verification_verdict | TEXT | success, failure or parse_error
+/// | approved_by | TEXT | email of the user who approved or rejected it
+/// | note | TEXT | why it failed
+/// | deadline | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
 /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-/// | blocked | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-/// | blocked_until | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
-async fn create_blacklist_table(mut connection: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
+/// | updated | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn create_remediations_table(
+    mut connection: PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
     let _res = query(
         r#"CREATE TABLE IF NOT EXISTS 
-        blacklist(
-            id TEXT NOT NULL,
-            ip TEXT PRIMARY KEY NOT NULL,
-            tries NUMERIC NOT NULL,
+        remediations(
+            id TEXT PRIMARY KEY NOT NULL,
+            action_id TEXT NOT NULL,
+            host_id TEXT NOT NULL,
+            sched_id TEXT NOT NULL,
+            check_execution_id TEXT NOT NULL,
+            attempt INT NOT NULL,
+            state TEXT NOT NULL,
+            output TEXT,
+            remediation_verdict TEXT,
+            script_revision INT,
+            verification_execution_id TEXT,
+            verification_verdict TEXT,
+            approved_by TEXT,
+            note TEXT,
+            deadline TEXT,
             created TEXT NOT NULL,
-            blocked TEXT,
-            blocked_until TEXT
+            updated TEXT NOT NULL,
+            FOREIGN KEY(action_id) REFERENCES remediation_actions(id) ON DELETE CASCADE,
+            FOREIGN KEY(host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+            FOREIGN KEY(sched_id) REFERENCES schedules(id) ON DELETE CASCADE
         )"#,
     )
     .execute(&mut *connection)
     .await?;
+    add_column_if_missing("remediations", "script_revision", "INT", &mut connection).await?;
+    Ok(())
+}
+
+/// Add a column to a table created by an older server version, noop if it exists already
+async fn add_column_if_missing(
+    table: &str,
+    column: &str,
+    definition: &str,
+    connection: &mut PoolConnection<Sqlite>,
+) -> Result<(), sqlx::Error> {
+    let stmt = format!(
+        "SELECT count(*) as col_count FROM pragma_table_info('{table}') WHERE name = '{column}'"
+    );
+    let col_count = query(&stmt).fetch_one(&mut **connection).await?;
+    if col_count.get::<i64, _>("col_count") == 0 {
+        info!("DB migration: adding column {column} to table {table}");
+        let stmt = format!("ALTER TABLE {table} ADD COLUMN {column} {definition}");
+        query(&stmt).execute(&mut **connection).await?;
+    }
     Ok(())
 }
 
@@ -285,6 +1570,8 @@ async fn init_samples(pool: &Pool<Sqlite>) {
         labels: vec!["linux".to_string(), "sample1".to_string()],
         timeout,
         script_content: r#"uptime -p"#.into(),
+        interpreter: Interpreter::Sh,
+        ..Default::default()
     };
     let uptime_mac = Script {
         id: Uuid::new_v4(),
@@ -294,6 +1581,8 @@ async fn init_samples(pool: &Pool<Sqlite>) {
         labels: vec!["mac".to_string(), "sample3".to_string()],
         timeout,
         script_content: r#"uptime"#.into(),
+        interpreter: Interpreter::Sh,
+        ..Default::default()
     };
 
     let name = "os_version".to_string();
@@ -306,6 +1595,10 @@ async fn init_samples(pool: &Pool<Sqlite>) {
         labels: vec!["linux".to_string(), "sample2".to_string()],
         timeout,
         script_content: r#"cat /etc/os-release"#.into(),
+        interpreter: Interpreter::Sh,
+        parser: OutputParser::OsRelease,
+        track_drift: true,
+        ..Default::default()
     };
     let os_version_mac = Script {
         id: Uuid::new_v4(),
@@ -315,19 +1608,60 @@ async fn init_samples(pool: &Pool<Sqlite>) {
         labels: vec!["mac".to_string(), "sample4".to_string()],
         timeout,
         script_content: r#"sw_vers"#.into(),
+        interpreter: Interpreter::Sh,
+        parser: OutputParser::OsRelease,
+        track_drift: true,
+        ..Default::default()
+    };
+    let inventory = Script {
+        id: Uuid::new_v4(),
+        name: "package_inventory".to_string(),
+        version: version.to_string(),
+        output_regex: output_regex.to_string(),
+        labels: vec!["linux".to_string(), "inventory".to_string()],
+        timeout: Duration::new(120, 0),
+        script_content: INVENTORY_SCRIPT.into(),
+        interpreter: Interpreter::Sh,
+        parser: OutputParser::Packages,
+        ..Default::default()
+    };
+    let updates = Script {
+        id: Uuid::new_v4(),
+        name: "pending_updates".to_string(),
+        version: version.to_string(),
+        output_regex: output_regex.to_string(),
+        labels: vec!["linux".to_string(), "updates".to_string()],
+        timeout: Duration::new(300, 0),
+        script_content: UPDATES_SCRIPT.into(),
+        interpreter: Interpreter::Sh,
+        parser: OutputParser::Updates,
+        ..Default::default()
+    };
+    let reboot = Script {
+        id: Uuid::new_v4(),
+        name: "reboot_status".to_string(),
+        version: version.to_string(),
+        output_regex: output_regex.to_string(),
+        labels: vec!["linux".to_string(), "reboot".to_string()],
+        timeout,
+        script_content: REBOOT_SCRIPT.into(),
+        interpreter: Interpreter::Sh,
+        parser: OutputParser::RebootStatus,
+        ..Default::default()
     };
+    let every_minute = "* * * * *";
     let v = vec![
-        uptime_linux.clone(),
-        os_version_linux,
-        uptime_mac,
-        os_version_mac,
+        (uptime_linux.clone(), every_minute),
+        (os_version_linux, every_minute),
+        (uptime_mac, every_minute),
+        (os_version_mac, every_minute),
+        (inventory, "0 * * * *"),
+        (updates, "30 * * * *"),
+        (reboot, "15 * * * *"),
     ];
-    for s in v {
-        let res = s
-            .clone()
-            .insert_into_db(pool.acquire().await.unwrap())
-            .await;
-        if res.rows_affected() > 0 {
+    for (s, cron) in v {
+        let res = save_script(s.clone(), "unpatched-server", "sample script", pool).await;
+        if res.is_ok() {
             info!(
                 "DB init: sample script {} version {} with labels {} loaded",
                 s.name,
@@ -346,8 +1680,9 @@ async fn init_samples(pool: &Pool<Sqlite>) {
             id: Uuid::new_v4(),
             script_id: s.id,
             target: schedule::Target::Attributes(vec![s.labels[0].clone()]),
-            timer: schedule::Timer::Cron("* * * * *".into()),
+            timer: schedule::Timer::Cron(cron.into()),
             active: true,
+            ..Default::default()
         };
         let Ok(sched_res) = sched
             .clone()
@@ -381,6 +1716,7 @@ async fn init_samples(pool: &Pool<Sqlite>) {
         target: schedule::Target::Attributes(vec![uptime_linux.labels[0].clone()]),
         timer: schedule::Timer::Timestamp(Utc::now()),
         active: true,
+        ..Default::default()
     };
 
     let Ok(sched_res) = sched
@@ -486,7 +1822,7 @@ mod tests {
             .fetch_all(&mut *pool.acquire().await.unwrap())
             .await
             .unwrap();
-        assert_eq!(tables.len(), 8);
+        assert_eq!(tables.len(), 37);
 
         // run again to check already-present branch
         init_database(
@@ -500,6 +1836,122 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_add_column_if_missing() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        // scripts table as created by an older server version
+        query(
+            r#"CREATE TABLE scripts(id TEXT PRIMARY KEY NOT NULL, name TEXT, version TEXT, output_regex TEXT, labels TEXT, timeout_in_s INT, script_content TEXT)"#,
+        )
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await
+        .unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let scripts = script::get_scripts_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(scripts.len(), 7);
+        assert!(scripts[0].parameters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revision_backfill() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let revisions = crate::revision::get_revisions_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .len();
+        // a script stored by an older server version, without revisions
+        let legacy = Script {
+            id: Uuid::new_v4(),
+            name: "legacy".into(),
+            script_content: "uname -r".into(),
+            ..Default::default()
+        };
+        legacy
+            .clone()
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let filter = format!("script_id='{}'", legacy.id);
+        let backfilled =
+            crate::revision::get_revisions_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await;
+        assert_eq!(backfilled.len(), 1);
+        assert_eq!(backfilled[0].revision, 1);
+        assert_eq!(backfilled[0].script.script_content, "uname -r");
+        let filter = format!("id='{}'", legacy.id);
+        let scripts =
+            script::get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(scripts[0].revision, 1);
+        // only scripts without revisions are backfilled
+        assert_eq!(
+            crate::revision::get_revisions_from_db(None, pool.acquire().await.unwrap())
+                .await
+                .len(),
+            revisions + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_packages_component_migration() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host_id = Uuid::new_v4();
+        let host = crate::host::Host {
+            id: host_id,
+            ..Default::default()
+        };
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        // packages table as created by an older server version, without component
+        query("DROP TABLE packages")
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        query(
+            r#"CREATE TABLE packages(host_id TEXT NOT NULL, format TEXT NOT NULL, name TEXT NOT NULL, arch TEXT NOT NULL, version TEXT NOT NULL, source TEXT NOT NULL, first_seen TEXT NOT NULL, PRIMARY KEY(host_id, format, name, arch, version))"#,
+        )
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await
+        .unwrap();
+        query("INSERT INTO packages VALUES(?, 'dpkg', 'openssl', 'amd64', '3.0.2', 'openssl', '2024-01-01T00:00:00.000Z')")
+            .bind(host_id.to_string())
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let packages =
+            crate::package::get_packages_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].host_id, host_id);
+        assert_eq!(packages[0].component, "");
+        // a second start keeps the migrated table
+        init_database(&pool, None).await.unwrap();
+        let packages =
+            crate::package::get_packages_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(packages.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_update_text_field_error() {
         registry()
@@ -542,14 +1994,14 @@ mod tests {
 
         let scripts = script::get_scripts_from_db(None, pool.acquire().await.unwrap()).await;
         let schedules = schedule::get_schedules_from_db(None, pool.acquire().await.unwrap()).await;
-        assert_eq!(scripts.len(), 4);
-        assert_eq!(schedules.len(), 5);
+        assert_eq!(scripts.len(), 7);
+        assert_eq!(schedules.len(), 8);
 
         // run again to tests already-present branch
         init_samples(&pool).await;
         let scripts = script::get_scripts_from_db(None, pool.acquire().await.unwrap()).await;
         let schedules = schedule::get_schedules_from_db(None, pool.acquire().await.unwrap()).await;
-        assert_eq!(scripts.len(), 8);
-        assert_eq!(schedules.len(), 10);
+        assert_eq!(scripts.len(), 14);
+        assert_eq!(schedules.len(), 16);
     }
 }
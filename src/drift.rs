@@ -0,0 +1,450 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    execution::{get_executions_from_db, Execution},
+    jwt::Claims,
+};
+
+/// Change of the output of a schedule on a host between two runs
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DriftEvent {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub host_id: Uuid,
+    #[serde(default)]
+    pub alias: String,
+    pub sched_id: Uuid,
+    pub script_id: Uuid,
+    /// run with the changed output
+    pub execution_id: Uuid,
+    /// run the output is compared to
+    pub previous_execution_id: Uuid,
+    pub previous_hash: String,
+    pub hash: String,
+    /// unified diff of the normalized outputs
+    pub diff: String,
+    #[serde(default = "Utc::now")]
+    pub detected: DateTime<Utc>,
+}
+
+impl DriftEvent {
+    /// Insert `DriftEvent` into drift_events table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | host_id | TEXT | uuid
+    /// | sched_id | TEXT | uuid
+    /// | script_id | TEXT | uuid
+    /// | execution_id | TEXT | uuid
+    /// | previous_execution_id | TEXT | uuid
+    /// | previous_hash | TEXT | hex sha256
+    /// | hash | TEXT | hex sha256
+    /// | diff | TEXT | unified diff
+    /// | detected | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO drift_events( id, host_id, sched_id, script_id, execution_id, previous_execution_id, previous_hash, hash, diff, detected ) VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.host_id.to_string())
+            .bind(self.sched_id.to_string())
+            .bind(self.script_id.to_string())
+            .bind(self.execution_id.to_string())
+            .bind(self.previous_execution_id.to_string())
+            .bind(self.previous_hash)
+            .bind(self.hash)
+            .bind(self.diff)
+            .bind(utc_to_str(self.detected))
+            .execute(&mut *connection)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+impl From<SqliteRow> for DriftEvent {
+    fn from(s: SqliteRow) -> Self {
+        DriftEvent {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            alias: s.get::<Option<String>, _>("alias").unwrap_or_default(),
+            sched_id: s.get::<String, _>("sched_id").parse().unwrap(),
+            script_id: s.get::<String, _>("script_id").parse().unwrap(),
+            execution_id: s.get::<String, _>("execution_id").parse().unwrap(),
+            previous_execution_id: s.get::<String, _>("previous_execution_id").parse().unwrap(),
+            previous_hash: s.get::<String, _>("previous_hash"),
+            hash: s.get::<String, _>("hash"),
+            diff: s.get::<String, _>("diff"),
+            detected: utc_from_str(&s.get::<String, _>("detected")),
+        }
+    }
+}
+
+/// Output without line ending and trailing whitespace differences and without leading or
+/// trailing empty lines
+pub fn normalize_output(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().map(|l| l.trim_end()).collect();
+    let start = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(start, |e| e + 1);
+    lines[start..end].iter().map(|l| format!("{l}\n")).collect()
+}
+
+/// hex encoded sha256 of the normalized output
+pub fn output_hash(output: &str) -> String {
+    Sha256::digest(normalize_output(output).as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Compare `output` of `exe` to the previous run of the schedule on the host and record a
+/// drift event if the normalized output changed, the first run has nothing to compare to
+pub async fn detect_drift(
+    exe: &Execution,
+    script_id: Uuid,
+    hash: &str,
+    output: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Option<DriftEvent> {
+    let filter = format!(
+        "host_id='{}' AND sched_id='{}' AND id != '{}' AND output_hash IS NOT NULL ORDER BY response DESC LIMIT 1",
+        exe.host_id, exe.sched_id, exe.id
+    );
+    let previous = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()?;
+    let previous_hash = previous.output_hash?;
+    if previous_hash == hash {
+        return None;
+    }
+    let (old, new) = (normalize_output(&previous.output), normalize_output(output));
+    let diff = TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(
+            &format!("execution {}", previous.id),
+            &format!("execution {}", exe.id),
+        )
+        .to_string();
+    let event = DriftEvent {
+        id: Uuid::new_v4(),
+        host_id: exe.host_id,
+        alias: "".into(),
+        sched_id: exe.sched_id,
+        script_id,
+        execution_id: exe.id,
+        previous_execution_id: previous.id,
+        previous_hash,
+        hash: hash.to_string(),
+        diff,
+        detected: now,
+    };
+    let res = event
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+    if res.rows_affected() != 1 {
+        warn!("Drift of host {} could not be stored", exe.host_id);
+        return None;
+    }
+    debug!(
+        "Output of schedule {} drifted on host {}",
+        exe.sched_id, exe.host_id
+    );
+    Some(event)
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DriftQueryParams {
+    host_id: Option<Uuid>,
+    sched_id: Option<Uuid>,
+    script_id: Option<Uuid>,
+    since: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+/// newest first, the latest 100 without a `limit`
+fn timeline_filter(params: &DriftQueryParams) -> String {
+    let mut conditions = vec!["1=1".to_string()];
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    if let Some(sched_id) = params.sched_id {
+        conditions.push(format!("sched_id = '{sched_id}'"));
+    }
+    if let Some(script_id) = params.script_id {
+        conditions.push(format!("script_id = '{script_id}'"));
+    }
+    if let Some(since) = params.since {
+        conditions.push(format!("detected >= '{}'", utc_to_str(since)));
+    }
+    format!(
+        "{} ORDER BY detected DESC LIMIT {}",
+        conditions.join(" AND "),
+        params.limit.unwrap_or(100)
+    )
+}
+
+/// API to get the timeline of output changes, newest first
+pub async fn get_changes_api(
+    _claims: Claims,
+    Query(params): Query<DriftQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = timeline_filter(&params);
+    let event_vec = get_drift_events_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(event_vec)
+}
+
+/// API to get one output change with its diff
+pub async fn get_one_change_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let events = get_drift_events_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match events.into_iter().next() {
+        Some(event) => Json(event).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// API to get the timeline of output changes of a host, newest first
+pub async fn get_host_changes_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DriftQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let params = DriftQueryParams {
+        host_id: Some(id),
+        ..params
+    };
+    let filter = timeline_filter(&params);
+    let event_vec = get_drift_events_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(event_vec)
+}
+
+pub async fn get_drift_events_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<DriftEvent> {
+    let q = match filter {
+        Some(f) => format!(
+            "SELECT * FROM (SELECT drift_events.*, hosts.alias FROM drift_events LEFT JOIN hosts ON hosts.id = drift_events.host_id) WHERE {f}"
+        ),
+        None => "SELECT drift_events.*, hosts.alias FROM drift_events LEFT JOIN hosts ON hosts.id = drift_events.host_id".into(),
+    };
+    match query(&q).fetch_all(&mut *connection).await {
+        Ok(rows) => rows.into_iter().map(|r| r.into()).collect(),
+        Err(e) => {
+            warn!("{e}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alert::{evaluate_rules, AlertCondition, AlertRule, AlertState},
+        db::{create_database, init_database},
+        fixtures,
+        host::Host,
+        schedule::Schedule,
+        script::Script,
+    };
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    const BOOKWORM: &str = "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nVERSION_ID=\"12\"\nVERSION=\"12 (bookworm)\"\n";
+    const TRIXIE: &str = "PRETTY_NAME=\"Debian GNU/Linux 13 (trixie)\"\nVERSION_ID=\"13\"\nVERSION=\"13 (trixie)\"\n";
+
+    /// host with the os release schedule
+    async fn setup(track_drift: bool, pool: &SqlitePool) -> (Host, Schedule) {
+        let script = Script {
+            id: Uuid::new_v4(),
+            name: "os_release".into(),
+            script_content: "cat /etc/os-release".into(),
+            output_regex: ".*".into(),
+            track_drift,
+            ..Default::default()
+        };
+        fixtures::scheduled_host("web-1", &[], script, pool).await
+    }
+
+    #[test]
+    fn test_normalize_output() {
+        assert_eq!(normalize_output("\r\n a  \r\nb\t\n\n\n"), " a\nb\n");
+        assert_eq!(normalize_output("\n \n"), "");
+        assert_eq!(output_hash("a\nb"), output_hash("a  \r\nb\n\n"));
+        assert_ne!(output_hash("a\nb"), output_hash("b\na"));
+        assert_eq!(output_hash("").len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_drift() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (host, sched) = setup(true, &pool).await;
+        let first = fixtures::run(&host, sched.id, BOOKWORM, fixtures::at(0), &pool).await;
+        // whitespace only changes are no drift
+        let same = BOOKWORM.replace('\n', "  \r\n");
+        fixtures::run(&host, sched.id, &same, fixtures::at(1), &pool).await;
+        assert!(
+            get_drift_events_from_db(None, pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+        let filter = format!("id='{first}'");
+        let exe = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert_eq!(exe.output_hash, Some(output_hash(BOOKWORM)));
+
+        let upgraded = fixtures::run(&host, sched.id, TRIXIE, fixtures::at(2), &pool).await;
+        let events = get_drift_events_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.alias, "web-1");
+        assert_eq!(event.execution_id, upgraded);
+        assert_eq!(event.script_id, sched.script_id);
+        assert_eq!(event.previous_hash, output_hash(BOOKWORM));
+        assert_eq!(event.hash, output_hash(TRIXIE));
+        assert!(event.diff.starts_with("--- execution "));
+        assert!(event.diff.contains("\n-VERSION_ID=\"12\"\n"));
+        assert!(event.diff.contains("\n+VERSION_ID=\"13\"\n"));
+
+        // a schedule without track_drift is hashed but not compared
+        let (other, untracked) = setup(false, &pool).await;
+        fixtures::run(&other, untracked.id, "up 1 minute", fixtures::at(0), &pool).await;
+        fixtures::run(&other, untracked.id, "up 2 minutes", fixtures::at(1), &pool).await;
+        assert_eq!(
+            get_drift_events_from_db(None, pool.acquire().await.unwrap())
+                .await
+                .len(),
+            1
+        );
+
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "os release changed".into(),
+            condition: AlertCondition::OutputDrift { within_minutes: 60 },
+            severity: Default::default(),
+            labels: Default::default(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule.insert_into_db(pool.acquire().await.unwrap()).await;
+        let changed = evaluate_rules(event.detected, &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].state, AlertState::Firing);
+        assert_eq!(changed[0].host_id, host.id);
+        assert_eq!(changed[0].value, Some(1.0));
+        let changed = evaluate_rules(event.detected + Duration::minutes(61), &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].state, AlertState::Resolved);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let (host, sched) = setup(true, &pool).await;
+        fixtures::run(&host, sched.id, "a", fixtures::at(0), &pool).await;
+        fixtures::run(&host, sched.id, "b", fixtures::at(1), &pool).await;
+        fixtures::run(&host, sched.id, "c", fixtures::at(2), &pool).await;
+
+        let api_changes = get_changes_api(
+            claims.clone(),
+            axum::extract::Query(DriftQueryParams {
+                sched_id: Some(sched.id),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_changes.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_changes.into_body())
+            .await
+            .unwrap();
+        let changes: Vec<DriftEvent> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].diff.ends_with("-b\n+c\n"));
+
+        let api_host = get_host_changes_api(
+            claims.clone(),
+            axum::extract::Path(host.id),
+            axum::extract::Query(DriftQueryParams {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_host.into_body()).await.unwrap();
+        let latest: Vec<DriftEvent> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(latest, changes[..1]);
+
+        let api_one = get_one_change_api(
+            claims.clone(),
+            axum::extract::Path(changes[1].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_one.status(), StatusCode::OK);
+        let api_unknown = get_one_change_api(
+            claims,
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+    }
+}
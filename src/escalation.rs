@@ -0,0 +1,853 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    alert::{get_alerts_from_db, Alert, AlertState},
+    db::{utc_from_str, utc_to_str},
+    host::get_hosts_from_db,
+    jwt::Claims,
+    notification::{
+        get_channels_from_db, notify_channels, EventKind, NotificationDelivery, NotificationEvent,
+    },
+};
+
+/// Channels notified if an alert is still unacknowledged `delay_mins` after the previous step
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EscalationStep {
+    pub delay_mins: i64,
+    pub channels: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EscalationPolicy {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    pub steps: Vec<EscalationStep>,
+    /// minutes after the last step until the policy starts over, no repeat if 0
+    #[serde(default)]
+    pub repeat_interval_mins: i64,
+    #[serde(default)]
+    pub max_repeats: i64,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+/// Running escalation of one firing alert, removed once the alert is acknowledged or resolved
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Escalation {
+    pub alert_id: Uuid,
+    pub policy_id: Uuid,
+    /// index of the next step
+    pub step: i64,
+    pub repeats: i64,
+    pub next_at: DateTime<Utc>,
+    pub started: DateTime<Utc>,
+}
+
+impl EscalationPolicy {
+    /// Insert or Update `EscalationPolicy` in escalation_policies table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | steps | TEXT | json list
+    /// | repeat_interval_mins | INT |
+    /// | max_repeats | INT |
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO escalation_policies(id, name, steps, repeat_interval_mins, max_repeats, created) VALUES(?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, steps=excluded.steps, repeat_interval_mins=excluded.repeat_interval_mins, max_repeats=excluded.max_repeats"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(serde_json::to_string(&self.steps).unwrap())
+            .bind(self.repeat_interval_mins)
+            .bind(self.max_repeats)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+            .unwrap()
+    }
+
+    async fn validate(&self, pool: &SqlitePool) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.steps.is_empty() {
+            return Err("at least one step is required".into());
+        }
+        if self.repeat_interval_mins < 0 || self.max_repeats < 0 {
+            return Err("repeat_interval_mins and max_repeats must not be negative".into());
+        }
+        let channels = get_channels_from_db(None, pool.acquire().await.unwrap()).await;
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.delay_mins < 0 {
+                return Err(format!("step {i}: delay_mins must not be negative"));
+            }
+            if step.channels.is_empty() {
+                return Err(format!("step {i}: no channels"));
+            }
+            if let Some(missing) = step
+                .channels
+                .iter()
+                .find(|id| !channels.iter().any(|c| c.id == **id))
+            {
+                return Err(format!("step {i}: channel {missing} not found"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<SqliteRow> for EscalationPolicy {
+    fn from(s: SqliteRow) -> Self {
+        EscalationPolicy {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            steps: serde_json::from_str(&s.get::<String, _>("steps")).unwrap_or_default(),
+            repeat_interval_mins: s.get::<i64, _>("repeat_interval_mins"),
+            max_repeats: s.get::<i64, _>("max_repeats"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+impl Escalation {
+    /// Insert into or Replace `Escalation` in escalations table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | alert_id | TEXT | uuid
+    /// | policy_id | TEXT | uuid
+    /// | step | INT | index of the next step
+    /// | repeats | INT |
+    /// | next_at | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | started | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"REPLACE INTO escalations(alert_id, policy_id, step, repeats, next_at, started) VALUES(?, ?, ?, ?, ?, ?)"#;
+        query(q)
+            .bind(self.alert_id.to_string())
+            .bind(self.policy_id.to_string())
+            .bind(self.step)
+            .bind(self.repeats)
+            .bind(utc_to_str(self.next_at))
+            .bind(utc_to_str(self.started))
+            .execute(&mut *connection)
+            .await
+    }
+}
+
+impl From<SqliteRow> for Escalation {
+    fn from(s: SqliteRow) -> Self {
+        Escalation {
+            alert_id: s.get::<String, _>("alert_id").parse().unwrap(),
+            policy_id: s.get::<String, _>("policy_id").parse().unwrap(),
+            step: s.get::<i64, _>("step"),
+            repeats: s.get::<i64, _>("repeats"),
+            next_at: utc_from_str(&s.get::<String, _>("next_at")),
+            started: utc_from_str(&s.get::<String, _>("started")),
+        }
+    }
+}
+
+/// start escalating `alert` with policy `policy_id`, noop if it escalates already
+pub async fn start_escalation(
+    alert: &Alert,
+    policy_id: Uuid,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) {
+    let filter = format!("id='{policy_id}'");
+    let Some(policy) = get_policies_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return;
+    };
+    let filter = format!("alert_id='{}'", alert.id);
+    if !get_escalations_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .is_empty()
+    {
+        return;
+    }
+    let escalation = Escalation {
+        alert_id: alert.id,
+        policy_id,
+        step: 0,
+        repeats: 0,
+        next_at: now + Duration::minutes(policy.steps[0].delay_mins),
+        started: now,
+    };
+    info!("Escalating alert {} with {}", alert.id, policy.name);
+    let _res = escalation
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+}
+
+/// notify the due steps of all escalations, stops escalating acknowledged and resolved alerts
+pub async fn escalate(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<NotificationDelivery> {
+    let mut deliveries = vec![];
+    let escalations = get_escalations_from_db(None, pool.acquire().await.unwrap()).await;
+    for mut escalation in escalations {
+        let stop_filter = format!("alert_id='{}'", escalation.alert_id);
+        let filter = format!("id='{}'", escalation.alert_id);
+        let alert: Option<Alert> = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .next();
+        let Some(alert) =
+            alert.filter(|a| a.state == AlertState::Firing && a.acknowledged.is_none())
+        else {
+            info!("Escalation of alert {} stopped", escalation.alert_id);
+            let _del =
+                delete_escalations_from_db(Some(&stop_filter), pool.acquire().await.unwrap()).await;
+            continue;
+        };
+        if escalation.next_at > now {
+            continue;
+        }
+        let filter = format!("id='{}'", escalation.policy_id);
+        let policy = get_policies_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .next();
+        let Some(step) = policy
+            .as_ref()
+            .and_then(|p| p.steps.get(escalation.step as usize))
+        else {
+            let _del =
+                delete_escalations_from_db(Some(&stop_filter), pool.acquire().await.unwrap()).await;
+            continue;
+        };
+        let policy = policy.as_ref().unwrap();
+        let filter = format!("id='{}'", alert.host_id);
+        if let Some(host) = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .next()
+        {
+            let mut event = NotificationEvent::from_alert(&alert, host);
+            event.kind = EventKind::AlertEscalated;
+            event.summary = format!(
+                "{} (not acknowledged, escalation step {})",
+                event.summary,
+                escalation.step + 1
+            );
+            deliveries.extend(notify_channels(event, &step.channels, pool.clone()).await);
+        }
+        escalation.step += 1;
+        if let Some(next) = policy.steps.get(escalation.step as usize) {
+            escalation.next_at = now + Duration::minutes(next.delay_mins);
+        } else if policy.repeat_interval_mins > 0 && escalation.repeats < policy.max_repeats {
+            escalation.step = 0;
+            escalation.repeats += 1;
+            escalation.next_at = now + Duration::minutes(policy.repeat_interval_mins);
+        } else {
+            info!("Escalation of alert {} finished", alert.id);
+            let _del =
+                delete_escalations_from_db(Some(&stop_filter), pool.acquire().await.unwrap()).await;
+            continue;
+        }
+        let _res = escalation
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+    }
+    deliveries
+}
+
+/// API to get all escalation policies
+pub async fn get_policies_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let policy_vec = get_policies_from_db(None, pool.acquire().await.unwrap()).await;
+    Json(policy_vec)
+}
+
+/// API to get one escalation policy
+pub async fn get_one_policy_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let policy_vec = get_policies_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(policy_vec)
+}
+
+/// API to create or replace an escalation policy
+pub async fn post_policies_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<EscalationPolicy>,
+) -> Response {
+    if let Err(e) = payload.validate(&pool).await {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Nothing added",
+        )
+            .into_response()
+    }
+}
+
+/// API to delete an escalation policy, routes using it no longer escalate
+pub async fn delete_one_policy_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_policies_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// API to get all running escalations
+pub async fn get_escalations_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let escalation_vec =
+        get_escalations_from_db(Some("1=1 ORDER BY next_at"), pool.acquire().await.unwrap()).await;
+    Json(escalation_vec)
+}
+
+pub async fn get_policies_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<EscalationPolicy> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM escalation_policies WHERE {f}"),
+        None => "SELECT * FROM escalation_policies".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| EscalationPolicy::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_policies_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM escalation_policies WHERE {f}"),
+        None => "DELETE FROM escalation_policies".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_escalations_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Escalation> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM escalations WHERE {f}"),
+        None => "SELECT * FROM escalations".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Escalation::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_escalations_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM escalations WHERE {f}"),
+        None => "DELETE FROM escalations".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alert::{acknowledge_alert, AlertCondition, AlertRule, Severity},
+        db::{create_database, init_database},
+        host::Host,
+        notification::{tests::http_stand_in, ChannelConfig, NotificationChannel},
+        revision::save_script,
+        routing::Route,
+        schedule::Schedule,
+        script::Script,
+    };
+    use std::collections::BTreeMap;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn slack(name: &str, url: String) -> NotificationChannel {
+        NotificationChannel {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            config: ChannelConfig::Slack { url },
+            title_template: "{{ event.title }}".into(),
+            body_template: "{{ event.summary }}".into(),
+            min_severity: Severity::Info,
+            events: vec![],
+            active: true,
+            created: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_escalations() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (base, captured) = http_stand_in().await;
+
+        let first = slack("on call", format!("{base}/first"));
+        let second = slack("team lead", format!("{base}/second"));
+        for c in [first.clone(), second.clone()] {
+            let _c = c.insert_into_db(pool.acquire().await.unwrap()).await;
+        }
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let _s = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "disk full".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 1 },
+            severity: Severity::Critical,
+            labels: BTreeMap::new(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let now = Utc::now();
+        let alert = Alert {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            host_id: host.id,
+            sched_id: sched.id,
+            state: AlertState::Firing,
+            severity: Severity::Critical,
+            labels: BTreeMap::from([
+                ("alertname".to_string(), "disk full".to_string()),
+                ("team".to_string(), "ops".to_string()),
+            ]),
+            summary: "disk.free is 1 (< 5)".into(),
+            fired: Some(now),
+            ..Default::default()
+        };
+        let _a = alert
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        let policy = EscalationPolicy {
+            id: Uuid::new_v4(),
+            name: "ops".into(),
+            steps: vec![
+                EscalationStep {
+                    delay_mins: 0,
+                    channels: vec![first.id],
+                },
+                EscalationStep {
+                    delay_mins: 10,
+                    channels: vec![second.id],
+                },
+            ],
+            repeat_interval_mins: 30,
+            max_repeats: 1,
+            created: Utc::now(),
+        };
+        assert!(policy.validate(&pool).await.is_ok());
+        let _p = policy
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let route = Route {
+            id: Uuid::new_v4(),
+            name: "ops".into(),
+            parent_id: None,
+            position: 0,
+            matchers: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            channels: vec![first.id],
+            escalation_id: Some(policy.id),
+            continue_matching: false,
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = route
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+
+        // firing alerts of the route start its policy, only the route channels are notified
+        let deliveries = crate::notification::notify(
+            NotificationEvent::from_alert(&alert, host.clone()),
+            pool.clone(),
+        )
+        .await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].channel_id, first.id);
+        let running = get_escalations_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(running.len(), 1);
+        assert_eq!((running[0].step, running[0].repeats), (0, 0));
+
+        // steps are sent once due, then the policy repeats once
+        let sent = |deliveries: Vec<NotificationDelivery>| -> Vec<Uuid> {
+            assert!(deliveries
+                .iter()
+                .all(|d| d.event == EventKind::AlertEscalated));
+            deliveries.iter().map(|d| d.channel_id).collect()
+        };
+        assert_eq!(sent(escalate(now, &pool).await), vec![first.id]);
+        assert!(escalate(now, &pool).await.is_empty());
+        let at = |mins: i64| now + Duration::minutes(mins);
+        assert_eq!(sent(escalate(at(10), &pool).await), vec![second.id]);
+        let running = get_escalations_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!((running[0].step, running[0].repeats), (0, 1));
+        assert_eq!(
+            running[0].next_at.timestamp_millis(),
+            at(40).timestamp_millis()
+        );
+        assert!(escalate(at(39), &pool).await.is_empty());
+        assert_eq!(sent(escalate(at(40), &pool).await), vec![first.id]);
+        assert_eq!(sent(escalate(at(50), &pool).await), vec![second.id]);
+        assert!(get_escalations_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+        {
+            let requests = captured.lock().unwrap();
+            let request = requests.iter().find(|r| r.1 == "/second").unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&request.3).unwrap();
+            assert_eq!(
+                body["text"],
+                "*disk full*\ndisk.free is 1 (< 5) (not acknowledged, escalation step 2)"
+            );
+        }
+
+        // acknowledging stops the escalation
+        start_escalation(&alert, policy.id, now, &pool).await;
+        let _ack = acknowledge_alert(alert.id, "b@test.int", &pool).await;
+        assert!(escalate(now, &pool).await.is_empty());
+        assert!(get_escalations_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+
+        // invalid policies
+        let mut no_steps = policy.clone();
+        no_steps.steps = vec![];
+        assert!(no_steps.validate(&pool).await.is_err());
+        let mut unknown_channel = policy.clone();
+        unknown_channel.steps[0].channels = vec![Uuid::new_v4()];
+        assert!(unknown_channel.validate(&pool).await.is_err());
+
+        // updating a policy keeps running escalations, deleting it stops them
+        let _a = alert
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        start_escalation(&alert, policy.id, now, &pool).await;
+        let _p = policy
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        assert_eq!(
+            get_escalations_from_db(None, pool.acquire().await.unwrap())
+                .await
+                .len(),
+            1
+        );
+        let filter = format!("id='{}'", policy.id);
+        let _d = delete_policies_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert!(get_escalations_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+        let routes = crate::routing::get_routes_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(routes[0].escalation_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_escalation_across_evaluations() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (base, _captured) = http_stand_in().await;
+        let first = slack("on call", format!("{base}/first"));
+        let second = slack("team lead", format!("{base}/second"));
+        for c in [first.clone(), second.clone()] {
+            let _c = c.insert_into_db(pool.acquire().await.unwrap()).await;
+        }
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            active: true,
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            output_regex: "^ok$".into(),
+            fail_on_no_match: true,
+            ..Default::default()
+        };
+        let _s = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let exe = crate::execution::Execution {
+            id: Uuid::new_v4(),
+            host_id: host.id,
+            sched_id: sched.id,
+            ..Default::default()
+        };
+        let exe_id = exe.id;
+        exe.insert_into_db(pool.acquire().await.unwrap()).await;
+        crate::execution::store_result(exe_id, "disk full".into(), Utc::now(), &pool).await;
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "disk full".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 1 },
+            severity: Severity::Critical,
+            labels: BTreeMap::new(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let policy = EscalationPolicy {
+            id: Uuid::new_v4(),
+            name: "ops".into(),
+            steps: vec![
+                EscalationStep {
+                    delay_mins: 0,
+                    channels: vec![first.id],
+                },
+                EscalationStep {
+                    delay_mins: 10,
+                    channels: vec![second.id],
+                },
+            ],
+            repeat_interval_mins: 0,
+            max_repeats: 0,
+            created: Utc::now(),
+        };
+        let _p = policy
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let route = Route {
+            id: Uuid::new_v4(),
+            name: "disk".into(),
+            parent_id: None,
+            position: 0,
+            matchers: BTreeMap::from([("alertname".to_string(), "disk full".to_string())]),
+            channels: vec![first.id],
+            escalation_id: Some(policy.id),
+            continue_matching: false,
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = route
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+
+        // the evaluation loop: rules, notifications of changed alerts, escalations
+        let now = Utc::now();
+        let at = |mins: i64| now + Duration::minutes(mins);
+        let changed = crate::alert::evaluate_rules(now, &pool).await;
+        assert_eq!(changed.len(), 1);
+        let _n = crate::notification::notify_alerts(changed, pool.clone()).await;
+        assert_eq!(
+            escalate(now, &pool)
+                .await
+                .iter()
+                .map(|d| d.channel_id)
+                .collect::<Vec<_>>(),
+            vec![first.id]
+        );
+
+        // the next cycle updates the still firing alert and keeps its escalation
+        assert!(crate::alert::evaluate_rules(at(5), &pool).await.is_empty());
+        let running = get_escalations_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].step, 1);
+        assert!(crate::alert::evaluate_rules(at(10), &pool).await.is_empty());
+        assert_eq!(
+            escalate(at(10), &pool)
+                .await
+                .iter()
+                .map(|d| d.channel_id)
+                .collect::<Vec<_>>(),
+            vec![second.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let on_call = slack("on call", "http://127.0.0.1:1/hook".into());
+        let _c = on_call
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let policy = EscalationPolicy {
+            id: Uuid::new_v4(),
+            name: "ops".into(),
+            steps: vec![EscalationStep {
+                delay_mins: 5,
+                channels: vec![on_call.id],
+            }],
+            repeat_interval_mins: 0,
+            max_repeats: 0,
+            created: Utc::now(),
+        };
+        let api_post = post_policies_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(policy.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let mut invalid = policy.clone();
+        invalid.steps[0].delay_mins = -1;
+        let api_post_invalid = post_policies_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_post_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_all = get_policies_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+
+        let api_get_one = get_one_policy_api(
+            claims.clone(),
+            axum::extract::Path(policy.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get_one.into_body())
+            .await
+            .unwrap();
+        let stored: Vec<EscalationPolicy> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stored[0].steps, policy.steps);
+
+        let api_escalations =
+            get_escalations_api(claims.clone(), axum::extract::State(pool.clone()))
+                .await
+                .into_response();
+        assert_eq!(api_escalations.status(), StatusCode::OK);
+
+        let api_del = delete_one_policy_api(
+            claims.clone(),
+            axum::extract::Path(policy.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+    }
+}
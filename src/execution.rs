@@ -1,22 +1,40 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
-use chrono::{DateTime, Utc};
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::{
     pool::PoolConnection,
     query,
     sqlite::{SqliteQueryResult, SqliteRow},
     Row, Sqlite, SqlitePool,
 };
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
     db::{utc_from_str, utc_to_str},
+    drift::{detect_drift, output_hash},
+    exporter::{observe, timed, DISPATCH_LATENCY},
+    host::merge_facts,
     jwt::Claims,
+    lifecycle::parse_os_release,
+    metric::record_fields,
+    package::{parse_inventory, store_inventory},
+    parser::{typed, Fields, OutputParser},
+    reboot::{parse_reboot_status, store_reboot_status, RebootState},
+    remediation::check_result,
+    revision::{get_revision, get_script_for_schedule},
+    schedule::get_schedules_from_db,
+    script::Script,
+    update::{parse_updates, store_updates},
 };
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
@@ -32,6 +50,239 @@ pub struct Execution {
     pub created: DateTime<Utc>,
     #[serde(default = "String::new")]
     pub output: String,
+    /// script content with all placeholders replaced, as sent to the agent
+    #[serde(default)]
+    pub rendered_script: Option<String>,
+    /// revision of the script that was sent to the agent
+    #[serde(default)]
+    pub script_revision: Option<i64>,
+    /// output matched the `output_regex` of the script
+    #[serde(default)]
+    pub matched: Option<bool>,
+    #[serde(default)]
+    pub verdict: Option<Verdict>,
+    /// named capture groups of the `output_regex`
+    #[serde(default)]
+    pub extracted: HashMap<String, String>,
+    /// typed fields from the output parser of the script
+    #[serde(default)]
+    pub fields: Fields,
+    #[serde(default)]
+    pub parse_error: Option<String>,
+    /// when the script was sent to the agent
+    #[serde(default)]
+    pub dispatched: Option<DateTime<Utc>>,
+    /// progress of the reboot step of the script, the verdict stays open until it finishes
+    #[serde(default)]
+    pub reboot_state: Option<RebootState>,
+    /// the host has to be back and pass the post-check before this point in time
+    #[serde(default)]
+    pub reboot_deadline: Option<DateTime<Utc>>,
+    /// id of the post-check sent to the agent after the reboot
+    #[serde(default)]
+    pub check_id: Option<Uuid>,
+    /// revision of the post-check script sent to the agent
+    #[serde(default)]
+    pub check_revision: Option<i64>,
+    /// hex sha256 of the normalized output, compared between runs to detect drift
+    #[serde(default)]
+    pub output_hash: Option<String>,
+}
+
+/// Outcome of an execution, derived from its output
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Success,
+    Failure,
+    /// output could not be parsed by the output parser of the script
+    ParseError,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Success => write!(f, "success"),
+            Verdict::Failure => write!(f, "failure"),
+            Verdict::ParseError => write!(f, "parse_error"),
+        }
+    }
+}
+
+impl Verdict {
+    pub fn from_db(s: &str) -> Option<Verdict> {
+        match s {
+            "success" => Some(Verdict::Success),
+            "failure" => Some(Verdict::Failure),
+            "parse_error" => Some(Verdict::ParseError),
+            _ => None,
+        }
+    }
+}
+
+/// Result of applying the `output_regex` and the parser of a script to an output
+#[derive(PartialEq, Debug, Clone)]
+pub struct OutputEvaluation {
+    pub matched: bool,
+    pub verdict: Verdict,
+    pub extracted: HashMap<String, String>,
+    pub fields: Fields,
+    pub parse_error: Option<String>,
+}
+
+/// Evaluate `output` against the `output_regex` and the parser of `script`
+///
+/// the first match is used for named capture groups, a no-match is only a failure
+/// with `fail_on_no_match`, a parse failure always wins
+pub fn evaluate_output(script: &Script, output: &str) -> OutputEvaluation {
+    let captures = match Regex::new(&script.output_regex) {
+        Ok(regex) => regex.captures(output).map(|c| {
+            regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| Some((name.to_string(), c.name(name)?.as_str().to_string())))
+                .collect::<HashMap<String, String>>()
+        }),
+        Err(e) => {
+            // scripts saved before the regex got validated
+            warn!("output_regex of script {} is invalid: {e}", script.id);
+            None
+        }
+    };
+    let matched = captures.is_some();
+    let (fields, parse_error) = match script.parser.parse(output) {
+        Ok(fields) => (fields, None),
+        Err(e) => (Fields::new(), Some(e.to_string())),
+    };
+    let verdict = if parse_error.is_some() {
+        Verdict::ParseError
+    } else if matched || !script.fail_on_no_match {
+        Verdict::Success
+    } else {
+        Verdict::Failure
+    };
+    OutputEvaluation {
+        matched,
+        verdict,
+        extracted: captures.unwrap_or_default(),
+        fields,
+        parse_error,
+    }
+}
+
+/// Store the output an agent returned at `now`, evaluated against the executed script revision
+pub async fn store_result(
+    id: Uuid,
+    output: String,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> SqliteQueryResult {
+    let filter = format!("id='{id}'");
+    let execution = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next();
+    let script = match &execution {
+        Some(exe) => get_executed_script(exe, pool).await,
+        None => None,
+    };
+    let evaluation = script.as_ref().map(|s| evaluate_output(s, &output));
+    if let Some(dispatched) = execution.as_ref().and_then(|exe| exe.dispatched) {
+        let latency = (Utc::now() - dispatched).num_milliseconds() as f64 / 1000.0;
+        observe(DISPATCH_LATENCY, &[], latency);
+    }
+    if let (Some(exe), Some(evaluation)) = (&execution, &evaluation) {
+        let _samples =
+            record_fields(exe.host_id, exe.sched_id, now, &evaluation.fields, pool).await;
+    }
+    if let (Some(exe), Some(script)) = (&execution, &script) {
+        if script.parser == OutputParser::Packages {
+            if let Ok(packages) = parse_inventory(&output) {
+                if let Err(e) = store_inventory(exe.host_id, packages, now, pool).await {
+                    warn!("Inventory of host {} could not be stored: {e}", exe.host_id);
+                }
+            }
+        }
+        if script.parser == OutputParser::Updates {
+            if let Ok(updates) = parse_updates(&output) {
+                if let Err(e) = store_updates(exe.host_id, updates, now, pool).await {
+                    warn!("Updates of host {} could not be stored: {e}", exe.host_id);
+                }
+            }
+        }
+        if script.parser == OutputParser::OsRelease {
+            if let Ok(facts) = parse_os_release(&output) {
+                let _res = merge_facts(exe.host_id, facts, pool).await;
+            }
+        }
+        if script.parser == OutputParser::RebootStatus {
+            if let Ok(status) = parse_reboot_status(&output) {
+                let _res = store_reboot_status(exe.host_id, &status, now, pool).await;
+            }
+        }
+    }
+    let hash = output_hash(&output);
+    if let (Some(exe), Some(script)) = (&execution, &script) {
+        if script.track_drift {
+            let _drift = detect_drift(exe, script.id, &hash, &output, now, pool).await;
+        }
+    }
+    // a successful run with a reboot step only succeeds once the host is back and checked
+    let reboot = match (&script, &evaluation) {
+        (Some(script), Some(e)) if e.verdict == Verdict::Success => script.reboot.as_ref(),
+        _ => None,
+    };
+    // the verdict of a run with a reboot step is only known after the reboot
+    let verdict = evaluation
+        .as_ref()
+        .filter(|_| reboot.is_none())
+        .map(|e| e.verdict.clone());
+    let q = "UPDATE executions SET response = ?, output = ?, matched = ?, verdict = ?, extracted = ?, fields = ?, parse_error = ?, reboot_state = ?, reboot_deadline = ?, output_hash = ? WHERE id = ?";
+    let stmt = query(q)
+        .bind(utc_to_str(now))
+        .bind(output)
+        .bind(evaluation.as_ref().map(|e| e.matched))
+        .bind(verdict.as_ref().map(|v| v.to_string()))
+        .bind(
+            evaluation
+                .as_ref()
+                .map(|e| serde_json::to_string(&e.extracted).unwrap()),
+        )
+        .bind(
+            evaluation
+                .as_ref()
+                .map(|e| serde_json::to_string(&e.fields).unwrap()),
+        )
+        .bind(evaluation.and_then(|e| e.parse_error))
+        .bind(reboot.map(|_| RebootState::Pending.to_string()))
+        .bind(reboot.map(|r| utc_to_str(now + Duration::seconds(r.deadline_secs as i64))))
+        .bind(hash)
+        .bind(id.to_string());
+    let res = timed("store_result", async {
+        stmt.execute(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap_or_default()
+    })
+    .await;
+    if let (Some(exe), Some(verdict)) = (&execution, &verdict) {
+        let _remediations = check_result(exe, verdict, now, pool).await;
+    }
+    res
+}
+
+/// the script revision that was sent to the agent for `exe`
+pub async fn get_executed_script(exe: &Execution, pool: &SqlitePool) -> Option<Script> {
+    let filter = format!("id='{}'", exe.sched_id);
+    let schedule = get_schedules_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()?;
+    match exe.script_revision {
+        Some(revision) => get_revision(schedule.script_id, revision, pool.acquire().await.unwrap())
+            .await
+            .map(|r| r.script),
+        None => get_script_for_schedule(&schedule, pool.acquire().await.unwrap()).await,
+    }
 }
 
 impl Execution {
@@ -46,6 +297,19 @@ impl Execution {
     /// | sched_id | TEXT | uuid
     /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
     /// | output | TEXT | <-- implemented by another call, always created as NULL
+    /// | rendered_script | TEXT | <-- implemented by another call, always created as NULL
+    /// | script_revision | INT | <-- implemented by another call, always created as NULL
+    /// | matched | NUMERIC | <-- implemented by another call, always created as NULL
+    /// | verdict | TEXT | <-- implemented by another call, always created as NULL
+    /// | extracted | TEXT | <-- implemented by another call, always created as NULL
+    /// | fields | TEXT | <-- implemented by another call, always created as NULL
+    /// | parse_error | TEXT | <-- implemented by another call, always created as NULL
+    /// | dispatched | TEXT | <-- implemented by another call, always created as NULL
+    /// | reboot_state | TEXT | <-- implemented by another call, always created as NULL
+    /// | reboot_deadline | TEXT | <-- implemented by another call, always created as NULL
+    /// | check_id | TEXT | <-- implemented by another call, always created as NULL
+    /// | check_revision | INT | <-- implemented by another call, always created as NULL
+    /// | output_hash | TEXT | <-- implemented by another call, always created as NULL
     pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
         let q = r#"REPLACE INTO executions( id, request, host_id, sched_id, created ) VALUES( ?, ?, ?, ?, ? )"#;
         query(q)
@@ -73,39 +337,131 @@ impl From<SqliteRow> for Execution {
             sched_id: s.get::<String, _>("sched_id").parse().unwrap(),
             created: utc_from_str(&s.get::<String, _>("created")),
             output: s.get::<String, _>("output"),
+            rendered_script: s.get::<Option<String>, _>("rendered_script"),
+            script_revision: s.get::<Option<i64>, _>("script_revision"),
+            matched: s.get::<Option<bool>, _>("matched"),
+            verdict: Verdict::from_db(&s.get::<String, _>("verdict")),
+            extracted: serde_json::from_str(&s.get::<String, _>("extracted")).unwrap_or_default(),
+            fields: serde_json::from_str(&s.get::<String, _>("fields")).unwrap_or_default(),
+            parse_error: s.get::<Option<String>, _>("parse_error"),
+            dispatched: s
+                .get::<Option<String>, _>("dispatched")
+                .as_deref()
+                .map(utc_from_str),
+            reboot_state: s
+                .get::<Option<String>, _>("reboot_state")
+                .as_deref()
+                .and_then(RebootState::from_db),
+            reboot_deadline: s
+                .get::<Option<String>, _>("reboot_deadline")
+                .as_deref()
+                .map(utc_from_str),
+            check_id: s
+                .get::<Option<String>, _>("check_id")
+                .and_then(|id| id.parse().ok()),
+            check_revision: s.get::<Option<i64>, _>("check_revision"),
+            output_hash: s.get::<Option<String>, _>("output_hash"),
         }
     }
 }
 
+/// Filter executions by verdict, reboot state or by a parsed field
+///
+/// `field` alone filters executions that have the field, `eq` compares typed values,
+/// `gt` and `lt` compare numbers
+#[derive(Debug, Deserialize, Default)]
+pub struct ExecutionQueryParams {
+    verdict: Option<Verdict>,
+    reboot_state: Option<RebootState>,
+    field: Option<String>,
+    eq: Option<String>,
+    gt: Option<f64>,
+    lt: Option<f64>,
+}
+
+impl ExecutionQueryParams {
+    /// SQL conditions for the `executions` table, appended to `base`
+    fn to_filter(&self, base: Option<String>) -> Result<Option<String>, String> {
+        let mut conditions: Vec<String> = base.into_iter().collect();
+        if let Some(verdict) = &self.verdict {
+            conditions.push(format!("verdict='{verdict}'"));
+        }
+        if let Some(state) = &self.reboot_state {
+            conditions.push(format!("reboot_state='{state}'"));
+        }
+        if let Some(field) = &self.field {
+            if field.contains(['"', '\'', '\\']) {
+                return Err(format!("field name '{field}' is invalid"));
+            }
+            let path = format!("json_extract(fields, '$.\"{field}\"')");
+            conditions.push(format!("{path} IS NOT NULL"));
+            if let Some(eq) = &self.eq {
+                let literal = match typed(eq) {
+                    Value::Bool(b) => i64::from(b).to_string(),
+                    Value::Number(n) => n.to_string(),
+                    _ => format!("'{}'", eq.replace('\'', "''")),
+                };
+                conditions.push(format!("{path} = {literal}"));
+            }
+            if let Some(gt) = self.gt {
+                conditions.push(format!("{path} > {gt}"));
+            }
+            if let Some(lt) = self.lt {
+                conditions.push(format!("{path} < {lt}"));
+            }
+        } else if self.eq.is_some() || self.gt.is_some() || self.lt.is_some() {
+            return Err("eq, gt and lt need a field".into());
+        }
+        if conditions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(conditions.join(" AND ")))
+        }
+    }
+}
+
+async fn query_executions_response(
+    base: Option<String>,
+    params: &ExecutionQueryParams,
+    pool: &SqlitePool,
+) -> Response {
+    match params.to_filter(base) {
+        Ok(filter) => {
+            let execution_vec =
+                get_executions_from_db(filter.as_deref(), pool.acquire().await.unwrap()).await;
+            Json(execution_vec).into_response()
+        }
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
 /// API to get all executions
 pub async fn get_executions_api(
     _claims: Claims,
+    Query(params): Query<ExecutionQueryParams>,
     State(pool): State<SqlitePool>,
-) -> impl IntoResponse {
-    let execution_vec = get_executions_from_db(None, pool.acquire().await.unwrap()).await;
-    Json(execution_vec)
+) -> Response {
+    query_executions_response(None, &params, &pool).await
 }
 
 /// API to get all executions for host
 pub async fn get_host_executions_api(
     _claims: Claims,
     Path(id): Path<Uuid>,
+    Query(params): Query<ExecutionQueryParams>,
     State(pool): State<SqlitePool>,
-) -> impl IntoResponse {
-    let filter = format!("host_id='{id}'",);
-    let execution_vec = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
-    Json(execution_vec)
+) -> Response {
+    query_executions_response(Some(format!("host_id='{id}'")), &params, &pool).await
 }
 
-/// API to get all executions for host
+/// API to get all executions for schedule
 pub async fn get_schedule_executions_api(
     _claims: Claims,
     Path(id): Path<Uuid>,
+    Query(params): Query<ExecutionQueryParams>,
     State(pool): State<SqlitePool>,
-) -> impl IntoResponse {
-    let filter = format!("sched_id='{id}'",);
-    let execution_vec = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
-    Json(execution_vec)
+) -> Response {
+    query_executions_response(Some(format!("sched_id='{id}'")), &params, &pool).await
 }
 
 /// API to get one execution
@@ -192,6 +548,8 @@ mod tests {
     use crate::{
         db::{create_database, init_database},
         host::Host,
+        parser::OutputParser,
+        revision::save_script,
         schedule::{get_schedules_from_db, Schedule},
         script::Script,
     };
@@ -220,7 +578,9 @@ mod tests {
 
         // prepare a script to reference in the schedule (nil_id)
         let script = Script::default();
-        let _script = script.insert_into_db(pool.acquire().await.unwrap()).await;
+        let _script = script
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await;
 
         // prepare a sched to reference in the execution (nil_id)
         let sched = Schedule::default();
@@ -286,6 +646,195 @@ mod tests {
         assert_eq!(executions, 0);
     }
 
+    #[test]
+    fn test_evaluate_output() {
+        let script = Script {
+            output_regex: r"load average: (?P<load1>[0-9.]+), (?P<load5>[0-9.]+)(?P<never>x)?"
+                .into(),
+            ..Default::default()
+        };
+        let eval = evaluate_output(&script, "10:00 up 3 days, load average: 0.52, 0.58, 0.59");
+        assert!(eval.matched);
+        assert_eq!(eval.verdict, Verdict::Success);
+        assert_eq!(eval.extracted.len(), 2);
+        assert_eq!(eval.extracted["load1"], "0.52");
+        assert_eq!(eval.extracted["load5"], "0.58");
+
+        let eval = evaluate_output(&script, "command not found");
+        assert!(!eval.matched);
+        assert_eq!(eval.verdict, Verdict::Success);
+        assert!(eval.extracted.is_empty());
+
+        let strict = Script {
+            fail_on_no_match: true,
+            ..script.clone()
+        };
+        assert_eq!(
+            evaluate_output(&strict, "command not found").verdict,
+            Verdict::Failure
+        );
+        let broken = Script {
+            output_regex: "[".into(),
+            ..Default::default()
+        };
+        assert!(!evaluate_output(&broken, "anything").matched);
+    }
+
+    #[tokio::test]
+    async fn test_store_result() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let host = Host::default();
+        let host_id = host.id;
+        let _host = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let mut script = Script {
+            id: Uuid::new_v4(),
+            output_regex: r"^ok (?P<count>\d+)$".into(),
+            fail_on_no_match: true,
+            ..Default::default()
+        };
+        let _r1 = save_script(script.clone(), "a@test.int", "", &pool).await;
+        script.output_regex = ".*".into();
+        let _r2 = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            script_id: script.id,
+            ..Default::default()
+        };
+        let sched_id = sched.id;
+        let _sched = sched.insert_into_db(pool.acquire().await.unwrap()).await;
+
+        // executed with revision 1, evaluated with the regex of revision 1
+        let execution = Execution {
+            host_id,
+            sched_id,
+            script_revision: Some(1),
+            ..Default::default()
+        };
+        let _i1 = execution
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let _upd = update_text_field(
+            execution.id,
+            "script_revision",
+            "1".into(),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        let res = store_result(execution.id, "ok 42".into(), Utc::now(), &pool).await;
+        assert_eq!(res.rows_affected(), 1);
+        let filter = format!("id='{}'", execution.id);
+        let stored = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].output, "ok 42");
+        assert!(stored[0].response.is_some());
+        assert_eq!(stored[0].matched, Some(true));
+        assert_eq!(stored[0].verdict, Some(Verdict::Success));
+        assert_eq!(stored[0].extracted["count"], "42");
+
+        let _res = store_result(execution.id, "error".into(), Utc::now(), &pool).await;
+        let stored = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].matched, Some(false));
+        assert_eq!(stored[0].verdict, Some(Verdict::Failure));
+        assert!(stored[0].extracted.is_empty());
+
+        // unknown execution, nothing to update
+        let res = store_result(Uuid::new_v4(), "ok 1".into(), Utc::now(), &pool).await;
+        assert_eq!(res.rows_affected(), 0);
+
+        // parsed fields are stored and can be queried
+        let parsed = Script {
+            id: Uuid::new_v4(),
+            parser: OutputParser::Json,
+            ..Default::default()
+        };
+        let _r3 = save_script(parsed.clone(), "a@test.int", "", &pool).await;
+        let parsed_sched = Schedule {
+            script_id: parsed.id,
+            ..Default::default()
+        };
+        let parsed_sched_id = parsed_sched.id;
+        let _sched = parsed_sched
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let parsed_execution = Execution {
+            host_id,
+            sched_id: parsed_sched_id,
+            ..Default::default()
+        };
+        let _i2 = parsed_execution
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let _res = store_result(
+            parsed_execution.id,
+            r#"{"disk": {"free": 12.5}, "os": "debian", "ok": true}"#.into(),
+            Utc::now(),
+            &pool,
+        )
+        .await;
+        let filter = format!("id='{}'", parsed_execution.id);
+        let stored = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].verdict, Some(Verdict::Success));
+        assert_eq!(stored[0].fields["disk.free"], serde_json::json!(12.5));
+        // numeric fields are recorded as metrics
+        let metrics = sqlx::query("SELECT name FROM metrics")
+            .fetch_all(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].get::<String, _>("name"), "disk.free");
+
+        let query = |field: &str, eq: Option<&str>, gt: Option<f64>, lt: Option<f64>| {
+            ExecutionQueryParams {
+                field: Some(field.into()),
+                eq: eq.map(String::from),
+                gt,
+                lt,
+                ..Default::default()
+            }
+            .to_filter(None)
+            .unwrap()
+        };
+        let count = |filter: Option<String>| {
+            let pool = pool.clone();
+            async move {
+                get_executions_from_db(filter.as_deref(), pool.acquire().await.unwrap())
+                    .await
+                    .len()
+            }
+        };
+        assert_eq!(count(query("disk.free", None, None, None)).await, 1);
+        assert_eq!(count(query("disk.free", None, Some(10.0), None)).await, 1);
+        assert_eq!(count(query("disk.free", None, None, Some(10.0))).await, 0);
+        assert_eq!(count(query("os", Some("debian"), None, None)).await, 1);
+        assert_eq!(count(query("ok", Some("true"), None, None)).await, 1);
+        assert_eq!(count(query("missing", None, None, None)).await, 0);
+        let success = ExecutionQueryParams {
+            verdict: Some(Verdict::Success),
+            ..Default::default()
+        };
+        assert_eq!(count(success.to_filter(None).unwrap()).await, 1);
+
+        // output the parser rejects is a parse error, not a failure
+        let _res = store_result(parsed_execution.id, "{broken".into(), Utc::now(), &pool).await;
+        let stored = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].verdict, Some(Verdict::ParseError));
+        assert!(stored[0].fields.is_empty());
+        assert!(stored[0].parse_error.is_some());
+        let parse_errors = ExecutionQueryParams {
+            verdict: Some(Verdict::ParseError),
+            ..Default::default()
+        };
+        assert_eq!(count(parse_errors.to_filter(None).unwrap()).await, 1);
+    }
+
     #[tokio::test]
     async fn test_apis() {
         registry()
@@ -305,7 +854,9 @@ mod tests {
 
         // prepare a script to reference in the schedule (nil_id)
         let script = Script::default();
-        let _script = script.insert_into_db(pool.acquire().await.unwrap()).await;
+        let _script = script
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await;
 
         // prepare a sched to reference in the execution (nil_id)
         let sched = Schedule::default();
@@ -324,11 +875,28 @@ mod tests {
             .await;
         assert_eq!(i1.rows_affected(), 1);
 
-        let api_get_all = get_executions_api(claims.clone(), axum::extract::State(pool.clone()))
-            .await
-            .into_response();
+        let api_get_all = get_executions_api(
+            claims.clone(),
+            axum::extract::Query(ExecutionQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
         assert_eq!(api_get_all.status(), axum::http::StatusCode::OK);
 
+        let api_get_invalid = get_executions_api(
+            claims.clone(),
+            axum::extract::Query(ExecutionQueryParams {
+                field: Some("a'b".into()),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(
+            api_get_invalid.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+
         let api_get_one = get_one_execution_api(
             claims.clone(),
             axum::extract::Path(execution.id),
@@ -341,10 +909,10 @@ mod tests {
         let get_host_executions_api = get_host_executions_api(
             claims.clone(),
             axum::extract::Path(host_id),
+            axum::extract::Query(ExecutionQueryParams::default()),
             axum::extract::State(pool.clone()),
         )
-        .await
-        .into_response();
+        .await;
         assert_eq!(get_host_executions_api.status(), axum::http::StatusCode::OK);
 
         let api_del_one = delete_one_execution_api(
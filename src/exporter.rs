@@ -0,0 +1,414 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use axum::{
+    extract::{State, TypedHeader},
+    headers::{authorization::Bearer, Authorization},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use once_cell::sync::{Lazy, OnceCell};
+use sqlx::{query, sqlite::SqliteRow, Row, SqlitePool};
+
+use crate::db::utc_to_str;
+
+/// Token Prometheus has to send as bearer token, the endpoint is disabled without it
+pub static SCRAPE_TOKEN: OnceCell<String> = OnceCell::new();
+
+/// Agents with an open websocket
+pub static CONNECTED_AGENTS: AtomicI64 = AtomicI64::new(0);
+
+/// Upper bounds of histogram buckets in seconds
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+pub const DISPATCH_LATENCY: &str = "unpatched_dispatch_latency_seconds";
+pub const SCHEDULER_LAG: &str = "unpatched_scheduler_lag_seconds";
+pub const DB_QUERY_DURATION: &str = "unpatched_db_query_duration_seconds";
+pub const LOGIN_FAILURES: &str = "unpatched_login_failures_total";
+
+/// name, type and help of every exported metric
+const DESCRIPTIONS: [(&str, &str, &str); 8] = [
+    (
+        "unpatched_connected_agents",
+        "gauge",
+        "Agents with an open websocket",
+    ),
+    ("unpatched_executions", "gauge", "Executions by state"),
+    (
+        DISPATCH_LATENCY,
+        "histogram",
+        "Time from sending a script to an agent until its result arrives",
+    ),
+    (
+        SCHEDULER_LAG,
+        "histogram",
+        "Time from the requested execution time until the script is sent",
+    ),
+    (
+        DB_QUERY_DURATION,
+        "histogram",
+        "Duration of database queries on the hot paths",
+    ),
+    (LOGIN_FAILURES, "counter", "Failed logins by reason"),
+    (
+        "unpatched_blacklisted_ips",
+        "gauge",
+        "IPs currently blocked after failed logins",
+    ),
+    (
+        "unpatched_script_metric",
+        "gauge",
+        "Latest numeric field of a script output per host",
+    ),
+];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// histograms and counters, keyed by metric name and rendered labels
+static HISTOGRAMS: Lazy<Mutex<BTreeMap<(&str, String), Histogram>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+static COUNTERS: Lazy<Mutex<BTreeMap<(&str, String), u64>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// render label pairs in exposition format, values are escaped
+fn labels(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{k}=\"{v}\"")
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// record one observation of `seconds` in the histogram `name`
+pub fn observe(name: &'static str, pairs: &[(&str, &str)], seconds: f64) {
+    let mut histograms = HISTOGRAMS.lock().unwrap();
+    let histogram = histograms.entry((name, labels(pairs))).or_default();
+    for (bucket, le) in histogram.buckets.iter_mut().zip(BUCKETS) {
+        if seconds <= le {
+            *bucket += 1;
+        }
+    }
+    histogram.sum += seconds;
+    histogram.count += 1;
+}
+
+/// increase the counter `name` by one
+pub fn inc(name: &'static str, pairs: &[(&str, &str)]) {
+    *COUNTERS
+        .lock()
+        .unwrap()
+        .entry((name, labels(pairs)))
+        .or_default() += 1;
+}
+
+/// run a database query and record its duration under `query`
+pub async fn timed<T>(name: &str, fut: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let result = fut.await;
+    observe(
+        DB_QUERY_DURATION,
+        &[("query", name)],
+        start.elapsed().as_secs_f64(),
+    );
+    result
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    if labels.is_empty() {
+        let _ = writeln!(out, "{name} {value}");
+    } else {
+        let _ = writeln!(out, "{name}{{{labels}}} {value}");
+    }
+}
+
+fn write_histograms(out: &mut String, name: &str) {
+    let histograms = HISTOGRAMS.lock().unwrap();
+    for ((_, labels), histogram) in histograms.iter().filter(|((n, _), _)| *n == name) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (count, le) in histogram.buckets.iter().zip(BUCKETS) {
+            write_sample(
+                out,
+                &format!("{name}_bucket"),
+                &format!("{labels}{sep}le=\"{le}\""),
+                count,
+            );
+        }
+        write_sample(
+            out,
+            &format!("{name}_bucket"),
+            &format!("{labels}{sep}le=\"+Inf\""),
+            histogram.count,
+        );
+        write_sample(out, &format!("{name}_sum"), labels, histogram.sum);
+        write_sample(out, &format!("{name}_count"), labels, histogram.count);
+    }
+}
+
+/// render all metrics in Prometheus text exposition format
+pub async fn render(pool: &SqlitePool) -> String {
+    let mut samples: BTreeMap<&str, String> = BTreeMap::new();
+    let mut out = String::new();
+    write_sample(
+        &mut out,
+        "unpatched_connected_agents",
+        "",
+        CONNECTED_AGENTS.load(Ordering::Relaxed),
+    );
+    samples.insert("unpatched_connected_agents", out);
+
+    let now = utc_to_str(Utc::now());
+    let q = format!(
+        r#"SELECT CASE
+            WHEN response IS NULL AND request > '{now}' THEN 'scheduled'
+            WHEN response IS NULL THEN 'due'
+            WHEN response = '1970-01-01T00:00:00.000Z' THEN 'dispatched'
+            WHEN verdict IS NOT NULL THEN verdict
+            ELSE 'done' END AS state, COUNT(*) AS count
+        FROM executions GROUP BY state ORDER BY state"#
+    );
+    let rows = timed("exporter_executions", async {
+        query(&q)
+            .fetch_all(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap_or_default()
+    })
+    .await;
+    let mut out = String::new();
+    for row in rows {
+        let state: String = row.get("state");
+        write_sample(
+            &mut out,
+            "unpatched_executions",
+            &labels(&[("state", &state)]),
+            row.get::<i64, _>("count"),
+        );
+    }
+    samples.insert("unpatched_executions", out);
+
+    for name in [DISPATCH_LATENCY, SCHEDULER_LAG, DB_QUERY_DURATION] {
+        let mut out = String::new();
+        write_histograms(&mut out, name);
+        samples.insert(name, out);
+    }
+
+    let mut out = String::new();
+    for ((name, labels), count) in COUNTERS.lock().unwrap().iter() {
+        if *name == LOGIN_FAILURES {
+            write_sample(&mut out, name, labels, count);
+        }
+    }
+    samples.insert(LOGIN_FAILURES, out);
+
+    let q = format!("SELECT COUNT(*) AS count FROM blacklist WHERE blocked_until > '{now}'");
+    let blocked = timed("exporter_blacklist", async {
+        query(&q)
+            .map(|row: SqliteRow| row.get::<i64, _>("count"))
+            .fetch_one(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap_or_default()
+    })
+    .await;
+    let mut out = String::new();
+    write_sample(&mut out, "unpatched_blacklisted_ips", "", blocked);
+    samples.insert("unpatched_blacklisted_ips", out);
+
+    // sqlite returns the bare columns of the row with MAX(ts)
+    let q = r#"SELECT m.host_id, m.sched_id, m.name, m.value, MAX(m.ts), hosts.alias, hosts.attributes
+        FROM metrics m JOIN hosts ON hosts.id = m.host_id
+        GROUP BY m.host_id, m.sched_id, m.name ORDER BY hosts.alias, m.name"#;
+    let rows = timed("exporter_script_metrics", async {
+        query(q)
+            .fetch_all(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap_or_default()
+    })
+    .await;
+    let mut out = String::new();
+    for row in rows {
+        let attributes: Vec<String> =
+            serde_json::from_str(&row.get::<String, _>("attributes")).unwrap_or_default();
+        let pairs = [
+            ("host", row.get::<String, _>("alias")),
+            ("host_id", row.get::<String, _>("host_id")),
+            ("attributes", attributes.join(",")),
+            ("sched_id", row.get::<String, _>("sched_id")),
+            ("field", row.get::<String, _>("name")),
+        ];
+        let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        write_sample(
+            &mut out,
+            "unpatched_script_metric",
+            &labels(&pairs),
+            row.get::<f64, _>("value"),
+        );
+    }
+    samples.insert("unpatched_script_metric", out);
+
+    let mut exposition = String::new();
+    for (name, kind, help) in DESCRIPTIONS {
+        let _ = writeln!(exposition, "# HELP {name} {help}");
+        let _ = writeln!(exposition, "# TYPE {name} {kind}");
+        exposition.push_str(samples.get(name).map(String::as_str).unwrap_or_default());
+    }
+    exposition
+}
+
+/// Prometheus scrape endpoint, needs `--metrics-token` and the token as bearer token
+pub async fn metrics_api(
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let Some(token) = SCRAPE_TOKEN.get() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let authorized = bearer.is_some_and(|TypedHeader(Authorization(b))| {
+        // constant time comparison
+        b.token().len() == token.len()
+            && b.token()
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    });
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&pool).await,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        host::Host,
+        metric::record_fields,
+        parser::Fields,
+        schedule::Schedule,
+        script::Script,
+    };
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_exporter() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web \"1\"".into(),
+            attributes: vec!["prod".into(), "web".into()],
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let _s = script
+            .clone()
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await;
+        let sched = Schedule {
+            script_id: script.id,
+            ..Default::default()
+        };
+        let sched_id = sched.id;
+        let _sched = sched.insert_into_db(pool.acquire().await.unwrap()).await;
+        for (minutes, updates) in [(10, 3), (5, 7)] {
+            let fields = Fields::from([("updates".to_string(), json!(updates))]);
+            let ts = Utc::now() - chrono::Duration::minutes(minutes);
+            record_fields(host_id, sched_id, ts, &fields, &pool).await;
+        }
+
+        observe(SCHEDULER_LAG, &[], 0.2);
+        observe(SCHEDULER_LAG, &[], 20.0);
+        inc(LOGIN_FAILURES, &[("reason", "wrong_password")]);
+
+        let exposition = render(&pool).await;
+        for (name, kind, _) in DESCRIPTIONS {
+            assert!(exposition.contains(&format!("# TYPE {name} {kind}\n")));
+        }
+        assert!(exposition.contains("unpatched_scheduler_lag_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(exposition.contains("unpatched_scheduler_lag_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(exposition.contains("unpatched_scheduler_lag_seconds_count 2\n"));
+        assert!(exposition.contains("unpatched_login_failures_total{reason=\"wrong_password\"}"));
+        assert!(exposition
+            .contains("unpatched_db_query_duration_seconds_count{query=\"exporter_executions\"}"));
+        let script_metric = format!(
+            "unpatched_script_metric{{host=\"web \\\"1\\\"\",host_id=\"{host_id}\",attributes=\"prod,web\",sched_id=\"{sched_id}\",field=\"updates\"}} 7\n"
+        );
+        assert!(exposition.contains(&script_metric), "{exposition}");
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let _token = SCRAPE_TOKEN.set("scrape-token".into());
+
+        let api_no_token = metrics_api(None, axum::extract::State(pool.clone())).await;
+        assert_eq!(api_no_token.status(), StatusCode::UNAUTHORIZED);
+
+        let api_wrong_token = metrics_api(
+            Some(TypedHeader(Authorization::bearer("wrong-token").unwrap())),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_wrong_token.status(), StatusCode::UNAUTHORIZED);
+
+        let api_metrics = metrics_api(
+            Some(TypedHeader(Authorization::bearer("scrape-token").unwrap())),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_metrics.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_metrics.into_body())
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("unpatched_connected_agents "));
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    db::utc_from_str,
+    execution::{store_result, Execution},
+    host::Host,
+    revision::save_script,
+    schedule::{Schedule, Target, Timer},
+    script::Script,
+};
+
+/// active host `alias` with `attributes`
+pub async fn host(alias: &str, attributes: &[&str], pool: &SqlitePool) -> Host {
+    let host = Host {
+        id: Uuid::new_v4(),
+        alias: alias.into(),
+        attributes: attributes.iter().map(|a| a.to_string()).collect(),
+        active: true,
+        ..Default::default()
+    };
+    let _h = host
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+    host
+}
+
+/// save `script` with an active hourly schedule on the hosts with `attributes`
+pub async fn schedule(script: Script, attributes: &[&str], pool: &SqlitePool) -> Schedule {
+    let sched = Schedule {
+        id: Uuid::new_v4(),
+        script_id: script.id,
+        target: Target::Attributes(attributes.iter().map(|a| a.to_string()).collect()),
+        timer: Timer::Cron("0 * * * *".into()),
+        active: true,
+        ..Default::default()
+    };
+    let _s = save_script(script, "a@test.int", "", pool).await;
+    let _sched = sched
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+    sched
+}
+
+/// active host `alias` with `attributes` and an active hourly schedule of `script` on them
+pub async fn scheduled_host(
+    alias: &str,
+    attributes: &[&str],
+    script: Script,
+    pool: &SqlitePool,
+) -> (Host, Schedule) {
+    let host = host(alias, attributes, pool).await;
+    (host, schedule(script, attributes, pool).await)
+}
+
+/// store `output` as a run of `sched_id` on `host` answered at `at`, returns the execution id
+pub async fn run(
+    host: &Host,
+    sched_id: Uuid,
+    output: &str,
+    at: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Uuid {
+    let exe = Execution {
+        id: Uuid::new_v4(),
+        host_id: host.id,
+        sched_id,
+        ..Default::default()
+    };
+    let id = exe.id;
+    exe.insert_into_db(pool.acquire().await.unwrap()).await;
+    store_result(id, output.into(), at, pool).await;
+    id
+}
+
+/// `minutes` after the fixed start of a test
+pub fn at(minutes: i64) -> DateTime<Utc> {
+    utc_from_str("2026-10-01T00:00:00.000Z") + Duration::minutes(minutes)
+}
@@ -23,6 +23,9 @@ use crate::{
     schedule::{self, Schedule, Target},
 };
 
+/// Attribute of hosts with a pending reboot, usable in selectors like any other attribute
+pub const REBOOT_REQUIRED: &str = "reboot-required";
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
 pub struct Host {
     pub id: Uuid,
@@ -35,6 +38,12 @@ pub struct Host {
     pub last_checkin: Option<DateTime<Utc>>,
     #[serde(default = "Utc::now")]
     pub created: DateTime<Utc>,
+    /// facts about the host (os, kernel, ...), usable as `{{ facts.<name> }}` in scripts
+    #[serde(default)]
+    pub facts: HashMap<String, String>,
+    /// interpreters the agent can run scripts with, reported by the agent
+    #[serde(default)]
+    pub interpreters: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
@@ -47,6 +56,20 @@ pub enum ScheduleState {
 }
 
 impl Host {
+    /// attributes plus the ones derived from facts, e.g. `reboot-required`
+    pub fn selector_attributes(&self) -> Vec<String> {
+        let mut attributes = self.attributes.clone();
+        if self
+            .facts
+            .get("reboot_required")
+            .is_some_and(|r| r == "true")
+            && !attributes.iter().any(|a| a == REBOOT_REQUIRED)
+        {
+            attributes.push(REBOOT_REQUIRED.to_string());
+        }
+        attributes
+    }
+
     /// Insert into or Replace `Host` in hosts table in SQLite database
     ///
     /// | Name | Type | Comment | Extended Comment
@@ -58,8 +81,10 @@ impl Host {
     /// | active | NUMERIC |
     /// | last_checkin | TEXT | last checkin from agent | implemented by another call, always created as NULL
     /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | facts | TEXT | json map of host facts
+    /// | interpreters | TEXT | json list of interpreters the agent supports
     pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
-        let q = r#"REPLACE INTO hosts(id, alias, attributes, ip, active, created) VALUES(?, ?, ?, ?, ?, ?)"#;
+        let q = r#"REPLACE INTO hosts(id, alias, attributes, ip, active, created, facts, interpreters) VALUES(?, ?, ?, ?, ?, ?, ?, ?)"#;
         query(q)
             .bind(self.id.to_string())
             .bind(self.alias)
@@ -67,6 +92,8 @@ impl Host {
             .bind(self.ip)
             .bind(self.active)
             .bind(utc_to_str(self.created))
+            .bind(serde_json::to_string(&self.facts).unwrap())
+            .bind(serde_json::to_string(&self.interpreters).unwrap())
             .execute(&mut *connection)
             .await
             .unwrap()
@@ -87,7 +114,7 @@ impl Host {
 
         // Add all schedules that fit via host_id or attribute to schedule list
         let mut found_schedules = Vec::new();
-        let mut host_attributes = self.attributes.clone();
+        let mut host_attributes = self.selector_attributes();
         host_attributes.sort();
         for sched in schedules {
             if let Target::HostId(h) = sched.target {
@@ -118,6 +145,9 @@ impl From<SqliteRow> for Host {
             active: s.get::<bool, _>("active"),
             last_checkin: try_utc_from_str(&s.get::<String, _>("last_checkin")).ok(),
             created: utc_from_str(&s.get::<String, _>("created")),
+            facts: serde_json::from_str(&s.get::<String, _>("facts")).unwrap_or_default(),
+            interpreters: serde_json::from_str(&s.get::<String, _>("interpreters"))
+                .unwrap_or_default(),
         }
     }
 }
@@ -252,6 +282,25 @@ pub async fn delete_hosts_from_db(
         StatusCode::OK
     }
 }
+/// Merge `facts` into the stored facts of a host, existing keys get overwritten
+pub async fn merge_facts(
+    id: Uuid,
+    facts: HashMap<String, String>,
+    pool: &SqlitePool,
+) -> SqliteQueryResult {
+    let filter = format!("id='{id}'");
+    let hosts = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let mut merged = hosts.first().map(|h| h.facts.clone()).unwrap_or_default();
+    merged.extend(facts);
+    update_text_field(
+        id,
+        "facts",
+        serde_json::to_string(&merged).unwrap(),
+        pool.acquire().await.unwrap(),
+    )
+    .await
+}
+
 #[allow(dead_code)]
 // FIXME: make undead
 pub async fn update_text_field(
@@ -332,6 +381,26 @@ mod tests {
         assert_eq!(hosts.len(), 1);
         assert_eq!(hosts[0].alias, "cargo-test");
 
+        let _facts = merge_facts(
+            host.id,
+            HashMap::from([("os".to_string(), "debian".to_string())]),
+            &pool,
+        )
+        .await;
+        let _facts = merge_facts(
+            host.id,
+            HashMap::from([("kernel".to_string(), "6.1".to_string())]),
+            &pool,
+        )
+        .await;
+        let hosts = get_hosts_from_db(
+            Some(format!("id='{}'", host.id).as_str()),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        assert_eq!(hosts[0].facts.len(), 2);
+        assert_eq!(hosts[0].facts["os"], "debian");
+
         let single_del = delete_hosts_from_db(
             Some(format!("id='{}'", host.id).as_str()),
             pool.acquire().await.unwrap(),
@@ -32,6 +32,7 @@ use uuid::Uuid;
 
 use crate::{
     db::{utc_from_str, utc_to_str},
+    exporter::{inc, LOGIN_FAILURES},
     user::get_users_from_db,
     JWT_SECRET,
 };
@@ -171,6 +172,7 @@ pub async fn api_authorize_user(
     if let Some(block) = bl_item.blocked_until {
         if block > Utc::now() {
             error!("Login for {addr} failed multiple times, blacklisted until {block}");
+            inc(LOGIN_FAILURES, &[("reason", "blacklisted")]);
             return Err(AuthError::WrongCredentials);
         } else {
             let filter = format!("id='{}'", bl_item.id);
@@ -195,6 +197,7 @@ pub async fn api_authorize_user(
     let users = get_users_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
     let Some(user) = users.first() else {
         error!("Login for {validate_email} failed. Wrong credentials");
+        inc(LOGIN_FAILURES, &[("reason", "unknown_user")]);
         bl_item.tries += 1;
         if bl_item.tries >= BLACKLIST_AFTER {
             bl_item.blocked = Some(Utc::now());
@@ -209,6 +212,7 @@ pub async fn api_authorize_user(
         .is_err()
     {
         error!("Login for {validate_email} failed. Wrong credentials");
+        inc(LOGIN_FAILURES, &[("reason", "wrong_password")]);
         bl_item.tries += 1;
         if bl_item.tries >= BLACKLIST_AFTER {
             bl_item.blocked = Some(Utc::now());
@@ -0,0 +1,657 @@
+use std::{collections::HashMap, path::Path as FilePath};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    db::utc_to_str,
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    parser::ParseError,
+};
+
+/// Lifecycle dataset shipped with the server, imported files take precedence
+pub const BUNDLED_LIFECYCLES: &str = include_str!("../data/os_lifecycle.json");
+
+/// Default days before the end of life a host counts as expiring
+pub const EOL_WARNING_DAYS: i64 = 90;
+
+/// parse the output of `cat /etc/os-release` or `sw_vers` into the facts
+/// `os`, `os_version`, `os_codename` and `os_name`
+pub fn parse_os_release(output: &str) -> Result<HashMap<String, String>, ParseError> {
+    let mut facts = HashMap::new();
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .or(line.split_once(':'))
+            .ok_or(ParseError::Line(n + 1, "missing '='".into()))?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        let fact = match key.trim() {
+            "ID" => "os",
+            "VERSION_ID" | "ProductVersion" => "os_version",
+            "VERSION_CODENAME" => "os_codename",
+            // older Ubuntu releases only have UBUNTU_CODENAME
+            "UBUNTU_CODENAME" if !facts.contains_key("os_codename") => "os_codename",
+            "PRETTY_NAME" => "os_name",
+            "ProductName" => {
+                facts.insert("os_name".to_string(), value.to_string());
+                "os"
+            }
+            _ => continue,
+        };
+        let value = match fact {
+            "os" => value.to_lowercase().replace(' ', ""),
+            _ => value.to_string(),
+        };
+        if !value.is_empty() {
+            facts.insert(fact.to_string(), value);
+        }
+    }
+    match facts.contains_key("os") {
+        true => Ok(facts),
+        false => Err(ParseError::Line(1, "no ID or ProductName found".into())),
+    }
+}
+
+/// Support period of a distribution release
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct OsLifecycle {
+    /// os-release id, e.g. `debian`
+    pub distro: String,
+    /// e.g. `12` or `3.19`, matches `os_version` exactly or as prefix of a point release
+    pub release: String,
+    #[serde(default)]
+    pub codename: String,
+    /// end of the standard support
+    pub eol: NaiveDate,
+    /// end of extended (paid) support, e.g. Debian LTS or Ubuntu ESM
+    #[serde(default)]
+    pub extended_eol: Option<NaiveDate>,
+}
+
+impl From<SqliteRow> for OsLifecycle {
+    fn from(s: SqliteRow) -> Self {
+        let date = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .and_then(|d| d.parse::<NaiveDate>().ok())
+        };
+        OsLifecycle {
+            distro: s.get::<String, _>("distro"),
+            release: s.get::<String, _>("release"),
+            codename: s.get::<String, _>("codename"),
+            eol: date("eol").unwrap_or_default(),
+            extended_eol: date("extended_eol"),
+        }
+    }
+}
+
+impl OsLifecycle {
+    /// whether the release is the one of a host with `facts`
+    fn matches(&self, facts: &HashMap<String, String>) -> bool {
+        if !facts
+            .get("os")
+            .is_some_and(|os| os.eq_ignore_ascii_case(&self.distro))
+        {
+            return false;
+        }
+        let version = facts.get("os_version");
+        let codename = facts.get("os_codename");
+        version.is_some_and(|v| *v == self.release || v.starts_with(&format!("{}.", self.release)))
+            || (!self.codename.is_empty()
+                && codename.is_some_and(|c| c.eq_ignore_ascii_case(&self.codename)))
+    }
+}
+
+/// the lifecycle of a host with `facts`, the most specific release wins
+pub fn lifecycle_of<'a>(
+    facts: &HashMap<String, String>,
+    lifecycles: &'a [OsLifecycle],
+) -> Option<&'a OsLifecycle> {
+    lifecycles
+        .iter()
+        .filter(|l| l.matches(facts))
+        .max_by_key(|l| l.release.len())
+}
+
+/// parse a lifecycle dataset, a json array of [OsLifecycle]
+pub fn parse_lifecycles(input: &str) -> Result<Vec<OsLifecycle>, String> {
+    let lifecycles: Vec<OsLifecycle> = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    match lifecycles
+        .iter()
+        .find(|l| l.distro.is_empty() || l.release.is_empty())
+    {
+        Some(l) => Err(format!("distro and release required: {l:?}")),
+        None => Ok(lifecycles),
+    }
+}
+
+/// insert or update `lifecycles`, bundled ones do not overwrite imported ones
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | distro | TEXT | os-release id
+/// | release | TEXT | version
+/// | codename | TEXT |
+/// | eol | TEXT | date, end of the standard support
+/// | extended_eol | TEXT | date, end of extended support
+/// | bundled | NUMERIC | bool, shipped with the server
+/// | imported | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+pub async fn store_lifecycles(
+    lifecycles: Vec<OsLifecycle>,
+    bundled: bool,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut stored = 0;
+    for l in lifecycles {
+        let q = r#"INSERT INTO os_lifecycles(distro, release, codename, eol, extended_eol, bundled, imported) VALUES(?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(distro, release) DO UPDATE SET codename=excluded.codename, eol=excluded.eol, extended_eol=excluded.extended_eol, bundled=excluded.bundled, imported=excluded.imported
+        WHERE excluded.bundled = 0 OR bundled = 1"#;
+        stored += query(q)
+            .bind(l.distro.to_lowercase())
+            .bind(l.release)
+            .bind(l.codename)
+            .bind(l.eol.to_string())
+            .bind(l.extended_eol.map(|d| d.to_string()))
+            .bind(bundled)
+            .bind(utc_to_str(now))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+    }
+    tx.commit().await?;
+    debug!("Stored {stored} os lifecycles");
+    Ok(stored)
+}
+
+/// load the bundled dataset, called on every start
+pub async fn load_bundled(pool: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let lifecycles = parse_lifecycles(BUNDLED_LIFECYCLES).expect("bundled lifecycles are valid");
+    store_lifecycles(lifecycles, true, Utc::now(), pool).await
+}
+
+/// import a lifecycle dataset from a local file
+pub async fn import_file(file: &FilePath, pool: &SqlitePool) -> Result<usize, String> {
+    let input = std::fs::read_to_string(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let lifecycles = parse_lifecycles(&input).map_err(|e| format!("{}: {e}", file.display()))?;
+    store_lifecycles(lifecycles, false, Utc::now(), pool)
+        .await
+        .map_err(|e| format!("{}: {e}", file.display()))
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleStatus {
+    Supported,
+    /// end of life within the warning period
+    Expiring,
+    Eol,
+    /// os facts missing or release not in the dataset
+    Unknown,
+}
+
+/// Lifecycle of the operating system of a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostLifecycle {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub os_codename: Option<String>,
+    pub eol: Option<NaiveDate>,
+    pub extended_eol: Option<NaiveDate>,
+    /// days until the end of life, negative if it has passed
+    pub days_left: Option<i64>,
+    pub status: LifecycleStatus,
+}
+
+impl HostLifecycle {
+    /// human readable state, e.g. `debian 11 (bullseye) end of life since 2024-08-14`
+    pub fn summary(&self) -> String {
+        let mut release = vec![self.os.clone().unwrap_or("unknown os".into())];
+        release.extend(self.os_version.clone());
+        if let Some(codename) = &self.os_codename {
+            release.push(format!("({codename})"));
+        }
+        let release = release.join(" ");
+        match (self.eol, self.days_left) {
+            (Some(eol), Some(days)) if days < 0 => format!("{release} end of life since {eol}"),
+            (Some(eol), Some(days)) => format!("{release} end of life {eol}, in {days} days"),
+            _ => format!("{release} has no known end of life"),
+        }
+    }
+}
+
+/// the lifecycle of `host`, expiring within `within_days` of `today`
+pub fn host_lifecycle(
+    host: &Host,
+    within_days: i64,
+    today: NaiveDate,
+    lifecycles: &[OsLifecycle],
+) -> HostLifecycle {
+    let lifecycle = lifecycle_of(&host.facts, lifecycles);
+    let days_left = lifecycle.map(|l| (l.eol - today).num_days());
+    HostLifecycle {
+        host_id: host.id,
+        alias: host.alias.clone(),
+        os: host.facts.get("os").cloned(),
+        os_version: host.facts.get("os_version").cloned(),
+        os_codename: host.facts.get("os_codename").cloned(),
+        eol: lifecycle.map(|l| l.eol),
+        extended_eol: lifecycle.and_then(|l| l.extended_eol),
+        days_left,
+        status: match days_left {
+            None => LifecycleStatus::Unknown,
+            Some(days) if days < 0 => LifecycleStatus::Eol,
+            Some(days) if days <= within_days => LifecycleStatus::Expiring,
+            Some(_) => LifecycleStatus::Supported,
+        },
+    }
+}
+
+/// lifecycle of all hosts, nearest end of life first
+pub async fn fleet_lifecycle(
+    within_days: i64,
+    today: NaiveDate,
+    pool: &SqlitePool,
+) -> Vec<HostLifecycle> {
+    let lifecycles = get_lifecycles_from_db(None, pool.acquire().await.unwrap()).await;
+    let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut fleet: Vec<HostLifecycle> = hosts
+        .iter()
+        .map(|h| host_lifecycle(h, within_days, today, &lifecycles))
+        .collect();
+    fleet.sort_by_key(|h| (h.days_left.is_none(), h.days_left, h.alias.clone()));
+    fleet
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct LifecycleQueryParams {
+    /// days before the end of life a host counts as expiring, default 90
+    within: Option<i64>,
+    status: Option<LifecycleStatus>,
+}
+
+/// API to get the os lifecycle of all hosts, nearest end of life first
+pub async fn get_lifecycle_api(
+    _claims: Claims,
+    Query(params): Query<LifecycleQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let within = params.within.unwrap_or(EOL_WARNING_DAYS);
+    let mut fleet = fleet_lifecycle(within, Utc::now().date_naive(), &pool).await;
+    fleet.retain(|h| params.status.is_none_or(|s| h.status == s));
+    Json(fleet)
+}
+
+/// API to get the os lifecycle of a host
+pub async fn get_host_lifecycle_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<LifecycleQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let hosts = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(host) = hosts.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let lifecycles = get_lifecycles_from_db(None, pool.acquire().await.unwrap()).await;
+    let within = params.within.unwrap_or(EOL_WARNING_DAYS);
+    Json(host_lifecycle(
+        host,
+        within,
+        Utc::now().date_naive(),
+        &lifecycles,
+    ))
+    .into_response()
+}
+
+/// API to get the lifecycle dataset
+pub async fn get_os_lifecycles_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let lifecycles = get_lifecycles_from_db(
+        Some("1=1 ORDER BY distro, eol"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    Json(lifecycles)
+}
+
+/// API to import a lifecycle dataset from the request body
+pub async fn post_os_lifecycles_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    body: String,
+) -> Response {
+    let lifecycles = match parse_lifecycles(&body) {
+        Ok(lifecycles) => lifecycles,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    };
+    match store_lifecycles(lifecycles, false, Utc::now(), &pool).await {
+        Ok(stored) => (StatusCode::CREATED, Json(stored)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+pub async fn get_lifecycles_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<OsLifecycle> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM os_lifecycles WHERE {f}"),
+        None => "SELECT * FROM os_lifecycles".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| OsLifecycle::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alert::{evaluate_rules, AlertCondition, AlertRule, AlertState},
+        db::{create_database, init_database},
+        execution::{store_result, Execution},
+        parser::OutputParser,
+        revision::save_script,
+        schedule::{Schedule, Timer},
+        script::Script,
+    };
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    const DEBIAN_11: &str = r#"PRETTY_NAME="Debian GNU/Linux 11 (bullseye)"
+NAME="Debian GNU/Linux"
+VERSION_ID="11"
+VERSION="11 (bullseye)"
+VERSION_CODENAME=bullseye
+ID=debian
+HOME_URL="https://www.debian.org/"
+"#;
+
+    const DEBIAN_13: &str = "ID=debian\nVERSION_ID=\"13\"\nVERSION_CODENAME=trixie\n";
+
+    fn date(d: &str) -> NaiveDate {
+        d.parse().unwrap()
+    }
+
+    fn facts(os: &str, version: &str, codename: &str) -> HashMap<String, String> {
+        [
+            ("os", os),
+            ("os_version", version),
+            ("os_codename", codename),
+        ]
+        .into_iter()
+        .filter(|(_, v)| !v.is_empty())
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        assert_eq!(
+            parse_os_release(DEBIAN_11).unwrap(),
+            HashMap::from([
+                ("os".to_string(), "debian".to_string()),
+                ("os_version".to_string(), "11".to_string()),
+                ("os_codename".to_string(), "bullseye".to_string()),
+                (
+                    "os_name".to_string(),
+                    "Debian GNU/Linux 11 (bullseye)".to_string()
+                ),
+            ])
+        );
+        let ubuntu =
+            parse_os_release("ID=ubuntu\nVERSION_ID=\"18.04\"\nUBUNTU_CODENAME=bionic\n").unwrap();
+        assert_eq!(ubuntu["os_codename"], "bionic");
+        let mac = parse_os_release(
+            "ProductName:\t\tmacOS\nProductVersion:\t\t14.5\nBuildVersion:\t\t23F79\n",
+        )
+        .unwrap();
+        assert_eq!(mac["os"], "macos");
+        assert_eq!(mac["os_version"], "14.5");
+        assert!(parse_os_release("NAME=\"Linux\"\n").is_err());
+        assert!(parse_os_release("garbage").is_err());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let lifecycles = get_lifecycles_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(
+            lifecycles.len(),
+            parse_lifecycles(BUNDLED_LIFECYCLES).unwrap().len()
+        );
+
+        // point releases and codenames match, the most specific release wins
+        let alpine = lifecycle_of(&facts("alpine", "3.19.1", ""), &lifecycles).unwrap();
+        assert_eq!(alpine.release, "3.19");
+        let rhel = lifecycle_of(&facts("rhel", "9.4", ""), &lifecycles).unwrap();
+        assert_eq!(rhel.eol, date("2032-05-31"));
+        let codename = lifecycle_of(&facts("debian", "", "bookworm"), &lifecycles).unwrap();
+        assert_eq!(codename.release, "12");
+        assert!(lifecycle_of(&facts("debian", "1", ""), &lifecycles).is_none());
+        assert!(lifecycle_of(&facts("", "12", ""), &lifecycles).is_none());
+
+        // imported data takes precedence over the bundled one, also after a restart
+        assert!(
+            parse_lifecycles(r#"[{"distro": "", "release": "1", "eol": "2030-01-01"}]"#).is_err()
+        );
+        let extended = parse_lifecycles(
+            r#"[{"distro": "debian", "release": "12", "codename": "bookworm", "eol": "2028-06-30"},
+                {"distro": "Custom", "release": "1", "eol": "2030-01-01"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            store_lifecycles(extended, false, Utc::now(), &pool)
+                .await
+                .unwrap(),
+            2
+        );
+        let _l = load_bundled(&pool).await.unwrap();
+        let lifecycles = get_lifecycles_from_db(None, pool.acquire().await.unwrap()).await;
+        let bookworm = lifecycle_of(&facts("debian", "12", ""), &lifecycles).unwrap();
+        assert_eq!(bookworm.eol, date("2028-06-30"));
+        assert!(lifecycle_of(&facts("custom", "1", ""), &lifecycles).is_some());
+
+        let host = |f: HashMap<String, String>| Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            facts: f,
+            ..Default::default()
+        };
+        let today = date("2026-03-01");
+        let bullseye = host_lifecycle(&host(facts("debian", "11", "")), 90, today, &lifecycles);
+        assert_eq!(bullseye.status, LifecycleStatus::Eol);
+        assert_eq!(bullseye.days_left, Some(-564));
+        assert_eq!(bullseye.extended_eol, Some(date("2026-08-31")));
+        assert_eq!(bullseye.summary(), "debian 11 end of life since 2024-08-14");
+        let noble = host(facts("ubuntu", "24.04", "noble"));
+        assert_eq!(
+            host_lifecycle(&noble, 90, today, &lifecycles).status,
+            LifecycleStatus::Supported
+        );
+        let jammy = host(facts("ubuntu", "22.04", "jammy"));
+        let expiring = host_lifecycle(&jammy, 500, today, &lifecycles);
+        assert_eq!(expiring.status, LifecycleStatus::Expiring);
+        assert_eq!(
+            expiring.summary(),
+            "ubuntu 22.04 (jammy) end of life 2027-06-01, in 457 days"
+        );
+        let unknown = host_lifecycle(&host(HashMap::new()), 90, today, &lifecycles);
+        assert_eq!(unknown.status, LifecycleStatus::Unknown);
+
+        // the os_release parser feeds the facts, the alert fires until the upgrade
+        let web = Host {
+            active: true,
+            ..host(HashMap::new())
+        };
+        let _h = web
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            name: "os_version".into(),
+            script_content: "cat /etc/os-release".into(),
+            parser: OutputParser::OsRelease,
+            ..Default::default()
+        };
+        let _s = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            timer: Timer::Cron("0 * * * *".into()),
+            active: true,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "eol".into(),
+            condition: AlertCondition::OsEndOfLife { within_days: 30 },
+            severity: Default::default(),
+            labels: Default::default(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule.insert_into_db(pool.acquire().await.unwrap()).await;
+        let now = DateTime::parse_from_rfc3339("2026-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(evaluate_rules(now, &pool).await.is_empty());
+        for output in [DEBIAN_11, DEBIAN_13] {
+            let exe = Execution {
+                id: Uuid::new_v4(),
+                host_id: web.id,
+                sched_id: sched.id,
+                ..Default::default()
+            };
+            let id = exe.id;
+            exe.insert_into_db(pool.acquire().await.unwrap()).await;
+            store_result(id, output.into(), Utc::now(), &pool).await;
+            let changed = evaluate_rules(now, &pool).await;
+            assert_eq!(changed.len(), 1);
+            match output {
+                DEBIAN_11 => {
+                    assert_eq!(changed[0].state, AlertState::Firing);
+                    assert_eq!(changed[0].sched_id, sched.id);
+                    assert_eq!(
+                        changed[0].summary,
+                        "debian 11 (bullseye) end of life since 2024-08-14"
+                    );
+                }
+                _ => assert_eq!(changed[0].state, AlertState::Resolved),
+            }
+        }
+        let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(hosts[0].facts["os_codename"], "trixie");
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "legacy".into(),
+            facts: facts("centos", "7.9.2009", ""),
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        let api_import = post_os_lifecycles_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            r#"[{"distro": "centos", "release": "7", "eol": "2024-06-30", "extended_eol": "2028-06-30"}]"#.into(),
+        )
+        .await;
+        assert_eq!(api_import.status(), StatusCode::CREATED);
+        let api_invalid = post_os_lifecycles_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            "{}".into(),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_fleet = get_lifecycle_api(
+            claims.clone(),
+            axum::extract::Query(LifecycleQueryParams {
+                status: Some(LifecycleStatus::Eol),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_fleet.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_fleet.into_body()).await.unwrap();
+        let fleet: Vec<HostLifecycle> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(fleet.len(), 1);
+        assert_eq!(fleet[0].extended_eol, Some(date("2028-06-30")));
+
+        let api_host = get_host_lifecycle_api(
+            claims.clone(),
+            axum::extract::Path(host.id),
+            axum::extract::Query(LifecycleQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_host.status(), StatusCode::OK);
+        let api_unknown = get_host_lifecycle_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::Query(LifecycleQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_dataset = get_os_lifecycles_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_dataset.status(), StatusCode::OK);
+    }
+}
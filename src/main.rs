@@ -2,12 +2,14 @@ use crate::{
     db::utc_to_str,
     execution::Execution,
     host::{Host, ScheduleState},
+    reboot::RebootState,
+    remediation::RemediationState,
     schedule::Timer,
 };
 use axum::{
     extract::connect_info::ConnectInfo,
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
@@ -15,7 +17,7 @@ use axum::{
 };
 use axum_server::tls_rustls::RustlsConfig;
 use chrono::{prelude::*, Days};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use email_address::EmailAddress;
 use futures::{sink::SinkExt, stream::StreamExt};
 use futures_util::{future::join_all, stream::SplitSink};
@@ -25,30 +27,75 @@ use include_dir::{include_dir, Dir};
 use jwt::KEYS;
 use once_cell::sync::OnceCell;
 use schedule::Schedule;
+use script::Script;
 use serde::{Deserialize, Serialize};
 use sqlx::{pool::PoolConnection, sqlite::SqlitePool, Sqlite};
-use std::{fs::File, io::ErrorKind, path::PathBuf, time::Duration};
-use std::{net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, fs::File, io::ErrorKind, path::PathBuf, time::Duration};
+use std::{
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+};
 use tokio::sync::Mutex;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
 use uuid::Uuid;
 
+mod advisory;
+mod alert;
+mod comparison;
+mod compliance;
 mod db;
+mod drift;
+mod escalation;
 mod execution;
+mod exporter;
+#[cfg(test)]
+mod fixtures;
 mod host;
 mod jwt;
+mod lifecycle;
+mod maintenance;
+mod metric;
+mod notification;
+mod package;
+mod parser;
+mod reboot;
+mod remediation;
+mod revision;
+mod risk;
+mod routing;
+mod sbom;
 mod schedule;
 mod script;
+mod signing;
+mod silence;
 mod swagger;
+mod template;
+mod update;
 mod user;
+mod variable;
+mod version;
+mod waiver;
 mod webpage;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
 struct ScriptExec {
     pub id: Uuid,
     pub script: script::Script,
+    /// complete environment for the script process, the rendered `script.environment`
+    /// plus resolved script parameters as `UNPATCHED_PARAM_<NAME>`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub environment: HashMap<String, String>,
+    /// host the payload is meant for, agents reject payloads for other hosts
+    #[serde(default)]
+    pub host_id: Uuid,
+    /// agents must not start the script after this point in time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    /// signature over the canonical json of all other fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<signing::PayloadSignature>,
 }
 
 static WEBPAGE: Dir = include_dir!("$CARGO_MANIFEST_DIR/target/site");
@@ -81,6 +128,36 @@ struct Args {
     /// Password of first user to initialize the server with
     #[arg(long)]
     init_password: Option<String>,
+    /// File with the private key scripts are signed with, generated if missing
+    #[arg(long, value_name = "FILE", default_value = SIGNING_KEY)]
+    signing_key: PathBuf,
+    /// only dispatch script revisions approved by a second user
+    #[arg(long)]
+    require_approval: bool,
+    /// days metrics are kept before they are deleted
+    #[arg(long, value_name = "DAYS", default_value = "730")]
+    metric_retention: i64,
+    /// enable the prometheus endpoint /metrics, scrapes need this as bearer token
+    #[arg(long, value_name = "TOKEN")]
+    metrics_token: Option<String>,
+    /// base url of the server used in links of notifications, defaults to bind address and port
+    #[arg(long, value_name = "URL")]
+    external_url: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// import vulnerability advisories or the KEV catalog from files or directories of json files and exit
+    ImportAdvisories {
+        #[arg(long, value_enum)]
+        format: advisory::AdvisoryFormat,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// import an os lifecycle dataset (json array of distro, release, codename, eol, extended_eol) and exit
+    ImportLifecycles { file: PathBuf },
 }
 
 const UPDATE_RATE: Duration = Duration::new(5, 0);
@@ -88,9 +165,18 @@ const SQLITE_DB: &str = "sqlite:unpatched_server_internal.sqlite";
 const TLS_CERT: &str = "unpatched.server.crt";
 const TLS_KEY: &str = "unpatched.server.key";
 const JWT_SECRET: &str = "jwt.secret";
+const SIGNING_KEY: &str = "script_signing.key";
+const SIGNATURE_TTL: Duration = Duration::new(300, 0);
+const ALERT_EVALUATION_RATE: Duration = Duration::new(30, 0);
+const DOWNSAMPLE_RATE: Duration = Duration::new(3600, 0);
+const WAIVER_CHECK_RATE: Duration = Duration::new(3600, 0);
+const COMPLIANCE_RATE: Duration = Duration::new(300, 0);
 const API_KEY_LOGIN_TTL: u64 = 30;
+const ADVISORY_IMPORT_LIMIT: usize = 512 * 1024 * 1024;
 
 static CRON: OnceCell<bool> = OnceCell::new();
+static REQUIRE_APPROVAL: OnceCell<bool> = OnceCell::new();
+static EXTERNAL_URL: OnceCell<String> = OnceCell::new();
 
 #[tokio::main]
 async fn main() {
@@ -103,22 +189,122 @@ async fn main() {
     let pool = db::create_database(SQLITE_DB)
         .await
         .expect("Unable to create database connection!");
-    let creds = if args.init_user.is_some() && args.init_password.is_some() {
-        Some((args.init_user.unwrap(), args.init_password.unwrap()))
-    } else {
-        None
+    let creds = match (args.init_user, args.init_password) {
+        (Some(user), Some(password)) => Some((user, password)),
+        _ => None,
     };
     db::init_database(&pool, creds)
         .await
         .expect("Unable to initialize database!");
 
+    match args.command {
+        Some(Command::ImportAdvisories { format, files }) => {
+            match advisory::import_files(format, &files, &pool).await {
+                Ok(summary) => info!(
+                    "Imported {} advisories with {} affected packages and {} known exploited",
+                    summary.advisories, summary.affected, summary.known_exploited
+                ),
+                Err(e) => {
+                    error!("Import of advisories failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::ImportLifecycles { file }) => {
+            match lifecycle::import_file(&file, &pool).await {
+                Ok(stored) => info!("Imported {stored} os lifecycles"),
+                Err(e) => {
+                    error!("Import of os lifecycles failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
+    }
+
     // cron
     CRON.set(args.seven_part_cron)
         .expect("Error configuring cron format!");
 
+    REQUIRE_APPROVAL
+        .set(args.require_approval)
+        .expect("Error configuring approval requirement!");
+
+    let scheme = if args.no_tls { "http" } else { "https" };
+    let external_url = args
+        .external_url
+        .unwrap_or_else(|| format!("{scheme}://{}:{}", args.bind, args.port));
+    EXTERNAL_URL
+        .set(external_url.trim_end_matches('/').to_string())
+        .expect("Error configuring external url!");
+
     // JWT secret
     let _init_jwt = &KEYS;
 
+    // script signing key
+    let signing_key = signing::ServerKey::load(&args.signing_key, &pool)
+        .await
+        .expect("Unable to load script signing key!");
+    signing::set_active_key(signing_key);
+
+    // prometheus endpoint
+    if let Some(token) = args.metrics_token {
+        exporter::SCRAPE_TOKEN
+            .set(token)
+            .expect("Error configuring metrics token!");
+    }
+
+    // alert rule evaluation
+    let alert_pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(ALERT_EVALUATION_RATE).await;
+            let timed_out = reboot::expire_reboots(Utc::now(), &alert_pool).await;
+            debug!("{} reboots timed out", timed_out.len());
+            let expired = remediation::expire_remediations(Utc::now(), &alert_pool).await;
+            debug!("{} remediations timed out", expired.len());
+            let changed = alert::evaluate_rules(Utc::now(), &alert_pool).await;
+            debug!("Alert evaluation changed {} alerts", changed.len());
+            if !changed.is_empty() {
+                tokio::spawn(notification::notify_alerts(changed, alert_pool.clone()));
+            }
+            let escalated = escalation::escalate(Utc::now(), &alert_pool).await;
+            debug!("Escalation sent {} notifications", escalated.len());
+        }
+    });
+
+    // metric downsampling and retention
+    let metric_pool = pool.clone();
+    let metric_retention = chrono::Duration::days(args.metric_retention);
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = metric::downsample(Utc::now(), metric_retention, &metric_pool).await {
+                warn!("Metric downsampling failed: {e}");
+            }
+            tokio::time::sleep(DOWNSAMPLE_RATE).await;
+        }
+    });
+
+    // waiver expiry notifications
+    let waiver_pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            let _deliveries = waiver::check_expiry(Utc::now(), &waiver_pool).await;
+            tokio::time::sleep(WAIVER_CHECK_RATE).await;
+        }
+    });
+
+    // compliance score history
+    let compliance_pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(COMPLIANCE_RATE).await;
+            let _scores = compliance::record_scores(Utc::now(), &compliance_pool).await;
+        }
+    });
+
     // build our application with some routes
     let app = Router::new()
         .route("/protected", get(jwt::protected))
@@ -132,6 +318,184 @@ async fn main() {
             "/api/v1/executions",
             get(execution::get_executions_api).delete(execution::delete_executions_api),
         )
+        .route(
+            "/api/v1/alert-rules/:id",
+            get(alert::get_one_alert_rule_api).delete(alert::delete_one_alert_rule_api),
+        )
+        .route(
+            "/api/v1/alert-rules",
+            get(alert::get_alert_rules_api).post(alert::post_alert_rules_api),
+        )
+        .route(
+            "/api/v1/alerts/:id/acknowledge",
+            post(alert::acknowledge_alert_api),
+        )
+        .route("/api/v1/alerts/groups", get(alert::get_alert_groups_api))
+        .route(
+            "/api/v1/alerts/ack/:token",
+            get(alert::acknowledge_link_page_api).post(alert::acknowledge_link_api),
+        )
+        .route("/api/v1/alerts/:id", get(alert::get_one_alert_api))
+        .route("/api/v1/alerts", get(alert::get_alerts_api))
+        .route(
+            "/api/v1/notification-channels/:id/test",
+            post(notification::test_channel_api),
+        )
+        .route(
+            "/api/v1/notification-channels/:id",
+            get(notification::get_one_channel_api).delete(notification::delete_one_channel_api),
+        )
+        .route(
+            "/api/v1/notification-channels",
+            get(notification::get_channels_api).post(notification::post_channels_api),
+        )
+        .route(
+            "/api/v1/notification-deliveries",
+            get(notification::get_deliveries_api),
+        )
+        .route(
+            "/api/v1/routes/:id",
+            get(routing::get_one_route_api).delete(routing::delete_one_route_api),
+        )
+        .route(
+            "/api/v1/routes",
+            get(routing::get_routes_api).post(routing::post_routes_api),
+        )
+        .route(
+            "/api/v1/baselines/:id/compliance",
+            get(compliance::get_baseline_compliance_api),
+        )
+        .route(
+            "/api/v1/baselines/:id/history",
+            get(compliance::get_baseline_history_api),
+        )
+        .route(
+            "/api/v1/baselines/:id",
+            get(compliance::get_one_baseline_api).delete(compliance::delete_one_baseline_api),
+        )
+        .route(
+            "/api/v1/baselines",
+            get(compliance::get_baselines_api).post(compliance::post_baselines_api),
+        )
+        .route(
+            "/api/v1/compliance/report",
+            get(compliance::get_compliance_report_api),
+        )
+        .route("/api/v1/changes/:id", get(drift::get_one_change_api))
+        .route("/api/v1/changes", get(drift::get_changes_api))
+        .route(
+            "/api/v1/escalation-policies/:id",
+            get(escalation::get_one_policy_api).delete(escalation::delete_one_policy_api),
+        )
+        .route(
+            "/api/v1/escalation-policies",
+            get(escalation::get_policies_api).post(escalation::post_policies_api),
+        )
+        .route("/api/v1/escalations", get(escalation::get_escalations_api))
+        .route(
+            "/api/v1/silences/:id",
+            get(silence::get_one_silence_api).delete(silence::delete_one_silence_api),
+        )
+        .route(
+            "/api/v1/silences",
+            get(silence::get_silences_api).post(silence::post_silences_api),
+        )
+        .route(
+            "/api/v1/maintenance-windows/:id",
+            get(maintenance::get_one_window_api).delete(maintenance::delete_one_window_api),
+        )
+        .route(
+            "/api/v1/maintenance-windows",
+            get(maintenance::get_windows_api).post(maintenance::post_windows_api),
+        )
+        .route("/api/v1/packages", get(package::get_packages_api))
+        .route(
+            "/api/v1/package-changes",
+            get(package::get_package_changes_api),
+        )
+        .route("/api/v1/updates", get(update::get_updates_api))
+        .route(
+            "/api/v1/advisories/import",
+            post(advisory::post_import_api).layer(DefaultBodyLimit::max(ADVISORY_IMPORT_LIMIT)),
+        )
+        .route(
+            "/api/v1/advisories/:id",
+            get(advisory::get_one_advisory_api),
+        )
+        .route(
+            "/api/v1/vulnerabilities",
+            get(advisory::get_vulnerabilities_api),
+        )
+        .route(
+            "/api/v1/waivers",
+            get(waiver::get_waivers_api).post(waiver::post_waivers_api),
+        )
+        .route(
+            "/api/v1/waivers/:id",
+            get(waiver::get_one_waiver_api).delete(waiver::delete_one_waiver_api),
+        )
+        .route("/api/v1/waiver-audit", get(waiver::get_waiver_audit_api))
+        .route("/api/v1/risk", get(risk::get_risk_api))
+        .route("/api/v1/lifecycle", get(lifecycle::get_lifecycle_api))
+        .route(
+            "/api/v1/os-lifecycles",
+            get(lifecycle::get_os_lifecycles_api).post(lifecycle::post_os_lifecycles_api),
+        )
+        .route("/api/v1/reboots", get(reboot::get_reboots_api))
+        .route(
+            "/api/v1/remediation-actions/:id",
+            get(remediation::get_one_action_api).delete(remediation::delete_one_action_api),
+        )
+        .route(
+            "/api/v1/remediation-actions",
+            get(remediation::get_actions_api).post(remediation::post_actions_api),
+        )
+        .route(
+            "/api/v1/remediations/:id/approve",
+            post(remediation::approve_remediation_api),
+        )
+        .route(
+            "/api/v1/remediations/:id/reject",
+            post(remediation::reject_remediation_api),
+        )
+        .route(
+            "/api/v1/remediations/:id",
+            get(remediation::get_one_remediation_api),
+        )
+        .route(
+            "/api/v1/remediations",
+            get(remediation::get_remediations_api),
+        )
+        .route(
+            "/api/v1/patch-priorities",
+            get(risk::get_patch_priorities_api),
+        )
+        .route(
+            "/api/v1/updates/summary",
+            get(update::get_update_summary_api),
+        )
+        .route("/api/v1/metrics/names", get(metric::get_metric_names_api))
+        .route("/api/v1/metrics", get(metric::get_metrics_api))
+        .route(
+            "/api/v1/scripts/:id/revisions/:revision/approve",
+            post(revision::approve_script_revision_api),
+        )
+        .route(
+            "/api/v1/scripts/:id/revisions/:revision",
+            get(revision::get_one_script_revision_api),
+        )
+        .route(
+            "/api/v1/scripts/:id/revisions",
+            get(revision::get_script_revisions_api),
+        )
+        .route(
+            "/api/v1/scripts/:id/diff",
+            get(revision::get_script_diff_api),
+        )
+        .route(
+            "/api/v1/scripts/:id/compare",
+            get(comparison::get_script_comparison_api),
+        )
         .route(
             "/api/v1/scripts/:id",
             get(script::get_one_script_api).delete(script::delete_one_script_api),
@@ -158,6 +522,37 @@ async fn main() {
             "/api/v1/hosts/:id/executions",
             get(execution::get_host_executions_api),
         )
+        .route(
+            "/api/v1/hosts/:id/packages",
+            get(package::get_host_packages_api),
+        )
+        .route(
+            "/api/v1/hosts/:id/sbom",
+            post(sbom::post_sbom_api).delete(sbom::delete_sbom_api),
+        )
+        .route("/api/v1/hosts/:id/sboms", get(sbom::get_host_sboms_api))
+        .route(
+            "/api/v1/hosts/:id/updates",
+            get(update::get_host_updates_api),
+        )
+        .route(
+            "/api/v1/hosts/:id/vulnerabilities",
+            get(advisory::get_host_vulnerabilities_api),
+        )
+        .route("/api/v1/hosts/:id/risk", get(risk::get_host_risk_api))
+        .route(
+            "/api/v1/hosts/:id/lifecycle",
+            get(lifecycle::get_host_lifecycle_api),
+        )
+        .route("/api/v1/hosts/:id/reboot", get(reboot::get_host_reboot_api))
+        .route(
+            "/api/v1/hosts/:id/compliance",
+            get(compliance::get_host_compliance_api),
+        )
+        .route(
+            "/api/v1/hosts/:id/changes",
+            get(drift::get_host_changes_api),
+        )
         .route(
             "/api/v1/hosts/:id",
             get(host::get_one_host_api)
@@ -183,6 +578,14 @@ async fn main() {
                 .delete(schedule::delete_schedules_api)
                 .post(schedule::post_schedules_api),
         )
+        .route(
+            "/api/v1/variables/:id",
+            axum::routing::delete(variable::delete_one_variable_api),
+        )
+        .route(
+            "/api/v1/variables",
+            get(variable::get_variables_api).post(variable::post_variables_api),
+        )
         .route(
             "/api/v1/users/:id",
             get(user::get_one_user_api)
@@ -203,10 +606,16 @@ async fn main() {
         .route("/api/api.yaml", get(swagger::api_def))
         // .route_layer(AuthLayer::verify())
         .route("/api/v1/authorize", post(jwt::api_authorize_user))
+        .route("/api/v1/signing-keys", get(signing::get_signing_keys_api))
+        .route(
+            "/api/v1/signing-keys/rotate",
+            post(signing::rotate_signing_key_api),
+        )
         .route(
             "/api/v1/unblock/:id",
             post(jwt::remove_ip_from_blacklist_api),
         )
+        .route("/metrics", get(exporter::metrics_api))
         // Websocket for Agents
         .route("/ws", get(ws_handler))
         .fallback(webpage::web_page)
@@ -319,6 +728,7 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
     let (sender, mut receiver) = socket.split();
     let arc_sink = Arc::new(Mutex::new(sender));
     info!("Connection established to agent: {}", who);
+    exporter::CONNECTED_AGENTS.fetch_add(1, Ordering::Relaxed);
 
     // ##################
     // General tasks per Connection
@@ -348,9 +758,12 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                     "host_id='{}' AND sched_id='{}' AND request > '{now}'",
                     host.id, sched.id
                 );
-                let execs = execution::get_executions_from_db(
-                    Some(&exec_filter),
-                    general_pool.acquire().await.unwrap(),
+                let execs = exporter::timed(
+                    "future_executions",
+                    execution::get_executions_from_db(
+                        Some(&exec_filter),
+                        general_pool.acquire().await.unwrap(),
+                    ),
                 )
                 .await;
                 debug!("Found executions for {}: {execs:?}", host.alias);
@@ -419,12 +832,40 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                 host.id
             );
             // FIXME: Filter out overdue executions (now() + x)
-            let execs = execution::get_executions_from_db(
-                Some(&exec_filter),
-                sender_pool.acquire().await.unwrap(),
+            let execs = exporter::timed(
+                "due_executions",
+                execution::get_executions_from_db(
+                    Some(&exec_filter),
+                    sender_pool.acquire().await.unwrap(),
+                ),
             )
             .await;
             debug!("{:?}", execs);
+            // reboot steps of finished executions, also while paused by a maintenance window
+            for exe in reboot::reboot_executions(host.id, RebootState::Pending, &sender_pool).await
+            {
+                send_reboot(&exe, &host, &sender_arc_sink, &sender_pool).await;
+            }
+            for exe in
+                reboot::reboot_executions(host.id, RebootState::PostCheck, &sender_pool).await
+            {
+                if exe.check_id.is_none() {
+                    send_post_check(&exe, &host, &sender_arc_sink, &sender_pool).await;
+                }
+            }
+            if let Some(window) = maintenance::paused_by(&host, Utc::now(), &sender_pool).await {
+                for exe in execs {
+                    let reason = format!("Paused by maintenance window {}", window.name);
+                    skip_execution(exe.id, &reason, &sender_pool).await;
+                }
+                continue;
+            }
+            // remediations change the host, they wait for the end of maintenance windows
+            for rem in
+                remediation::remediations(host.id, RemediationState::Pending, &sender_pool).await
+            {
+                send_remediation(&rem, &host, &sender_arc_sink, &sender_pool).await;
+            }
             let mut script_exec_vec = Vec::new();
             for exe in execs {
                 let filter = format!("id = '{}'", exe.sched_id);
@@ -438,54 +879,55 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                         "execution {} did not find a schedule with id {}. Execution Skipped",
                         exe.id, exe.sched_id
                     );
-                    execution::update_text_field(
-                        exe.id,
-                        "response",
-                        utc_to_str(Utc::now()),
-                        sender_pool.acquire().await.unwrap(),
-                    )
-                    .await;
-                    execution::update_text_field(
-                        exe.id,
-                        "output",
-                        "Schedule not found, execution skipped".into(),
-                        sender_pool.acquire().await.unwrap(),
-                    )
-                    .await;
+                    skip_execution(exe.id, "Schedule not found", &sender_pool).await;
                     continue;
                 };
-                let filter = format!("id = '{}'", schedule.script_id);
-                let scripts = script::get_scripts_from_db(
-                    Some(&filter),
-                    sender_pool.acquire().await.unwrap(),
+                let script = revision::get_dispatch_script_for_schedule(
+                    schedule,
+                    *REQUIRE_APPROVAL.get().unwrap_or(&false),
+                    &sender_pool,
                 )
                 .await;
-                debug!("{:?}", scripts);
-                let Some(script) = scripts.first() else {
+                debug!("{:?}", script);
+                let Some(script) = script else {
                     warn!(
-                        "execution {} did not find a script with id {}. Execution Skipped",
-                        exe.id, exe.sched_id
+                        "execution {} did not find (approved) script {} revision {:?}. Execution Skipped",
+                        exe.id, schedule.script_id, schedule.script_revision
                     );
-                    execution::update_text_field(
-                        exe.id,
-                        "response",
-                        utc_to_str(Utc::now()),
-                        sender_pool.acquire().await.unwrap(),
-                    )
-                    .await;
-                    execution::update_text_field(
-                        exe.id,
-                        "output",
-                        "Script not found, execution skipped".into(),
-                        sender_pool.acquire().await.unwrap(),
-                    )
-                    .await;
+                    skip_execution(exe.id, "Script not found or not approved", &sender_pool).await;
                     continue;
                 };
-                let script_exec = ScriptExec {
-                    id: exe.id,
-                    script: script.to_owned(),
-                };
+                let mut script_exec =
+                    match build_script_exec(exe.id, schedule, &script, &host, &sender_pool).await {
+                        Ok(se) => se,
+                        Err(e) => {
+                            warn!("execution {} could not be rendered: {e}", exe.id);
+                            skip_execution(exe.id, &e, &sender_pool).await;
+                            continue;
+                        }
+                    };
+                execution::update_text_field(
+                    exe.id,
+                    "rendered_script",
+                    script_exec.script.script_content.clone(),
+                    sender_pool.acquire().await.unwrap(),
+                )
+                .await;
+                execution::update_text_field(
+                    exe.id,
+                    "script_revision",
+                    script.revision.to_string(),
+                    sender_pool.acquire().await.unwrap(),
+                )
+                .await;
+                match signing::sign(&script_exec) {
+                    Ok(signature) => script_exec.signature = Some(signature),
+                    Err(e) => {
+                        warn!("execution {} could not be signed: {e}", exe.id);
+                        skip_execution(exe.id, "Signing failed", &sender_pool).await;
+                        continue;
+                    }
+                }
                 // lock execution via timestamp 1970
                 execution::update_text_field(
                     exe.id,
@@ -494,6 +936,16 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                     sender_pool.acquire().await.unwrap(),
                 )
                 .await;
+                let dispatched = Utc::now();
+                execution::update_text_field(
+                    exe.id,
+                    "dispatched",
+                    utc_to_str(dispatched),
+                    sender_pool.acquire().await.unwrap(),
+                )
+                .await;
+                let lag = (dispatched - exe.request).num_milliseconds() as f64 / 1000.0;
+                exporter::observe(exporter::SCHEDULER_LAG, &[], lag);
                 script_exec_vec.push(script_exec)
             }
             for script_exec in script_exec_vec {
@@ -584,6 +1036,18 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                                 receiver_pool.acquire().await.unwrap(),
                             )
                             .await;
+                            if !host.interpreters.is_empty() {
+                                host::update_text_field(
+                                    host.id,
+                                    "interpreters",
+                                    serde_json::to_string(&host.interpreters).unwrap(),
+                                    receiver_pool.acquire().await.unwrap(),
+                                )
+                                .await;
+                            }
+                            if !host.facts.is_empty() {
+                                host::merge_facts(host.id, host.facts, &receiver_pool).await;
+                            }
                             let filter = format!("id='{}'", host.id);
                             let central_host = host::get_hosts_from_db(
                                 Some(&filter),
@@ -593,6 +1057,10 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
 
                             debug!("{:?}", central_host);
 
+                            // a host coming back after a reboot step continues with its post-check
+                            let _back =
+                                reboot::reconnected(host.id, Utc::now(), &receiver_pool).await;
+
                             let mut this_host = recv_arc_this_host.lock().await;
                             *this_host = central_host.first().cloned();
                             continue;
@@ -600,22 +1068,101 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
                         "script" => {
                             let script_exec: ScriptExec = serde_json::from_str(v).unwrap();
                             debug!("{:?}", script_exec);
-                            execution::update_text_field(
+                            let check = reboot::store_check_result(
                                 script_exec.id,
-                                "response",
-                                utc_to_str(Utc::now()),
-                                receiver_pool.acquire().await.unwrap(),
+                                &script_exec.script.script_content,
+                                &receiver_pool,
                             )
                             .await;
-                            execution::update_text_field(
+                            if check {
+                                continue;
+                            }
+                            let remediated = remediation::store_remediation_result(
+                                script_exec.id,
+                                &script_exec.script.script_content,
+                                Utc::now(),
+                                &receiver_pool,
+                            )
+                            .await;
+                            if remediated {
+                                continue;
+                            }
+                            execution::store_result(
                                 script_exec.id,
-                                "output",
                                 script_exec.script.script_content,
-                                receiver_pool.acquire().await.unwrap(),
+                                Utc::now(),
+                                &receiver_pool,
                             )
                             .await;
                             continue;
                         }
+                        "packages" => {
+                            let Some(host) = recv_arc_this_host.lock().await.clone() else {
+                                warn!("Packages of unknown agent {who} skipped");
+                                continue;
+                            };
+                            match serde_json::from_str::<Vec<package::Package>>(v) {
+                                Ok(packages) => {
+                                    let res = package::store_inventory(
+                                        host.id,
+                                        packages,
+                                        Utc::now(),
+                                        &receiver_pool,
+                                    )
+                                    .await;
+                                    if let Err(e) = res {
+                                        warn!("Inventory of {} could not be stored: {e}", host.id);
+                                    }
+                                }
+                                Err(e) => warn!("Packages of {} are invalid: {e}", host.id),
+                            }
+                            continue;
+                        }
+                        "sbom" => {
+                            let Some(host) = recv_arc_this_host.lock().await.clone() else {
+                                warn!("SBOM of unknown agent {who} skipped");
+                                continue;
+                            };
+                            match serde_json::from_str::<sbom::SbomMessage>(v) {
+                                Ok(msg) => {
+                                    let res = sbom::store_sbom(
+                                        host.id,
+                                        msg.component.as_deref(),
+                                        &msg.document.to_string(),
+                                        Utc::now(),
+                                        &receiver_pool,
+                                    )
+                                    .await;
+                                    if let Err(e) = res {
+                                        warn!("SBOM of {} could not be stored: {e}", host.id);
+                                    }
+                                }
+                                Err(e) => warn!("SBOM of {} is invalid: {e}", host.id),
+                            }
+                            continue;
+                        }
+                        "updates" => {
+                            let Some(host) = recv_arc_this_host.lock().await.clone() else {
+                                warn!("Updates of unknown agent {who} skipped");
+                                continue;
+                            };
+                            match serde_json::from_str::<Vec<update::Update>>(v) {
+                                Ok(updates) => {
+                                    let res = update::store_updates(
+                                        host.id,
+                                        updates,
+                                        Utc::now(),
+                                        &receiver_pool,
+                                    )
+                                    .await;
+                                    if let Err(e) = res {
+                                        warn!("Updates of {} could not be stored: {e}", host.id);
+                                    }
+                                }
+                                Err(e) => warn!("Updates of {} are invalid: {e}", host.id),
+                            }
+                            continue;
+                        }
                         // ignore all unknown fields
                         x => {
                             warn!("{x} is unsupported!");
@@ -628,6 +1175,21 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
             // FIXME: implement something with this who
             let _who = who;
         }
+        exporter::CONNECTED_AGENTS.fetch_sub(1, Ordering::Relaxed);
+        if let Some(host) = &*recv_arc_this_host.lock().await {
+            let rebooting =
+                reboot::reboot_executions(host.id, RebootState::AwaitingReconnect, &receiver_pool)
+                    .await;
+            if rebooting.is_empty() {
+                info!("Agent {} went offline", host.id);
+                tokio::spawn(notification::notify(
+                    notification::NotificationEvent::host_offline(host.clone()),
+                    receiver_pool.clone(),
+                ));
+            } else {
+                info!("Agent {} went offline for a reboot", host.id);
+            }
+        }
     });
 
     // await all tasks
@@ -635,6 +1197,252 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, pool: SqlitePool) {
     join_all(handle_vec).await;
 }
 
+/// Mark execution as answered without sending it to the agent
+async fn skip_execution(id: Uuid, reason: &str, pool: &SqlitePool) {
+    execution::update_text_field(
+        id,
+        "response",
+        utc_to_str(Utc::now()),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    execution::update_text_field(
+        id,
+        "output",
+        format!("{reason}, execution skipped"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+}
+
+/// Send the reboot command of the reboot step of `exe`, the host is expected to reconnect
+async fn send_reboot(exe: &Execution, host: &Host, sink: &SenderSinkArc, pool: &SqlitePool) {
+    let Some(step) = execution::get_executed_script(exe, pool)
+        .await
+        .and_then(|s| s.reboot)
+    else {
+        reboot::finish(exe.id, RebootState::Failed, "reboot step not found", pool).await;
+        return;
+    };
+    let mut script_exec = ScriptExec {
+        id: Uuid::new_v4(),
+        script: Script {
+            id: Uuid::new_v4(),
+            name: "reboot".into(),
+            script_content: step.command,
+            interpreter: script::Interpreter::Sh,
+            timeout: Duration::new(60, 0),
+            ..Default::default()
+        },
+        host_id: host.id,
+        expires: Some(Utc::now() + SIGNATURE_TTL),
+        ..Default::default()
+    };
+    match signing::sign(&script_exec) {
+        Ok(signature) => script_exec.signature = Some(signature),
+        Err(e) => {
+            warn!("reboot of execution {} could not be signed: {e}", exe.id);
+            reboot::finish(exe.id, RebootState::Failed, "Signing failed", pool).await;
+            return;
+        }
+    }
+    // only one connection of the host sends the reboot
+    if !reboot::transition(
+        exe.id,
+        RebootState::Pending,
+        RebootState::AwaitingReconnect,
+        pool,
+    )
+    .await
+    {
+        return;
+    }
+    info!("Rebooting {} for execution {}", host.alias, exe.id);
+    let json_script = serde_json::to_string(&script_exec).unwrap();
+    let _sent_reboot = send_message(sink, Message::Text(format!("script:{json_script}"))).await;
+}
+
+/// Send the post-check script of the reboot step of `exe` to the host that came back,
+/// without post-check the reboot succeeded
+async fn send_post_check(exe: &Execution, host: &Host, sink: &SenderSinkArc, pool: &SqlitePool) {
+    let step = execution::get_executed_script(exe, pool)
+        .await
+        .and_then(|s| s.reboot);
+    let Some((check_script_id, check_revision)) =
+        step.and_then(|s| Some((s.post_check?, s.post_check_revision)))
+    else {
+        reboot::finish(exe.id, RebootState::Succeeded, "host is back", pool).await;
+        return;
+    };
+    let filter = format!("id='{}'", exe.sched_id);
+    let schedule = schedule::get_schedules_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next();
+    // parameters of the schedule belong to the patch script
+    let schedule = schedule.map(|s| Schedule {
+        script_id: check_script_id,
+        script_revision: check_revision,
+        parameters: HashMap::new(),
+        ..s
+    });
+    let check_script = match &schedule {
+        Some(s) => {
+            let require_approval = *REQUIRE_APPROVAL.get().unwrap_or(&false);
+            revision::get_dispatch_script_for_schedule(s, require_approval, pool).await
+        }
+        None => None,
+    };
+    let (Some(check_script), Some(schedule)) = (check_script, schedule) else {
+        let note = format!("post-check script {check_script_id} not found or not approved");
+        reboot::finish(exe.id, RebootState::Failed, &note, pool).await;
+        return;
+    };
+    let check_id = Uuid::new_v4();
+    let mut script_exec =
+        match build_script_exec(check_id, &schedule, &check_script, host, pool).await {
+            Ok(se) => se,
+            Err(e) => {
+                reboot::finish(exe.id, RebootState::Failed, &e, pool).await;
+                return;
+            }
+        };
+    match signing::sign(&script_exec) {
+        Ok(signature) => script_exec.signature = Some(signature),
+        Err(e) => {
+            warn!(
+                "post-check of execution {} could not be signed: {e}",
+                exe.id
+            );
+            reboot::finish(exe.id, RebootState::Failed, "Signing failed", pool).await;
+            return;
+        }
+    }
+    execution::update_text_field(
+        exe.id,
+        "check_id",
+        check_id.to_string(),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    execution::update_text_field(
+        exe.id,
+        "check_revision",
+        check_script.revision.to_string(),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    let json_script = serde_json::to_string(&script_exec).unwrap();
+    let _sent_check = send_message(sink, Message::Text(format!("script:{json_script}"))).await;
+}
+
+/// Send the remediation script of `rem` to the host, the check runs again once it succeeded
+async fn send_remediation(
+    rem: &remediation::Remediation,
+    host: &Host,
+    sink: &SenderSinkArc,
+    pool: &SqlitePool,
+) {
+    let filter = format!("id='{}'", rem.sched_id);
+    let schedule = schedule::get_schedules_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next();
+    let require_approval = *REQUIRE_APPROVAL.get().unwrap_or(&false);
+    let script = remediation::remediation_script(rem, require_approval, pool).await;
+    let (Some(script), Some(schedule)) = (script, schedule) else {
+        let note = "remediation script not found or not approved";
+        remediation::fail(rem.id, note, Utc::now(), pool).await;
+        return;
+    };
+    // parameters of the schedule belong to the check script
+    let schedule = Schedule {
+        parameters: HashMap::new(),
+        ..schedule
+    };
+    let mut script_exec = match build_script_exec(rem.id, &schedule, &script, host, pool).await {
+        Ok(se) => se,
+        Err(e) => {
+            remediation::fail(rem.id, &e, Utc::now(), pool).await;
+            return;
+        }
+    };
+    match signing::sign(&script_exec) {
+        Ok(signature) => script_exec.signature = Some(signature),
+        Err(e) => {
+            warn!("remediation {} could not be signed: {e}", rem.id);
+            remediation::fail(rem.id, "Signing failed", Utc::now(), pool).await;
+            return;
+        }
+    }
+    // only one connection of the host sends the remediation
+    if !remediation::transition(
+        rem.id,
+        RemediationState::Pending,
+        RemediationState::Running,
+        Utc::now(),
+        pool,
+    )
+    .await
+    {
+        return;
+    }
+    remediation::sent(rem.id, script.revision, pool).await;
+    info!("Remediating {} with {}", host.alias, script.name);
+    let json_script = serde_json::to_string(&script_exec).unwrap();
+    let _sent = send_message(sink, Message::Text(format!("script:{json_script}"))).await;
+}
+
+/// Resolve parameters and render placeholders of `script` for `host`
+///
+/// fails if the agent lacks the interpreter, parameters don't match the script declaration
+/// or a placeholder is unknown
+async fn build_script_exec(
+    id: Uuid,
+    schedule: &Schedule,
+    script: &Script,
+    host: &Host,
+    pool: &SqlitePool,
+) -> Result<ScriptExec, String> {
+    if !script.interpreter.is_supported_by(&host.interpreters) {
+        return Err(format!(
+            "Interpreter {} not supported by agent",
+            script.interpreter.name()
+        ));
+    }
+    let params = script
+        .resolve_parameters(&schedule.parameters)
+        .map_err(|e| format!("Parameter validation failed: {e}"))?;
+    let param_env: Vec<(String, String)> = params
+        .iter()
+        .map(|(k, v)| {
+            (
+                format!("UNPATCHED_PARAM_{}", k.to_uppercase()),
+                template::value_to_string(v),
+            )
+        })
+        .collect();
+    let vars = variable::get_host_variables(host, pool.acquire().await.unwrap()).await;
+    let context = template::TemplateContext::new(host, params, vars);
+    let mut rendered = script.clone();
+    rendered.script_content = template::render(&script.script_content, &context)
+        .map_err(|e| format!("Template rendering failed: {e}"))?;
+    for value in rendered.environment.values_mut() {
+        *value = template::render(value, &context)
+            .map_err(|e| format!("Template rendering failed: {e}"))?;
+    }
+    let mut environment = rendered.environment.clone();
+    environment.extend(param_env);
+    Ok(ScriptExec {
+        id,
+        script: rendered,
+        environment,
+        host_id: host.id,
+        expires: Some(Utc::now() + SIGNATURE_TTL),
+        signature: None,
+    })
+}
+
 /// Get ARC to Splitsink and push message onto it and flush them
 async fn send_message(arc: &SenderSinkArc, m: Message) -> Result<(), Error> {
     let mut x = arc.lock().await;
@@ -726,7 +1534,7 @@ async fn generate_execution_timestamp(
                 }
             };
             let ts_vec: Vec<DateTime<Utc>> = cron_schedule.upcoming(Utc).take(1).collect();
-            return ts_vec.first().copied();
+            ts_vec.first().copied()
         }
         Timer::Timestamp(ts) => {
             schedule::update_text_field(schedule.id, "active", "0".into(), connection).await;
@@ -783,4 +1591,105 @@ mod tests {
             generate_execution_timestamp(&schedule, pool.acquire().await.unwrap(), false).await;
         assert!(exe.is_none());
     }
+
+    #[tokio::test]
+    async fn test_build_script_exec() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let _var = variable::Variable {
+            attribute: "linux".into(),
+            name: "mount".into(),
+            value: "/var".into(),
+            ..Default::default()
+        }
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+
+        let host = Host {
+            alias: "web-1".into(),
+            attributes: vec!["linux".into()],
+            ..Default::default()
+        };
+        let script = Script {
+            script_content:
+                "df {{ vars.mount }} | awk '$5 > {{ params.threshold }}' # {{ host.alias }}".into(),
+            environment: HashMap::from([("MOUNT".to_string(), "{{ vars.mount }}".to_string())]),
+            parameters: vec![script::ScriptParameter {
+                name: "threshold".into(),
+                kind: script::ParameterKind::Integer,
+                default: Some(serde_json::json!(90)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let schedule = Schedule {
+            parameters: HashMap::from([("threshold".to_string(), serde_json::json!(80))]),
+            ..Default::default()
+        };
+        let id = Uuid::new_v4();
+        let script_exec = build_script_exec(id, &schedule, &script, &host, &pool)
+            .await
+            .unwrap();
+        assert_eq!(script_exec.id, id);
+        assert_eq!(
+            script_exec.script.script_content,
+            "df /var | awk '$5 > 80' # web-1"
+        );
+        assert_eq!(script_exec.environment["UNPATCHED_PARAM_THRESHOLD"], "80");
+        assert_eq!(script_exec.environment["MOUNT"], "/var");
+        assert_eq!(script_exec.script.environment["MOUNT"], "/var");
+        assert_eq!(script_exec.host_id, host.id);
+        assert!(script_exec.expires.unwrap() > Utc::now());
+
+        // signature survives the json roundtrip to the agent
+        let path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        let key = signing::ServerKey::load(&path, &pool).await.unwrap();
+        let mut signed = script_exec.clone();
+        signed.signature = Some(key.sign(&script_exec));
+        let json = serde_json::to_string(&signed).unwrap();
+        let mut received: ScriptExec = serde_json::from_str(&json).unwrap();
+        let signature = received.signature.take().unwrap();
+        let keys = signing::get_keys_from_db(None, pool.acquire().await.unwrap()).await;
+        assert!(signing::verify(&received, &signature, &keys));
+        received.script.script_content = "rm -rf /".into();
+        assert!(!signing::verify(&received, &signature, &keys));
+        let _rm = std::fs::remove_file(path);
+
+        let python = Script {
+            interpreter: script::Interpreter::Python3,
+            ..Default::default()
+        };
+        let err = build_script_exec(id, &Schedule::default(), &python, &host, &pool).await;
+        assert_eq!(
+            err.unwrap_err(),
+            "Interpreter python3 not supported by agent"
+        );
+        let python_host = Host {
+            interpreters: vec!["python3".into()],
+            ..host.clone()
+        };
+        let ok = build_script_exec(id, &Schedule::default(), &python, &python_host, &pool).await;
+        assert!(ok.is_ok());
+
+        let bad_schedule = Schedule {
+            parameters: HashMap::from([("threshold".to_string(), serde_json::json!("80"))]),
+            ..Default::default()
+        };
+        let err = build_script_exec(id, &bad_schedule, &script, &host, &pool).await;
+        assert!(err.unwrap_err().starts_with("Parameter validation failed"));
+
+        let unknown_placeholder = Script {
+            script_content: "echo {{ facts.kernel }}".into(),
+            ..Default::default()
+        };
+        let err =
+            build_script_exec(id, &Schedule::default(), &unknown_placeholder, &host, &pool).await;
+        assert!(err.unwrap_err().starts_with("Template rendering failed"));
+    }
 }
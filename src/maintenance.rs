@@ -0,0 +1,420 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    host::Host,
+    jwt::Claims,
+};
+
+/// Planned maintenance of a host group, alerts are suppressed while it is active
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct MaintenanceWindow {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    /// hosts with all of these attributes are in maintenance
+    pub attributes: Vec<String>,
+    pub starts: DateTime<Utc>,
+    pub ends: DateTime<Utc>,
+    /// skip executions of the hosts while the window is active
+    #[serde(default)]
+    pub pause_schedules: bool,
+    #[serde(default)]
+    pub comment: String,
+    /// set to the logged in user
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+impl MaintenanceWindow {
+    /// Insert into or Replace `MaintenanceWindow` in maintenance_windows table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | attributes | TEXT | json list
+    /// | starts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | ends | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | pause_schedules | NUMERIC | bool
+    /// | comment | TEXT |
+    /// | created_by | TEXT | email of the user
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"REPLACE INTO maintenance_windows(id, name, attributes, starts, ends, pause_schedules, comment, created_by, created) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(utc_to_str(self.starts))
+            .bind(utc_to_str(self.ends))
+            .bind(self.pause_schedules)
+            .bind(self.comment)
+            .bind(self.created_by)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+            .unwrap()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.attributes.is_empty() {
+            return Err("attributes must not be empty".into());
+        }
+        if self.ends <= self.starts {
+            return Err("ends must be after starts".into());
+        }
+        Ok(())
+    }
+
+    /// `host` belongs to the group of this window
+    pub fn applies_to(&self, host: &Host) -> bool {
+        let attributes = host.selector_attributes();
+        !self.attributes.is_empty() && self.attributes.iter().all(|a| attributes.contains(a))
+    }
+}
+
+impl From<SqliteRow> for MaintenanceWindow {
+    fn from(s: SqliteRow) -> Self {
+        MaintenanceWindow {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            starts: utc_from_str(&s.get::<String, _>("starts")),
+            ends: utc_from_str(&s.get::<String, _>("ends")),
+            pause_schedules: s.get::<bool, _>("pause_schedules"),
+            comment: s.get::<String, _>("comment"),
+            created_by: s.get::<String, _>("created_by"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+fn active_filter(now: DateTime<Utc>) -> String {
+    let now = utc_to_str(now);
+    format!("starts <= '{now}' AND ends > '{now}'")
+}
+
+/// active windows `host` belongs to
+pub async fn active_windows_for(
+    host: &Host,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Vec<MaintenanceWindow> {
+    get_windows_from_db(Some(&active_filter(now)), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .filter(|w| w.applies_to(host))
+        .collect()
+}
+
+/// active window pausing the schedules of `host`
+pub async fn paused_by(
+    host: &Host,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Option<MaintenanceWindow> {
+    active_windows_for(host, now, pool)
+        .await
+        .into_iter()
+        .find(|w| w.pause_schedules)
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct WindowQueryParams {
+    /// only windows active right now
+    active: Option<bool>,
+}
+
+/// API to get maintenance windows, latest start first
+pub async fn get_windows_api(
+    _claims: Claims,
+    Query(params): Query<WindowQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let condition = match params.active {
+        Some(true) => active_filter(Utc::now()),
+        _ => "1=1".into(),
+    };
+    let filter = format!("{condition} ORDER BY starts DESC");
+    let window_vec = get_windows_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(window_vec)
+}
+
+/// API to get one maintenance window
+pub async fn get_one_window_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let window_vec = get_windows_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(window_vec)
+}
+
+/// API to create or replace a maintenance window
+pub async fn post_windows_api(
+    claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(mut payload): Json<MaintenanceWindow>,
+) -> Response {
+    if let Err(e) = payload.validate() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    payload.created_by = claims.sub.to_string();
+    let id = payload.id.to_string();
+    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Nothing added",
+        )
+            .into_response()
+    }
+}
+
+/// API to delete a maintenance window
+pub async fn delete_one_window_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_windows_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+pub async fn get_windows_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<MaintenanceWindow> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM maintenance_windows WHERE {f}"),
+        None => "SELECT * FROM maintenance_windows".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| MaintenanceWindow::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_windows_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM maintenance_windows WHERE {f}"),
+        None => "DELETE FROM maintenance_windows".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_database, init_database};
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_maintenance_windows() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let now = Utc::now();
+        let db_host = Host {
+            id: Uuid::new_v4(),
+            attributes: vec!["db".into(), "prod".into()],
+            ..Default::default()
+        };
+        let web_host = Host {
+            id: Uuid::new_v4(),
+            attributes: vec!["web".into(), "prod".into()],
+            ..Default::default()
+        };
+        let window = MaintenanceWindow {
+            id: Uuid::new_v4(),
+            name: "db patching".into(),
+            attributes: vec!["db".into(), "prod".into()],
+            starts: now - Duration::minutes(5),
+            ends: now + Duration::hours(1),
+            pause_schedules: true,
+            comment: "".into(),
+            created_by: "".into(),
+            created: now,
+        };
+        assert!(window.validate().is_ok());
+        assert!(window.applies_to(&db_host));
+        assert!(!window.applies_to(&web_host));
+        let _w = window
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let upcoming = MaintenanceWindow {
+            id: Uuid::new_v4(),
+            name: "web patching".into(),
+            attributes: vec!["web".into()],
+            starts: now + Duration::hours(1),
+            ends: now + Duration::hours(2),
+            pause_schedules: false,
+            ..window.clone()
+        };
+        let _w = upcoming
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        let active = active_windows_for(&db_host, now, &pool).await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, window.id);
+        assert!(active_windows_for(&web_host, now, &pool).await.is_empty());
+        assert_eq!(
+            paused_by(&db_host, now, &pool).await.map(|w| w.id),
+            Some(window.id)
+        );
+        // active but not pausing
+        let later = now + Duration::minutes(90);
+        assert_eq!(active_windows_for(&web_host, later, &pool).await.len(), 1);
+        assert_eq!(paused_by(&web_host, later, &pool).await, None);
+
+        let mut invalid = window.clone();
+        invalid.ends = invalid.starts;
+        assert!(invalid.validate().is_err());
+        invalid.ends = now + Duration::hours(1);
+        invalid.attributes = vec![];
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let now = Utc::now();
+        let window = MaintenanceWindow {
+            id: Uuid::new_v4(),
+            name: "db patching".into(),
+            attributes: vec!["db".into()],
+            starts: now - Duration::minutes(5),
+            ends: now + Duration::hours(1),
+            pause_schedules: false,
+            comment: "kernel update".into(),
+            created_by: "".into(),
+            created: now,
+        };
+        let api_post = post_windows_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(window.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let mut past = window.clone();
+        past.id = Uuid::new_v4();
+        past.starts = now - Duration::hours(3);
+        past.ends = now - Duration::hours(2);
+        let api_post_past = post_windows_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(past),
+        )
+        .await;
+        assert_eq!(api_post_past.status(), StatusCode::CREATED);
+
+        let mut invalid = window.clone();
+        invalid.attributes = vec![];
+        let api_post_invalid = post_windows_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_post_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_active = get_windows_api(
+            claims.clone(),
+            axum::extract::Query(WindowQueryParams { active: Some(true) }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_active.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get_active.into_body())
+            .await
+            .unwrap();
+        let active: Vec<MaintenanceWindow> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].created_by, claims.sub.to_string());
+
+        let api_get_all = get_windows_api(
+            claims.clone(),
+            axum::extract::Query(WindowQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_get_all.into_body())
+            .await
+            .unwrap();
+        let all: Vec<MaintenanceWindow> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(all.len(), 2);
+
+        let api_get_one = get_one_window_api(
+            claims.clone(),
+            axum::extract::Path(window.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+
+        let api_del = delete_one_window_api(
+            claims.clone(),
+            axum::extract::Path(window.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,445 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row, SqlitePool};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{jwt::Claims, parser::Fields};
+
+/// Raw samples are kept this long before they are rolled up into hourly buckets
+pub const RAW_RETENTION: Duration = Duration::days(7);
+/// Hourly buckets are kept this long before they are rolled up into daily buckets
+pub const HOURLY_RETENTION: Duration = Duration::days(90);
+const HOUR: i64 = 3600;
+const DAY: i64 = 86400;
+/// upper bound of points per series a single query may return
+const MAX_POINTS: i64 = 10000;
+
+/// Aggregation applied to all samples within one step
+///
+/// `sum` adds up the average of every series (host and schedule) within the step,
+/// the other aggregations work on all samples of the step
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    #[default]
+    Avg,
+    Min,
+    Max,
+    Sum,
+    Count,
+}
+
+impl Aggregation {
+    fn expression(&self) -> &'static str {
+        match self {
+            Aggregation::Avg => "SUM(avg_v * cnt) / SUM(cnt)",
+            Aggregation::Min => "MIN(min_v)",
+            Aggregation::Max => "MAX(max_v)",
+            Aggregation::Sum => "SUM(avg_v)",
+            Aggregation::Count => "SUM(cnt)",
+        }
+    }
+}
+
+/// One aggregated value, `ts` is the start of the step
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct MetricPoint {
+    pub ts: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// Aggregated values of a metric, `host_id` is only set when grouped by host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct MetricSeries {
+    pub name: String,
+    pub host_id: Option<Uuid>,
+    pub points: Vec<MetricPoint>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct MetricQueryParams {
+    pub name: String,
+    /// defaults to 24 hours before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// defaults to now
+    pub to: Option<DateTime<Utc>>,
+    /// step in seconds, defaults to one hour
+    pub step: Option<i64>,
+    #[serde(default)]
+    pub agg: Aggregation,
+    pub host_id: Option<Uuid>,
+    pub sched_id: Option<Uuid>,
+    /// host attribute, only hosts with this attribute are included
+    pub group: Option<String>,
+    /// one series per host instead of one series across all hosts
+    #[serde(default)]
+    pub by_host: bool,
+}
+
+/// store all numeric fields of an execution as raw samples, returns the number of samples stored
+pub async fn record_fields(
+    host_id: Uuid,
+    sched_id: Uuid,
+    ts: DateTime<Utc>,
+    fields: &Fields,
+    pool: &SqlitePool,
+) -> u64 {
+    let q = r#"REPLACE INTO metrics(host_id, sched_id, name, resolution, ts, value, min, max, count) VALUES(?, ?, ?, 0, ?, ?, ?, ?, 1)"#;
+    let mut stored = 0;
+    for (name, value) in fields {
+        let Some(value) = value.as_f64() else {
+            continue;
+        };
+        let res = query(q)
+            .bind(host_id.to_string())
+            .bind(sched_id.to_string())
+            .bind(name)
+            .bind(ts.timestamp())
+            .bind(value)
+            .bind(value)
+            .bind(value)
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await;
+        match res {
+            Ok(r) => stored += r.rows_affected(),
+            Err(e) => warn!("Could not store metric {name}: {e}"),
+        }
+    }
+    stored
+}
+
+/// roll samples older than their tier's retention up into the next coarser tier
+/// and drop everything older than `retention`
+pub async fn downsample(
+    now: DateTime<Utc>,
+    retention: Duration,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (from, to, keep) in [(0, HOUR, RAW_RETENTION), (HOUR, DAY, HOURLY_RETENTION)] {
+        // only complete buckets are rolled up
+        let cutoff = (now - keep).timestamp().div_euclid(to) * to;
+        let q = format!(
+            r#"INSERT INTO metrics(host_id, sched_id, name, resolution, ts, value, min, max, count)
+            SELECT host_id, sched_id, name, {to}, (ts / {to}) * {to} AS bucket,
+                SUM(value * count) / SUM(count), MIN(min), MAX(max), SUM(count)
+            FROM metrics WHERE resolution = {from} AND ts < {cutoff}
+            GROUP BY host_id, sched_id, name, bucket
+            ON CONFLICT(host_id, sched_id, name, resolution, ts) DO UPDATE SET
+                value = (value * count + excluded.value * excluded.count) / (count + excluded.count),
+                min = MIN(min, excluded.min),
+                max = MAX(max, excluded.max),
+                count = count + excluded.count"#
+        );
+        let rolled = query(&q).execute(&mut *tx).await?;
+        let deleted = query(&format!(
+            "DELETE FROM metrics WHERE resolution = {from} AND ts < {cutoff}"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        debug!(
+            "Metrics: rolled {} samples up into {} buckets of {to}s",
+            deleted.rows_affected(),
+            rolled.rows_affected()
+        );
+    }
+    let expired = (now - retention).timestamp();
+    let _res = query(&format!("DELETE FROM metrics WHERE ts < {expired}"))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+impl MetricQueryParams {
+    /// time range and step, validated against `MAX_POINTS`
+    fn range(&self) -> Result<(i64, i64, i64), String> {
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self.from.unwrap_or(to - Duration::days(1));
+        let step = self.step.unwrap_or(HOUR);
+        if step <= 0 {
+            return Err("step must be positive".into());
+        }
+        if from >= to {
+            return Err("from must be before to".into());
+        }
+        if (to - from).num_seconds() / step > MAX_POINTS {
+            return Err(format!(
+                "more than {MAX_POINTS} points requested, increase step"
+            ));
+        }
+        Ok((from.timestamp(), to.timestamp(), step))
+    }
+}
+
+/// aggregate a metric over time, steps are aligned to the unix epoch
+pub async fn query_metrics(
+    params: &MetricQueryParams,
+    pool: &SqlitePool,
+) -> Result<Vec<MetricSeries>, String> {
+    let (from, to, step) = params.range()?;
+    let mut conditions = vec![format!("name = ? AND ts >= {from} AND ts < {to}")];
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    if let Some(sched_id) = params.sched_id {
+        conditions.push(format!("sched_id = '{sched_id}'"));
+    }
+    if params.group.is_some() {
+        conditions.push(
+            "host_id IN (SELECT hosts.id FROM hosts, json_each(hosts.attributes) WHERE json_each.value = ?)"
+                .into(),
+        );
+    }
+    let series = if params.by_host { "host_id" } else { "NULL" };
+    let q = format!(
+        r#"SELECT {series} AS series, bucket, CAST({agg} AS REAL) AS value FROM (
+            SELECT host_id, sched_id, (ts / {step}) * {step} AS bucket,
+                SUM(value * count) / SUM(count) AS avg_v, MIN(min) AS min_v, MAX(max) AS max_v, SUM(count) AS cnt
+            FROM metrics WHERE {conditions}
+            GROUP BY host_id, sched_id, bucket
+        ) GROUP BY series, bucket ORDER BY series, bucket"#,
+        agg = params.agg.expression(),
+        conditions = conditions.join(" AND ")
+    );
+    let mut stmt = query(&q).bind(&params.name);
+    if let Some(group) = &params.group {
+        stmt = stmt.bind(group);
+    }
+    let rows = stmt
+        .fetch_all(&mut *pool.acquire().await.unwrap())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut result: Vec<MetricSeries> = vec![];
+    for row in rows {
+        let host_id = row
+            .get::<Option<String>, _>("series")
+            .and_then(|s| Uuid::parse_str(&s).ok());
+        let point = MetricPoint {
+            ts: Utc.timestamp_opt(row.get("bucket"), 0).unwrap(),
+            value: row.get("value"),
+        };
+        match result.last_mut() {
+            Some(last) if last.host_id == host_id => last.points.push(point),
+            _ => result.push(MetricSeries {
+                name: params.name.clone(),
+                host_id,
+                points: vec![point],
+            }),
+        }
+    }
+    Ok(result)
+}
+
+/// API to query a metric over time
+pub async fn get_metrics_api(
+    _claims: Claims,
+    Query(params): Query<MetricQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    match query_metrics(&params, &pool).await {
+        Ok(series) => Json(series).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
+/// API to get the names of all stored metrics
+pub async fn get_metric_names_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let names = query("SELECT DISTINCT name FROM metrics ORDER BY name")
+        .map(|row: SqliteRow| row.get::<String, _>("name"))
+        .fetch_all(&mut *pool.acquire().await.unwrap())
+        .await
+        .unwrap_or_default();
+    Json(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        host::Host,
+        schedule::Schedule,
+        script::Script,
+    };
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_metrics() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let prod = Host {
+            id: Uuid::new_v4(),
+            attributes: vec!["prod".into()],
+            ..Default::default()
+        };
+        let dev = Host {
+            id: Uuid::new_v4(),
+            attributes: vec!["dev".into()],
+            ..Default::default()
+        };
+        let (prod_id, dev_id) = (prod.id, dev.id);
+        let _h1 = prod.insert_into_db(pool.acquire().await.unwrap()).await;
+        let _h2 = dev.insert_into_db(pool.acquire().await.unwrap()).await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let _s = script
+            .clone()
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await;
+        let sched = Schedule {
+            script_id: script.id,
+            ..Default::default()
+        };
+        let sched_id = sched.id;
+        let _sched = sched.insert_into_db(pool.acquire().await.unwrap()).await;
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let fields = |updates: i64| {
+            Fields::from([
+                ("updates".to_string(), json!(updates)),
+                ("os".to_string(), json!("debian")),
+            ])
+        };
+        // prod: 2 and 4 in the first hour, dev: 10
+        for (host_id, minute, updates) in [
+            (prod_id, 0, 2),
+            (prod_id, 30, 4),
+            (dev_id, 10, 10),
+            (prod_id, 90, 6),
+        ] {
+            let ts = start + Duration::minutes(minute);
+            let stored = record_fields(host_id, sched_id, ts, &fields(updates), &pool).await;
+            assert_eq!(stored, 1);
+        }
+
+        let mut params = MetricQueryParams {
+            name: "updates".into(),
+            from: Some(start),
+            to: Some(start + Duration::hours(2)),
+            step: Some(HOUR),
+            ..Default::default()
+        };
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].host_id, None);
+        assert_eq!(series[0].points[0].ts, start);
+        assert_eq!(series[0].points[0].value, 16.0 / 3.0);
+        assert_eq!(series[0].points[1].value, 6.0);
+
+        params.agg = Aggregation::Sum;
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series[0].points[0].value, 13.0);
+
+        params.agg = Aggregation::Max;
+        params.group = Some("prod".into());
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series[0].points[0].value, 4.0);
+
+        params.agg = Aggregation::Count;
+        params.group = None;
+        params.by_host = true;
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series.len(), 2);
+        let prod_series = series.iter().find(|s| s.host_id == Some(prod_id)).unwrap();
+        assert_eq!(prod_series.points.len(), 2);
+        assert_eq!(prod_series.points[0].value, 2.0);
+
+        params.step = Some(0);
+        assert!(query_metrics(&params, &pool).await.is_err());
+        params.step = Some(1);
+        params.to = Some(start + Duration::days(1));
+        assert!(query_metrics(&params, &pool).await.is_err());
+
+        // raw samples become hourly buckets, the query result stays the same
+        let now = start + RAW_RETENTION + Duration::hours(3);
+        downsample(now, Duration::days(730), &pool).await.unwrap();
+        let rows = query("SELECT resolution, count FROM metrics ORDER BY ts")
+            .fetch_all(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|r| r.get::<i64, _>("resolution") == HOUR));
+        params.step = Some(HOUR);
+        params.to = Some(start + Duration::hours(2));
+        params.by_host = false;
+        params.agg = Aggregation::Avg;
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series[0].points[0].value, 16.0 / 3.0);
+
+        // hourly buckets become daily buckets
+        let now = start + HOURLY_RETENTION + Duration::days(2);
+        downsample(now, Duration::days(730), &pool).await.unwrap();
+        params.step = Some(DAY);
+        params.to = Some(start + Duration::days(1));
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert_eq!(series[0].points[0].value, 5.5);
+
+        // everything expired
+        downsample(now, Duration::days(30), &pool).await.unwrap();
+        let series = query_metrics(&params, &pool).await.unwrap();
+        assert!(series.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let api_names = get_metric_names_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_names.status(), StatusCode::OK);
+
+        let api_query = get_metrics_api(
+            claims.clone(),
+            axum::extract::Query(MetricQueryParams {
+                name: "updates".into(),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_query.status(), StatusCode::OK);
+
+        let api_invalid = get_metrics_api(
+            claims.clone(),
+            axum::extract::Query(MetricQueryParams {
+                name: "updates".into(),
+                step: Some(-1),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
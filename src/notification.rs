@@ -0,0 +1,1717 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    alert::{ack_url, get_alerts_from_db, Alert, AlertState, Severity},
+    db::{utc_from_str, utc_to_str},
+    escalation::start_escalation,
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    routing::{get_routes_from_db, match_routes},
+    silence::{suppressed_by, Subject},
+    template::{render, TemplateContext},
+    waiver::Waiver,
+};
+
+/// Header carrying the hex encoded HMAC-SHA256 of the webhook body
+pub const SIGNATURE_HEADER: &str = "X-Unpatched-Signature";
+/// Header carrying the id of the delivery, identical for all retries
+pub const DELIVERY_HEADER: &str = "X-Unpatched-Delivery";
+const REDACTED: &str = "********";
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Unable to build http client!")
+});
+
+/// Attempts per delivery, the delay doubles after every failed attempt
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+pub const DEFAULT_RETRY: RetryPolicy = RetryPolicy {
+    attempts: 3,
+    backoff: Duration::from_secs(5),
+};
+const TEST_RETRY: RetryPolicy = RetryPolicy {
+    attempts: 1,
+    backoff: Duration::ZERO,
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    AlertFiring,
+    AlertResolved,
+    AlertEscalated,
+    HostOffline,
+    WaiverExpiring,
+    WaiverExpired,
+    Test,
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            EventKind::AlertFiring => "alert_firing",
+            EventKind::AlertResolved => "alert_resolved",
+            EventKind::AlertEscalated => "alert_escalated",
+            EventKind::HostOffline => "host_offline",
+            EventKind::WaiverExpiring => "waiver_expiring",
+            EventKind::WaiverExpired => "waiver_expired",
+            EventKind::Test => "test",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl EventKind {
+    fn from_db(s: &str) -> EventKind {
+        match s {
+            "alert_firing" => EventKind::AlertFiring,
+            "alert_resolved" => EventKind::AlertResolved,
+            "alert_escalated" => EventKind::AlertEscalated,
+            "host_offline" => EventKind::HostOffline,
+            "waiver_expiring" => EventKind::WaiverExpiring,
+            "waiver_expired" => EventKind::WaiverExpired,
+            _ => EventKind::Test,
+        }
+    }
+}
+
+/// Something channels get notified about
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationEvent {
+    pub kind: EventKind,
+    pub severity: Severity,
+    pub title: String,
+    pub summary: String,
+    pub host: Host,
+    pub alert: Option<Alert>,
+    /// alerts with the same fingerprint sent as this one event
+    pub grouped: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NotificationEvent {
+    pub fn from_alert(alert: &Alert, host: Host) -> Self {
+        let kind = match alert.state {
+            AlertState::Resolved => EventKind::AlertResolved,
+            _ => EventKind::AlertFiring,
+        };
+        NotificationEvent {
+            kind,
+            severity: alert.severity.clone(),
+            title: alert.labels.get("alertname").cloned().unwrap_or_default(),
+            summary: alert.summary.clone(),
+            host,
+            alert: Some(alert.clone()),
+            grouped: 1,
+            timestamp: alert.resolved.or(alert.fired).unwrap_or_else(Utc::now),
+        }
+    }
+
+    pub fn host_offline(host: Host) -> Self {
+        NotificationEvent {
+            kind: EventKind::HostOffline,
+            severity: Severity::Warning,
+            title: "host offline".into(),
+            summary: format!("agent {} disconnected", host.alias),
+            host,
+            alert: None,
+            grouped: 1,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// waiver expiring soon or expired, `host` is empty for waivers of several hosts
+    pub fn waiver(waiver: &Waiver, expired: bool, host: Host, now: DateTime<Utc>) -> Self {
+        let (kind, severity, title, verb) = match expired {
+            true => (
+                EventKind::WaiverExpired,
+                Severity::Warning,
+                "waiver expired",
+                "expired",
+            ),
+            false => (
+                EventKind::WaiverExpiring,
+                Severity::Info,
+                "waiver expiring",
+                "expires",
+            ),
+        };
+        NotificationEvent {
+            kind,
+            severity,
+            title: title.into(),
+            summary: format!(
+                "waiver for {} {verb} {}, approved by {}: {}",
+                waiver.scope(),
+                waiver.expires.format("%Y-%m-%d %H:%M UTC"),
+                waiver.approver,
+                waiver.justification
+            ),
+            host,
+            alert: None,
+            grouped: 1,
+            timestamp: now,
+        }
+    }
+
+    pub fn test() -> Self {
+        NotificationEvent {
+            kind: EventKind::Test,
+            severity: Severity::Info,
+            title: "test notification".into(),
+            summary: "notification channel works".into(),
+            host: Host {
+                alias: "test-host".into(),
+                ..Default::default()
+            },
+            alert: None,
+            grouped: 1,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// labels of the alert, `alertname` and `host` for other events
+    pub fn labels(&self) -> BTreeMap<String, String> {
+        match &self.alert {
+            Some(alert) => alert.labels.clone(),
+            None => BTreeMap::from([
+                ("alertname".to_string(), self.title.clone()),
+                ("host".to_string(), self.host.alias.clone()),
+            ]),
+        }
+    }
+
+    /// signed link acknowledging the alert, only for firing alerts
+    pub fn ack_url(&self) -> Option<String> {
+        self.alert
+            .as_ref()
+            .filter(|a| a.state == AlertState::Firing)
+            .map(|a| ack_url(a.id, self.timestamp))
+    }
+
+    fn context(&self) -> TemplateContext {
+        let mut context = TemplateContext::new(&self.host, HashMap::new(), HashMap::new());
+        let value = self.alert.as_ref().and_then(|a| a.value);
+        context.event = HashMap::from([
+            ("kind".to_string(), self.kind.to_string()),
+            ("title".to_string(), self.title.clone()),
+            ("summary".to_string(), self.summary.clone()),
+            ("severity".to_string(), self.severity.to_string()),
+            ("timestamp".to_string(), utc_to_str(self.timestamp)),
+            (
+                "state".to_string(),
+                self.alert
+                    .as_ref()
+                    .map(|a| a.state.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "value".to_string(),
+                value.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            (
+                "alert_id".to_string(),
+                self.alert
+                    .as_ref()
+                    .map(|a| a.id.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("count".to_string(), self.grouped.to_string()),
+            ("ack_url".to_string(), self.ack_url().unwrap_or_default()),
+        ]);
+        context.labels = self.labels().into_iter().collect();
+        context
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    None,
+    #[default]
+    Starttls,
+    Tls,
+}
+
+/// Where and how a channel delivers, secrets are redacted when read through the API
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelConfig {
+    /// json payload signed with HMAC-SHA256 of `secret`
+    Webhook { url: String, secret: String },
+    Email {
+        host: String,
+        port: u16,
+        #[serde(default)]
+        tls: SmtpTls,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+    /// slack compatible incoming webhook
+    Slack { url: String },
+    /// teams incoming webhook (MessageCard)
+    Teams { url: String },
+    Matrix {
+        homeserver: String,
+        room_id: String,
+        access_token: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NotificationChannel {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    pub config: ChannelConfig,
+    #[serde(default = "default_title_template")]
+    pub title_template: String,
+    #[serde(default = "default_body_template")]
+    pub body_template: String,
+    /// events below this severity are not sent
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    /// event kinds sent to this channel, all if empty
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+fn default_title_template() -> String {
+    "[{{ event.severity }}] {{ event.title }} on {{ host.alias }}".into()
+}
+
+fn default_body_template() -> String {
+    "{{ event.summary }}\nevent: {{ event.kind }}\nhost: {{ host.alias }} ({{ host.id }})\ntime: {{ event.timestamp }}".into()
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Info
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    #[default]
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl Display for DeliveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        };
+        write!(f, "{status}")
+    }
+}
+
+impl DeliveryStatus {
+    fn from_db(s: &str) -> DeliveryStatus {
+        match s {
+            "delivered" => DeliveryStatus::Delivered,
+            "failed" => DeliveryStatus::Failed,
+            _ => DeliveryStatus::Pending,
+        }
+    }
+}
+
+/// One event sent to one channel, including all retries
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NotificationDelivery {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub event: EventKind,
+    pub title: String,
+    pub alert_id: Option<Uuid>,
+    pub host_id: Option<Uuid>,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    pub error: Option<String>,
+    pub created: DateTime<Utc>,
+    pub delivered: Option<DateTime<Utc>>,
+}
+
+fn hmac_hex(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// fail on transport errors and non 2xx answers
+async fn check(response: Result<reqwest::Response, reqwest::Error>) -> Result<(), String> {
+    let response = response.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Err(format!(
+            "{status}: {}",
+            body.chars().take(200).collect::<String>()
+        ))
+    }
+}
+
+impl NotificationChannel {
+    /// Insert or Update `NotificationChannel` in notification_channels table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | config | TEXT | json
+    /// | title_template | TEXT |
+    /// | body_template | TEXT |
+    /// | min_severity | TEXT | info, warning or critical
+    /// | events | TEXT | json list
+    /// | active | NUMERIC | bool
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO notification_channels(id, name, config, title_template, body_template, min_severity, events, active, created) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, config=excluded.config, title_template=excluded.title_template, body_template=excluded.body_template, min_severity=excluded.min_severity, events=excluded.events, active=excluded.active"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(serde_json::to_string(&self.config).unwrap())
+            .bind(self.title_template)
+            .bind(self.body_template)
+            .bind(self.min_severity.to_string())
+            .bind(serde_json::to_string(&self.events).unwrap())
+            .bind(self.active)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+            .unwrap()
+    }
+
+    /// templates render and addresses parse
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        let context = NotificationEvent::test().context();
+        render(&self.title_template, &context).map_err(|e| format!("title_template: {e}"))?;
+        render(&self.body_template, &context).map_err(|e| format!("body_template: {e}"))?;
+        match &self.config {
+            ChannelConfig::Webhook { url, .. }
+            | ChannelConfig::Slack { url }
+            | ChannelConfig::Teams { url }
+            | ChannelConfig::Matrix {
+                homeserver: url, ..
+            } => {
+                reqwest::Url::parse(url).map_err(|e| format!("invalid url {url}: {e}"))?;
+            }
+            ChannelConfig::Email { from, to, .. } => {
+                if to.is_empty() {
+                    return Err("no recipients".into());
+                }
+                for address in to.iter().chain([from]) {
+                    address
+                        .parse::<Mailbox>()
+                        .map_err(|e| format!("invalid address {address}: {e}"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// copy without secrets, for the API
+    pub fn redacted(mut self) -> Self {
+        match &mut self.config {
+            ChannelConfig::Webhook { secret, .. } => *secret = REDACTED.into(),
+            ChannelConfig::Email { password, .. } => {
+                if password.is_some() {
+                    *password = Some(REDACTED.into())
+                }
+            }
+            ChannelConfig::Matrix { access_token, .. } => *access_token = REDACTED.into(),
+            ChannelConfig::Slack { .. } | ChannelConfig::Teams { .. } => {}
+        }
+        self
+    }
+
+    /// take the secrets still redacted from `stored`, for channels posted back as read
+    fn keep_secrets(mut self, stored: &NotificationChannel) -> Self {
+        match (&mut self.config, &stored.config) {
+            (
+                ChannelConfig::Webhook { secret, .. },
+                ChannelConfig::Webhook {
+                    secret: stored_secret,
+                    ..
+                },
+            ) if secret == REDACTED => *secret = stored_secret.clone(),
+            (
+                ChannelConfig::Email { password, .. },
+                ChannelConfig::Email {
+                    password: stored_password,
+                    ..
+                },
+            ) if password.as_deref() == Some(REDACTED) => *password = stored_password.clone(),
+            (
+                ChannelConfig::Matrix { access_token, .. },
+                ChannelConfig::Matrix {
+                    access_token: stored_token,
+                    ..
+                },
+            ) if access_token == REDACTED => *access_token = stored_token.clone(),
+            _ => {}
+        }
+        self
+    }
+
+    pub fn accepts(&self, event: &NotificationEvent) -> bool {
+        self.active
+            && event.severity >= self.min_severity
+            && (self.events.is_empty() || self.events.contains(&event.kind))
+    }
+
+    /// send `event` once
+    pub async fn send(&self, event: &NotificationEvent, delivery_id: Uuid) -> Result<(), String> {
+        let context = event.context();
+        let title = render(&self.title_template, &context).map_err(|e| e.to_string())?;
+        let body = render(&self.body_template, &context).map_err(|e| e.to_string())?;
+        match &self.config {
+            ChannelConfig::Webhook { url, secret } => {
+                let payload = serde_json::to_vec(&json!({
+                    "delivery_id": delivery_id,
+                    "event": event.kind,
+                    "severity": event.severity,
+                    "title": title,
+                    "message": body,
+                    "host": {"id": event.host.id, "alias": event.host.alias},
+                    "alert": event.alert,
+                    "ack_url": event.ack_url(),
+                    "timestamp": event.timestamp,
+                }))
+                .unwrap();
+                let signature = hmac_hex(secret, &payload);
+                check(
+                    CLIENT
+                        .post(url)
+                        .header(reqwest::header::CONTENT_TYPE, "application/json")
+                        .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+                        .header(DELIVERY_HEADER, delivery_id.to_string())
+                        .body(payload)
+                        .send()
+                        .await,
+                )
+                .await
+            }
+            ChannelConfig::Slack { url } => {
+                let payload = json!({ "text": format!("*{title}*\n{body}") });
+                check(CLIENT.post(url).json(&payload).send().await).await
+            }
+            ChannelConfig::Teams { url } => {
+                let color = match event.severity {
+                    Severity::Info => "0078D7",
+                    Severity::Warning => "FFA500",
+                    Severity::Critical => "D70000",
+                };
+                let payload = json!({
+                    "@type": "MessageCard",
+                    "@context": "https://schema.org/extensions",
+                    "summary": title,
+                    "themeColor": color,
+                    "title": title,
+                    "text": body.replace('\n', "<br>"),
+                });
+                check(CLIENT.post(url).json(&payload).send().await).await
+            }
+            ChannelConfig::Matrix {
+                homeserver,
+                room_id,
+                access_token,
+            } => {
+                let mut url = reqwest::Url::parse(homeserver).map_err(|e| e.to_string())?;
+                url.path_segments_mut()
+                    .map_err(|_| format!("invalid homeserver {homeserver}"))?
+                    .pop_if_empty()
+                    .extend([
+                        "_matrix",
+                        "client",
+                        "v3",
+                        "rooms",
+                        room_id,
+                        "send",
+                        "m.room.message",
+                        &delivery_id.to_string(),
+                    ]);
+                let payload = json!({
+                    "msgtype": "m.text",
+                    "body": format!("{title}\n{body}"),
+                });
+                check(
+                    CLIENT
+                        .put(url)
+                        .bearer_auth(access_token)
+                        .json(&payload)
+                        .send()
+                        .await,
+                )
+                .await
+            }
+            ChannelConfig::Email {
+                host,
+                port,
+                tls,
+                username,
+                password,
+                from,
+                to,
+            } => {
+                let builder = match tls {
+                    SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+                    SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                        .map_err(|e| e.to_string())?,
+                    SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                        .map_err(|e| e.to_string())?,
+                };
+                let mut builder = builder.port(*port).timeout(Some(Duration::from_secs(10)));
+                if let Some(username) = username {
+                    builder = builder.credentials(Credentials::new(
+                        username.clone(),
+                        password.clone().unwrap_or_default(),
+                    ));
+                }
+                let mut message = Message::builder()
+                    .from(from.parse::<Mailbox>().map_err(|e| e.to_string())?)
+                    .subject(title);
+                for address in to {
+                    message = message.to(address.parse::<Mailbox>().map_err(|e| e.to_string())?);
+                }
+                let email = message.body(body).map_err(|e| e.to_string())?;
+                builder
+                    .build()
+                    .send(email)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+impl From<SqliteRow> for NotificationChannel {
+    fn from(s: SqliteRow) -> Self {
+        NotificationChannel {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            config: serde_json::from_str(&s.get::<String, _>("config")).unwrap(),
+            title_template: s.get::<String, _>("title_template"),
+            body_template: s.get::<String, _>("body_template"),
+            min_severity: serde_json::from_value(json!(s.get::<String, _>("min_severity")))
+                .unwrap_or(Severity::Info),
+            events: serde_json::from_str(&s.get::<String, _>("events")).unwrap_or_default(),
+            active: s.get::<bool, _>("active"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+impl NotificationDelivery {
+    /// Insert into or Replace `NotificationDelivery` in notification_deliveries table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | channel_id | TEXT | uuid
+    /// | event | TEXT | alert_firing, alert_resolved, host_offline or test
+    /// | title | TEXT |
+    /// | alert_id | TEXT | uuid
+    /// | host_id | TEXT | uuid
+    /// | status | TEXT | pending, delivered or failed
+    /// | attempts | INT |
+    /// | error | TEXT | error of the last attempt
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | delivered | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"REPLACE INTO notification_deliveries(id, channel_id, event, title, alert_id, host_id, status, attempts, error, created, delivered) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.channel_id.to_string())
+            .bind(self.event.to_string())
+            .bind(self.title)
+            .bind(self.alert_id.map(|id| id.to_string()))
+            .bind(self.host_id.map(|id| id.to_string()))
+            .bind(self.status.to_string())
+            .bind(self.attempts)
+            .bind(self.error)
+            .bind(utc_to_str(self.created))
+            .bind(self.delivered.map(utc_to_str))
+            .execute(&mut *connection)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+impl From<SqliteRow> for NotificationDelivery {
+    fn from(s: SqliteRow) -> Self {
+        let uuid = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .and_then(|id| id.parse().ok())
+        };
+        NotificationDelivery {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            channel_id: s.get::<String, _>("channel_id").parse().unwrap(),
+            event: EventKind::from_db(&s.get::<String, _>("event")),
+            title: s.get::<String, _>("title"),
+            alert_id: uuid("alert_id"),
+            host_id: uuid("host_id"),
+            status: DeliveryStatus::from_db(&s.get::<String, _>("status")),
+            attempts: s.get::<i64, _>("attempts"),
+            error: s.get::<Option<String>, _>("error"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+            delivered: s
+                .get::<Option<String>, _>("delivered")
+                .as_deref()
+                .map(utc_from_str),
+        }
+    }
+}
+
+/// send `event` to `channel`, retried according to `policy`, every attempt is logged
+pub async fn deliver(
+    channel: &NotificationChannel,
+    event: &NotificationEvent,
+    policy: RetryPolicy,
+    pool: &SqlitePool,
+) -> NotificationDelivery {
+    let mut delivery = NotificationDelivery {
+        id: Uuid::new_v4(),
+        channel_id: channel.id,
+        event: event.kind.clone(),
+        title: event.title.clone(),
+        alert_id: event.alert.as_ref().map(|a| a.id),
+        host_id: (!event.host.id.is_nil()).then_some(event.host.id),
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        error: None,
+        created: Utc::now(),
+        delivered: None,
+    };
+    let mut backoff = policy.backoff;
+    for attempt in 1..=policy.attempts.max(1) {
+        delivery.attempts = attempt.into();
+        match channel.send(event, delivery.id).await {
+            Ok(()) => {
+                delivery.status = DeliveryStatus::Delivered;
+                delivery.error = None;
+                delivery.delivered = Some(Utc::now());
+            }
+            Err(e) => {
+                warn!(
+                    "Notification to {} failed (attempt {attempt}): {e}",
+                    channel.name
+                );
+                delivery.error = Some(e);
+                if attempt == policy.attempts.max(1) {
+                    delivery.status = DeliveryStatus::Failed;
+                }
+            }
+        }
+        let _res = delivery
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        if delivery.status != DeliveryStatus::Pending {
+            break;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+    delivery
+}
+
+/// silence or maintenance window muting `event`
+async fn suppressed(event: &NotificationEvent, pool: &SqlitePool) -> Option<String> {
+    let labels = event.labels();
+    let subject = Subject {
+        host: &event.host,
+        sched_id: event.alert.as_ref().map(|a| a.sched_id),
+        labels: &labels,
+    };
+    let reason = suppressed_by(&subject, Utc::now(), pool).await;
+    if let Some(reason) = &reason {
+        info!(
+            "Event {} for {} suppressed by {reason}",
+            event.kind, event.host.alias
+        );
+    }
+    reason
+}
+
+/// send `event` to the channels of the matching routes, or to all channels if no route
+/// matches, unless a silence or maintenance window mutes it
+///
+/// firing alerts start the escalation policies of the matching routes
+pub async fn notify(event: NotificationEvent, pool: SqlitePool) -> Vec<NotificationDelivery> {
+    if suppressed(&event, &pool).await.is_some() {
+        return vec![];
+    }
+    let mut labels = event.labels();
+    labels.insert("severity".into(), event.severity.to_string());
+    let routes = get_routes_from_db(None, pool.acquire().await.unwrap()).await;
+    let matched = match_routes(&routes, &labels);
+    let mut channels =
+        get_channels_from_db(Some("active = 1"), pool.acquire().await.unwrap()).await;
+    if !matched.is_empty() {
+        debug!(
+            "Event {} matched routes {:?}",
+            event.kind,
+            matched.iter().map(|r| &r.name).collect::<Vec<_>>()
+        );
+        channels.retain(|c| matched.iter().any(|r| r.channels.contains(&c.id)));
+    }
+    if let (EventKind::AlertFiring, Some(alert)) = (&event.kind, &event.alert) {
+        for policy_id in matched.iter().filter_map(|r| r.escalation_id) {
+            start_escalation(alert, policy_id, event.timestamp, &pool).await;
+        }
+    }
+    let deliveries = channels
+        .iter()
+        .filter(|c| c.accepts(&event))
+        .map(|c| deliver(c, &event, DEFAULT_RETRY, &pool));
+    let deliveries = join_all(deliveries).await;
+    debug!("Event {} sent to {} channels", event.kind, deliveries.len());
+    deliveries
+}
+
+/// send `event` to the active channels in `ids` regardless of their event filter,
+/// unless a silence or maintenance window mutes it
+pub async fn notify_channels(
+    event: NotificationEvent,
+    ids: &[Uuid],
+    pool: SqlitePool,
+) -> Vec<NotificationDelivery> {
+    if suppressed(&event, &pool).await.is_some() {
+        return vec![];
+    }
+    let channels = get_channels_from_db(Some("active = 1"), pool.acquire().await.unwrap()).await;
+    let deliveries = channels
+        .iter()
+        .filter(|c| ids.contains(&c.id))
+        .map(|c| deliver(c, &event, DEFAULT_RETRY, &pool));
+    join_all(deliveries).await
+}
+
+/// notify about alerts that started firing or got resolved
+///
+/// alerts with the same fingerprint are sent as one event, and only if no other alert
+/// of the fingerprint was firing before (firing) or still fires (resolved)
+pub async fn notify_alerts(alerts: Vec<Alert>, pool: SqlitePool) -> Vec<NotificationDelivery> {
+    let mut groups: BTreeMap<(String, String), Vec<Alert>> = BTreeMap::new();
+    for alert in alerts {
+        groups
+            .entry((alert.fingerprint.clone(), alert.state.to_string()))
+            .or_default()
+            .push(alert);
+    }
+    let mut deliveries = vec![];
+    for ((fingerprint, state), group) in groups {
+        let ids: Vec<String> = group.iter().map(|a| format!("'{}'", a.id)).collect();
+        let filter = format!(
+            "fingerprint = '{fingerprint}' AND state = 'firing' AND id NOT IN ({})",
+            ids.join(",")
+        );
+        let firing = get_alerts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        if !firing.is_empty() {
+            debug!("Alerts {fingerprint} {state} deduplicated, still firing");
+            continue;
+        }
+        let filter = format!("id='{}'", group[0].host_id);
+        let Some(host) = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .next()
+        else {
+            continue;
+        };
+        info!("Notifying about alerts {fingerprint} ({state})");
+        let mut event = NotificationEvent::from_alert(&group[0], host);
+        event.grouped = group.len();
+        if let Some(severity) =
+            group
+                .iter()
+                .map(|a| a.severity.clone())
+                .reduce(|a, b| if b > a { b } else { a })
+        {
+            event.severity = severity;
+        }
+        if group.len() > 1 {
+            event.summary = format!("{} (+{} more)", event.summary, group.len() - 1);
+        }
+        deliveries.extend(notify(event, pool.clone()).await);
+    }
+    deliveries
+}
+
+/// API to get all notification channels, secrets are redacted
+pub async fn get_channels_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let channel_vec: Vec<NotificationChannel> =
+        get_channels_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .map(NotificationChannel::redacted)
+            .collect();
+    Json(channel_vec)
+}
+
+/// API to get one notification channel, secrets are redacted
+pub async fn get_one_channel_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let channel_vec: Vec<NotificationChannel> =
+        get_channels_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .into_iter()
+            .map(NotificationChannel::redacted)
+            .collect();
+    Json(channel_vec)
+}
+
+/// API to create or replace a notification channel, redacted secrets keep their stored value
+pub async fn post_channels_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<NotificationChannel>,
+) -> Response {
+    let filter = format!("id='{}'", payload.id);
+    let payload = match get_channels_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .first()
+    {
+        Some(stored) => payload.keep_secrets(stored),
+        None => payload,
+    };
+    if let Err(e) = payload.validate() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Nothing added",
+        )
+            .into_response()
+    }
+}
+
+/// API to delete a notification channel and its delivery log
+pub async fn delete_one_channel_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_channels_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// API to send a test notification through a channel, answers 502 if the channel failed
+pub async fn test_channel_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let Some(channel) = get_channels_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return (StatusCode::NOT_FOUND, "Channel not found").into_response();
+    };
+    let delivery = deliver(&channel, &NotificationEvent::test(), TEST_RETRY, &pool).await;
+    let status = match delivery.status {
+        DeliveryStatus::Delivered => StatusCode::OK,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    (status, Json(delivery)).into_response()
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DeliveryQueryParams {
+    channel_id: Option<Uuid>,
+    status: Option<DeliveryStatus>,
+}
+
+/// API to get the delivery log, newest first
+pub async fn get_deliveries_api(
+    _claims: Claims,
+    Query(params): Query<DeliveryQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec!["1=1".to_string()];
+    if let Some(channel_id) = params.channel_id {
+        conditions.push(format!("channel_id='{channel_id}'"));
+    }
+    if let Some(status) = params.status {
+        conditions.push(format!("status='{status}'"));
+    }
+    let filter = format!("{} ORDER BY created DESC", conditions.join(" AND "));
+    let delivery_vec = get_deliveries_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(delivery_vec)
+}
+
+pub async fn get_channels_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<NotificationChannel> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM notification_channels WHERE {f}"),
+        None => "SELECT * FROM notification_channels".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| NotificationChannel::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_channels_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM notification_channels WHERE {f}"),
+        None => "DELETE FROM notification_channels".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_deliveries_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<NotificationDelivery> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM notification_deliveries WHERE {f}"),
+        None => "SELECT * FROM notification_deliveries".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| NotificationDelivery::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        alert::{fingerprint, AlertCondition, AlertRule},
+        db::{create_database, init_database},
+        revision::save_script,
+        routing::Route,
+        schedule::Schedule,
+        script::Script,
+        silence::Silence,
+    };
+    use axum::{body::Bytes, http::HeaderMap, routing::any, Router};
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    /// method, path, headers and body of a request the stand-in received
+    pub type Captured = Arc<Mutex<Vec<(String, String, HeaderMap, Bytes)>>>;
+
+    /// local HTTP stand-in, `/fail` always answers 500, `/flaky` fails the first request
+    pub async fn http_stand_in() -> (String, Captured) {
+        let captured: Captured = Arc::new(Mutex::new(vec![]));
+        let requests = captured.clone();
+        let app = Router::new().fallback(any(
+            move |method: axum::http::Method,
+                  uri: axum::http::Uri,
+                  headers: HeaderMap,
+                  body: Bytes| {
+                let requests = requests.clone();
+                async move {
+                    let path = uri.path().to_string();
+                    let mut requests = requests.lock().unwrap();
+                    let earlier = requests.iter().filter(|r| r.1 == path).count();
+                    requests.push((method.to_string(), path.clone(), headers, body));
+                    match path.as_str() {
+                        "/fail" => StatusCode::INTERNAL_SERVER_ERROR,
+                        "/flaky" if earlier == 0 => StatusCode::SERVICE_UNAVAILABLE,
+                        _ => StatusCode::OK,
+                    }
+                }
+            },
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+        (format!("http://{addr}"), captured)
+    }
+
+    /// local SMTP stand-in without TLS, collects the DATA of every mail
+    pub async fn smtp_stand_in() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mails = Arc::new(Mutex::new(vec![]));
+        let received = mails.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    let _ = write.write_all(b"220 stand-in ESMTP\r\n").await;
+                    let mut data: Option<String> = None;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if let Some(mail) = data.as_mut() {
+                            if line == "." {
+                                received.lock().unwrap().push(data.take().unwrap());
+                                let _ = write.write_all(b"250 queued\r\n").await;
+                            } else {
+                                mail.push_str(&line);
+                                mail.push('\n');
+                            }
+                            continue;
+                        }
+                        let command = line.to_uppercase();
+                        let reply: &[u8] = if command.starts_with("EHLO") {
+                            b"250-stand-in\r\n250 8BITMIME\r\n"
+                        } else if command.starts_with("DATA") {
+                            data = Some(String::new());
+                            b"354 end with .\r\n"
+                        } else if command.starts_with("QUIT") {
+                            let _ = write.write_all(b"221 bye\r\n").await;
+                            break;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        let _ = write.write_all(reply).await;
+                    }
+                });
+            }
+        });
+        (port, mails)
+    }
+
+    fn channel(name: &str, config: ChannelConfig) -> NotificationChannel {
+        NotificationChannel {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            config,
+            title_template: default_title_template(),
+            body_template: default_body_template(),
+            min_severity: Severity::Info,
+            events: vec![],
+            active: true,
+            created: Utc::now(),
+        }
+    }
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notifications() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (base, captured) = http_stand_in().await;
+        let (smtp_port, mails) = smtp_stand_in().await;
+
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let alert = Alert {
+            id: Uuid::new_v4(),
+            host_id: host.id,
+            state: AlertState::Firing,
+            severity: Severity::Critical,
+            labels: [("alertname".to_string(), "disk full".to_string())].into(),
+            summary: "disk.free is 1 (< 5)".into(),
+            value: Some(1.0),
+            fired: Some(Utc::now()),
+            ..Default::default()
+        };
+        let event = NotificationEvent::from_alert(&alert, host.clone());
+        assert_eq!(event.kind, EventKind::AlertFiring);
+
+        // webhook with hmac signature over the body
+        let webhook = channel(
+            "webhook",
+            ChannelConfig::Webhook {
+                url: format!("{base}/hook"),
+                secret: "s3cret".into(),
+            },
+        );
+        let delivery = deliver(&webhook, &event, fast(), &pool).await;
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 1);
+        {
+            let requests = captured.lock().unwrap();
+            let (method, path, headers, body) = requests.last().unwrap();
+            assert_eq!((method.as_str(), path.as_str()), ("POST", "/hook"));
+            let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+            assert_eq!(signature, format!("sha256={}", hmac_hex("s3cret", body)));
+            assert_eq!(headers[DELIVERY_HEADER], delivery.id.to_string().as_str());
+            let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+            assert_eq!(payload["event"], "alert_firing");
+            assert_eq!(payload["title"], "[critical] disk full on web-1");
+            assert_eq!(payload["alert"]["id"], alert.id.to_string());
+            assert!(payload["ack_url"]
+                .as_str()
+                .unwrap()
+                .contains("/api/v1/alerts/ack/"));
+        }
+
+        // chat formats
+        let slack = channel(
+            "slack",
+            ChannelConfig::Slack {
+                url: format!("{base}/slack"),
+            },
+        );
+        let teams = channel(
+            "teams",
+            ChannelConfig::Teams {
+                url: format!("{base}/teams"),
+            },
+        );
+        let matrix = channel(
+            "matrix",
+            ChannelConfig::Matrix {
+                homeserver: base.clone(),
+                room_id: "!ops:example.org".into(),
+                access_token: "token".into(),
+            },
+        );
+        for c in [&slack, &teams, &matrix] {
+            let delivery = deliver(c, &event, fast(), &pool).await;
+            assert_eq!(delivery.status, DeliveryStatus::Delivered, "{}", c.name);
+        }
+        {
+            let requests = captured.lock().unwrap();
+            let body = |path: &str| {
+                let request = requests.iter().find(|r| r.1.starts_with(path)).unwrap();
+                serde_json::from_slice::<serde_json::Value>(&request.3).unwrap()
+            };
+            assert!(body("/slack")["text"]
+                .as_str()
+                .unwrap()
+                .starts_with("*[critical] disk full on web-1*\ndisk.free is 1 (< 5)"));
+            assert_eq!(body("/teams")["@type"], "MessageCard");
+            assert_eq!(body("/teams")["themeColor"], "D70000");
+            let matrix_request = requests
+                .iter()
+                .find(|r| r.1.starts_with("/_matrix"))
+                .unwrap();
+            assert_eq!(matrix_request.0, "PUT");
+            assert!(matrix_request
+                .1
+                .starts_with("/_matrix/client/v3/rooms/!ops:example.org/send/m.room.message/"));
+            assert_eq!(matrix_request.2["authorization"], "Bearer token");
+            assert_eq!(body("/_matrix")["msgtype"], "m.text");
+        }
+
+        // email with a custom template
+        let mut email = channel(
+            "email",
+            ChannelConfig::Email {
+                host: "127.0.0.1".into(),
+                port: smtp_port,
+                tls: SmtpTls::None,
+                username: None,
+                password: None,
+                from: "unpatched@example.org".into(),
+                to: vec!["ops@example.org".into()],
+            },
+        );
+        email.title_template = "{{ labels.alertname }} is {{ event.state }}".into();
+        email.body_template = "value {{ event.value }} on {{ host.alias }}".into();
+        assert!(email.validate().is_ok());
+        let delivery = deliver(&email, &event, fast(), &pool).await;
+        assert_eq!(delivery.status, DeliveryStatus::Delivered, "{delivery:?}");
+        {
+            let mails = mails.lock().unwrap();
+            assert!(mails[0].contains("Subject: disk full is firing"));
+            assert!(mails[0].contains("To: ops@example.org"));
+            assert!(mails[0].contains("value 1 on web-1"));
+        }
+
+        // retries until the stand-in answers, failures are logged
+        let flaky = channel(
+            "flaky",
+            ChannelConfig::Slack {
+                url: format!("{base}/flaky"),
+            },
+        );
+        let delivery = deliver(&flaky, &event, fast(), &pool).await;
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 2);
+        let failing = channel(
+            "failing",
+            ChannelConfig::Slack {
+                url: format!("{base}/fail"),
+            },
+        );
+        let _c = failing
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let delivery = deliver(&failing, &event, fast(), &pool).await;
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 3);
+        assert!(delivery.error.unwrap().starts_with("500"));
+        let filter = format!("channel_id='{}'", failing.id);
+        let logged = get_deliveries_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].status, DeliveryStatus::Failed);
+        assert_eq!(logged[0].alert_id, Some(alert.id));
+        let _d = delete_channels_from_db(None, pool.acquire().await.unwrap()).await;
+
+        // channels only get the events they accept
+        let mut critical_only = channel(
+            "critical",
+            ChannelConfig::Slack {
+                url: format!("{base}/critical"),
+            },
+        );
+        critical_only.min_severity = Severity::Critical;
+        let mut offline_only = channel(
+            "offline",
+            ChannelConfig::Slack {
+                url: format!("{base}/offline"),
+            },
+        );
+        offline_only.events = vec![EventKind::HostOffline];
+        let offline = NotificationEvent::host_offline(host.clone());
+        assert!(critical_only.accepts(&event));
+        assert!(!critical_only.accepts(&offline));
+        assert!(!offline_only.accepts(&event));
+        assert!(offline_only.accepts(&offline));
+        let _c1 = critical_only
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let _c2 = offline_only
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let deliveries = notify(offline, pool.clone()).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, EventKind::HostOffline);
+        assert!(captured.lock().unwrap().iter().any(|r| r.1 == "/offline"));
+
+        // alerts with the same fingerprint are grouped and deduplicated
+        let _d = delete_channels_from_db(None, pool.acquire().await.unwrap()).await;
+        let grouped = channel(
+            "grouped",
+            ChannelConfig::Slack {
+                url: format!("{base}/grouped"),
+            },
+        );
+        let _c = grouped.insert_into_db(pool.acquire().await.unwrap()).await;
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let script = Script {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let _s = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "disk full".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 1 },
+            severity: Severity::Warning,
+            labels: BTreeMap::new(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let labels = BTreeMap::from([
+            ("alertname".to_string(), "disk full".to_string()),
+            ("host".to_string(), "web-1".to_string()),
+        ]);
+        let firing = |severity: Severity| Alert {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            host_id: host.id,
+            sched_id: sched.id,
+            state: AlertState::Firing,
+            severity,
+            fingerprint: fingerprint(&labels),
+            labels: labels.clone(),
+            summary: "disk.free is 1 (< 5)".into(),
+            ..Default::default()
+        };
+        let (first, second) = (firing(Severity::Warning), firing(Severity::Critical));
+        let deliveries = notify_alerts(vec![first.clone(), second.clone()], pool.clone()).await;
+        assert_eq!(deliveries.len(), 1);
+        for alert in [&first, &second] {
+            let _a = alert
+                .clone()
+                .insert_into_db(pool.acquire().await.unwrap())
+                .await;
+        }
+        {
+            let requests = captured.lock().unwrap();
+            let request = requests.iter().find(|r| r.1 == "/grouped").unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&request.3).unwrap();
+            assert!(body["text"]
+                .as_str()
+                .unwrap()
+                .starts_with("*[critical] disk full on web-1*\ndisk.free is 1 (< 5) (+1 more)"));
+        }
+        // already firing
+        let third = firing(Severity::Warning);
+        assert!(notify_alerts(vec![third], pool.clone()).await.is_empty());
+        // resolved, but the second one still fires
+        let mut resolved = first.clone();
+        resolved.state = AlertState::Resolved;
+        let _a = resolved
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        assert!(notify_alerts(vec![resolved], pool.clone()).await.is_empty());
+        let mut resolved = second.clone();
+        resolved.state = AlertState::Resolved;
+        let _a = resolved
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        assert_eq!(notify_alerts(vec![resolved], pool.clone()).await.len(), 1);
+
+        // silenced alerts are not sent
+        let silence = Silence {
+            id: Uuid::new_v4(),
+            host_id: Some(host.id),
+            attributes: vec![],
+            sched_id: None,
+            labels: BTreeMap::from([("alertname".to_string(), "disk full".to_string())]),
+            starts: Utc::now() - chrono::Duration::minutes(1),
+            ends: Utc::now() + chrono::Duration::hours(1),
+            comment: "".into(),
+            created_by: "".into(),
+            created: Utc::now(),
+        };
+        let _s = silence
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert!(
+            notify_alerts(vec![firing(Severity::Critical)], pool.clone())
+                .await
+                .is_empty()
+        );
+        // other alertname
+        let deliveries = notify(NotificationEvent::host_offline(host.clone()), pool.clone()).await;
+        assert_eq!(deliveries.len(), 1);
+
+        // matching routes pick the channels, other events go to all channels
+        let routed = channel(
+            "routed",
+            ChannelConfig::Slack {
+                url: format!("{base}/routed"),
+            },
+        );
+        let _c = routed
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let mut route = Route {
+            id: Uuid::new_v4(),
+            name: "offline".into(),
+            parent_id: None,
+            position: 0,
+            matchers: BTreeMap::from([("alertname".to_string(), "host offline".to_string())]),
+            channels: vec![routed.id],
+            escalation_id: None,
+            continue_matching: false,
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = route
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let deliveries = notify(NotificationEvent::host_offline(host.clone()), pool.clone()).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].channel_id, routed.id);
+        route.matchers = BTreeMap::from([("severity".to_string(), "critical".to_string())]);
+        let _r = route
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let deliveries = notify(NotificationEvent::host_offline(host.clone()), pool.clone()).await;
+        assert_eq!(deliveries.len(), 2);
+
+        // validation and redaction
+        let mut invalid = webhook.clone();
+        invalid.title_template = "{{ event.unknown }}".into();
+        assert!(invalid.validate().is_err());
+        let redacted = webhook.clone().redacted();
+        assert_eq!(
+            redacted.config,
+            ChannelConfig::Webhook {
+                url: format!("{base}/hook"),
+                secret: REDACTED.into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let (base, captured) = http_stand_in().await;
+
+        let webhook = channel(
+            "webhook",
+            ChannelConfig::Webhook {
+                url: format!("{base}/hook"),
+                secret: "s3cret".into(),
+            },
+        );
+        let api_post = post_channels_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(webhook.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let mut invalid = webhook.clone();
+        invalid.config = ChannelConfig::Slack {
+            url: "not a url".into(),
+        };
+        let api_post_invalid = post_channels_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_post_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_all = get_channels_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get_all.into_body())
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("s3cret"));
+
+        let api_get_one = get_one_channel_api(
+            claims.clone(),
+            axum::extract::Path(webhook.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+
+        // posting the channel back as read keeps its secret
+        let body = hyper::body::to_bytes(api_get_one.into_body())
+            .await
+            .unwrap();
+        let read: Vec<NotificationChannel> = serde_json::from_slice(&body).unwrap();
+        let api_post_read = post_channels_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(NotificationChannel {
+                name: "renamed webhook".into(),
+                ..read[0].clone()
+            }),
+        )
+        .await;
+        assert_eq!(api_post_read.status(), StatusCode::CREATED);
+        let filter = format!("id='{}'", webhook.id);
+        let stored = get_channels_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].name, "renamed webhook");
+        assert_eq!(stored[0].config, webhook.config);
+
+        let api_test = test_channel_api(
+            claims.clone(),
+            axum::extract::Path(webhook.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_test.status(), StatusCode::OK);
+        assert_eq!(captured.lock().unwrap().len(), 1);
+
+        let mut failing = channel(
+            "failing",
+            ChannelConfig::Slack {
+                url: format!("{base}/fail"),
+            },
+        );
+        failing.id = Uuid::new_v4();
+        let _c = failing
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let api_test_failing = test_channel_api(
+            claims.clone(),
+            axum::extract::Path(failing.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_test_failing.status(), StatusCode::BAD_GATEWAY);
+
+        let api_test_unknown = test_channel_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_test_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_deliveries = get_deliveries_api(
+            claims.clone(),
+            axum::extract::Query(DeliveryQueryParams {
+                channel_id: Some(failing.id),
+                status: Some(DeliveryStatus::Failed),
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_deliveries.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_deliveries.into_body())
+            .await
+            .unwrap();
+        let deliveries: Vec<NotificationDelivery> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        // editing the channel keeps its delivery log
+        let _c = NotificationChannel {
+            active: false,
+            ..failing.clone()
+        }
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+        let filter = format!("channel_id='{}'", failing.id);
+        assert_eq!(
+            get_deliveries_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .len(),
+            1
+        );
+
+        let api_del = delete_one_channel_api(
+            claims.clone(),
+            axum::extract::Path(failing.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,897 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    jwt::Claims,
+    parser::ParseError,
+};
+
+/// Built-in script listing the installed packages of all package managers found on the host,
+/// the output of each package manager starts with a `# format: <format>` line
+pub const INVENTORY_SCRIPT: &str = r##"if command -v dpkg-query >/dev/null 2>&1; then
+  echo "# format: dpkg"
+  dpkg-query -W -f='${Package}\t${Version}\t${Architecture}\t${source:Package}\n'
+fi
+if command -v rpm >/dev/null 2>&1; then
+  echo "# format: rpm"
+  rpm -qa --qf '%{NAME}\t%{EPOCHNUM}:%{VERSION}-%{RELEASE}\t%{ARCH}\t%{SOURCERPM}\n'
+fi
+if command -v apk >/dev/null 2>&1; then
+  echo "# format: apk"
+  apk list --installed 2>/dev/null
+fi
+if command -v pacman >/dev/null 2>&1; then
+  echo "# format: pacman"
+  pacman -Q
+fi
+if command -v brew >/dev/null 2>&1; then
+  echo "# format: homebrew"
+  brew list --versions
+fi
+"##;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageFormat {
+    Dpkg,
+    Rpm,
+    Apk,
+    Pacman,
+    Homebrew,
+    Npm,
+    Pypi,
+    Maven,
+    Golang,
+    Cargo,
+    Gem,
+    Nuget,
+}
+
+impl Display for PackageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self {
+            PackageFormat::Dpkg => "dpkg",
+            PackageFormat::Rpm => "rpm",
+            PackageFormat::Apk => "apk",
+            PackageFormat::Pacman => "pacman",
+            PackageFormat::Homebrew => "homebrew",
+            PackageFormat::Npm => "npm",
+            PackageFormat::Pypi => "pypi",
+            PackageFormat::Maven => "maven",
+            PackageFormat::Golang => "golang",
+            PackageFormat::Cargo => "cargo",
+            PackageFormat::Gem => "gem",
+            PackageFormat::Nuget => "nuget",
+        };
+        write!(f, "{format}")
+    }
+}
+
+impl PackageFormat {
+    pub(crate) fn from_db(s: &str) -> Option<PackageFormat> {
+        match s {
+            "dpkg" => Some(PackageFormat::Dpkg),
+            "rpm" => Some(PackageFormat::Rpm),
+            "apk" => Some(PackageFormat::Apk),
+            "pacman" => Some(PackageFormat::Pacman),
+            "homebrew" => Some(PackageFormat::Homebrew),
+            "npm" => Some(PackageFormat::Npm),
+            "pypi" => Some(PackageFormat::Pypi),
+            "maven" => Some(PackageFormat::Maven),
+            "golang" => Some(PackageFormat::Golang),
+            "cargo" => Some(PackageFormat::Cargo),
+            "gem" => Some(PackageFormat::Gem),
+            "nuget" => Some(PackageFormat::Nuget),
+            _ => None,
+        }
+    }
+
+    /// name as compared between advisories and inventories, PyPI names are normalized like
+    /// pip does (PEP 503) and NuGet names are case-insensitive
+    pub fn normalize_name(&self, name: &str) -> String {
+        match self {
+            PackageFormat::Pypi => name
+                .to_lowercase()
+                .split(['-', '_', '.'])
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("-"),
+            PackageFormat::Nuget => name.to_lowercase(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// Installed package as reported by an agent, the inventory script or an SBOM
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Package {
+    pub format: PackageFormat,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub arch: String,
+    /// source package, empty if unknown
+    #[serde(default)]
+    pub source: String,
+}
+
+/// Package installed on a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct InstalledPackage {
+    pub host_id: Uuid,
+    /// SBOM component, empty for the host inventory
+    pub component: String,
+    pub format: PackageFormat,
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    pub source: String,
+    /// first inventory this version was part of
+    pub first_seen: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for InstalledPackage {
+    fn from(s: SqliteRow) -> Self {
+        InstalledPackage {
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            component: s.get::<String, _>("component"),
+            format: PackageFormat::from_db(&s.get::<String, _>("format"))
+                .unwrap_or(PackageFormat::Dpkg),
+            name: s.get::<String, _>("name"),
+            version: s.get::<String, _>("version"),
+            arch: s.get::<String, _>("arch"),
+            source: s.get::<String, _>("source"),
+            first_seen: utc_from_str(&s.get::<String, _>("first_seen")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Installed,
+    Updated,
+    Removed,
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            ChangeKind::Installed => "installed",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Removed => "removed",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl ChangeKind {
+    fn from_db(s: &str) -> ChangeKind {
+        match s {
+            "installed" => ChangeKind::Installed,
+            "updated" => ChangeKind::Updated,
+            _ => ChangeKind::Removed,
+        }
+    }
+}
+
+/// Package installed, updated or removed between two inventories of a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct PackageChange {
+    pub id: Uuid,
+    pub host_id: Uuid,
+    /// SBOM component, empty for the host inventory
+    pub component: String,
+    pub format: PackageFormat,
+    pub name: String,
+    pub arch: String,
+    pub change: ChangeKind,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub ts: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for PackageChange {
+    fn from(s: SqliteRow) -> Self {
+        PackageChange {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            component: s.get::<String, _>("component"),
+            format: PackageFormat::from_db(&s.get::<String, _>("format"))
+                .unwrap_or(PackageFormat::Dpkg),
+            name: s.get::<String, _>("name"),
+            arch: s.get::<String, _>("arch"),
+            change: ChangeKind::from_db(&s.get::<String, _>("change")),
+            old_version: s.get::<Option<String>, _>("old_version"),
+            new_version: s.get::<Option<String>, _>("new_version"),
+            ts: utc_from_str(&s.get::<String, _>("ts")),
+        }
+    }
+}
+
+/// parse the output of `INVENTORY_SCRIPT`
+///
+/// | Format | Line
+/// :--- | :---
+/// | dpkg | `name<TAB>version<TAB>arch<TAB>source` (dpkg-query)
+/// | rpm | `name<TAB>epoch:version-release<TAB>arch<TAB>source rpm`, epoch 0 is dropped
+/// | apk | `name-version-release arch {origin} (license) [installed]` (apk list)
+/// | pacman | `name version` (pacman -Q)
+/// | homebrew | `name version...` (brew list --versions), the last version is used
+pub fn parse_inventory(output: &str) -> Result<Vec<Package>, ParseError> {
+    let mut format = None;
+    let mut packages = vec![];
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let err = |e: &str| ParseError::Line(n + 1, e.to_string());
+        if let Some(name) = line.strip_prefix("# format:") {
+            format = Some(
+                PackageFormat::from_db(name.trim())
+                    .ok_or(err(&format!("unknown format {}", name.trim())))?,
+            );
+            continue;
+        }
+        let format = format.ok_or(err("missing '# format:' line"))?;
+        let package = match format {
+            PackageFormat::Dpkg | PackageFormat::Rpm => {
+                let mut columns = line.split('\t');
+                let name = columns.next().unwrap_or_default();
+                let version = columns.next().ok_or(err("missing version"))?;
+                let version = match format {
+                    PackageFormat::Rpm => version
+                        .strip_prefix("0:")
+                        .or(version.strip_prefix("(none):"))
+                        .unwrap_or(version),
+                    _ => version,
+                };
+                Package {
+                    format,
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    arch: columns.next().unwrap_or_default().to_string(),
+                    source: columns.next().unwrap_or_default().to_string(),
+                }
+            }
+            PackageFormat::Apk => {
+                let mut columns = line.split_whitespace();
+                let full = columns.next().unwrap_or_default();
+                let mut parts = full.rsplitn(3, '-');
+                let (Some(release), Some(version), Some(name)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(err("expected name-version-release"));
+                };
+                Package {
+                    format,
+                    name: name.to_string(),
+                    version: format!("{version}-{release}"),
+                    arch: columns.next().unwrap_or_default().to_string(),
+                    source: columns
+                        .next()
+                        .map(|s| s.trim_matches(['{', '}']).to_string())
+                        .unwrap_or_default(),
+                }
+            }
+            _ => {
+                let mut columns = line.split_whitespace();
+                let name = columns.next().unwrap_or_default();
+                let version = columns.last().ok_or(err("missing version"))?;
+                Package {
+                    format,
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    arch: "".into(),
+                    source: "".into(),
+                }
+            }
+        };
+        if package.name.is_empty() || package.version.is_empty() {
+            return Err(err("name and version must not be empty"));
+        }
+        packages.push(package);
+    }
+    Ok(packages)
+}
+
+/// replace the inventory of `host_id` for every format in `packages`, returns the changes
+///
+/// the first inventory of a format is the baseline and records no changes,
+/// a package with exactly one removed and one added version counts as updated
+pub async fn store_inventory(
+    host_id: Uuid,
+    packages: Vec<Package>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<Vec<PackageChange>, sqlx::Error> {
+    store_component_inventory(host_id, "", packages, now, pool).await
+}
+
+/// replace the inventory of `component` on `host_id`, returns the changes
+///
+/// the host inventory (empty `component`) is replaced for every format in `packages`,
+/// the inventory of an SBOM component as a whole
+pub async fn store_component_inventory(
+    host_id: Uuid,
+    component: &str,
+    packages: Vec<Package>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<Vec<PackageChange>, sqlx::Error> {
+    // name, arch and version per format, the same package may be installed in several versions
+    let mut reported: BTreeMap<PackageFormat, BTreeMap<(String, String, String), Package>> =
+        BTreeMap::new();
+    for package in packages {
+        if package.name.is_empty() || package.version.is_empty() {
+            warn!("Package without name or version of host {host_id} skipped");
+            continue;
+        }
+        let key = (
+            package.name.clone(),
+            package.arch.clone(),
+            package.version.clone(),
+        );
+        reported
+            .entry(package.format)
+            .or_default()
+            .insert(key, package);
+    }
+    let mut changes = vec![];
+    let mut tx = pool.begin().await?;
+    if !component.is_empty() {
+        let q = "SELECT DISTINCT format FROM packages WHERE host_id = ? AND component = ?";
+        let formats = query(q)
+            .bind(host_id.to_string())
+            .bind(component)
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in formats {
+            if let Some(format) = PackageFormat::from_db(&row.get::<String, _>("format")) {
+                reported.entry(format).or_default();
+            }
+        }
+    }
+    for (format, packages) in reported {
+        let filter = format!(
+            "host_id='{host_id}' AND component={} AND format='{format}'",
+            literal(component)
+        );
+        let q = format!("SELECT * FROM packages WHERE {filter}");
+        let installed: Vec<InstalledPackage> = query(&q)
+            .map(|row: SqliteRow| InstalledPackage::from(row))
+            .fetch_all(&mut *tx)
+            .await?;
+        let baseline = installed.is_empty();
+        // versions per name and arch
+        let mut versions: BTreeMap<(String, String), (BTreeSet<String>, BTreeSet<String>)> =
+            BTreeMap::new();
+        for p in &installed {
+            versions
+                .entry((p.name.clone(), p.arch.clone()))
+                .or_default()
+                .0
+                .insert(p.version.clone());
+        }
+        for p in packages.values() {
+            versions
+                .entry((p.name.clone(), p.arch.clone()))
+                .or_default()
+                .1
+                .insert(p.version.clone());
+        }
+        let change = |name: &str, arch: &str, kind, old: Option<&String>, new: Option<&String>| {
+            PackageChange {
+                id: Uuid::new_v4(),
+                host_id,
+                component: component.to_string(),
+                format,
+                name: name.to_string(),
+                arch: arch.to_string(),
+                change: kind,
+                old_version: old.cloned(),
+                new_version: new.cloned(),
+                ts: now,
+            }
+        };
+        let mut format_changes = vec![];
+        for ((name, arch), (old, new)) in &versions {
+            let removed: Vec<&String> = old.difference(new).collect();
+            let added: Vec<&String> = new.difference(old).collect();
+            if let ([old], [new]) = (removed.as_slice(), added.as_slice()) {
+                format_changes.push(change(
+                    name,
+                    arch,
+                    ChangeKind::Updated,
+                    Some(old),
+                    Some(new),
+                ));
+                continue;
+            }
+            for version in removed {
+                format_changes.push(change(name, arch, ChangeKind::Removed, Some(version), None));
+            }
+            for version in added {
+                format_changes.push(change(
+                    name,
+                    arch,
+                    ChangeKind::Installed,
+                    None,
+                    Some(version),
+                ));
+            }
+        }
+        for c in &format_changes {
+            if let Some(old) = &c.old_version {
+                query("DELETE FROM packages WHERE host_id = ? AND component = ? AND format = ? AND name = ? AND arch = ? AND version = ?")
+                    .bind(host_id.to_string())
+                    .bind(component)
+                    .bind(format.to_string())
+                    .bind(&c.name)
+                    .bind(&c.arch)
+                    .bind(old)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        for p in packages.values() {
+            let q = r#"INSERT INTO packages(host_id, component, format, name, arch, version, source, first_seen) VALUES(?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(host_id, component, format, name, arch, version) DO UPDATE SET source=excluded.source"#;
+            query(q)
+                .bind(host_id.to_string())
+                .bind(component)
+                .bind(format.to_string())
+                .bind(&p.name)
+                .bind(&p.arch)
+                .bind(&p.version)
+                .bind(&p.source)
+                .bind(utc_to_str(now))
+                .execute(&mut *tx)
+                .await?;
+        }
+        if baseline {
+            info!(
+                "Inventory baseline of host {host_id}: {} {format} packages",
+                packages.len()
+            );
+            continue;
+        }
+        for c in &format_changes {
+            let q = r#"INSERT INTO package_changes(id, host_id, component, format, name, arch, change, old_version, new_version, ts) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+            query(q)
+                .bind(c.id.to_string())
+                .bind(host_id.to_string())
+                .bind(component)
+                .bind(format.to_string())
+                .bind(&c.name)
+                .bind(&c.arch)
+                .bind(c.change.to_string())
+                .bind(&c.old_version)
+                .bind(&c.new_version)
+                .bind(utc_to_str(now))
+                .execute(&mut *tx)
+                .await?;
+        }
+        debug!(
+            "Inventory of host {host_id}: {} {format} package changes",
+            format_changes.len()
+        );
+        changes.extend(format_changes);
+    }
+    tx.commit().await?;
+    Ok(changes)
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PackageQueryParams {
+    /// `*` matches any characters
+    name: Option<String>,
+    version: Option<String>,
+    format: Option<PackageFormat>,
+    host_id: Option<Uuid>,
+    /// SBOM component, empty for the host inventory
+    component: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ChangeQueryParams {
+    host_id: Option<Uuid>,
+    name: Option<String>,
+    change: Option<ChangeKind>,
+    since: Option<DateTime<Utc>>,
+}
+
+/// quoted SQL string literal
+fn literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+impl PackageQueryParams {
+    fn to_filter(&self, base: Option<String>) -> String {
+        let mut conditions: Vec<String> = base.into_iter().collect();
+        if let Some(name) = &self.name {
+            if name.contains('*') {
+                let pattern = name
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+                    .replace('*', "%");
+                conditions.push(format!("name LIKE {} ESCAPE '\\'", literal(&pattern)));
+            } else {
+                conditions.push(format!("name = {}", literal(name)));
+            }
+        }
+        if let Some(version) = &self.version {
+            conditions.push(format!("version = {}", literal(version)));
+        }
+        if let Some(format) = &self.format {
+            conditions.push(format!("format = '{format}'"));
+        }
+        if let Some(host_id) = &self.host_id {
+            conditions.push(format!("host_id = '{host_id}'"));
+        }
+        if let Some(component) = &self.component {
+            conditions.push(format!("component = {}", literal(component)));
+        }
+        conditions.push("1=1 ORDER BY name, host_id, version".into());
+        conditions.join(" AND ")
+    }
+}
+
+/// API to search the packages of all hosts, e.g. `?name=openssl&version=3.0.2`
+pub async fn get_packages_api(
+    _claims: Claims,
+    Query(params): Query<PackageQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    if params.name.is_none() && params.host_id.is_none() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "name or host_id is required",
+        )
+            .into_response();
+    }
+    let filter = params.to_filter(None);
+    let package_vec = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(package_vec).into_response()
+}
+
+/// API to get the package inventory of a host
+pub async fn get_host_packages_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PackageQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = params.to_filter(Some(format!("host_id = '{id}'")));
+    let package_vec = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(package_vec)
+}
+
+/// API to get the package change history, newest first
+pub async fn get_package_changes_api(
+    _claims: Claims,
+    Query(params): Query<ChangeQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec![];
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    if let Some(name) = &params.name {
+        conditions.push(format!("name = {}", literal(name)));
+    }
+    if let Some(change) = &params.change {
+        conditions.push(format!("change = '{change}'"));
+    }
+    if let Some(since) = params.since {
+        conditions.push(format!("ts >= '{}'", utc_to_str(since)));
+    }
+    conditions.push("1=1 ORDER BY ts DESC, name".into());
+    let filter = conditions.join(" AND ");
+    let change_vec =
+        get_package_changes_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(change_vec)
+}
+
+pub async fn get_packages_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<InstalledPackage> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM packages WHERE {f}"),
+        None => "SELECT * FROM packages".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| InstalledPackage::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn get_package_changes_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<PackageChange> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM package_changes WHERE {f}"),
+        None => "SELECT * FROM package_changes".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| PackageChange::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        host::Host,
+    };
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn package(name: &str, version: &str) -> Package {
+        Package {
+            format: PackageFormat::Dpkg,
+            name: name.into(),
+            version: version.into(),
+            arch: "amd64".into(),
+            source: "".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_packages() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let output = "# format: dpkg\n\
+            libssl3\t3.0.2-0ubuntu1.15\tamd64\topenssl\n\
+            bash\t5.1-6ubuntu1\tamd64\t\n\
+            # format: rpm\n\
+            openssl-libs\t1:3.0.7-27.el9\tx86_64\topenssl-3.0.7-27.el9.src.rpm\n\
+            kernel\t0:5.14.0-427.el9\tx86_64\tkernel-5.14.0-427.el9.src.rpm\n\
+            # format: apk\n\
+            musl-1.2.4-r2 x86_64 {musl} (MIT) [installed]\n\
+            # format: pacman\n\
+            openssl 3.3.1-1\n\
+            # format: homebrew\n\
+            openssl@3 3.3.0 3.3.1\n";
+        let packages = parse_inventory(output).unwrap();
+        assert_eq!(packages.len(), 7);
+        assert_eq!(
+            packages[0],
+            Package {
+                format: PackageFormat::Dpkg,
+                name: "libssl3".into(),
+                version: "3.0.2-0ubuntu1.15".into(),
+                arch: "amd64".into(),
+                source: "openssl".into(),
+            }
+        );
+        assert_eq!(packages[1].source, "");
+        assert_eq!(packages[2].version, "1:3.0.7-27.el9");
+        assert_eq!(packages[3].version, "5.14.0-427.el9");
+        assert_eq!(
+            (
+                packages[4].name.as_str(),
+                packages[4].version.as_str(),
+                packages[4].arch.as_str(),
+                packages[4].source.as_str()
+            ),
+            ("musl", "1.2.4-r2", "x86_64", "musl")
+        );
+        assert_eq!(packages[5].version, "3.3.1-1");
+        assert_eq!(packages[6].version, "3.3.1");
+        assert_eq!(
+            parse_inventory("bash 5.1").unwrap_err(),
+            ParseError::Line(1, "missing '# format:' line".into())
+        );
+        assert!(parse_inventory("# format: msi\n").is_err());
+        assert!(parse_inventory("# format: apk\nmusl x86_64").is_err());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+
+        // first inventory is the baseline
+        let now = Utc::now();
+        let baseline = vec![
+            package("openssl", "3.0.2-0ubuntu1.14"),
+            package("bash", "5.1-6ubuntu1"),
+            package("linux-image", "5.15.0-100"),
+            package("curl", "7.81.0-1"),
+        ];
+        let changes = store_inventory(host_id, baseline, now, &pool)
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+        let installed = get_packages_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(installed.len(), 4);
+
+        // updated, removed, installed and a second kernel next to the first
+        let later = now + Duration::hours(1);
+        let inventory = vec![
+            package("openssl", "3.0.2-0ubuntu1.15"),
+            package("bash", "5.1-6ubuntu1"),
+            package("linux-image", "5.15.0-100"),
+            package("linux-image", "5.15.0-101"),
+            package("vim", "2:8.2.3995-1"),
+        ];
+        let mut changes = store_inventory(host_id, inventory.clone(), later, &pool)
+            .await
+            .unwrap();
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+        let summary: Vec<(&str, ChangeKind)> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.change.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("curl", ChangeKind::Removed),
+                ("linux-image", ChangeKind::Installed),
+                ("openssl", ChangeKind::Updated),
+                ("vim", ChangeKind::Installed),
+            ]
+        );
+        assert_eq!(changes[2].old_version.as_deref(), Some("3.0.2-0ubuntu1.14"));
+        assert_eq!(changes[2].new_version.as_deref(), Some("3.0.2-0ubuntu1.15"));
+        let logged = get_package_changes_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(logged.len(), 4);
+        // unchanged packages keep their first_seen
+        let filter = "name = 'bash'";
+        let bash = get_packages_from_db(Some(filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(bash[0].first_seen.timestamp(), now.timestamp());
+        // same inventory again, no changes
+        let changes = store_inventory(host_id, inventory, later, &pool)
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+        // other formats are not touched
+        let brew = Package {
+            format: PackageFormat::Homebrew,
+            ..package("openssl@3", "3.3.1")
+        };
+        let _c = store_inventory(host_id, vec![brew], later, &pool)
+            .await
+            .unwrap();
+        let installed = get_packages_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(installed.len(), 6);
+
+        // search
+        let search = |name: &str, version: Option<&str>| PackageQueryParams {
+            name: Some(name.into()),
+            version: version.map(String::from),
+            ..Default::default()
+        };
+        let filter = search("openssl", Some("3.0.2-0ubuntu1.15")).to_filter(None);
+        let found = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].host_id, host_id);
+        let filter = search("openssl*", None).to_filter(None);
+        let found = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(found.len(), 2);
+        let filter = search("o'penssl", None).to_filter(None);
+        let found = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert!(found.is_empty());
+
+        // deleting the host deletes its inventory
+        let filter = format!("id='{host_id}'");
+        let _d =
+            crate::host::delete_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert!(get_packages_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+        assert!(
+            get_package_changes_from_db(None, pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let _c = store_inventory(
+            host_id,
+            vec![package("openssl", "3.0.2")],
+            Utc::now(),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let api_search = get_packages_api(
+            claims.clone(),
+            axum::extract::Query(PackageQueryParams {
+                name: Some("openssl".into()),
+                version: Some("3.0.2".into()),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_search.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_search.into_body()).await.unwrap();
+        let found: Vec<InstalledPackage> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(found.len(), 1);
+
+        let api_search_all = get_packages_api(
+            claims.clone(),
+            axum::extract::Query(PackageQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_search_all.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_host = get_host_packages_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            axum::extract::Query(PackageQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_host.status(), StatusCode::OK);
+
+        let api_changes = get_package_changes_api(
+            claims.clone(),
+            axum::extract::Query(ChangeQueryParams {
+                host_id: Some(host_id),
+                change: Some(ChangeKind::Updated),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_changes.status(), StatusCode::OK);
+    }
+}
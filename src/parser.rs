@@ -0,0 +1,398 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::{
+    lifecycle::parse_os_release, package::parse_inventory, reboot::parse_reboot_status,
+    update::parse_updates,
+};
+
+/// Built-in parser turning the output of a script into typed fields
+///
+/// | Parser | Output | Field names
+/// :--- | :--- | :---
+/// | json | json document | dotted path, e.g. `disks.0.free`
+/// | key_value | `key=value` lines, `#` comments | key
+/// | prometheus | prometheus exposition text | metric name with sorted labels, e.g. `up{job="node"}`
+/// | csv | comma-seperated table, first line is the header | `<row>.<column>`, e.g. `0.mount`
+/// | table | whitespace-seperated table, first line is the header | `<row>.<column>`
+/// | packages | package inventory, see `package::parse_inventory` | `packages` (count), stored as the host inventory
+/// | updates | pending updates, see `update::parse_updates` | `pending_updates`, `security_updates` (counts), stored as the pending updates of the host
+/// | os_release | `/etc/os-release` or `sw_vers` | `os`, `os_version`, `os_codename`, `os_name`, merged into the host facts
+/// | reboot_status | `key=value` lines, see `reboot::REBOOT_SCRIPT` | `running_kernel`, `installed_kernel`, `kernel_pending`, `reboot_required`, stored as the reboot state of the host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputParser {
+    #[default]
+    None,
+    Json,
+    KeyValue,
+    Prometheus,
+    Csv,
+    Table,
+    Packages,
+    Updates,
+    OsRelease,
+    RebootStatus,
+}
+
+pub type Fields = BTreeMap<String, Value>;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Json(String),
+    Line(usize, String),
+    EmptyTable,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Json(e) => write!(f, "invalid json: {e}"),
+            ParseError::Line(n, e) => write!(f, "line {n}: {e}"),
+            ParseError::EmptyTable => write!(f, "table has no header line"),
+        }
+    }
+}
+
+impl OutputParser {
+    /// parse `output` into fields, `None` as parser yields no fields
+    pub fn parse(&self, output: &str) -> Result<Fields, ParseError> {
+        match self {
+            OutputParser::None => Ok(Fields::new()),
+            OutputParser::Json => parse_json(output),
+            OutputParser::KeyValue => parse_key_value(output),
+            OutputParser::Prometheus => parse_prometheus(output),
+            OutputParser::Csv => parse_table(output, |l| l.split(',').map(str::trim).collect()),
+            OutputParser::Table => parse_table(output, |l| l.split_whitespace().collect()),
+            OutputParser::Packages => {
+                let packages = parse_inventory(output)?;
+                Ok(Fields::from([(
+                    "packages".to_string(),
+                    Value::from(packages.len()),
+                )]))
+            }
+            OutputParser::Updates => {
+                let updates = parse_updates(output)?;
+                let security = updates.iter().filter(|u| u.security).count();
+                Ok(Fields::from([
+                    ("pending_updates".to_string(), Value::from(updates.len())),
+                    ("security_updates".to_string(), Value::from(security)),
+                ]))
+            }
+            OutputParser::OsRelease => Ok(parse_os_release(output)?
+                .into_iter()
+                .map(|(fact, value)| (fact, Value::String(value)))
+                .collect()),
+            OutputParser::RebootStatus => {
+                let status = parse_reboot_status(output)?;
+                let mut fields = Fields::from([
+                    (
+                        "running_kernel".to_string(),
+                        Value::from(status.running_kernel.clone()),
+                    ),
+                    (
+                        "kernel_pending".to_string(),
+                        Value::from(status.kernel_pending()),
+                    ),
+                    (
+                        "reboot_required".to_string(),
+                        Value::from(status.reboot_required()),
+                    ),
+                ]);
+                if !status.installed_kernel.is_empty() {
+                    fields.insert(
+                        "installed_kernel".to_string(),
+                        Value::from(status.installed_kernel),
+                    );
+                }
+                Ok(fields)
+            }
+        }
+    }
+}
+
+/// numbers and booleans become typed values, everything else stays a string
+///
+/// a number only counts if it reads the same as a string again, so versions like `22.10`
+/// or `007` keep their form; exponent notation like `1.5e9` is always a number
+pub fn typed(raw: &str) -> Value {
+    if let Ok(i) = raw.parse::<i64>() {
+        if i.to_string() == raw {
+            return Value::from(i);
+        }
+    }
+    if let Some(n) = raw.parse::<f64>().ok().and_then(Number::from_f64) {
+        if n.to_string() == raw || raw.contains(['e', 'E']) {
+            return Value::Number(n);
+        }
+    }
+    match raw {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+fn flatten(prefix: &str, value: Value, fields: &mut Fields) {
+    let key = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{prefix}.{k}")
+        }
+    };
+    match value {
+        Value::Object(map) => map
+            .into_iter()
+            .for_each(|(k, v)| flatten(&key(&k), v, fields)),
+        Value::Array(vec) => vec
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, v)| flatten(&key(&i.to_string()), v, fields)),
+        v if prefix.is_empty() => {
+            fields.insert("value".into(), v);
+        }
+        v => {
+            fields.insert(prefix.to_string(), v);
+        }
+    }
+}
+
+fn parse_json(output: &str) -> Result<Fields, ParseError> {
+    let value: Value = serde_json::from_str(output).map_err(|e| ParseError::Json(e.to_string()))?;
+    let mut fields = Fields::new();
+    flatten("", value, &mut fields);
+    Ok(fields)
+}
+
+fn parse_key_value(output: &str) -> Result<Fields, ParseError> {
+    let mut fields = Fields::new();
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(ParseError::Line(n + 1, "missing '='".into()))?;
+        let value = value.trim().trim_matches('"');
+        fields.insert(key.trim().to_string(), typed(value));
+    }
+    Ok(fields)
+}
+
+fn parse_prometheus(output: &str) -> Result<Fields, ParseError> {
+    let mut fields = Fields::new();
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |e: &str| ParseError::Line(n + 1, e.to_string());
+        let (name, rest) = match line.find('{') {
+            Some(open) => {
+                let close = line.rfind('}').ok_or(err("missing '}'"))?;
+                let mut labels =
+                    parse_labels(&line[open + 1..close]).ok_or(err("invalid labels"))?;
+                labels.sort();
+                let labels: Vec<String> = labels
+                    .into_iter()
+                    .map(|(k, v)| format!("{k}=\"{v}\""))
+                    .collect();
+                (
+                    format!("{}{{{}}}", line[..open].trim(), labels.join(",")),
+                    &line[close + 1..],
+                )
+            }
+            None => {
+                let (name, rest) = line
+                    .split_once(char::is_whitespace)
+                    .ok_or(err("missing value"))?;
+                (name.to_string(), rest)
+            }
+        };
+        // an optional timestamp may follow the value
+        let raw = rest.split_whitespace().next().ok_or(err("missing value"))?;
+        let value = match raw {
+            "NaN" | "+Inf" | "-Inf" => Value::String(raw.to_string()),
+            _ => raw
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or(err("value is not a number"))?,
+        };
+        fields.insert(name, value);
+    }
+    Ok(fields)
+}
+
+fn parse_labels(raw: &str) -> Option<Vec<(String, String)>> {
+    let mut labels = Vec::new();
+    let mut rest = raw.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start().strip_prefix('"')?;
+        // find the closing quote, skipping escaped ones
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in after.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = Some(i);
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        let end = end?;
+        labels.push((key.trim().to_string(), after[..end].to_string()));
+        rest = after[end + 1..]
+            .trim_start()
+            .trim_start_matches(',')
+            .trim_start();
+    }
+    Some(labels)
+}
+
+fn parse_table(output: &str, split: impl Fn(&str) -> Vec<&str>) -> Result<Fields, ParseError> {
+    let mut lines = output
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines.next().ok_or(ParseError::EmptyTable)?;
+    let header = split(header);
+    let mut fields = Fields::new();
+    for (row, (n, line)) in lines.enumerate() {
+        let columns = split(line);
+        if columns.len() != header.len() {
+            return Err(ParseError::Line(
+                n + 1,
+                format!("expected {} columns, found {}", header.len(), columns.len()),
+            ));
+        }
+        for (name, value) in header.iter().zip(columns) {
+            fields.insert(format!("{row}.{name}"), typed(value));
+        }
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parsers() {
+        assert!(OutputParser::None.parse("anything").unwrap().is_empty());
+
+        let fields = OutputParser::Json
+            .parse(r#"{"host": "web-1", "disks": [{"free": 12.5}, {"free": 3}], "ok": true}"#)
+            .unwrap();
+        assert_eq!(fields["host"], json!("web-1"));
+        assert_eq!(fields["disks.0.free"], json!(12.5));
+        assert_eq!(fields["disks.1.free"], json!(3));
+        assert_eq!(fields["ok"], json!(true));
+        assert_eq!(OutputParser::Json.parse("42").unwrap()["value"], json!(42));
+        assert!(matches!(
+            OutputParser::Json.parse("{broken"),
+            Err(ParseError::Json(_))
+        ));
+
+        let fields = OutputParser::KeyValue
+            .parse(
+                "# comment\nNAME=\"Debian GNU/Linux\"\nVERSION_ID=12\nload = 0.5\n\nsecure=false",
+            )
+            .unwrap();
+        assert_eq!(fields["NAME"], json!("Debian GNU/Linux"));
+        assert_eq!(fields["VERSION_ID"], json!(12));
+        assert_eq!(fields["load"], json!(0.5));
+        assert_eq!(fields["secure"], json!(false));
+        // versions keep their form instead of becoming a different number
+        let fields = OutputParser::KeyValue
+            .parse(
+                "VERSION_ID=22.10
+build=007
+limit=-3
+size=1.5e3
+version=1.10",
+            )
+            .unwrap();
+        assert_eq!(fields["VERSION_ID"], json!("22.10"));
+        assert_eq!(fields["build"], json!("007"));
+        assert_eq!(fields["limit"], json!(-3));
+        assert_eq!(fields["size"], json!(1500.0));
+        assert_eq!(fields["version"], json!("1.10"));
+        assert_eq!(
+            OutputParser::KeyValue.parse("a=1\nbroken").unwrap_err(),
+            ParseError::Line(2, "missing '='".into())
+        );
+
+        let fields = OutputParser::Prometheus
+            .parse(
+                "# HELP up target up\n# TYPE up gauge\nup 1\nnode_load1 0.52 1700000000000\nfs_free{mountpoint=\"/\",device=\"sda1\"} 1.5e9\nbad_value NaN",
+            )
+            .unwrap();
+        assert_eq!(fields["up"], json!(1.0));
+        assert_eq!(fields["node_load1"], json!(0.52));
+        assert_eq!(
+            fields["fs_free{device=\"sda1\",mountpoint=\"/\"}"],
+            json!(1.5e9)
+        );
+        assert_eq!(fields["bad_value"], json!("NaN"));
+        assert!(OutputParser::Prometheus.parse("up one").is_err());
+        assert!(OutputParser::Prometheus.parse("up{job=\"x} 1").is_err());
+
+        let fields = OutputParser::Csv
+            .parse("mount,used,ro\n/,42,false\n/var, 7.5 ,true\n")
+            .unwrap();
+        assert_eq!(fields.len(), 6);
+        assert_eq!(fields["0.mount"], json!("/"));
+        assert_eq!(fields["1.used"], json!(7.5));
+        assert_eq!(fields["1.ro"], json!(true));
+
+        let fields = OutputParser::Table
+            .parse("Filesystem  Size  Use%\n/dev/sda1   20G   43%\n")
+            .unwrap();
+        assert_eq!(fields["0.Filesystem"], json!("/dev/sda1"));
+        assert_eq!(fields["0.Use%"], json!("43%"));
+        assert_eq!(
+            OutputParser::Table.parse("a b\n1 2 3").unwrap_err(),
+            ParseError::Line(2, "expected 2 columns, found 3".into())
+        );
+        assert_eq!(
+            OutputParser::Table.parse("\n").unwrap_err(),
+            ParseError::EmptyTable
+        );
+
+        let fields = OutputParser::Packages
+            .parse("# format: pacman\nbash 5.2.026-2\nopenssl 3.3.1-1\n")
+            .unwrap();
+        assert_eq!(fields["packages"], json!(2));
+        assert!(OutputParser::Packages.parse("bash 5.2").is_err());
+
+        let fields = OutputParser::Updates
+            .parse("# format: dpkg\nListing...\nopenssl/jammy-security 3.0.2-0ubuntu1.15 amd64 [upgradable from: 3.0.2-0ubuntu1.14]\nvim/jammy-updates 2:8.2.3995-1ubuntu2.16 amd64 [upgradable from: 2:8.2.3995-1ubuntu2.15]\n")
+            .unwrap();
+        assert_eq!(fields["pending_updates"], json!(2));
+        assert_eq!(fields["security_updates"], json!(1));
+
+        // versions stay strings, 22.04 is not a number
+        let fields = OutputParser::OsRelease
+            .parse("ID=ubuntu\nVERSION_ID=\"22.04\"\nVERSION_CODENAME=jammy\n")
+            .unwrap();
+        assert_eq!(fields["os_version"], json!("22.04"));
+        assert_eq!(fields.len(), 3);
+
+        let fields = OutputParser::RebootStatus
+            .parse("running_kernel=6.1.0-18-amd64\ninstalled_kernel=6.1.0-21-amd64\n")
+            .unwrap();
+        assert_eq!(fields["kernel_pending"], json!(true));
+        assert_eq!(fields["reboot_required"], json!(true));
+        assert_eq!(fields.len(), 4);
+    }
+}
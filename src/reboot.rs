@@ -0,0 +1,930 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Display};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{try_utc_from_str, utc_from_str, utc_to_str},
+    execution::{evaluate_output, get_executed_script, get_executions_from_db, Execution, Verdict},
+    host::merge_facts,
+    jwt::Claims,
+    package::PackageFormat,
+    parser::ParseError,
+    revision::get_revision,
+    script::get_scripts_from_db,
+    version::compare,
+};
+
+/// Built-in script reporting the running and the newest installed kernel and whether the
+/// package manager asks for a reboot (`/var/run/reboot-required`, `needs-restarting -r`)
+pub const REBOOT_SCRIPT: &str = r##"echo "running_kernel=$(uname -r)"
+if command -v rpm >/dev/null 2>&1 && rpm -q kernel >/dev/null 2>&1; then
+  echo "installed_kernel=$(rpm -q --qf '%{VERSION}-%{RELEASE}.%{ARCH}\n' kernel | sort -V | tail -n 1)"
+elif [ -d /lib/modules ]; then
+  echo "installed_kernel=$(ls -1 /lib/modules | sort -V | tail -n 1)"
+fi
+if [ -f /var/run/reboot-required ]; then
+  echo "reboot_required=true"
+  echo "reboot_indicator=/var/run/reboot-required"
+  if [ -f /var/run/reboot-required.pkgs ]; then
+    echo "reboot_packages=$(sort -u /var/run/reboot-required.pkgs | tr '\n' ' ')"
+  fi
+elif command -v needs-restarting >/dev/null 2>&1; then
+  echo "reboot_indicator=needs-restarting -r"
+  if needs-restarting -r >/dev/null 2>&1; then
+    echo "reboot_required=false"
+  else
+    echo "reboot_required=true"
+  fi
+fi
+"##;
+
+/// Kernel and reboot indicators as reported by the reboot script
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct RebootStatus {
+    /// `uname -r`
+    pub running_kernel: String,
+    /// newest installed kernel, empty if unknown
+    #[serde(default)]
+    pub installed_kernel: String,
+    /// the package manager asks for a reboot
+    #[serde(default)]
+    pub flagged: bool,
+    /// where the reboot request comes from, e.g. `/var/run/reboot-required`
+    #[serde(default)]
+    pub indicator: String,
+    /// packages asking for the reboot, if the indicator names them
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+impl RebootStatus {
+    /// a newer kernel is installed than the one running, kernel releases sort like rpm versions
+    pub fn kernel_pending(&self) -> bool {
+        !self.installed_kernel.is_empty()
+            && compare(
+                PackageFormat::Rpm,
+                &self.installed_kernel,
+                &self.running_kernel,
+            ) == Ordering::Greater
+    }
+
+    pub fn reboot_required(&self) -> bool {
+        self.flagged || self.kernel_pending()
+    }
+
+    /// why the host has to reboot, empty if it does not
+    pub fn reasons(&self) -> Vec<String> {
+        let mut reasons = vec![];
+        if self.kernel_pending() {
+            reasons.push(format!(
+                "kernel {} installed, {} running",
+                self.installed_kernel, self.running_kernel
+            ));
+        }
+        if self.flagged {
+            let indicator = match self.indicator.is_empty() {
+                true => "reboot requested",
+                false => &self.indicator,
+            };
+            reasons.push(match self.packages.is_empty() {
+                true => indicator.to_string(),
+                false => format!("{indicator} by {}", self.packages.join(", ")),
+            });
+        }
+        reasons
+    }
+}
+
+/// parse the output of [REBOOT_SCRIPT], `key=value` lines with the keys `running_kernel`,
+/// `installed_kernel`, `reboot_required`, `reboot_indicator` and `reboot_packages`
+pub fn parse_reboot_status(output: &str) -> Result<RebootStatus, ParseError> {
+    let mut status = RebootStatus::default();
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |e: &str| ParseError::Line(n + 1, e.to_string());
+        let (key, value) = line.split_once('=').ok_or(err("missing '='"))?;
+        let value = value.trim();
+        match key.trim() {
+            "running_kernel" => status.running_kernel = value.to_string(),
+            "installed_kernel" => status.installed_kernel = value.to_string(),
+            "reboot_required" => {
+                status.flagged = value
+                    .parse()
+                    .map_err(|_| err("reboot_required must be true or false"))?
+            }
+            "reboot_indicator" => status.indicator = value.to_string(),
+            "reboot_packages" => {
+                status.packages = value.split_whitespace().map(String::from).collect()
+            }
+            _ => continue,
+        }
+    }
+    match status.running_kernel.is_empty() {
+        true => Err(ParseError::Line(1, "no running_kernel found".into())),
+        false => Ok(status),
+    }
+}
+
+/// Kernel and reboot state of a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostReboot {
+    pub host_id: Uuid,
+    #[serde(default)]
+    pub alias: String,
+    pub running_kernel: String,
+    pub installed_kernel: String,
+    pub reboot_required: bool,
+    pub reasons: Vec<String>,
+    /// first report asking for the pending reboot
+    pub required_since: Option<DateTime<Utc>>,
+    /// latest report
+    pub checked: DateTime<Utc>,
+}
+
+impl HostReboot {
+    /// human readable state, e.g. `reboot required since 2026-03-01 00:00:00 UTC: ...`
+    pub fn summary(&self) -> String {
+        match self.required_since {
+            Some(since) if self.reboot_required => {
+                format!("reboot required since {since}: {}", self.reasons.join("; "))
+            }
+            _ => format!("running kernel {}, no reboot required", self.running_kernel),
+        }
+    }
+}
+
+impl From<SqliteRow> for HostReboot {
+    fn from(s: SqliteRow) -> Self {
+        HostReboot {
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            alias: s.get::<String, _>("alias"),
+            running_kernel: s.get::<String, _>("running_kernel"),
+            installed_kernel: s.get::<String, _>("installed_kernel"),
+            reboot_required: s.get::<bool, _>("reboot_required"),
+            reasons: serde_json::from_str(&s.get::<String, _>("reasons")).unwrap_or_default(),
+            required_since: s
+                .get::<Option<String>, _>("required_since")
+                .and_then(|t| try_utc_from_str(&t).ok()),
+            checked: utc_from_str(&s.get::<String, _>("checked")),
+        }
+    }
+}
+
+/// store the reboot state of `host_id` and merge the facts `kernel`, `kernel_installed` and
+/// `reboot_required`, a pending reboot keeps its `required_since`
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | host_id | TEXT | uuid
+/// | running_kernel | TEXT | `uname -r`
+/// | installed_kernel | TEXT | newest installed kernel, empty if unknown
+/// | reboot_required | NUMERIC | bool
+/// | reasons | TEXT | json list
+/// | required_since | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ"), NULL if no reboot is required
+/// | checked | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+pub async fn store_reboot_status(
+    host_id: Uuid,
+    status: &RebootStatus,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let required = status.reboot_required();
+    let q = r#"INSERT INTO host_reboots(host_id, running_kernel, installed_kernel, reboot_required, reasons, required_since, checked) VALUES(?, ?, ?, ?, ?, ?, ?)
+    ON CONFLICT(host_id) DO UPDATE SET running_kernel=excluded.running_kernel, installed_kernel=excluded.installed_kernel, reboot_required=excluded.reboot_required, reasons=excluded.reasons,
+    required_since=CASE WHEN excluded.reboot_required = 1 THEN COALESCE(required_since, excluded.required_since) END, checked=excluded.checked"#;
+    query(q)
+        .bind(host_id.to_string())
+        .bind(&status.running_kernel)
+        .bind(&status.installed_kernel)
+        .bind(required)
+        .bind(serde_json::to_string(&status.reasons()).unwrap())
+        .bind(required.then(|| utc_to_str(now)))
+        .bind(utc_to_str(now))
+        .execute(&mut *pool.acquire().await?)
+        .await?;
+    debug!("Reboot state of host {host_id}: reboot required {required}");
+    let mut facts = HashMap::from([
+        ("kernel".to_string(), status.running_kernel.clone()),
+        ("reboot_required".to_string(), required.to_string()),
+    ]);
+    if !status.installed_kernel.is_empty() {
+        facts.insert(
+            "kernel_installed".to_string(),
+            status.installed_kernel.clone(),
+        );
+    }
+    let _res = merge_facts(host_id, facts, pool).await;
+    Ok(())
+}
+
+/// Default seconds a host has to come back after a reboot step
+pub const REBOOT_DEADLINE_SECS: u64 = 900;
+
+/// Reboot of the host after a successful run of a script, e.g. one installing a kernel update
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RebootStep {
+    /// command the agent runs to reboot the host
+    #[serde(default = "default_reboot_command")]
+    pub command: String,
+    /// seconds after the run the host has to reconnect and pass the post-check
+    #[serde(default = "default_reboot_deadline")]
+    pub deadline_secs: u64,
+    /// script run once the host is back, the execution only succeeds if it does
+    #[serde(default)]
+    pub post_check: Option<Uuid>,
+    /// pinned revision of the post-check script, `None` runs the latest revision
+    #[serde(default)]
+    pub post_check_revision: Option<i64>,
+}
+
+fn default_reboot_command() -> String {
+    "shutdown -r now".into()
+}
+
+fn default_reboot_deadline() -> u64 {
+    REBOOT_DEADLINE_SECS
+}
+
+impl RebootStep {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.command.trim().is_empty() {
+            return Err("command must not be empty".into());
+        }
+        if self.deadline_secs == 0 {
+            return Err("deadline_secs must be positive".into());
+        }
+        Ok(())
+    }
+}
+
+/// Progress of the reboot step of an execution
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RebootState {
+    /// the script succeeded, the reboot command is sent with the next update
+    Pending,
+    /// reboot command sent, waiting for the agent of the host to connect again
+    AwaitingReconnect,
+    /// the host is back, the post-check script runs
+    PostCheck,
+    Succeeded,
+    /// the post-check failed
+    Failed,
+    /// the host did not come back or pass the post-check before the deadline
+    TimedOut,
+}
+
+impl Display for RebootState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            RebootState::Pending => "pending",
+            RebootState::AwaitingReconnect => "awaiting_reconnect",
+            RebootState::PostCheck => "post_check",
+            RebootState::Succeeded => "succeeded",
+            RebootState::Failed => "failed",
+            RebootState::TimedOut => "timed_out",
+        };
+        write!(f, "{state}")
+    }
+}
+
+impl RebootState {
+    pub fn from_db(s: &str) -> Option<RebootState> {
+        match s {
+            "pending" => Some(RebootState::Pending),
+            "awaiting_reconnect" => Some(RebootState::AwaitingReconnect),
+            "post_check" => Some(RebootState::PostCheck),
+            "succeeded" => Some(RebootState::Succeeded),
+            "failed" => Some(RebootState::Failed),
+            "timed_out" => Some(RebootState::TimedOut),
+            _ => None,
+        }
+    }
+}
+
+/// executions of `host_id` in `state`
+pub async fn reboot_executions(
+    host_id: Uuid,
+    state: RebootState,
+    pool: &SqlitePool,
+) -> Vec<Execution> {
+    let filter = format!("host_id='{host_id}' AND reboot_state='{state}'");
+    get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// move the reboot step of execution `id` from `from` to `to`, false if it is in another state
+pub async fn transition(id: Uuid, from: RebootState, to: RebootState, pool: &SqlitePool) -> bool {
+    let res = query("UPDATE executions SET reboot_state = ? WHERE id = ? AND reboot_state = ?")
+        .bind(to.to_string())
+        .bind(id.to_string())
+        .bind(from.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    res.is_ok_and(|r| r.rows_affected() == 1)
+}
+
+/// the agent of `host_id` connected again, reboots awaiting it continue with the post-check
+pub async fn reconnected(host_id: Uuid, now: DateTime<Utc>, pool: &SqlitePool) -> usize {
+    let q = "UPDATE executions SET reboot_state = ? WHERE host_id = ? AND reboot_state = ? AND reboot_deadline >= ?";
+    let res = query(q)
+        .bind(RebootState::PostCheck.to_string())
+        .bind(host_id.to_string())
+        .bind(RebootState::AwaitingReconnect.to_string())
+        .bind(utc_to_str(now))
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    let back = res.map(|r| r.rows_affected() as usize).unwrap_or_default();
+    if back > 0 {
+        info!("Host {host_id} is back after {back} reboots");
+    }
+    back
+}
+
+/// finish the reboot step of execution `id`, the verdict becomes success only if it `succeeded`
+pub async fn finish(id: Uuid, state: RebootState, note: &str, pool: &SqlitePool) {
+    let verdict = match state {
+        RebootState::Succeeded => Verdict::Success,
+        _ => Verdict::Failure,
+    };
+    let q =
+        "UPDATE executions SET reboot_state = ?, verdict = ?, output = output || ? WHERE id = ?";
+    let res = query(q)
+        .bind(state.to_string())
+        .bind(verdict.to_string())
+        .bind(format!("\n--- reboot {state} ---\n{note}"))
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    if let Err(e) = res {
+        warn!("Reboot step of execution {id} could not be finished: {e}");
+    }
+}
+
+/// store the output of a post-check sent with `check_id`, false if no reboot step waits for it
+pub async fn store_check_result(check_id: Uuid, output: &str, pool: &SqlitePool) -> bool {
+    let filter = format!(
+        "check_id='{check_id}' AND reboot_state='{}'",
+        RebootState::PostCheck
+    );
+    let Some(exe) = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return false;
+    };
+    let check = match get_executed_script(&exe, pool).await.and_then(|s| s.reboot) {
+        Some(RebootStep {
+            post_check: Some(check_id),
+            ..
+        }) => match exe.check_revision {
+            // evaluated against the revision that was sent to the agent
+            Some(revision) => get_revision(check_id, revision, pool.acquire().await.unwrap())
+                .await
+                .map(|r| r.script),
+            None => {
+                let filter = format!("id='{check_id}'");
+                get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                    .await
+                    .into_iter()
+                    .next()
+            }
+        },
+        _ => None,
+    };
+    let state = match check.map(|c| evaluate_output(&c, output).verdict) {
+        Some(Verdict::Success) => RebootState::Succeeded,
+        _ => RebootState::Failed,
+    };
+    info!("Post-check of execution {} after reboot: {state}", exe.id);
+    finish(exe.id, state, output, pool).await;
+    true
+}
+
+/// time out the reboot steps past their deadline, returns the executions that timed out
+pub async fn expire_reboots(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<Execution> {
+    let filter = format!(
+        "reboot_state IN ('{}', '{}', '{}') AND reboot_deadline < '{}'",
+        RebootState::Pending,
+        RebootState::AwaitingReconnect,
+        RebootState::PostCheck,
+        utc_to_str(now)
+    );
+    let expired = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    for exe in &expired {
+        let note = match exe.reboot_state {
+            Some(RebootState::PostCheck) => "no post-check result before the deadline",
+            _ => "host did not come back before the deadline",
+        };
+        warn!("Reboot of host {} timed out: {note}", exe.host_id);
+        finish(exe.id, RebootState::TimedOut, note, pool).await;
+    }
+    expired
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct RebootQueryParams {
+    required: Option<bool>,
+}
+
+/// API to get the kernel and reboot state of all hosts, longest pending reboot first
+pub async fn get_reboots_api(
+    _claims: Claims,
+    Query(params): Query<RebootQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let condition = match params.required {
+        Some(required) => format!("reboot_required = {}", i64::from(required)),
+        None => "1=1".into(),
+    };
+    let filter = format!("{condition} ORDER BY reboot_required DESC, required_since, alias");
+    let reboot_vec = get_reboots_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(reboot_vec)
+}
+
+/// API to get the kernel and reboot state of a host
+pub async fn get_host_reboot_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("host_id='{id}'");
+    let reboots = get_reboots_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match reboots.into_iter().next() {
+        Some(reboot) => Json(reboot).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn get_reboots_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<HostReboot> {
+    let q = match filter {
+        Some(f) => format!(
+            "SELECT host_reboots.*, hosts.alias FROM host_reboots JOIN hosts ON hosts.id = host_reboots.host_id WHERE {f}"
+        ),
+        None => "SELECT host_reboots.*, hosts.alias FROM host_reboots JOIN hosts ON hosts.id = host_reboots.host_id".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| HostReboot::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alert::{evaluate_rules, AlertCondition, AlertRule, AlertState},
+        db::{create_database, init_database},
+        execution::{store_result, Execution},
+        host::{get_hosts_from_db, Host, ScheduleState, REBOOT_REQUIRED},
+        parser::OutputParser,
+        revision::save_script,
+        schedule::{Schedule, Target, Timer},
+        script::Script,
+    };
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    const DEBIAN_PENDING: &str = "running_kernel=6.1.0-18-amd64
+installed_kernel=6.1.0-21-amd64
+reboot_required=true
+reboot_indicator=/var/run/reboot-required
+reboot_packages=linux-image-6.1.0-21-amd64 libc6
+";
+
+    const DEBIAN_REBOOTED: &str =
+        "running_kernel=6.1.0-21-amd64\ninstalled_kernel=6.1.0-21-amd64\n";
+
+    #[tokio::test]
+    async fn test_reboot() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pending = parse_reboot_status(DEBIAN_PENDING).unwrap();
+        assert!(pending.kernel_pending());
+        assert_eq!(
+            pending.reasons(),
+            vec![
+                "kernel 6.1.0-21-amd64 installed, 6.1.0-18-amd64 running",
+                "/var/run/reboot-required by linux-image-6.1.0-21-amd64, libc6"
+            ]
+        );
+        // rhel kernels, needs-restarting without a newer kernel
+        let rhel = parse_reboot_status(
+            "running_kernel=5.14.0-427.13.1.el9_4.x86_64\ninstalled_kernel=5.14.0-427.13.1.el9_4.x86_64\nreboot_indicator=needs-restarting -r\nreboot_required=true\n",
+        )
+        .unwrap();
+        assert!(!rhel.kernel_pending());
+        assert_eq!(rhel.reasons(), vec!["needs-restarting -r"]);
+        let older = RebootStatus {
+            running_kernel: "5.14.0-503.11.1.el9_5.x86_64".into(),
+            installed_kernel: "5.14.0-427.13.1.el9_4.x86_64".into(),
+            ..Default::default()
+        };
+        assert!(!older.reboot_required());
+        assert!(parse_reboot_status(DEBIAN_REBOOTED)
+            .unwrap()
+            .reasons()
+            .is_empty());
+        assert!(parse_reboot_status("installed_kernel=6.1\n").is_err());
+        assert!(parse_reboot_status("running_kernel=6.1\nreboot_required=maybe\n").is_err());
+        assert!(parse_reboot_status("garbage").is_err());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let web = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            attributes: vec!["linux".into()],
+            active: true,
+            ..Default::default()
+        };
+        let _h = web
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        // the reboot keeps the time it was first required
+        let now = DateTime::parse_from_rfc3339("2026-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        store_reboot_status(web.id, &pending, now, &pool)
+            .await
+            .unwrap();
+        store_reboot_status(web.id, &pending, now + Duration::hours(1), &pool)
+            .await
+            .unwrap();
+        let reboots = get_reboots_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(reboots.len(), 1);
+        assert_eq!(reboots[0].alias, "web-1");
+        assert_eq!(reboots[0].required_since, Some(now));
+        assert_eq!(reboots[0].checked, now + Duration::hours(1));
+        let host = get_hosts_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert_eq!(host.facts["kernel"], "6.1.0-18-amd64");
+        assert_eq!(host.facts["kernel_installed"], "6.1.0-21-amd64");
+        assert_eq!(host.selector_attributes(), vec!["linux", REBOOT_REQUIRED]);
+
+        // schedules can select hosts waiting for a reboot
+        let script = Script {
+            id: Uuid::new_v4(),
+            name: "reboot_status".into(),
+            script_content: REBOOT_SCRIPT.into(),
+            parser: OutputParser::RebootStatus,
+            ..Default::default()
+        };
+        let _s = save_script(script.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: script.id,
+            target: Target::Attributes(vec![REBOOT_REQUIRED.into()]),
+            timer: Timer::Cron("0 * * * *".into()),
+            active: true,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let schedules = host
+            .get_all_schedules(pool.acquire().await.unwrap(), ScheduleState::Active)
+            .await;
+        assert!(schedules.iter().any(|s| s.id == sched.id));
+
+        // the alert fires once the reboot is pending for a day and resolves after it
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "reboot".into(),
+            condition: AlertCondition::RebootRequired { after_hours: 24 },
+            severity: Default::default(),
+            labels: Default::default(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule.insert_into_db(pool.acquire().await.unwrap()).await;
+        for (output, at) in [
+            (DEBIAN_PENDING, now + Duration::hours(2)),
+            (DEBIAN_PENDING, now + Duration::hours(25)),
+            (DEBIAN_REBOOTED, now + Duration::hours(26)),
+        ] {
+            let exe = Execution {
+                id: Uuid::new_v4(),
+                host_id: web.id,
+                sched_id: sched.id,
+                ..Default::default()
+            };
+            let id = exe.id;
+            exe.insert_into_db(pool.acquire().await.unwrap()).await;
+            store_result(id, output.into(), Utc::now(), &pool).await;
+            let changed = evaluate_rules(at, &pool).await;
+            match at - now {
+                d if d < Duration::hours(24) => assert!(changed.is_empty()),
+                d if d < Duration::hours(26) => {
+                    assert_eq!(changed.len(), 1);
+                    assert_eq!(changed[0].state, AlertState::Firing);
+                    assert_eq!(changed[0].value, Some(25.0));
+                    assert!(changed[0]
+                        .summary
+                        .starts_with("reboot required since 2026-03-01 00:00:00 UTC: kernel"));
+                }
+                _ => {
+                    assert_eq!(changed.len(), 1);
+                    assert_eq!(changed[0].state, AlertState::Resolved);
+                }
+            }
+        }
+        let reboot = get_reboots_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert!(!reboot.reboot_required);
+        assert_eq!(reboot.required_since, None);
+        let host = get_hosts_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert_eq!(host.facts["reboot_required"], "false");
+        assert_eq!(host.selector_attributes(), vec!["linux"]);
+    }
+
+    #[tokio::test]
+    async fn test_reboot_step() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "db-1".into(),
+            active: true,
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let check = Script {
+            id: Uuid::new_v4(),
+            name: "postgres_up".into(),
+            output_regex: "accepting connections".into(),
+            fail_on_no_match: true,
+            script_content: "pg_isready".into(),
+            ..Default::default()
+        };
+        let _c = save_script(check.clone(), "a@test.int", "", &pool).await;
+        let mut patch = Script {
+            id: Uuid::new_v4(),
+            name: "kernel_update".into(),
+            script_content: "apt-get -y upgrade".into(),
+            fail_on_no_match: true,
+            output_regex: "upgraded".into(),
+            reboot: Some(RebootStep {
+                command: "".into(),
+                deadline_secs: 600,
+                post_check: Some(check.id),
+                post_check_revision: None,
+            }),
+            ..Default::default()
+        };
+        assert!(patch.validate().is_err());
+        patch.reboot = serde_json::from_str(&format!(r#"{{"post_check": "{}"}}"#, check.id)).ok();
+        assert_eq!(patch.reboot.as_ref().unwrap().command, "shutdown -r now");
+        assert_eq!(
+            patch.reboot.as_ref().unwrap().deadline_secs,
+            REBOOT_DEADLINE_SECS
+        );
+        assert!(patch.validate().is_ok());
+        let _p = save_script(patch.clone(), "a@test.int", "", &pool).await;
+        let sched = Schedule {
+            id: Uuid::new_v4(),
+            script_id: patch.id,
+            timer: Timer::Cron("0 3 * * *".into()),
+            active: true,
+            ..Default::default()
+        };
+        let _sched = sched
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let run = |output: &'static str| {
+            let pool = pool.clone();
+            async move {
+                let exe = Execution {
+                    id: Uuid::new_v4(),
+                    host_id: host.id,
+                    sched_id: sched.id,
+                    ..Default::default()
+                };
+                let id = exe.id;
+                exe.insert_into_db(pool.acquire().await.unwrap()).await;
+                store_result(id, output.into(), Utc::now(), &pool).await;
+                let filter = format!("id='{id}'");
+                get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+                    .await
+                    .remove(0)
+            }
+        };
+
+        // a failed run does not reboot
+        let failed = run("0 packages changed").await;
+        assert_eq!(failed.verdict, Some(Verdict::Failure));
+        assert_eq!(failed.reboot_state, None);
+
+        // the verdict waits for the reboot and the post-check
+        let exe = run("1 upgraded").await;
+        assert_eq!(exe.verdict, None);
+        assert_eq!(exe.reboot_state, Some(RebootState::Pending));
+        let deadline = exe.reboot_deadline.unwrap();
+        assert!(deadline > Utc::now() + Duration::seconds(890));
+        let pending = reboot_executions(host.id, RebootState::Pending, &pool).await;
+        assert_eq!(pending.len(), 1);
+        let sent = RebootState::AwaitingReconnect;
+        assert!(transition(exe.id, RebootState::Pending, sent, &pool).await);
+        assert!(!transition(exe.id, RebootState::Pending, sent, &pool).await);
+        // coming back after the deadline does not count
+        assert_eq!(
+            reconnected(host.id, deadline + Duration::seconds(1), &pool).await,
+            0
+        );
+        assert_eq!(reconnected(host.id, Utc::now(), &pool).await, 1);
+        let check_id = Uuid::new_v4();
+        let _u = crate::execution::update_text_field(
+            exe.id,
+            "check_id",
+            check_id.to_string(),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        // the output is evaluated against the revision that was sent, not the latest
+        let edited = Script {
+            output_regex: "^ok$".into(),
+            ..check.clone()
+        };
+        let _c2 = save_script(edited, "a@test.int", "", &pool).await;
+        let _u = crate::execution::update_text_field(
+            exe.id,
+            "check_revision",
+            "1".into(),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        assert!(!store_check_result(Uuid::new_v4(), "", &pool).await);
+        assert!(store_check_result(check_id, "/tmp:5432 - accepting connections", &pool).await);
+        let filter = format!("id='{}'", exe.id);
+        let done = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert_eq!(done.check_revision, Some(1));
+        assert_eq!(done.reboot_state, Some(RebootState::Succeeded));
+        assert_eq!(done.verdict, Some(Verdict::Success));
+        assert!(done
+            .output
+            .starts_with("1 upgraded\n--- reboot succeeded ---\n/tmp:5432"));
+        // the post-check is stored once
+        assert!(!store_check_result(check_id, "no response", &pool).await);
+
+        // a host not coming back in time fails the execution and raises the alert
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "reboot failed".into(),
+            condition: AlertCondition::RebootFailed {},
+            severity: Default::default(),
+            labels: Default::default(),
+            for_secs: 0,
+            sched_id: None,
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule.insert_into_db(pool.acquire().await.unwrap()).await;
+        let lost = run("2 upgraded").await;
+        assert!(transition(lost.id, RebootState::Pending, sent, &pool).await);
+        assert!(expire_reboots(Utc::now(), &pool).await.is_empty());
+        let late = lost.reboot_deadline.unwrap() + Duration::minutes(5);
+        let expired = expire_reboots(late, &pool).await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, lost.id);
+        let filter = format!("id='{}'", lost.id);
+        let timed_out = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        assert_eq!(timed_out.reboot_state, Some(RebootState::TimedOut));
+        assert_eq!(timed_out.verdict, Some(Verdict::Failure));
+        let changed = evaluate_rules(late, &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].state, AlertState::Firing);
+        assert_eq!(changed[0].sched_id, sched.id);
+        assert_eq!(changed[0].value, Some(5.0));
+
+        // the next reboot passing resolves it
+        let next = run("3 upgraded").await;
+        assert!(transition(next.id, RebootState::Pending, sent, &pool).await);
+        assert_eq!(reconnected(host.id, Utc::now(), &pool).await, 1);
+        finish(next.id, RebootState::Succeeded, "host is back", &pool).await;
+        let changed = evaluate_rules(late, &pool).await;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].state, AlertState::Resolved);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let mut ids = vec![];
+        for (alias, output) in [("web-1", DEBIAN_PENDING), ("web-2", DEBIAN_REBOOTED)] {
+            let host = Host {
+                id: Uuid::new_v4(),
+                alias: alias.into(),
+                ..Default::default()
+            };
+            let _h = host
+                .clone()
+                .insert_into_db(pool.acquire().await.unwrap())
+                .await;
+            let status = parse_reboot_status(output).unwrap();
+            store_reboot_status(host.id, &status, Utc::now(), &pool)
+                .await
+                .unwrap();
+            ids.push(host.id);
+        }
+
+        let api_pending = get_reboots_api(
+            claims.clone(),
+            axum::extract::Query(RebootQueryParams {
+                required: Some(true),
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_pending.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_pending.into_body())
+            .await
+            .unwrap();
+        let pending: Vec<HostReboot> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].host_id, ids[0]);
+
+        let api_all = get_reboots_api(
+            claims.clone(),
+            axum::extract::Query(RebootQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_all.into_body()).await.unwrap();
+        let all: Vec<HostReboot> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].reboot_required);
+
+        let api_host = get_host_reboot_api(
+            claims.clone(),
+            axum::extract::Path(ids[1]),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_host.status(), StatusCode::OK);
+        let api_unknown = get_host_reboot_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,1335 @@
+use std::fmt::Display;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    execution::{evaluate_output, Execution, Verdict},
+    host::get_hosts_from_db,
+    jwt::Claims,
+    revision::{get_dispatch_script_for_schedule, get_revision, get_script_for_schedule},
+    schedule::{get_schedules_from_db, Schedule},
+    script::{get_scripts_from_db, Script},
+};
+
+/// the remediation run and the verification run each have to report back within this time
+pub const PHASE_DEADLINE: Duration = Duration::hours(1);
+
+/// Remediation script linked to a check script, runs on hosts where the check fails
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RemediationAction {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    /// a failed execution of this script triggers the remediation
+    pub check_script_id: Uuid,
+    pub remediation_script_id: Uuid,
+    /// pinned revision of the remediation script, `None` runs the latest revision
+    #[serde(default)]
+    pub remediation_script_revision: Option<i64>,
+    /// wait for a user to approve each remediation instead of running it right away
+    #[serde(default)]
+    pub require_approval: bool,
+    /// remediations of a host since the check last passed after one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: i64,
+    /// minimum time between the end of a remediation and the next one on the same host
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: i64,
+    /// only hosts with all of these attributes are remediated
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+fn default_max_attempts() -> i64 {
+    3
+}
+
+fn default_cooldown_secs() -> i64 {
+    3600
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl RemediationAction {
+    /// Insert into or Replace `RemediationAction` in remediation_actions table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | check_script_id | TEXT | uuid
+    /// | remediation_script_id | TEXT | uuid
+    /// | remediation_script_revision | INT | pinned revision of the remediation script, NULL for latest
+    /// | require_approval | NUMERIC | bool
+    /// | max_attempts | INT |
+    /// | cooldown_secs | INT |
+    /// | attributes | TEXT | json list
+    /// | active | NUMERIC | bool
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO remediation_actions( id, name, check_script_id, remediation_script_id, remediation_script_revision, require_approval, max_attempts, cooldown_secs, attributes, active, created ) VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, check_script_id=excluded.check_script_id, remediation_script_id=excluded.remediation_script_id, remediation_script_revision=excluded.remediation_script_revision, require_approval=excluded.require_approval, max_attempts=excluded.max_attempts, cooldown_secs=excluded.cooldown_secs, attributes=excluded.attributes, active=excluded.active"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(self.check_script_id.to_string())
+            .bind(self.remediation_script_id.to_string())
+            .bind(self.remediation_script_revision)
+            .bind(self.require_approval)
+            .bind(self.max_attempts)
+            .bind(self.cooldown_secs)
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(self.active)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn validate(&self, pool: &SqlitePool) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.check_script_id == self.remediation_script_id {
+            return Err("check and remediation script must differ".into());
+        }
+        if self.max_attempts < 1 {
+            return Err("max_attempts must be at least 1".into());
+        }
+        if self.cooldown_secs < 0 {
+            return Err("cooldown_secs must not be negative".into());
+        }
+        for script_id in [self.check_script_id, self.remediation_script_id] {
+            let filter = format!("id='{script_id}'");
+            if get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+            {
+                return Err(format!("script {script_id} not found"));
+            }
+        }
+        if let Some(revision) = self.remediation_script_revision {
+            let pinned = get_revision(
+                self.remediation_script_id,
+                revision,
+                pool.acquire().await.unwrap(),
+            )
+            .await;
+            if pinned.is_none() {
+                return Err(format!("remediation script revision {revision} not found"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<SqliteRow> for RemediationAction {
+    fn from(s: SqliteRow) -> Self {
+        RemediationAction {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            check_script_id: s.get::<String, _>("check_script_id").parse().unwrap(),
+            remediation_script_id: s.get::<String, _>("remediation_script_id").parse().unwrap(),
+            remediation_script_revision: s.get::<Option<i64>, _>("remediation_script_revision"),
+            require_approval: s.get::<bool, _>("require_approval"),
+            max_attempts: s.get::<i64, _>("max_attempts"),
+            cooldown_secs: s.get::<i64, _>("cooldown_secs"),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            active: s.get::<bool, _>("active"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+/// Progress of a remediation from the failed check to its verification
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RemediationState {
+    /// waits for a user to approve or reject it
+    AwaitingApproval,
+    /// the remediation script is sent with the next update
+    Pending,
+    /// remediation script sent, waiting for its output
+    Running,
+    /// the remediation succeeded, the check runs again
+    Verifying,
+    /// the check passed after the remediation
+    Resolved,
+    /// the remediation script or the check failed, or did not report back in time
+    Failed,
+    Rejected,
+}
+
+impl Display for RemediationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            RemediationState::AwaitingApproval => "awaiting_approval",
+            RemediationState::Pending => "pending",
+            RemediationState::Running => "running",
+            RemediationState::Verifying => "verifying",
+            RemediationState::Resolved => "resolved",
+            RemediationState::Failed => "failed",
+            RemediationState::Rejected => "rejected",
+        };
+        write!(f, "{state}")
+    }
+}
+
+impl RemediationState {
+    /// not finished yet
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            RemediationState::AwaitingApproval
+                | RemediationState::Pending
+                | RemediationState::Running
+                | RemediationState::Verifying
+        )
+    }
+
+    pub fn from_db(s: &str) -> Option<RemediationState> {
+        match s {
+            "awaiting_approval" => Some(RemediationState::AwaitingApproval),
+            "pending" => Some(RemediationState::Pending),
+            "running" => Some(RemediationState::Running),
+            "verifying" => Some(RemediationState::Verifying),
+            "resolved" => Some(RemediationState::Resolved),
+            "failed" => Some(RemediationState::Failed),
+            "rejected" => Some(RemediationState::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// One remediation of a host: the failed check, the remediation run and the verification run
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Remediation {
+    pub id: Uuid,
+    pub action_id: Uuid,
+    pub host_id: Uuid,
+    /// schedule of the check script on the host
+    pub sched_id: Uuid,
+    /// failed execution of the check that triggered the remediation
+    pub check_execution_id: Uuid,
+    /// counted since the check last passed after a remediation on the host
+    pub attempt: i64,
+    pub state: RemediationState,
+    /// output of the remediation script
+    pub output: Option<String>,
+    pub remediation_verdict: Option<Verdict>,
+    /// revision of the remediation script sent to the agent
+    pub script_revision: Option<i64>,
+    /// execution of the check after the remediation
+    pub verification_execution_id: Option<Uuid>,
+    pub verification_verdict: Option<Verdict>,
+    pub approved_by: Option<String>,
+    pub note: Option<String>,
+    /// the running phase has to report back before this point in time
+    pub deadline: Option<DateTime<Utc>>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl Remediation {
+    /// Insert `Remediation` into remediations table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid, also the id of the remediation script sent to the agent
+    /// | action_id | TEXT | uuid
+    /// | host_id | TEXT | uuid
+    /// | sched_id | TEXT | uuid
+    /// | check_execution_id | TEXT | uuid
+    /// | attempt | INT |
+    /// | state | TEXT |
+    /// | output | TEXT | <-- implemented by another call, always created as NULL
+    /// | remediation_verdict | TEXT | <-- implemented by another call, always created as NULL
+    /// | script_revision | INT | <-- implemented by another call, always created as NULL
+    /// | verification_execution_id | TEXT | <-- implemented by another call, always created as NULL
+    /// | verification_verdict | TEXT | <-- implemented by another call, always created as NULL
+    /// | approved_by | TEXT | <-- implemented by another call, always created as NULL
+    /// | note | TEXT | <-- implemented by another call, always created as NULL
+    /// | deadline | TEXT | <-- implemented by another call, always created as NULL
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | updated | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
+        let q = r#"INSERT INTO remediations( id, action_id, host_id, sched_id, check_execution_id, attempt, state, created, updated ) VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ? )"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.action_id.to_string())
+            .bind(self.host_id.to_string())
+            .bind(self.sched_id.to_string())
+            .bind(self.check_execution_id.to_string())
+            .bind(self.attempt)
+            .bind(self.state.to_string())
+            .bind(utc_to_str(self.created))
+            .bind(utc_to_str(self.updated))
+            .execute(&mut *connection)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+impl From<SqliteRow> for Remediation {
+    fn from(s: SqliteRow) -> Self {
+        let time = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .as_deref()
+                .map(utc_from_str)
+        };
+        let verdict = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .as_deref()
+                .and_then(Verdict::from_db)
+        };
+        Remediation {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            action_id: s.get::<String, _>("action_id").parse().unwrap(),
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            sched_id: s.get::<String, _>("sched_id").parse().unwrap(),
+            check_execution_id: s.get::<String, _>("check_execution_id").parse().unwrap(),
+            attempt: s.get::<i64, _>("attempt"),
+            state: RemediationState::from_db(&s.get::<String, _>("state"))
+                .unwrap_or(RemediationState::Failed),
+            output: s.get::<Option<String>, _>("output"),
+            remediation_verdict: verdict("remediation_verdict"),
+            script_revision: s.get::<Option<i64>, _>("script_revision"),
+            verification_execution_id: s
+                .get::<Option<String>, _>("verification_execution_id")
+                .and_then(|id| id.parse().ok()),
+            verification_verdict: verdict("verification_verdict"),
+            approved_by: s.get::<Option<String>, _>("approved_by"),
+            note: s.get::<Option<String>, _>("note"),
+            deadline: time("deadline"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+            updated: utc_from_str(&s.get::<String, _>("updated")),
+        }
+    }
+}
+
+/// Handle the verdict of a check execution, finishes the remediation `exe` verifies or starts
+/// remediations of the actions linked to the failed check, returns the started remediations
+pub async fn check_result(
+    exe: &Execution,
+    verdict: &Verdict,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Vec<Remediation> {
+    let filter = format!(
+        "verification_execution_id='{}' AND state='{}'",
+        exe.id,
+        RemediationState::Verifying
+    );
+    if let Some(verified) = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    {
+        let state = match verdict {
+            Verdict::Success => RemediationState::Resolved,
+            _ => RemediationState::Failed,
+        };
+        info!("Remediation {} verified: {state}", verified.id);
+        let q = "UPDATE remediations SET state = ?, verification_verdict = ?, deadline = NULL, updated = ? WHERE id = ?";
+        let _res = query(q)
+            .bind(state.to_string())
+            .bind(verdict.to_string())
+            .bind(utc_to_str(now))
+            .bind(verified.id.to_string())
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await;
+        return vec![];
+    }
+    if *verdict != Verdict::Failure {
+        return vec![];
+    }
+    let filter = format!("id='{}'", exe.sched_id);
+    let Some(schedule) = get_schedules_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return vec![];
+    };
+    let filter = format!("active = 1 AND check_script_id = '{}'", schedule.script_id);
+    let actions = get_actions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    if actions.is_empty() {
+        return vec![];
+    }
+    let filter = format!("id='{}'", exe.host_id);
+    let Some(host) = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return vec![];
+    };
+    let attributes = host.selector_attributes();
+    let mut started = vec![];
+    for action in actions {
+        if !action.attributes.iter().all(|a| attributes.contains(a)) {
+            continue;
+        }
+        let attempt = match next_attempt(&action, host.id, now, pool).await {
+            Ok(attempt) => attempt,
+            Err(reason) => {
+                debug!(
+                    "Remediation {} of {} skipped: {reason}",
+                    action.name, host.alias
+                );
+                continue;
+            }
+        };
+        let remediation = Remediation {
+            id: Uuid::new_v4(),
+            action_id: action.id,
+            host_id: host.id,
+            sched_id: exe.sched_id,
+            check_execution_id: exe.id,
+            attempt,
+            state: if action.require_approval {
+                RemediationState::AwaitingApproval
+            } else {
+                RemediationState::Pending
+            },
+            output: None,
+            remediation_verdict: None,
+            script_revision: None,
+            verification_execution_id: None,
+            verification_verdict: None,
+            approved_by: None,
+            note: None,
+            deadline: None,
+            created: now,
+            updated: now,
+        };
+        let res = remediation
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        if res.rows_affected() == 1 {
+            info!(
+                "Remediation {} of {} started, attempt {attempt}: {}",
+                action.name, host.alias, remediation.state
+            );
+            started.push(remediation);
+        }
+    }
+    started
+}
+
+/// attempt number of the next remediation of `action` on the host, or why there is none
+async fn next_attempt(
+    action: &RemediationAction,
+    host_id: Uuid,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<i64, String> {
+    let filter = format!(
+        "action_id = '{}' AND host_id = '{host_id}' ORDER BY created DESC",
+        action.id
+    );
+    let previous = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    if let Some(open) = previous.iter().find(|r| r.state.is_open()) {
+        return Err(format!("remediation {} is {}", open.id, open.state));
+    }
+    if let Some(latest) = previous.first() {
+        let ready = latest.updated + Duration::seconds(action.cooldown_secs);
+        if ready > now {
+            return Err(format!("cooling down until {ready}"));
+        }
+    }
+    let attempts = previous
+        .iter()
+        .take_while(|r| r.state != RemediationState::Resolved)
+        .filter(|r| r.state != RemediationState::Rejected)
+        .count() as i64;
+    if attempts >= action.max_attempts {
+        return Err(format!("{attempts} attempts failed"));
+    }
+    Ok(attempts + 1)
+}
+
+/// remediations of `host_id` in `state`
+pub async fn remediations(
+    host_id: Uuid,
+    state: RemediationState,
+    pool: &SqlitePool,
+) -> Vec<Remediation> {
+    let filter = format!("host_id='{host_id}' AND state='{state}' ORDER BY created");
+    get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+async fn action(remediation: &Remediation, pool: &SqlitePool) -> Option<RemediationAction> {
+    let filter = format!("id='{}'", remediation.action_id);
+    get_actions_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+}
+
+/// remediation script to send for `remediation`, the pinned or latest revision of its action
+///
+/// with `require_approval` only a revision approved by a second user is sent
+pub async fn remediation_script(
+    remediation: &Remediation,
+    require_approval: bool,
+    pool: &SqlitePool,
+) -> Option<Script> {
+    let action = action(remediation, pool).await?;
+    let schedule = Schedule {
+        script_id: action.remediation_script_id,
+        script_revision: action.remediation_script_revision,
+        ..Default::default()
+    };
+    get_dispatch_script_for_schedule(&schedule, require_approval, pool).await
+}
+
+/// the remediation script revision that was sent for `remediation`, the pinned or latest one
+/// if none was recorded
+async fn sent_script(remediation: &Remediation, pool: &SqlitePool) -> Option<Script> {
+    let action = action(remediation, pool).await?;
+    let schedule = Schedule {
+        script_id: action.remediation_script_id,
+        script_revision: remediation
+            .script_revision
+            .or(action.remediation_script_revision),
+        ..Default::default()
+    };
+    get_script_for_schedule(&schedule, pool.acquire().await.unwrap()).await
+}
+
+/// record the revision of the remediation script sent for remediation `id`
+pub async fn sent(id: Uuid, revision: i64, pool: &SqlitePool) {
+    let res = query("UPDATE remediations SET script_revision = ? WHERE id = ?")
+        .bind(revision)
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    if let Err(e) = res {
+        warn!("Script revision of remediation {id} could not be stored: {e}");
+    }
+}
+
+/// move remediation `id` from `from` to `to`, false if it is in another state
+pub async fn transition(
+    id: Uuid,
+    from: RemediationState,
+    to: RemediationState,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> bool {
+    let deadline = match to {
+        RemediationState::Running | RemediationState::Verifying => {
+            Some(utc_to_str(now + PHASE_DEADLINE))
+        }
+        _ => None,
+    };
+    let q =
+        "UPDATE remediations SET state = ?, deadline = ?, updated = ? WHERE id = ? AND state = ?";
+    let res = query(q)
+        .bind(to.to_string())
+        .bind(deadline)
+        .bind(utc_to_str(now))
+        .bind(id.to_string())
+        .bind(from.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    res.is_ok_and(|r| r.rows_affected() == 1)
+}
+
+/// fail remediation `id` with `note`
+pub async fn fail(id: Uuid, note: &str, now: DateTime<Utc>, pool: &SqlitePool) {
+    let q =
+        "UPDATE remediations SET state = ?, note = ?, deadline = NULL, updated = ? WHERE id = ?";
+    let res = query(q)
+        .bind(RemediationState::Failed.to_string())
+        .bind(note)
+        .bind(utc_to_str(now))
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    if let Err(e) = res {
+        warn!("Remediation {id} could not be failed: {e}");
+    }
+}
+
+/// store the output of the remediation script sent with `id`, false if no remediation waits
+/// for it; a successful run queues the check on the host again to verify the fix
+pub async fn store_remediation_result(
+    id: Uuid,
+    output: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> bool {
+    let filter = format!("id='{id}' AND state='{}'", RemediationState::Running);
+    let Some(remediation) = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return false;
+    };
+    // evaluated against the revision that was sent to the agent
+    let verdict = match sent_script(&remediation, pool).await {
+        Some(script) => evaluate_output(&script, output).verdict,
+        None => Verdict::Failure,
+    };
+    let q = "UPDATE remediations SET output = ?, remediation_verdict = ?, updated = ? WHERE id = ?";
+    let _res = query(q)
+        .bind(output)
+        .bind(verdict.to_string())
+        .bind(utc_to_str(now))
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    if verdict != Verdict::Success {
+        info!("Remediation {id} failed: {verdict}");
+        fail(id, "remediation script did not succeed", now, pool).await;
+        return true;
+    }
+    let verification = Execution {
+        id: Uuid::new_v4(),
+        request: now,
+        host_id: remediation.host_id,
+        sched_id: remediation.sched_id,
+        ..Default::default()
+    };
+    let verification_id = verification.id;
+    verification
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+    let q = "UPDATE remediations SET verification_execution_id = ? WHERE id = ?";
+    let _res = query(q)
+        .bind(verification_id.to_string())
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    transition(
+        id,
+        RemediationState::Running,
+        RemediationState::Verifying,
+        now,
+        pool,
+    )
+    .await;
+    info!("Remediation {id} succeeded, verifying with execution {verification_id}");
+    true
+}
+
+/// fail the remediations past their deadline, returns the ones that timed out
+pub async fn expire_remediations(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<Remediation> {
+    let filter = format!(
+        "state IN ('{}', '{}') AND deadline < '{}'",
+        RemediationState::Running,
+        RemediationState::Verifying,
+        utc_to_str(now)
+    );
+    let expired = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    for remediation in &expired {
+        let note = match remediation.state {
+            RemediationState::Running => "no remediation result before the deadline",
+            _ => "no verification result before the deadline",
+        };
+        warn!("Remediation {} timed out: {note}", remediation.id);
+        fail(remediation.id, note, now, pool).await;
+    }
+    expired
+}
+
+/// API to get all remediation actions
+pub async fn get_actions_api(_claims: Claims, State(pool): State<SqlitePool>) -> impl IntoResponse {
+    let action_vec =
+        get_actions_from_db(Some("1=1 ORDER BY name"), pool.acquire().await.unwrap()).await;
+    Json(action_vec)
+}
+
+/// API to get one remediation action
+pub async fn get_one_action_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let actions = get_actions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match actions.into_iter().next() {
+        Some(action) => Json(action).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// API to create or replace a remediation action
+pub async fn post_actions_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<RemediationAction>,
+) -> Response {
+    if let Err(e) = payload.validate(&pool).await {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Nothing added",
+        )
+            .into_response()
+    }
+}
+
+/// API to delete a remediation action with its remediations
+pub async fn delete_one_action_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_actions_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct RemediationQueryParams {
+    host_id: Option<Uuid>,
+    action_id: Option<Uuid>,
+    state: Option<RemediationState>,
+}
+
+/// API to get remediations, newest first
+pub async fn get_remediations_api(
+    _claims: Claims,
+    Query(params): Query<RemediationQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec!["1=1".to_string()];
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    if let Some(action_id) = params.action_id {
+        conditions.push(format!("action_id = '{action_id}'"));
+    }
+    if let Some(state) = params.state {
+        conditions.push(format!("state = '{state}'"));
+    }
+    let filter = format!("{} ORDER BY created DESC", conditions.join(" AND "));
+    let remediation_vec =
+        get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(remediation_vec)
+}
+
+/// API to get one remediation
+pub async fn get_one_remediation_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let remediations = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    match remediations.into_iter().next() {
+        Some(remediation) => Json(remediation).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// move a remediation awaiting approval to `to`, recording the user for approvals
+async fn decide(id: Uuid, to: RemediationState, user: &str, pool: &SqlitePool) -> Response {
+    let filter = format!("id='{id}'");
+    let Some(remediation) = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()
+    else {
+        return (StatusCode::NOT_FOUND, "Remediation not found").into_response();
+    };
+    if !transition(id, RemediationState::AwaitingApproval, to, Utc::now(), pool).await {
+        let msg = format!("Remediation is {}", remediation.state);
+        return (StatusCode::CONFLICT, msg).into_response();
+    }
+    let _res = query("UPDATE remediations SET approved_by = ? WHERE id = ?")
+        .bind(user)
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await;
+    info!("Remediation {id} {to} by {user}");
+    let remediations = get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(&remediations[0]).into_response()
+}
+
+/// API to approve a remediation, it runs with the next update of the host
+pub async fn approve_remediation_api(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    decide(id, RemediationState::Pending, claims.sub.as_str(), &pool).await
+}
+
+/// API to reject a remediation, rejected remediations do not count as attempts
+pub async fn reject_remediation_api(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    decide(id, RemediationState::Rejected, claims.sub.as_str(), &pool).await
+}
+
+pub async fn get_actions_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<RemediationAction> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM remediation_actions WHERE {f}"),
+        None => "SELECT * FROM remediation_actions".into(),
+    };
+    match query(&q).fetch_all(&mut *connection).await {
+        Ok(rows) => rows.into_iter().map(|r| r.into()).collect(),
+        Err(e) => {
+            warn!("{e}");
+            Vec::new()
+        }
+    }
+}
+
+pub async fn delete_actions_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM remediation_actions WHERE {f}"),
+        None => "DELETE FROM remediation_actions".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_remediations_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Remediation> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM remediations WHERE {f}"),
+        None => "SELECT * FROM remediations".into(),
+    };
+    match query(&q).fetch_all(&mut *connection).await {
+        Ok(rows) => rows.into_iter().map(|r| r.into()).collect(),
+        Err(e) => {
+            warn!("{e}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        execution::store_result,
+        fixtures,
+        host::Host,
+        revision::save_script,
+    };
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    struct Setup {
+        host: Host,
+        sched_id: Uuid,
+        check_script_id: Uuid,
+        remediation_script_id: Uuid,
+    }
+
+    async fn setup(pool: &SqlitePool) -> Setup {
+        let check = Script {
+            id: Uuid::new_v4(),
+            name: "nginx_running".into(),
+            script_content: "systemctl is-active nginx".into(),
+            output_regex: "^active".into(),
+            fail_on_no_match: true,
+            ..Default::default()
+        };
+        let remedy = Script {
+            id: Uuid::new_v4(),
+            name: "nginx_restart".into(),
+            script_content: "systemctl restart nginx && echo restarted".into(),
+            output_regex: "restarted".into(),
+            fail_on_no_match: true,
+            ..Default::default()
+        };
+        let _r = save_script(remedy.clone(), "a@test.int", "", pool).await;
+        let (host, sched) = fixtures::scheduled_host("web-1", &["nginx"], check, pool).await;
+        Setup {
+            host,
+            sched_id: sched.id,
+            check_script_id: sched.script_id,
+            remediation_script_id: remedy.id,
+        }
+    }
+
+    fn action(setup: &Setup, require_approval: bool) -> RemediationAction {
+        RemediationAction {
+            id: Uuid::new_v4(),
+            name: "restart nginx".into(),
+            check_script_id: setup.check_script_id,
+            remediation_script_id: setup.remediation_script_id,
+            remediation_script_revision: None,
+            require_approval,
+            max_attempts: 2,
+            cooldown_secs: 0,
+            attributes: vec!["nginx".into()],
+            active: true,
+            created: Utc::now(),
+        }
+    }
+
+    /// run the check on the host, returns the execution id
+    async fn run_check(setup: &Setup, output: &str, pool: &SqlitePool) -> Uuid {
+        fixtures::run(&setup.host, setup.sched_id, output, Utc::now(), pool).await
+    }
+
+    async fn one(id: Uuid, pool: &SqlitePool) -> Remediation {
+        let filter = format!("id='{id}'");
+        get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_remediation() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let setup = setup(&pool).await;
+        let action = action(&setup, false);
+        let _a = action
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+
+        // a passing check does not remediate
+        let _ok = run_check(&setup, "active", &pool).await;
+        assert!(
+            remediations(setup.host.id, RemediationState::Pending, &pool)
+                .await
+                .is_empty()
+        );
+
+        let check_id = run_check(&setup, "inactive", &pool).await;
+        let pending = remediations(setup.host.id, RemediationState::Pending, &pool).await;
+        assert_eq!(pending.len(), 1);
+        let rem = pending[0].clone();
+        assert_eq!((rem.check_execution_id, rem.attempt), (check_id, 1));
+        assert_eq!(
+            remediation_script(&rem, false, &pool).await.unwrap().id,
+            setup.remediation_script_id
+        );
+        // with --require-approval an unapproved remediation script is not sent
+        assert!(remediation_script(&rem, true, &pool).await.is_none());
+        // no second remediation while one is open
+        let _again = run_check(&setup, "inactive", &pool).await;
+        let filter = format!("host_id='{}'", setup.host.id);
+        assert_eq!(
+            get_remediations_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .len(),
+            1
+        );
+
+        // the revision sent is kept, later edits do not change how its output is evaluated
+        sent(rem.id, 1, &pool).await;
+        let filter = format!("id='{}'", setup.remediation_script_id);
+        let mut remedy = get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap())
+            .await
+            .remove(0);
+        remedy.output_regex = "^done$".into();
+        let _r2 = save_script(remedy, "a@test.int", "", &pool).await;
+        let latest = remediation_script(&rem, false, &pool).await.unwrap();
+        assert_eq!(latest.revision, 2);
+        let pinned = RemediationAction {
+            remediation_script_revision: Some(1),
+            ..action.clone()
+        };
+        let _p = pinned.insert_into_db(pool.acquire().await.unwrap()).await;
+        let pinned_script = remediation_script(&rem, false, &pool).await.unwrap();
+        assert_eq!(pinned_script.revision, 1);
+
+        // sent to the host
+        let now = Utc::now();
+        assert!(
+            transition(
+                rem.id,
+                RemediationState::Pending,
+                RemediationState::Running,
+                now,
+                &pool
+            )
+            .await
+        );
+        assert!(
+            !transition(
+                rem.id,
+                RemediationState::Pending,
+                RemediationState::Running,
+                now,
+                &pool
+            )
+            .await
+        );
+        assert_eq!(
+            one(rem.id, &pool).await.deadline.map(utc_to_str),
+            Some(utc_to_str(now + PHASE_DEADLINE))
+        );
+        // output of other scripts is not a remediation result
+        assert!(!store_remediation_result(Uuid::new_v4(), "restarted", now, &pool).await);
+
+        assert!(store_remediation_result(rem.id, "restarted", now, &pool).await);
+        let verifying = one(rem.id, &pool).await;
+        assert_eq!(verifying.state, RemediationState::Verifying);
+        assert_eq!(verifying.remediation_verdict, Some(Verdict::Success));
+        assert_eq!(verifying.script_revision, Some(1));
+        assert_eq!(verifying.output.as_deref(), Some("restarted"));
+        let verification_id = verifying.verification_execution_id.unwrap();
+
+        // the queued check passes on the host
+        store_result(verification_id, "active".into(), Utc::now(), &pool).await;
+        let resolved = one(rem.id, &pool).await;
+        assert_eq!(resolved.state, RemediationState::Resolved);
+        assert_eq!(resolved.verification_verdict, Some(Verdict::Success));
+        assert_eq!(resolved.deadline, None);
+
+        // a failing remediation script ends the chain without verification
+        let _check = run_check(&setup, "inactive", &pool).await;
+        let rem = remediations(setup.host.id, RemediationState::Pending, &pool).await[0].clone();
+        assert_eq!(rem.attempt, 1);
+        transition(
+            rem.id,
+            RemediationState::Pending,
+            RemediationState::Running,
+            Utc::now(),
+            &pool,
+        )
+        .await;
+        assert!(store_remediation_result(rem.id, "Job failed", Utc::now(), &pool).await);
+        let failed = one(rem.id, &pool).await;
+        assert_eq!(failed.state, RemediationState::Failed);
+        assert_eq!(failed.remediation_verdict, Some(Verdict::Failure));
+        assert_eq!(failed.verification_execution_id, None);
+
+        // the check still fails after the second attempt
+        let _check = run_check(&setup, "inactive", &pool).await;
+        let rem = remediations(setup.host.id, RemediationState::Pending, &pool).await[0].clone();
+        assert_eq!(rem.attempt, 2);
+        transition(
+            rem.id,
+            RemediationState::Pending,
+            RemediationState::Running,
+            Utc::now(),
+            &pool,
+        )
+        .await;
+        store_remediation_result(rem.id, "restarted", Utc::now(), &pool).await;
+        let verification_id = one(rem.id, &pool).await.verification_execution_id.unwrap();
+        store_result(verification_id, "failed".into(), Utc::now(), &pool).await;
+        let failed = one(rem.id, &pool).await;
+        assert_eq!(failed.state, RemediationState::Failed);
+        assert_eq!(failed.verification_verdict, Some(Verdict::Failure));
+
+        // max_attempts reached
+        let _check = run_check(&setup, "inactive", &pool).await;
+        assert!(
+            remediations(setup.host.id, RemediationState::Pending, &pool)
+                .await
+                .is_empty()
+        );
+        assert!(next_attempt(&action, setup.host.id, Utc::now(), &pool)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let setup = setup(&pool).await;
+        let action = RemediationAction {
+            cooldown_secs: 600,
+            ..action(&setup, false)
+        };
+        let _a = action
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        // another host without the attribute of the action
+        let other = fixtures::host("db-1", &[], &pool).await;
+        let _other = fixtures::run(&other, setup.sched_id, "inactive", Utc::now(), &pool).await;
+        assert!(remediations(other.id, RemediationState::Pending, &pool)
+            .await
+            .is_empty());
+
+        let _check = run_check(&setup, "inactive", &pool).await;
+        let rem = remediations(setup.host.id, RemediationState::Pending, &pool).await[0].clone();
+        let start = Utc::now();
+        transition(
+            rem.id,
+            RemediationState::Pending,
+            RemediationState::Running,
+            start,
+            &pool,
+        )
+        .await;
+
+        // the host did not report back in time
+        assert!(expire_remediations(start + Duration::minutes(59), &pool)
+            .await
+            .is_empty());
+        let expired = expire_remediations(start + Duration::minutes(61), &pool).await;
+        assert_eq!(expired.len(), 1);
+        let failed = one(rem.id, &pool).await;
+        assert_eq!(failed.state, RemediationState::Failed);
+        assert_eq!(
+            failed.note.as_deref(),
+            Some("no remediation result before the deadline")
+        );
+        // a late result is ignored
+        assert!(!store_remediation_result(rem.id, "restarted", Utc::now(), &pool).await);
+
+        // cooling down after the failed remediation
+        let failed_at = failed.updated;
+        let cooling = next_attempt(
+            &action,
+            setup.host.id,
+            failed_at + Duration::minutes(5),
+            &pool,
+        )
+        .await;
+        assert!(cooling.unwrap_err().starts_with("cooling down"));
+        let ready = next_attempt(
+            &action,
+            setup.host.id,
+            failed_at + Duration::minutes(11),
+            &pool,
+        )
+        .await;
+        assert_eq!(ready, Ok(2));
+
+        // inactive actions do not remediate
+        let _inactive = RemediationAction {
+            active: false,
+            cooldown_secs: 0,
+            ..action.clone()
+        }
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await;
+        let _check = run_check(&setup, "inactive", &pool).await;
+        assert!(
+            remediations(setup.host.id, RemediationState::Pending, &pool)
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let setup = setup(&pool).await;
+        let payload = action(&setup, true);
+
+        let api_post = post_actions_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::Json(payload.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+        for invalid in [
+            RemediationAction {
+                remediation_script_id: setup.check_script_id,
+                ..payload.clone()
+            },
+            RemediationAction {
+                max_attempts: 0,
+                ..payload.clone()
+            },
+            RemediationAction {
+                remediation_script_id: Uuid::new_v4(),
+                ..payload.clone()
+            },
+        ] {
+            let api_invalid = post_actions_api(
+                claims.clone(),
+                axum::extract::State(pool.clone()),
+                axum::Json(invalid),
+            )
+            .await;
+            assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let api_one = get_one_action_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_one.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_one.into_body()).await.unwrap();
+        let stored: RemediationAction = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stored.name, payload.name);
+        assert!(stored.require_approval);
+        let api_missing = get_one_action_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_missing.status(), StatusCode::NOT_FOUND);
+
+        // the remediation waits for approval
+        let _check = run_check(&setup, "inactive", &pool).await;
+        let waiting = remediations(setup.host.id, RemediationState::AwaitingApproval, &pool).await;
+        assert_eq!(waiting.len(), 1);
+        let api_approve = approve_remediation_api(
+            claims.clone(),
+            axum::extract::Path(waiting[0].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_approve.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_approve.into_body())
+            .await
+            .unwrap();
+        let approved: Remediation = serde_json::from_slice(&body).unwrap();
+        assert_eq!(approved.state, RemediationState::Pending);
+        assert!(approved.approved_by.is_some());
+        let api_twice = reject_remediation_api(
+            claims.clone(),
+            axum::extract::Path(waiting[0].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_twice.status(), StatusCode::CONFLICT);
+        let api_unknown = approve_remediation_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        // a rejected remediation does not count as attempt
+        fail(waiting[0].id, "test", Utc::now() - Duration::days(1), &pool).await;
+        let _check = run_check(&setup, "inactive", &pool).await;
+        let waiting = remediations(setup.host.id, RemediationState::AwaitingApproval, &pool).await;
+        assert_eq!(waiting[0].attempt, 2);
+        let api_reject = reject_remediation_api(
+            claims.clone(),
+            axum::extract::Path(waiting[0].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_reject.status(), StatusCode::OK);
+        assert_eq!(
+            next_attempt(&payload, setup.host.id, Utc::now(), &pool).await,
+            Ok(2)
+        );
+
+        let api_list = get_remediations_api(
+            claims.clone(),
+            axum::extract::Query(RemediationQueryParams {
+                host_id: Some(setup.host.id),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_list.into_body()).await.unwrap();
+        let listed: Vec<Remediation> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].state, RemediationState::Rejected);
+        let api_filtered = get_remediations_api(
+            claims.clone(),
+            axum::extract::Query(RemediationQueryParams {
+                state: Some(RemediationState::Failed),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        let body = hyper::body::to_bytes(api_filtered.into_body())
+            .await
+            .unwrap();
+        let listed: Vec<Remediation> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listed.len(), 1);
+        let api_one = get_one_remediation_api(
+            claims.clone(),
+            axum::extract::Path(listed[0].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_one.status(), StatusCode::OK);
+
+        // deleting the action removes its remediations
+        let api_delete = delete_one_action_api(
+            claims.clone(),
+            axum::extract::Path(payload.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_delete.status(), StatusCode::OK);
+        let api_gone = get_one_remediation_api(
+            claims.clone(),
+            axum::extract::Path(listed[0].id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_gone.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,691 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteConnection, SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    jwt::Claims,
+    schedule::Schedule,
+    script::{get_scripts_from_db, Script},
+    signing::{active_key, verify, PayloadSignature, PublishedKey, ServerKey},
+};
+
+/// Immutable snapshot of a script, created on every change
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ScriptRevision {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub script_id: Uuid,
+    pub revision: i64,
+    pub author: String,
+    #[serde(default)]
+    pub change_note: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+    pub script: Script,
+    /// second user who approved the revision, required for dispatch with `--require-approval`
+    #[serde(default)]
+    pub approved_by: Option<String>,
+    #[serde(default)]
+    pub approved: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub approval_signature: Option<PayloadSignature>,
+}
+
+/// What the server signs when a revision gets approved
+#[derive(Serialize)]
+struct ApprovalPayload<'a> {
+    script_id: Uuid,
+    revision: i64,
+    author: &'a str,
+    approved_by: &'a str,
+    script: &'a Script,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApprovalError {
+    NotFound,
+    AlreadyApproved,
+    SelfApproval,
+    Signing(String),
+}
+
+impl Display for ApprovalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalError::NotFound => write!(f, "Revision not found"),
+            ApprovalError::AlreadyApproved => write!(f, "Revision is already approved"),
+            ApprovalError::SelfApproval => {
+                write!(
+                    f,
+                    "Revision must be approved by a different user than its author"
+                )
+            }
+            ApprovalError::Signing(e) => write!(f, "Approval could not be signed: {e}"),
+        }
+    }
+}
+
+impl ScriptRevision {
+    /// Insert `ScriptRevision` into script_revisions table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | script_id | TEXT | uuid
+    /// | revision | INT | auto-incremented per script
+    /// | author | TEXT |
+    /// | change_note | TEXT |
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | script | TEXT | json snapshot of the script
+    /// | approved_by | TEXT | <-- implemented by another call, always created as NULL
+    /// | approved | TEXT | <-- implemented by another call, always created as NULL
+    /// | approval_signature | TEXT | <-- implemented by another call, always created as NULL
+    pub async fn insert_into_db(
+        self,
+        connection: &mut SqliteConnection,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO script_revisions( id, script_id, revision, author, change_note, created, script ) VALUES ( ?, ?, ?, ?, ?, ?, ? )"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.script_id.to_string())
+            .bind(self.revision)
+            .bind(self.author)
+            .bind(self.change_note)
+            .bind(utc_to_str(self.created))
+            .bind(serde_json::to_string(&self.script).unwrap())
+            .execute(connection)
+            .await
+    }
+
+    fn approval_payload(&self) -> Option<ApprovalPayload<'_>> {
+        Some(ApprovalPayload {
+            script_id: self.script_id,
+            revision: self.revision,
+            author: &self.author,
+            approved_by: self.approved_by.as_deref()?,
+            script: &self.script,
+        })
+    }
+
+    /// approval is present and signed by one of `keys`
+    pub fn approval_is_valid(&self, keys: &[PublishedKey]) -> bool {
+        match (self.approval_payload(), &self.approval_signature) {
+            (Some(payload), Some(signature)) => verify(&payload, signature, keys),
+            _ => false,
+        }
+    }
+}
+
+impl From<SqliteRow> for ScriptRevision {
+    fn from(s: SqliteRow) -> Self {
+        ScriptRevision {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            script_id: s.get::<String, _>("script_id").parse().unwrap(),
+            revision: s.get::<i64, _>("revision"),
+            author: s.get::<String, _>("author"),
+            change_note: s.get::<String, _>("change_note"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+            script: serde_json::from_str(&s.get::<String, _>("script")).unwrap(),
+            approved_by: s.get::<Option<String>, _>("approved_by"),
+            approved: s
+                .get::<Option<String>, _>("approved")
+                .as_deref()
+                .map(utc_from_str),
+            approval_signature: s
+                .get::<Option<String>, _>("approval_signature")
+                .and_then(|a| serde_json::from_str(&a).ok()),
+        }
+    }
+}
+
+/// Store `script` as new revision and update the script itself
+///
+/// returns the revision number, saving unchanged content creates no new revision
+pub async fn save_script(
+    mut script: Script,
+    author: &str,
+    change_note: &str,
+    pool: &SqlitePool,
+) -> Result<i64, sqlx::Error> {
+    let latest = get_latest_revision(script.id, pool.acquire().await?).await;
+    if let Some(latest) = &latest {
+        script.revision = latest.revision;
+        if latest.script == script {
+            return Ok(latest.revision);
+        }
+    }
+    let revision = latest.map(|l| l.revision).unwrap_or_default() + 1;
+    script.revision = revision;
+    let rev = ScriptRevision {
+        id: Uuid::new_v4(),
+        script_id: script.id,
+        revision,
+        author: author.to_string(),
+        change_note: change_note.to_string(),
+        created: Utc::now(),
+        script: script.clone(),
+        ..Default::default()
+    };
+    // a script is never saved without its revision
+    let mut tx = pool.begin().await?;
+    script.insert_into_db(&mut tx).await?;
+    rev.insert_into_db(&mut tx).await?;
+    tx.commit().await?;
+    Ok(revision)
+}
+
+pub async fn get_latest_revision(
+    script_id: Uuid,
+    connection: PoolConnection<Sqlite>,
+) -> Option<ScriptRevision> {
+    let filter = format!("script_id='{script_id}' ORDER BY revision DESC LIMIT 1");
+    get_revisions_from_db(Some(&filter), connection)
+        .await
+        .into_iter()
+        .next()
+}
+
+pub async fn get_revision(
+    script_id: Uuid,
+    revision: i64,
+    connection: PoolConnection<Sqlite>,
+) -> Option<ScriptRevision> {
+    let filter = format!("script_id='{script_id}' AND revision={revision}");
+    get_revisions_from_db(Some(&filter), connection)
+        .await
+        .into_iter()
+        .next()
+}
+
+/// Script to run for `schedule`, the pinned revision or the latest script
+pub async fn get_script_for_schedule(
+    schedule: &Schedule,
+    connection: PoolConnection<Sqlite>,
+) -> Option<Script> {
+    match schedule.script_revision {
+        Some(revision) => get_revision(schedule.script_id, revision, connection)
+            .await
+            .map(|r| r.script),
+        None => {
+            let filter = format!("id='{}'", schedule.script_id);
+            get_scripts_from_db(Some(&filter), connection)
+                .await
+                .into_iter()
+                .next()
+        }
+    }
+}
+
+/// Script to run for `schedule` when revisions need a second approver
+///
+/// the pinned revision or the latest approved revision, only with an approval signed by `key`
+/// or one of the keys it replaced
+pub async fn get_approved_script_for_schedule(
+    schedule: &Schedule,
+    key: &ServerKey,
+    pool: &SqlitePool,
+) -> Option<Script> {
+    let filter = match schedule.script_revision {
+        Some(revision) => format!(
+            "script_id='{}' AND revision={revision} AND approved_by IS NOT NULL",
+            schedule.script_id
+        ),
+        None => format!(
+            "script_id='{}' AND approved_by IS NOT NULL ORDER BY revision DESC LIMIT 1",
+            schedule.script_id
+        ),
+    };
+    let revision = get_revisions_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .into_iter()
+        .next()?;
+    // a key inserted into the database must not be able to sign approvals
+    if !revision.approval_is_valid(&key.trusted_keys()) {
+        warn!(
+            "approval of script {} revision {} has no valid signature",
+            revision.script_id, revision.revision
+        );
+        return None;
+    }
+    Some(revision.script)
+}
+
+/// Script to dispatch for `schedule`, with `require_approval` only an approved revision
+/// signed by the active key of the server
+pub async fn get_dispatch_script_for_schedule(
+    schedule: &Schedule,
+    require_approval: bool,
+    pool: &SqlitePool,
+) -> Option<Script> {
+    if !require_approval {
+        return get_script_for_schedule(schedule, pool.acquire().await.unwrap()).await;
+    }
+    let Some(key) = active_key() else {
+        warn!("no signing key loaded, approvals cannot be verified");
+        return None;
+    };
+    get_approved_script_for_schedule(schedule, &key, pool).await
+}
+
+/// Approve a revision as `approver` and sign the approval with `key`
+pub async fn approve_revision(
+    script_id: Uuid,
+    revision: i64,
+    approver: &str,
+    key: &ServerKey,
+    pool: &SqlitePool,
+) -> Result<ScriptRevision, ApprovalError> {
+    let mut rev = get_revision(script_id, revision, pool.acquire().await.unwrap())
+        .await
+        .ok_or(ApprovalError::NotFound)?;
+    if rev.approved_by.is_some() {
+        return Err(ApprovalError::AlreadyApproved);
+    }
+    if rev.author == approver {
+        return Err(ApprovalError::SelfApproval);
+    }
+    rev.approved_by = Some(approver.to_string());
+    rev.approved = Some(Utc::now());
+    let signature = key.sign(&rev.approval_payload());
+    let q = "UPDATE script_revisions SET approved_by = ?, approved = ?, approval_signature = ? WHERE id = ?";
+    query(q)
+        .bind(approver)
+        .bind(rev.approved.map(utc_to_str))
+        .bind(serde_json::to_string(&signature).unwrap())
+        .bind(rev.id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await
+        .map_err(|e| ApprovalError::Signing(e.to_string()))?;
+    rev.approval_signature = Some(signature);
+    Ok(rev)
+}
+
+/// All fields of the script but its content as pretty JSON with sorted keys
+fn settings(script: &Script) -> String {
+    let mut value = serde_json::to_value(script).unwrap();
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("script_content");
+        fields.remove("revision");
+    }
+    serde_json::to_string_pretty(&value).unwrap() + "\n"
+}
+
+/// unified diff of the script content between two revisions, followed by a diff of the other
+/// script fields if they changed
+pub fn diff(from: &ScriptRevision, to: &ScriptRevision) -> String {
+    let content = TextDiff::from_lines(&from.script.script_content, &to.script.script_content)
+        .unified_diff()
+        .header(
+            &format!("revision {}", from.revision),
+            &format!("revision {}", to.revision),
+        )
+        .to_string();
+    let (from_settings, to_settings) = (settings(&from.script), settings(&to.script));
+    let settings = TextDiff::from_lines(&from_settings, &to_settings)
+        .unified_diff()
+        .header(
+            &format!("revision {} settings", from.revision),
+            &format!("revision {} settings", to.revision),
+        )
+        .to_string();
+    content + &settings
+}
+
+/// API to get all revisions of a script
+pub async fn get_script_revisions_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("script_id='{id}' ORDER BY revision ASC");
+    let revision_vec = get_revisions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(revision_vec)
+}
+
+/// API to get one revision of a script
+pub async fn get_one_script_revision_api(
+    _claims: Claims,
+    Path((id, revision)): Path<(Uuid, i64)>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    Json(get_revision(id, revision, pool.acquire().await.unwrap()).await)
+}
+
+/// API to approve a revision, the approver has to be a different user than the author
+pub async fn approve_script_revision_api(
+    claims: Claims,
+    Path((id, revision)): Path<(Uuid, i64)>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let Some(key) = active_key() else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "No signing key loaded").into_response();
+    };
+    let approver = claims.sub.to_string();
+    match approve_revision(id, revision, &approver, &key, &pool).await {
+        Ok(rev) => Json(rev).into_response(),
+        Err(e @ ApprovalError::NotFound) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        Err(e @ ApprovalError::SelfApproval) => {
+            (StatusCode::FORBIDDEN, e.to_string()).into_response()
+        }
+        Err(e @ ApprovalError::AlreadyApproved) => {
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffParams {
+    from: i64,
+    to: i64,
+}
+
+/// API to get the unified diff of the script content between two revisions
+pub async fn get_script_diff_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DiffParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let from = get_revision(id, params.from, pool.acquire().await.unwrap()).await;
+    let to = get_revision(id, params.to, pool.acquire().await.unwrap()).await;
+    let (Some(from), Some(to)) = (from, to) else {
+        return (StatusCode::NOT_FOUND, "Revision not found").into_response();
+    };
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        diff(&from, &to),
+    )
+        .into_response()
+}
+
+pub async fn get_revisions_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<ScriptRevision> {
+    let stmt = if let Some(f) = filter {
+        format!("SELECT * FROM script_revisions WHERE {f}")
+    } else {
+        "SELECT * FROM script_revisions".into()
+    };
+    let revisions = match query(&stmt).fetch_all(&mut *connection).await {
+        Ok(d) => d,
+        Err(_) => return Vec::new(),
+    };
+
+    revisions.into_iter().map(|s| s.into()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        script::Interpreter,
+    };
+    use std::collections::HashMap;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_revisions() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let mut script = Script {
+            id: Uuid::new_v4(),
+            script_content: "echo one\necho two\n".into(),
+            ..Default::default()
+        };
+        let r1 = save_script(script.clone(), "a@test.int", "initial", &pool).await;
+        assert_eq!(r1.unwrap(), 1);
+        // unchanged content, no new revision
+        let r1 = save_script(script.clone(), "a@test.int", "again", &pool).await;
+        assert_eq!(r1.unwrap(), 1);
+        script.script_content = "echo one\necho three\n".into();
+        let r2 = save_script(script.clone(), "b@test.int", "three", &pool).await;
+        assert_eq!(r2.unwrap(), 2);
+
+        let filter = format!("script_id='{}'", script.id);
+        let revisions = get_revisions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(revisions.len(), 2);
+
+        let filter = format!("id='{}'", script.id);
+        let head = get_scripts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(head[0].revision, 2);
+        assert_eq!(head[0].script_content, "echo one\necho three\n");
+
+        // pinned schedule keeps the old content
+        let pinned = Schedule {
+            script_id: script.id,
+            script_revision: Some(1),
+            ..Default::default()
+        };
+        let pinned_script = get_script_for_schedule(&pinned, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(pinned_script.script_content, "echo one\necho two\n");
+        let latest = Schedule {
+            script_id: script.id,
+            ..Default::default()
+        };
+        let latest_script = get_script_for_schedule(&latest, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(latest_script.revision, 2);
+
+        let from = get_revision(script.id, 1, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let to = get_revision(script.id, 2, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            diff(&from, &to),
+            "--- revision 1\n+++ revision 2\n@@ -1,2 +1,2 @@\n echo one\n-echo two\n+echo three\n"
+        );
+
+        // approvals
+        let path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        let key = ServerKey::load(&path, &pool).await.unwrap();
+        assert!(get_approved_script_for_schedule(&latest, &key, &pool)
+            .await
+            .is_none());
+        let self_approval = approve_revision(script.id, 2, "b@test.int", &key, &pool).await;
+        assert_eq!(self_approval.unwrap_err(), ApprovalError::SelfApproval);
+        let missing = approve_revision(script.id, 3, "a@test.int", &key, &pool).await;
+        assert_eq!(missing.unwrap_err(), ApprovalError::NotFound);
+        let approved = approve_revision(script.id, 1, "b@test.int", &key, &pool)
+            .await
+            .unwrap();
+        assert_eq!(approved.approved_by.as_deref(), Some("b@test.int"));
+        let again = approve_revision(script.id, 1, "c@test.int", &key, &pool).await;
+        assert_eq!(again.unwrap_err(), ApprovalError::AlreadyApproved);
+        // latest approved revision is 1, even though 2 exists
+        let approved_script = get_approved_script_for_schedule(&latest, &key, &pool)
+            .await
+            .unwrap();
+        assert_eq!(approved_script.revision, 1);
+        assert!(get_approved_script_for_schedule(
+            &Schedule {
+                script_revision: Some(2),
+                ..latest.clone()
+            },
+            &key,
+            &pool
+        )
+        .await
+        .is_none());
+        // tampered snapshot invalidates the approval
+        query("UPDATE script_revisions SET approved_by='mallory@test.int' WHERE revision=1")
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert!(get_approved_script_for_schedule(&latest, &key, &pool)
+            .await
+            .is_none());
+        // a key published into the database cannot sign approvals
+        let forged_path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        let forged_key = ServerKey::load(&forged_path, &pool).await.unwrap();
+        let forged = approve_revision(script.id, 2, "mallory@test.int", &forged_key, &pool)
+            .await
+            .unwrap();
+        let published = crate::signing::get_keys_from_db(None, pool.acquire().await.unwrap()).await;
+        assert!(forged.approval_is_valid(&published));
+        assert!(get_approved_script_for_schedule(&latest, &key, &pool)
+            .await
+            .is_none());
+        // without approvals the latest revision is dispatched, with approvals none is left
+        let dispatched = get_dispatch_script_for_schedule(&latest, false, &pool)
+            .await
+            .unwrap();
+        assert_eq!(dispatched.revision, 2);
+        assert!(get_dispatch_script_for_schedule(&latest, true, &pool)
+            .await
+            .is_none());
+        let _rm = std::fs::remove_file(forged_path);
+        let _rm = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let mut script = Script {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let _r1 = save_script(script.clone(), "a@test.int", "", &pool).await;
+        script.script_content = "uptime".into();
+        let _r2 = save_script(script.clone(), "a@test.int", "", &pool).await;
+
+        let api_get_all = get_script_revisions_api(
+            claims.clone(),
+            axum::extract::Path(script.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+
+        let api_get_one = get_one_script_revision_api(
+            claims.clone(),
+            axum::extract::Path((script.id, 1)),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+
+        let api_diff = get_script_diff_api(
+            claims.clone(),
+            axum::extract::Path(script.id),
+            axum::extract::Query(DiffParams { from: 1, to: 2 }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_diff.status(), StatusCode::OK);
+
+        let api_diff_missing = get_script_diff_api(
+            claims.clone(),
+            axum::extract::Path(script.id),
+            axum::extract::Query(DiffParams { from: 1, to: 3 }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_diff_missing.status(), StatusCode::NOT_FOUND);
+
+        let path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        crate::signing::set_active_key(ServerKey::load(&path, &pool).await.unwrap());
+        let api_approve = approve_script_revision_api(
+            claims.clone(),
+            axum::extract::Path((script.id, 2)),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_approve.status(), StatusCode::OK);
+        let api_approve_missing = approve_script_revision_api(
+            claims.clone(),
+            axum::extract::Path((script.id, 3)),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_approve_missing.status(), StatusCode::NOT_FOUND);
+        let _rm = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_diff_settings() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let mut script = Script {
+            id: Uuid::new_v4(),
+            script_content: "print('one')\n".into(),
+            ..Default::default()
+        };
+        let _r1 = save_script(script.clone(), "a@test.int", "initial", &pool).await;
+        // same content for another interpreter and environment
+        script.interpreter = Interpreter::Python3;
+        script.environment = HashMap::from([("LANG".to_string(), "C".to_string())]);
+        let r2 = save_script(script.clone(), "a@test.int", "python", &pool).await;
+        assert_eq!(r2.unwrap(), 2);
+
+        let from = get_revision(script.id, 1, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let to = get_revision(script.id, 2, pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let changes = diff(&from, &to);
+        assert!(changes.starts_with("--- revision 1 settings\n+++ revision 2 settings\n"));
+        assert!(changes.contains("\n-  \"environment\": {},\n"));
+        assert!(changes.contains("\n+    \"LANG\": \"C\"\n"));
+        assert!(changes.contains("\n-  \"interpreter\": \"bash\",\n"));
+        assert!(changes.contains("\n+  \"interpreter\": \"python3\",\n"));
+        assert!(!changes.contains("script_content"));
+    }
+}
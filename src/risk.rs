@@ -0,0 +1,536 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    advisory::{host_vulnerabilities, Severity, Vulnerability},
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    package::PackageFormat,
+    version::compare,
+};
+
+/// days after which an available but not installed fix doubles the risk
+const FIX_AGE_DAYS: i64 = 90;
+
+/// factor for CVEs in the known exploited vulnerabilities catalog
+const EXPLOITED_FACTOR: f64 = 2.0;
+
+/// Criticality of a host, set with the attribute `criticality:<level>`
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Criticality {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+impl Criticality {
+    /// criticality of the `criticality:<level>` attribute of `host`, medium without one
+    pub fn of(host: &Host) -> Criticality {
+        host.attributes
+            .iter()
+            .filter_map(|a| a.strip_prefix("criticality:"))
+            .find_map(|level| match level {
+                "low" => Some(Criticality::Low),
+                "medium" => Some(Criticality::Medium),
+                "high" => Some(Criticality::High),
+                "critical" => Some(Criticality::Critical),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn weight(&self) -> f64 {
+        match self {
+            Criticality::Low => 0.5,
+            Criticality::Medium => 1.0,
+            Criticality::High => 1.5,
+            Criticality::Critical => 2.0,
+        }
+    }
+}
+
+/// risk of a vulnerability on a host of medium criticality
+///
+/// the CVSS base score (or the middle of its severity range) doubles if the CVE is known to be
+/// exploited and grows linearly up to the double while a fix is available but not installed
+pub fn vulnerability_risk(v: &Vulnerability, now: DateTime<Utc>) -> f64 {
+    let base = v.cvss.unwrap_or(match v.severity {
+        Severity::Critical => 9.5,
+        Severity::High => 8.0,
+        Severity::Medium => 5.5,
+        Severity::Low => 2.0,
+        Severity::Unknown => 5.0,
+    });
+    let exploited = match v.known_exploited {
+        true => EXPLOITED_FACTOR,
+        false => 1.0,
+    };
+    let age = 1.0 + fix_age(v, now).min(FIX_AGE_DAYS) as f64 / FIX_AGE_DAYS as f64;
+    base * exploited * age
+}
+
+/// days the fix of `v` is available, 0 without a fix
+fn fix_age(v: &Vulnerability, now: DateTime<Utc>) -> i64 {
+    v.fixed_since
+        .map(|ts| (now - ts).num_days().max(0))
+        .unwrap_or(0)
+}
+
+fn round(score: f64) -> f64 {
+    (score * 10.0).round() / 10.0
+}
+
+/// Risk score of a host, waived vulnerabilities are not counted
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostRisk {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub criticality: Criticality,
+    /// sum of the vulnerability risks weighted by the criticality
+    pub score: f64,
+    pub vulnerabilities: usize,
+    pub known_exploited: usize,
+    /// most severe vulnerability
+    pub max_severity: Severity,
+    /// days the oldest not installed fix is available
+    pub oldest_fix_days: Option<i64>,
+}
+
+/// Package to update on a host, with the CVEs it fixes
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct PatchPriority {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub criticality: Criticality,
+    pub format: PackageFormat,
+    pub package: String,
+    /// SBOM component of the package, empty for the host inventory
+    pub component: String,
+    pub installed_version: String,
+    /// lowest version fixing all listed CVEs
+    pub fixed_version: String,
+    pub cves: Vec<String>,
+    pub max_severity: Severity,
+    pub known_exploited: bool,
+    /// days the oldest fix is available
+    pub fix_days: i64,
+    /// risk removed by the update
+    pub score: f64,
+}
+
+/// risk of `host` and its packages to update, most risk first
+pub async fn host_risk(
+    host: &Host,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> (HostRisk, Vec<PatchPriority>) {
+    let criticality = Criticality::of(host);
+    let mut vulnerabilities = host_vulnerabilities(host, pool).await;
+    vulnerabilities.retain(|v| v.waiver_id.is_none());
+    let mut patches: BTreeMap<(&str, &str), PatchPriority> = BTreeMap::new();
+    for v in &vulnerabilities {
+        let Some(fixed) = &v.fixed_version else {
+            continue;
+        };
+        let risk = vulnerability_risk(v, now) * criticality.weight();
+        let patch = patches
+            .entry((v.component.as_str(), v.package.as_str()))
+            .or_insert_with(|| PatchPriority {
+                host_id: host.id,
+                alias: host.alias.clone(),
+                criticality,
+                format: v.format,
+                package: v.package.clone(),
+                component: v.component.clone(),
+                installed_version: v.installed_version.clone(),
+                fixed_version: fixed.clone(),
+                cves: vec![],
+                max_severity: v.severity,
+                known_exploited: false,
+                fix_days: 0,
+                score: 0.0,
+            });
+        if compare(v.format, fixed, &patch.fixed_version) == Ordering::Greater {
+            patch.fixed_version = fixed.clone();
+        }
+        patch.cves.push(v.cve.clone());
+        patch.max_severity = patch.max_severity.max(v.severity);
+        patch.known_exploited |= v.known_exploited;
+        patch.fix_days = patch.fix_days.max(fix_age(v, now));
+        patch.score += risk;
+    }
+    let score: f64 = vulnerabilities
+        .iter()
+        .map(|v| vulnerability_risk(v, now))
+        .sum::<f64>()
+        * criticality.weight();
+    let risk = HostRisk {
+        host_id: host.id,
+        alias: host.alias.clone(),
+        criticality,
+        score: round(score),
+        vulnerabilities: vulnerabilities.len(),
+        known_exploited: vulnerabilities.iter().filter(|v| v.known_exploited).count(),
+        max_severity: vulnerabilities
+            .iter()
+            .map(|v| v.severity)
+            .max()
+            .unwrap_or_default(),
+        oldest_fix_days: vulnerabilities
+            .iter()
+            .filter(|v| v.fixed_since.is_some())
+            .map(|v| fix_age(v, now))
+            .max(),
+    };
+    let mut patches: Vec<PatchPriority> = patches
+        .into_values()
+        .map(|p| PatchPriority {
+            score: round(p.score),
+            ..p
+        })
+        .collect();
+    sort_patches(&mut patches);
+    (risk, patches)
+}
+
+fn sort_patches(patches: &mut [PatchPriority]) {
+    patches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.alias.cmp(&b.alias))
+            .then_with(|| a.package.cmp(&b.package))
+    });
+}
+
+/// risk of all hosts and their packages to update, most risk first
+pub async fn fleet_risk(
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> (Vec<HostRisk>, Vec<PatchPriority>) {
+    let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+    let mut risks = vec![];
+    let mut patches = vec![];
+    for host in &hosts {
+        let (risk, host_patches) = host_risk(host, now, pool).await;
+        risks.push(risk);
+        patches.extend(host_patches);
+    }
+    risks.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.alias.cmp(&b.alias))
+    });
+    sort_patches(&mut patches);
+    (risks, patches)
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PatchQueryParams {
+    host_id: Option<Uuid>,
+    /// minimum severity of the fixed CVEs
+    severity: Option<Severity>,
+    known_exploited: Option<bool>,
+    limit: Option<usize>,
+}
+
+/// API to get the risk score of all hosts, most risk first
+pub async fn get_risk_api(_claims: Claims, State(pool): State<SqlitePool>) -> impl IntoResponse {
+    let (risks, _patches) = fleet_risk(Utc::now(), &pool).await;
+    Json(risks)
+}
+
+/// API to get the risk score of a host
+pub async fn get_host_risk_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let filter = format!("id='{id}'");
+    let hosts = get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let Some(host) = hosts.first() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (risk, _patches) = host_risk(host, Utc::now(), &pool).await;
+    Json(risk).into_response()
+}
+
+/// API to get the package updates of all hosts, the one removing the most risk first
+pub async fn get_patch_priorities_api(
+    _claims: Claims,
+    Query(params): Query<PatchQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let (_risks, mut patches) = fleet_risk(Utc::now(), &pool).await;
+    patches.retain(|p| {
+        params.host_id.is_none_or(|id| p.host_id == id)
+            && params.severity.is_none_or(|s| p.max_severity >= s)
+            && params
+                .known_exploited
+                .is_none_or(|k| p.known_exploited == k)
+    });
+    if let Some(limit) = params.limit {
+        patches.truncate(limit);
+    }
+    Json(patches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        advisory::{import, store_advisories, Advisory, AdvisoryFormat, Affected},
+        db::{create_database, init_database},
+        fixtures,
+        package::{store_inventory, Package},
+        update::{store_updates, Update},
+        waiver::{save_waiver, Waiver},
+    };
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn advisory(
+        id: &str,
+        name: &str,
+        fixed: Option<&str>,
+        severity: Severity,
+        cvss: Option<f64>,
+        published: Option<DateTime<Utc>>,
+    ) -> Advisory {
+        Advisory {
+            id: id.into(),
+            aliases: vec![],
+            summary: "".into(),
+            severity,
+            cvss,
+            published,
+            modified: None,
+            affected: vec![Affected {
+                source: AdvisoryFormat::Osv,
+                format: PackageFormat::Dpkg,
+                distro: "".into(),
+                release: "".into(),
+                name: name.into(),
+                introduced: "".into(),
+                fixed: fixed.map(|f| f.into()),
+                last_affected: None,
+            }],
+        }
+    }
+
+    /// active host with `packages` installed
+    async fn host(alias: &str, attributes: &[&str], packages: &[&str], pool: &SqlitePool) -> Host {
+        let host = fixtures::host(alias, attributes, pool).await;
+        let packages = packages
+            .iter()
+            .map(|name| Package {
+                format: PackageFormat::Dpkg,
+                name: name.to_string(),
+                version: "1.0".into(),
+                arch: "amd64".into(),
+                source: "".into(),
+            })
+            .collect();
+        let _c = store_inventory(host.id, packages, Utc::now(), pool)
+            .await
+            .unwrap();
+        host
+    }
+
+    async fn setup(now: DateTime<Utc>, pool: &SqlitePool) -> (Host, Host) {
+        let advisories = vec![
+            advisory(
+                "CVE-1",
+                "openssl",
+                Some("2.0"),
+                Severity::Critical,
+                Some(9.8),
+                Some(now - Duration::days(200)),
+            ),
+            advisory(
+                "CVE-2",
+                "openssl",
+                Some("2.1"),
+                Severity::Medium,
+                None,
+                None,
+            ),
+            advisory("CVE-3", "curl", Some("8.0"), Severity::High, None, None),
+            advisory("CVE-4", "zlib", None, Severity::Low, None, None),
+        ];
+        let _s = store_advisories(advisories, now, pool).await.unwrap();
+        let kev = r#"{"vulnerabilities": [{"cveID": "CVE-2", "vulnerabilityName": "", "dateAdded": "2024-07-01"}]}"#;
+        let _s = import(AdvisoryFormat::Kev, kev, now, pool).await.unwrap();
+        let web = host(
+            "web-1",
+            &["prod", "criticality:critical"],
+            &["openssl", "curl", "zlib"],
+            pool,
+        )
+        .await;
+        let dev = host("dev-1", &[], &["openssl"], pool).await;
+        let curl = Update {
+            format: PackageFormat::Dpkg,
+            name: "curl".into(),
+            arch: "amd64".into(),
+            installed_version: "".into(),
+            available_version: "8.0".into(),
+            security: true,
+            advisory: "".into(),
+        };
+        store_updates(web.id, vec![curl], now - Duration::days(45), pool)
+            .await
+            .unwrap();
+        (web, dev)
+    }
+
+    #[tokio::test]
+    async fn test_risk() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let now = Utc::now();
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (web, dev) = setup(now, &pool).await;
+        assert_eq!(Criticality::of(&web), Criticality::Critical);
+        assert_eq!(Criticality::of(&dev), Criticality::Medium);
+
+        // CVE-1 9.8 with a fix older than 90 days, CVE-2 5.5 exploited,
+        // CVE-3 8.0 with a pending update since 45 days, CVE-4 2.0 without fix
+        let (risk, patches) = host_risk(&web, now, &pool).await;
+        assert_eq!(risk.vulnerabilities, 4);
+        assert_eq!(risk.known_exploited, 1);
+        assert_eq!(risk.max_severity, Severity::Critical);
+        assert_eq!(risk.oldest_fix_days, Some(200));
+        assert_eq!(risk.score, 89.2);
+        let ranked: Vec<(&str, &str, f64)> = patches
+            .iter()
+            .map(|p| (p.package.as_str(), p.fixed_version.as_str(), p.score))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("openssl", "2.1", 61.2), ("curl", "8.0", 24.0)]
+        );
+        assert_eq!(patches[0].cves, vec!["CVE-1", "CVE-2"]);
+        assert!(patches[0].known_exploited);
+        assert_eq!(patches[1].fix_days, 45);
+
+        // fleet-wide the critical host comes first
+        let (risks, patches) = fleet_risk(now, &pool).await;
+        let hosts: Vec<(&str, f64)> = risks.iter().map(|r| (r.alias.as_str(), r.score)).collect();
+        assert_eq!(hosts, vec![("web-1", 89.2), ("dev-1", 30.6)]);
+        let queue: Vec<(&str, &str)> = patches
+            .iter()
+            .map(|p| (p.alias.as_str(), p.package.as_str()))
+            .collect();
+        assert_eq!(
+            queue,
+            vec![
+                ("web-1", "openssl"),
+                ("dev-1", "openssl"),
+                ("web-1", "curl")
+            ]
+        );
+
+        // waived vulnerabilities do not count
+        let waiver = Waiver {
+            id: Uuid::new_v4(),
+            cve: Some("CVE-1".into()),
+            package: None,
+            host_id: None,
+            attributes: vec!["prod".into()],
+            justification: "openssl is not exposed".into(),
+            approver: "ciso@example.com".into(),
+            expires: now + Duration::days(30),
+            created_by: "".into(),
+            created: now,
+        };
+        let _id = save_waiver(waiver, "admin@example.com", now, &pool)
+            .await
+            .unwrap();
+        let (risk, patches) = host_risk(&web, now, &pool).await;
+        assert_eq!(risk.vulnerabilities, 3);
+        assert_eq!(risk.score, 50.0);
+        assert_eq!(patches[0].package, "curl");
+        assert_eq!(patches[1].cves, vec!["CVE-2"]);
+        assert_eq!(patches[1].score, 22.0);
+        let (risk, _patches) = host_risk(&dev, now, &pool).await;
+        assert_eq!(risk.score, 30.6);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let (web, _dev) = setup(Utc::now(), &pool).await;
+        let claims: Claims = Claims::default();
+
+        let api_risk = get_risk_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_risk.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_risk.into_body()).await.unwrap();
+        let risks: Vec<HostRisk> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(risks.len(), 2);
+        assert_eq!(risks[0].host_id, web.id);
+
+        let api_host = get_host_risk_api(
+            claims.clone(),
+            axum::extract::Path(web.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_host.status(), StatusCode::OK);
+        let api_unknown = get_host_risk_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_patches = get_patch_priorities_api(
+            claims.clone(),
+            axum::extract::Query(PatchQueryParams {
+                severity: Some(Severity::High),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_patches.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_patches.into_body())
+            .await
+            .unwrap();
+        let patches: Vec<PatchPriority> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].package, "openssl");
+        assert_eq!(patches[0].host_id, web.id);
+    }
+}
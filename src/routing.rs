@@ -0,0 +1,452 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    jwt::Claims,
+    notification::get_channels_from_db,
+};
+
+/// Node of the routing tree, events matching it go to its channels
+///
+/// children are checked in `position` order, the deepest matching route wins,
+/// siblings after a match are only checked if `continue` is set
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Route {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    /// top level route if `None`
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+    #[serde(default)]
+    pub position: i64,
+    /// labels the event must have, `severity` matches the event severity
+    #[serde(default)]
+    pub matchers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub channels: Vec<Uuid>,
+    /// escalation policy started when an alert of this route fires
+    #[serde(default)]
+    pub escalation_id: Option<Uuid>,
+    #[serde(default, rename = "continue")]
+    pub continue_matching: bool,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl Route {
+    /// Insert or Update `Route` in routes table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | name | TEXT |
+    /// | parent_id | TEXT | uuid, NULL for top level routes
+    /// | position | INT |
+    /// | matchers | TEXT | json map
+    /// | channels | TEXT | json list of channel ids
+    /// | escalation_id | TEXT | uuid
+    /// | continue_matching | NUMERIC | bool
+    /// | active | NUMERIC | bool
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO routes(id, name, parent_id, position, matchers, channels, escalation_id, continue_matching, active, created) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, parent_id=excluded.parent_id, position=excluded.position, matchers=excluded.matchers, channels=excluded.channels, escalation_id=excluded.escalation_id, continue_matching=excluded.continue_matching, active=excluded.active"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.name)
+            .bind(self.parent_id.map(|id| id.to_string()))
+            .bind(self.position)
+            .bind(serde_json::to_string(&self.matchers).unwrap())
+            .bind(serde_json::to_string(&self.channels).unwrap())
+            .bind(self.escalation_id.map(|id| id.to_string()))
+            .bind(self.continue_matching)
+            .bind(self.active)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+    }
+
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.matchers.iter().all(|(k, v)| labels.get(k) == Some(v))
+    }
+
+    /// parent exists, is no descendant of this route and all channels exist
+    async fn validate(&self, pool: &SqlitePool) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        let routes = get_routes_from_db(None, pool.acquire().await.unwrap()).await;
+        let mut parent = self.parent_id;
+        while let Some(id) = parent {
+            if id == self.id {
+                return Err("route can not be its own ancestor".into());
+            }
+            parent = routes
+                .iter()
+                .find(|r| r.id == id)
+                .ok_or(format!("parent {id} not found"))?
+                .parent_id;
+        }
+        let channels = get_channels_from_db(None, pool.acquire().await.unwrap()).await;
+        if let Some(missing) = self
+            .channels
+            .iter()
+            .find(|id| !channels.iter().any(|c| c.id == **id))
+        {
+            return Err(format!("channel {missing} not found"));
+        }
+        Ok(())
+    }
+}
+
+impl From<SqliteRow> for Route {
+    fn from(s: SqliteRow) -> Self {
+        let uuid = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .and_then(|id| id.parse().ok())
+        };
+        Route {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            name: s.get::<String, _>("name"),
+            parent_id: uuid("parent_id"),
+            position: s.get::<i64, _>("position"),
+            matchers: serde_json::from_str(&s.get::<String, _>("matchers")).unwrap_or_default(),
+            channels: serde_json::from_str(&s.get::<String, _>("channels")).unwrap_or_default(),
+            escalation_id: uuid("escalation_id"),
+            continue_matching: s.get::<bool, _>("continue_matching"),
+            active: s.get::<bool, _>("active"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+/// routes an event with `labels` is sent to, empty if no top level route matches
+pub fn match_routes<'a>(routes: &'a [Route], labels: &BTreeMap<String, String>) -> Vec<&'a Route> {
+    fn walk<'a>(
+        parent: Option<Uuid>,
+        routes: &'a [Route],
+        labels: &BTreeMap<String, String>,
+        matched: &mut Vec<&'a Route>,
+    ) -> bool {
+        let mut children: Vec<&Route> = routes
+            .iter()
+            .filter(|r| r.active && r.parent_id == parent)
+            .collect();
+        children.sort_by_key(|r| r.position);
+        let mut found = false;
+        for route in children {
+            if !route.matches(labels) {
+                continue;
+            }
+            found = true;
+            if !walk(Some(route.id), routes, labels, matched) {
+                matched.push(route);
+            }
+            if !route.continue_matching {
+                break;
+            }
+        }
+        found
+    }
+    let mut matched = vec![];
+    walk(None, routes, labels, &mut matched);
+    matched
+}
+
+/// API to get all routes
+pub async fn get_routes_api(_claims: Claims, State(pool): State<SqlitePool>) -> impl IntoResponse {
+    let route_vec = get_routes_from_db(
+        Some("1=1 ORDER BY parent_id, position"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    Json(route_vec)
+}
+
+/// API to get one route
+pub async fn get_one_route_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let route_vec = get_routes_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(route_vec)
+}
+
+/// API to create or replace a route
+pub async fn post_routes_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<Route>,
+) -> Response {
+    if let Err(e) = payload.validate(&pool).await {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    let id = payload.id.to_string();
+    match payload.insert_into_db(pool.acquire().await.unwrap()).await {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(_) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Escalation policy not found",
+        )
+            .into_response(),
+    }
+}
+
+/// API to delete a route and its children
+pub async fn delete_one_route_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_routes_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+pub async fn get_routes_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Route> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM routes WHERE {f}"),
+        None => "SELECT * FROM routes".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Route::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_routes_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM routes WHERE {f}"),
+        None => "DELETE FROM routes".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_database, init_database};
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn route(
+        name: &str,
+        parent_id: Option<Uuid>,
+        position: i64,
+        matchers: &[(&str, &str)],
+    ) -> Route {
+        Route {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            parent_id,
+            position,
+            matchers: matchers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            channels: vec![],
+            escalation_id: None,
+            continue_matching: false,
+            active: true,
+            created: Utc::now(),
+        }
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let ops = route("ops", None, 0, &[("team", "ops")]);
+        let ops_critical = route("ops critical", Some(ops.id), 0, &[("severity", "critical")]);
+        let mut dev = route("dev", None, 1, &[("team", "dev")]);
+        dev.continue_matching = true;
+        let audit = route("audit", None, 2, &[]);
+        let routes = vec![
+            audit.clone(),
+            dev.clone(),
+            ops_critical.clone(),
+            ops.clone(),
+        ];
+        let names = |labels: &BTreeMap<String, String>| -> Vec<String> {
+            match_routes(&routes, labels)
+                .iter()
+                .map(|r| r.name.clone())
+                .collect()
+        };
+
+        // deepest match wins
+        assert_eq!(
+            names(&labels(&[("team", "ops"), ("severity", "critical")])),
+            vec!["ops critical"]
+        );
+        assert_eq!(
+            names(&labels(&[("team", "ops"), ("severity", "warning")])),
+            vec!["ops"]
+        );
+        // continue checks the siblings after a match
+        assert_eq!(names(&labels(&[("team", "dev")])), vec!["dev", "audit"]);
+        // catch all
+        assert_eq!(names(&labels(&[("team", "qa")])), vec!["audit"]);
+        // inactive routes are skipped
+        let mut inactive = routes.clone();
+        inactive[0].active = false;
+        assert!(match_routes(&inactive, &labels(&[("team", "qa")])).is_empty());
+
+        // stored and validated
+        for r in [ops.clone(), ops_critical.clone()] {
+            assert!(r.validate(&pool).await.is_ok());
+            let _r = r
+                .insert_into_db(pool.acquire().await.unwrap())
+                .await
+                .unwrap();
+        }
+        let stored = get_routes_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(stored.len(), 2);
+        // updating a parent keeps its children
+        let mut renamed = ops.clone();
+        renamed.name = "operations".into();
+        let _r = renamed
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let stored = get_routes_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(stored.len(), 2);
+        let mut cycle = ops.clone();
+        cycle.parent_id = Some(ops_critical.id);
+        assert!(cycle.validate(&pool).await.is_err());
+        let mut orphan = ops.clone();
+        orphan.parent_id = Some(Uuid::new_v4());
+        assert!(orphan.validate(&pool).await.is_err());
+        let mut unknown_channel = ops.clone();
+        unknown_channel.channels = vec![Uuid::new_v4()];
+        assert!(unknown_channel.validate(&pool).await.is_err());
+
+        // deleting a route deletes its children
+        let filter = format!("id='{}'", ops.id);
+        let _d = delete_routes_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert!(get_routes_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let ops = route("ops", None, 0, &[("team", "ops")]);
+        let api_post = post_routes_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(ops.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let mut unknown_policy = route("unknown", None, 1, &[]);
+        unknown_policy.escalation_id = Some(Uuid::new_v4());
+        let api_post_unknown = post_routes_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(unknown_policy),
+        )
+        .await;
+        assert_eq!(api_post_unknown.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let orphan = route("orphan", Some(Uuid::new_v4()), 0, &[]);
+        let api_post_orphan = post_routes_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(orphan),
+        )
+        .await;
+        assert_eq!(api_post_orphan.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_all = get_routes_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+
+        let api_get_one = get_one_route_api(
+            claims.clone(),
+            axum::extract::Path(ops.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get_one.into_body())
+            .await
+            .unwrap();
+        let stored: Vec<Route> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stored[0].matchers, ops.matchers);
+
+        let api_del = delete_one_route_api(
+            claims.clone(),
+            axum::extract::Path(ops.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,898 @@
+use std::{collections::HashMap, fmt::Display};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    host::get_hosts_from_db,
+    jwt::Claims,
+    package::{store_component_inventory, Package, PackageFormat},
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SbomFormat {
+    Cyclonedx,
+    Spdx,
+}
+
+impl Display for SbomFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self {
+            SbomFormat::Cyclonedx => "cyclonedx",
+            SbomFormat::Spdx => "spdx",
+        };
+        write!(f, "{format}")
+    }
+}
+
+impl SbomFormat {
+    fn from_db(s: &str) -> SbomFormat {
+        match s {
+            "spdx" => SbomFormat::Spdx,
+            _ => SbomFormat::Cyclonedx,
+        }
+    }
+}
+
+/// Software bill of materials normalized into packages
+#[derive(PartialEq, Debug, Clone)]
+pub struct Sbom {
+    pub format: SbomFormat,
+    pub spec_version: String,
+    /// component the document describes, empty if it names none
+    pub name: String,
+    /// os-release id of the operating system in the SBOM
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub packages: Vec<Package>,
+    /// components without a supported package url
+    pub skipped: usize,
+}
+
+/// SBOM stored for a component of a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostSbom {
+    pub host_id: Uuid,
+    pub component: String,
+    pub format: SbomFormat,
+    pub spec_version: String,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub packages: i64,
+    pub skipped: i64,
+    pub uploaded: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for HostSbom {
+    fn from(s: SqliteRow) -> Self {
+        HostSbom {
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            component: s.get::<String, _>("component"),
+            format: SbomFormat::from_db(&s.get::<String, _>("format")),
+            spec_version: s.get::<String, _>("spec_version"),
+            os: s.get::<Option<String>, _>("os"),
+            os_version: s.get::<Option<String>, _>("os_version"),
+            packages: s.get::<i64, _>("packages"),
+            skipped: s.get::<i64, _>("skipped"),
+            uploaded: utc_from_str(&s.get::<String, _>("uploaded")),
+        }
+    }
+}
+
+impl HostSbom {
+    /// facts advisories of the component are matched against, None if the SBOM names no
+    /// operating system and the facts of the host apply
+    pub fn facts(&self) -> Option<HashMap<String, String>> {
+        let os = self.os.clone()?;
+        let mut facts = HashMap::from([("os".to_string(), os)]);
+        if let Some(version) = &self.os_version {
+            let key = match version.starts_with(|c: char| c.is_ascii_digit()) {
+                true => "os_version",
+                false => "os_codename",
+            };
+            facts.insert(key.to_string(), version.clone());
+        }
+        Some(facts)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct CycloneDx {
+    spec_version: String,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CycloneDxMetadata {
+    component: Option<CycloneDxComponent>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    version: String,
+    purl: String,
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Spdx {
+    spdx_version: String,
+    name: String,
+    packages: Vec<SpdxPackage>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    version_info: String,
+    primary_package_purpose: String,
+    external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_type: String,
+    reference_locator: String,
+}
+
+/// package url, `pkg:type/namespace/name@version?qualifiers#subpath`
+#[derive(PartialEq, Debug)]
+struct Purl {
+    kind: String,
+    namespace: String,
+    name: String,
+    version: String,
+    qualifiers: HashMap<String, String>,
+}
+
+/// decode `%XX` escapes
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_purl(purl: &str) -> Option<Purl> {
+    let rest = purl.strip_prefix("pkg:")?.trim_start_matches('/');
+    let rest = rest.split_once('#').map_or(rest, |(rest, _subpath)| rest);
+    let (rest, qualifiers) = rest.split_once('?').unwrap_or((rest, ""));
+    let (kind, path) = rest.split_once('/')?;
+    let (path, version) = match path.rsplit_once('@') {
+        Some((path, version)) if !version.contains('/') => (path, version),
+        _ => (path, ""),
+    };
+    let (namespace, name) = path
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .unwrap_or(("", path));
+    let qualifiers = qualifiers
+        .split('&')
+        .filter_map(|q| q.split_once('='))
+        .map(|(k, v)| (k.to_lowercase(), percent_decode(v)))
+        .collect();
+    let purl = Purl {
+        kind: kind.to_lowercase(),
+        namespace: percent_decode(namespace),
+        name: percent_decode(name),
+        version: percent_decode(version),
+        qualifiers,
+    };
+    (!purl.name.is_empty()).then_some(purl)
+}
+
+impl Purl {
+    fn format(&self) -> Option<PackageFormat> {
+        let format = match self.kind.as_str() {
+            "deb" => PackageFormat::Dpkg,
+            "rpm" => PackageFormat::Rpm,
+            "apk" => PackageFormat::Apk,
+            "alpm" => PackageFormat::Pacman,
+            "npm" => PackageFormat::Npm,
+            "pypi" => PackageFormat::Pypi,
+            "maven" => PackageFormat::Maven,
+            "golang" => PackageFormat::Golang,
+            "cargo" => PackageFormat::Cargo,
+            "gem" => PackageFormat::Gem,
+            "nuget" => PackageFormat::Nuget,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// package named like the advisories of its ecosystem, `version` if the purl has none
+    fn package(&self, version: &str) -> Option<Package> {
+        let format = self.format()?;
+        let name = match (format, self.namespace.is_empty()) {
+            (PackageFormat::Npm | PackageFormat::Golang, false) => {
+                format!("{}/{}", self.namespace, self.name)
+            }
+            (PackageFormat::Maven, false) => format!("{}:{}", self.namespace, self.name),
+            _ => self.name.clone(),
+        };
+        let version = match self.version.is_empty() {
+            true => version,
+            false => &self.version,
+        };
+        let version = match self.qualifiers.get("epoch") {
+            Some(epoch) if format == PackageFormat::Rpm && epoch != "0" => {
+                format!("{epoch}:{version}")
+            }
+            _ => version.to_string(),
+        };
+        let source = self
+            .qualifiers
+            .get("upstream")
+            .map(|u| u.split_once('@').map_or(u.as_str(), |(name, _)| name))
+            .unwrap_or_default();
+        Some(Package {
+            format,
+            name: format.normalize_name(&name),
+            version,
+            arch: self.qualifiers.get("arch").cloned().unwrap_or_default(),
+            source: source.to_string(),
+        })
+    }
+
+    /// distribution from the `distro` qualifier of os packages, e.g. `debian-12` or `bookworm`
+    fn distro(&self) -> Option<(String, Option<String>)> {
+        let distro = self.qualifiers.get("distro")?;
+        match distro.rsplit_once('-') {
+            Some((os, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                Some((os.to_lowercase(), Some(version.to_string())))
+            }
+            _ if !self.namespace.is_empty() => {
+                Some((self.namespace.to_lowercase(), Some(distro.clone())))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// collect the packages of `purls` with their fallback version into `sbom`
+fn add_packages<'a>(sbom: &mut Sbom, purls: impl Iterator<Item = (&'a str, &'a str)>) {
+    let mut distro = None;
+    for (purl, version) in purls {
+        match parse_purl(purl).and_then(|p| Some((p.package(version)?, p))) {
+            Some((package, _)) if package.version.is_empty() => sbom.skipped += 1,
+            Some((package, purl)) => {
+                distro = distro.or(purl.distro());
+                sbom.packages.push(package);
+            }
+            None => sbom.skipped += 1,
+        }
+    }
+    if let (None, Some((os, version))) = (&sbom.os, distro) {
+        sbom.os = Some(os);
+        sbom.os_version = version;
+    }
+}
+
+fn flatten(components: &[CycloneDxComponent]) -> Vec<&CycloneDxComponent> {
+    components
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(flatten(&c.components)))
+        .collect()
+}
+
+/// parse a CycloneDX or SPDX json document
+///
+/// components are identified by their package url (`purl`), deb, rpm, apk, alpm, npm, pypi,
+/// maven, golang, cargo, gem and nuget are supported, other components are counted as skipped;
+/// the operating system is taken from an `operating-system` component, else from the `distro`
+/// qualifier of the os packages
+pub fn parse_sbom(input: &str) -> Result<Sbom, String> {
+    let document: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let mut sbom = Sbom {
+        format: SbomFormat::Cyclonedx,
+        spec_version: "".into(),
+        name: "".into(),
+        os: None,
+        os_version: None,
+        packages: vec![],
+        skipped: 0,
+    };
+    if document.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX") {
+        let bom: CycloneDx = serde_json::from_value(document).map_err(|e| e.to_string())?;
+        sbom.spec_version = bom.spec_version;
+        if let Some(subject) = &bom.metadata.component {
+            sbom.name = subject.name.clone();
+        }
+        let components = flatten(&bom.components);
+        if let Some(os) = components.iter().find(|c| c.kind == "operating-system") {
+            sbom.os = Some(os.name.to_lowercase());
+            sbom.os_version = Some(os.version.clone()).filter(|v| !v.is_empty());
+        }
+        let purls = components
+            .iter()
+            .filter(|c| c.kind != "operating-system")
+            .map(|c| (c.purl.as_str(), c.version.as_str()));
+        add_packages(&mut sbom, purls);
+    } else if document.get("spdxVersion").is_some() {
+        let spdx: Spdx = serde_json::from_value(document).map_err(|e| e.to_string())?;
+        sbom.format = SbomFormat::Spdx;
+        sbom.spec_version = spdx.spdx_version;
+        sbom.name = spdx.name;
+        let (os, packages): (Vec<&SpdxPackage>, Vec<&SpdxPackage>) = spdx
+            .packages
+            .iter()
+            .partition(|p| p.primary_package_purpose == "OPERATING-SYSTEM");
+        if let Some(os) = os.first() {
+            sbom.os = Some(os.name.to_lowercase());
+            sbom.os_version = Some(os.version_info.clone()).filter(|v| !v.is_empty());
+        }
+        let purls = packages.iter().map(|p| {
+            let purl = p
+                .external_refs
+                .iter()
+                .find(|r| r.reference_type == "purl")
+                .map(|r| r.reference_locator.as_str())
+                .unwrap_or_default();
+            (purl, p.version_info.as_str())
+        });
+        add_packages(&mut sbom, purls);
+    } else {
+        return Err("neither a CycloneDX nor an SPDX json document".into());
+    }
+    Ok(sbom)
+}
+
+/// parse `input` and replace the packages of `component` on `host_id` with it
+///
+/// without `component` the component named in the SBOM is used
+pub async fn store_sbom(
+    host_id: Uuid,
+    component: Option<&str>,
+    input: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<HostSbom, String> {
+    let sbom = parse_sbom(input)?;
+    let component = match component.filter(|c| !c.is_empty()) {
+        Some(component) => component.to_string(),
+        None if !sbom.name.is_empty() => sbom.name.clone(),
+        None => return Err("component is required, the SBOM names no component".into()),
+    };
+    let host_sbom = HostSbom {
+        host_id,
+        component,
+        format: sbom.format,
+        spec_version: sbom.spec_version,
+        os: sbom.os,
+        os_version: sbom.os_version,
+        packages: sbom.packages.len() as i64,
+        skipped: sbom.skipped as i64,
+        uploaded: now,
+    };
+    let changes =
+        store_component_inventory(host_id, &host_sbom.component, sbom.packages, now, pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    host_sbom
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        "SBOM of {} on host {host_id}: {} packages, {} skipped, {} changes",
+        host_sbom.component,
+        host_sbom.packages,
+        host_sbom.skipped,
+        changes.len()
+    );
+    Ok(host_sbom)
+}
+
+/// remove `component` and its packages from `host_id`, false if it has no SBOM
+pub async fn delete_sbom(
+    host_id: Uuid,
+    component: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<bool, sqlx::Error> {
+    let filter = format!(
+        "host_id='{host_id}' AND component='{}'",
+        component.replace('\'', "''")
+    );
+    if get_sboms_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .is_empty()
+    {
+        return Ok(false);
+    }
+    let removed = store_component_inventory(host_id, component, vec![], now, pool).await?;
+    debug!(
+        "SBOM of {component} on host {host_id} deleted, {} packages removed",
+        removed.len()
+    );
+    let _res = query(&format!("DELETE FROM sboms WHERE {filter}"))
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await?;
+    Ok(true)
+}
+
+impl HostSbom {
+    /// insert or replace the SBOM into the db
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | host_id | TEXT | uuid
+    /// | component | TEXT | component the SBOM describes, e.g. a container
+    /// | format | TEXT | cyclonedx or spdx
+    /// | spec_version | TEXT | e.g. `1.5` or `SPDX-2.3`
+    /// | os | TEXT | os-release id of the component, NULL if unknown
+    /// | os_version | TEXT | NULL if unknown
+    /// | packages | INT | packages stored in the inventory
+    /// | skipped | INT | components without a supported package url
+    /// | uploaded | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO sboms(host_id, component, format, spec_version, os, os_version, packages, skipped, uploaded) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(host_id, component) DO UPDATE SET format=excluded.format, spec_version=excluded.spec_version, os=excluded.os, os_version=excluded.os_version, packages=excluded.packages, skipped=excluded.skipped, uploaded=excluded.uploaded"#;
+        query(q)
+            .bind(self.host_id.to_string())
+            .bind(&self.component)
+            .bind(self.format.to_string())
+            .bind(&self.spec_version)
+            .bind(&self.os)
+            .bind(&self.os_version)
+            .bind(self.packages)
+            .bind(self.skipped)
+            .bind(utc_to_str(self.uploaded))
+            .execute(&mut *connection)
+            .await
+    }
+}
+
+/// SBOM sent by an agent, `sbom:{"component": "nginx", "document": {...}}`
+#[derive(Deserialize, Debug)]
+pub struct SbomMessage {
+    #[serde(default)]
+    pub component: Option<String>,
+    pub document: Value,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct SbomParams {
+    component: Option<String>,
+}
+
+/// API to upload a CycloneDX or SPDX json SBOM of a host
+pub async fn post_sbom_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<SbomParams>,
+    State(pool): State<SqlitePool>,
+    body: String,
+) -> Response {
+    let filter = format!("id='{id}'");
+    if get_hosts_from_db(Some(&filter), pool.acquire().await.unwrap())
+        .await
+        .is_empty()
+    {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match store_sbom(id, params.component.as_deref(), &body, Utc::now(), &pool).await {
+        Ok(sbom) => (StatusCode::CREATED, Json(sbom)).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
+/// API to get the SBOMs of a host
+pub async fn get_host_sboms_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("host_id='{id}' ORDER BY component");
+    let sbom_vec = get_sboms_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(sbom_vec)
+}
+
+/// API to remove the SBOM of a component and its packages from a host
+pub async fn delete_sbom_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<SbomParams>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let Some(component) = params.component else {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "component is required").into_response();
+    };
+    match delete_sbom(id, &component, Utc::now(), &pool).await {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::FORBIDDEN.into_response(),
+    }
+}
+
+pub async fn get_sboms_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<HostSbom> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM sboms WHERE {f}"),
+        None => "SELECT * FROM sboms".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| HostSbom::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        advisory::{host_vulnerabilities, import, AdvisoryFormat},
+        db::{create_database, init_database},
+        host::Host,
+        package::{get_package_changes_from_db, get_packages_from_db, store_inventory, ChangeKind},
+    };
+    use axum::http::StatusCode;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    const CYCLONEDX: &str = r#"{
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "metadata": {"component": {"type": "container", "name": "nginx"}},
+        "components": [
+            {"type": "operating-system", "name": "alpine", "version": "3.19.1"},
+            {"type": "library", "name": "libcrypto3", "version": "3.1.4-r5",
+             "purl": "pkg:apk/alpine/libcrypto3@3.1.4-r5?arch=x86_64&upstream=openssl&distro=alpine-3.19.1"},
+            {"type": "library", "name": "lodash", "version": "4.17.20", "purl": "pkg:npm/lodash@4.17.20"},
+            {"type": "library", "name": "core", "version": "7.23.0", "purl": "pkg:npm/%40babel/core@7.23.0",
+             "components": [
+                {"type": "library", "name": "log4j-core", "version": "2.14.1",
+                 "purl": "pkg:maven/org.apache.logging.log4j/log4j-core@2.14.1"}
+             ]},
+            {"type": "file", "name": "/etc/nginx/nginx.conf"}
+        ]
+    }"#;
+
+    const SPDX: &str = r#"{
+        "spdxVersion": "SPDX-2.3",
+        "name": "api",
+        "packages": [
+            {"name": "Django", "versionInfo": "4.2.0", "externalRefs": [
+                {"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": "pkg:pypi/django@4.2.0"}]},
+            {"name": "openssl", "versionInfo": "3.0.11-1~deb12u2", "externalRefs": [
+                {"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl",
+                 "referenceLocator": "pkg:deb/debian/openssl@3.0.11-1~deb12u2?arch=amd64&distro=debian-12"}]},
+            {"name": "golang.org/x/net", "versionInfo": "v0.17.0", "externalRefs": [
+                {"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": "pkg:golang/golang.org/x/net@v0.17.0"}]},
+            {"name": "bash", "versionInfo": "5.2"}
+        ]
+    }"#;
+
+    const OSV: &str = r#"[
+        {"id": "GHSA-35jh-r3h4-6jhm", "aliases": ["CVE-2021-23337"], "summary": "Command injection in lodash",
+         "affected": [{"package": {"ecosystem": "npm", "name": "lodash"},
+            "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}, {"fixed": "4.17.21"}]}]}]},
+        {"id": "ALPINE-CVE-2024-0727", "aliases": ["CVE-2024-0727"], "summary": "openssl",
+         "affected": [{"package": {"ecosystem": "Alpine:v3.19", "name": "openssl"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "3.1.4-r6"}]}]}]}
+    ]"#;
+
+    #[test]
+    fn test_parse_sbom() {
+        let sbom = parse_sbom(CYCLONEDX).unwrap();
+        assert_eq!(sbom.format, SbomFormat::Cyclonedx);
+        assert_eq!(sbom.spec_version, "1.5");
+        assert_eq!(sbom.name, "nginx");
+        assert_eq!(sbom.os.as_deref(), Some("alpine"));
+        assert_eq!(sbom.os_version.as_deref(), Some("3.19.1"));
+        assert_eq!(sbom.skipped, 1);
+        let names: Vec<&str> = sbom.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "libcrypto3",
+                "lodash",
+                "@babel/core",
+                "org.apache.logging.log4j:log4j-core"
+            ]
+        );
+        assert_eq!(
+            sbom.packages[0],
+            Package {
+                format: PackageFormat::Apk,
+                name: "libcrypto3".into(),
+                version: "3.1.4-r5".into(),
+                arch: "x86_64".into(),
+                source: "openssl".into(),
+            }
+        );
+
+        let sbom = parse_sbom(SPDX).unwrap();
+        assert_eq!(sbom.format, SbomFormat::Spdx);
+        assert_eq!(sbom.spec_version, "SPDX-2.3");
+        assert_eq!(sbom.name, "api");
+        // the os comes from the distro of the os packages
+        assert_eq!(sbom.os.as_deref(), Some("debian"));
+        assert_eq!(sbom.os_version.as_deref(), Some("12"));
+        assert_eq!(sbom.skipped, 1);
+        assert_eq!(sbom.packages.len(), 3);
+        assert_eq!(sbom.packages[0].format, PackageFormat::Pypi);
+        assert_eq!(sbom.packages[0].name, "django");
+        assert_eq!(sbom.packages[1].format, PackageFormat::Dpkg);
+        assert_eq!(sbom.packages[1].arch, "amd64");
+        assert_eq!(sbom.packages[2].name, "golang.org/x/net");
+        assert_eq!(sbom.packages[2].version, "v0.17.0");
+
+        let rpm = parse_purl("pkg:rpm/redhat/openssl@3.0.7-27.el9?arch=x86_64&epoch=1").unwrap();
+        assert_eq!(rpm.package("").unwrap().version, "1:3.0.7-27.el9");
+        let nuget = parse_purl("pkg:nuget/Newtonsoft.Json@13.0.1").unwrap();
+        assert_eq!(nuget.package("").unwrap().name, "newtonsoft.json");
+        assert_eq!(
+            parse_purl("pkg:github/actions/checkout@v4")
+                .unwrap()
+                .format(),
+            None
+        );
+        assert_eq!(parse_purl("openssl"), None);
+
+        assert!(parse_sbom(r#"{"packages": []}"#).is_err());
+        assert!(parse_sbom("<bom/>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sbom() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "docker-1".into(),
+            facts: HashMap::from([
+                ("os".to_string(), "debian".to_string()),
+                ("os_version".to_string(), "12".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let bash = Package {
+            format: PackageFormat::Dpkg,
+            name: "bash".into(),
+            version: "5.2.15-2+b2".into(),
+            arch: "amd64".into(),
+            source: "bash".into(),
+        };
+        store_inventory(host.id, vec![bash.clone()], Utc::now(), &pool)
+            .await
+            .unwrap();
+        let _a = import(AdvisoryFormat::Osv, OSV, Utc::now(), &pool)
+            .await
+            .unwrap();
+
+        // the component is named by the SBOM
+        let stored = store_sbom(host.id, None, CYCLONEDX, Utc::now(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(stored.component, "nginx");
+        assert_eq!(stored.packages, 4);
+        assert_eq!(stored.skipped, 1);
+        let filter = format!("host_id='{}' AND component='nginx'", host.id);
+        let packages = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(packages.len(), 4);
+        let filter = format!("host_id='{}'", host.id);
+        assert_eq!(
+            get_packages_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .len(),
+            5
+        );
+        let unnamed = r#"{"bomFormat": "CycloneDX", "specVersion": "1.5", "components": []}"#;
+        assert!(store_sbom(host.id, None, unnamed, Utc::now(), &pool)
+            .await
+            .is_err());
+
+        // os packages of the component match the advisories of its own distribution
+        let vulnerabilities = host_vulnerabilities(&host, &pool).await;
+        let mut found: Vec<(&str, &str, &str)> = vulnerabilities
+            .iter()
+            .map(|v| (v.cve.as_str(), v.component.as_str(), v.package.as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("CVE-2021-23337", "nginx", "lodash"),
+                ("CVE-2024-0727", "nginx", "libcrypto3")
+            ]
+        );
+
+        // the host inventory does not replace the component
+        store_inventory(host.id, vec![bash], Utc::now(), &pool)
+            .await
+            .unwrap();
+        // a new SBOM replaces the component as a whole
+        let updated = CYCLONEDX
+            .replace("lodash@4.17.20", "lodash@4.17.21")
+            .replace("log4j-core@2.14.1", "log4j-api@2.14.1");
+        store_sbom(host.id, Some("nginx"), &updated, Utc::now(), &pool)
+            .await
+            .unwrap();
+        let filter = "component='nginx' ORDER BY name";
+        let changes =
+            get_package_changes_from_db(Some(filter), pool.acquire().await.unwrap()).await;
+        let changes: Vec<(&str, &ChangeKind)> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), &c.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("lodash", &ChangeKind::Updated),
+                ("org.apache.logging.log4j:log4j-api", &ChangeKind::Installed),
+                ("org.apache.logging.log4j:log4j-core", &ChangeKind::Removed)
+            ]
+        );
+        let vulnerabilities = host_vulnerabilities(&host, &pool).await;
+        assert_eq!(vulnerabilities.len(), 1);
+
+        assert!(delete_sbom(host.id, "nginx", Utc::now(), &pool)
+            .await
+            .unwrap());
+        assert!(!delete_sbom(host.id, "nginx", Utc::now(), &pool)
+            .await
+            .unwrap());
+        assert!(host_vulnerabilities(&host, &pool).await.is_empty());
+        let filter = format!("host_id='{}'", host.id);
+        let packages = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].component, "");
+        assert!(get_sboms_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "k8s-node-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let params = || {
+            axum::extract::Query(SbomParams {
+                component: Some("api-server".into()),
+            })
+        };
+
+        let api_post = post_sbom_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            params(),
+            axum::extract::State(pool.clone()),
+            SPDX.into(),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+        let body = hyper::body::to_bytes(api_post.into_body()).await.unwrap();
+        let stored: HostSbom = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stored.component, "api-server");
+        assert_eq!(stored.format, SbomFormat::Spdx);
+        assert_eq!(stored.packages, 3);
+
+        let api_invalid = post_sbom_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            params(),
+            axum::extract::State(pool.clone()),
+            "{}".into(),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let api_unknown = post_sbom_api(
+            claims.clone(),
+            axum::extract::Path(Uuid::new_v4()),
+            params(),
+            axum::extract::State(pool.clone()),
+            SPDX.into(),
+        )
+        .await;
+        assert_eq!(api_unknown.status(), StatusCode::NOT_FOUND);
+
+        let api_sboms = get_host_sboms_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_sboms.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_sboms.into_body()).await.unwrap();
+        let sboms: Vec<HostSbom> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sboms.len(), 1);
+        assert_eq!(sboms[0].component, stored.component);
+        assert_eq!(sboms[0].os.as_deref(), Some("debian"));
+
+        let api_missing = delete_sbom_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            axum::extract::Query(SbomParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_missing.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let api_delete = delete_sbom_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            params(),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_delete.status(), StatusCode::OK);
+        let api_gone = delete_sbom_api(
+            claims,
+            axum::extract::Path(host_id),
+            params(),
+            axum::extract::State(pool.clone()),
+        )
+        .await;
+        assert_eq!(api_gone.status(), StatusCode::NOT_FOUND);
+    }
+}
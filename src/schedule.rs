@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::{
     extract::{Path, State},
     http::StatusCode,
@@ -6,6 +8,7 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::{
     pool::PoolConnection,
     query,
@@ -19,6 +22,7 @@ use crate::{
     db::{utc_from_str, utc_to_str},
     host::{get_hosts_from_db, ScheduleState},
     jwt::Claims,
+    revision::get_script_for_schedule,
 };
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
@@ -30,6 +34,12 @@ pub struct Schedule {
     pub target: Target,
     pub timer: Timer,
     pub active: bool,
+    /// values for the parameters declared by the script
+    #[serde(default)]
+    pub parameters: HashMap<String, Value>,
+    /// pinned revision of the script, `None` always runs the latest revision
+    #[serde(default)]
+    pub script_revision: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -39,6 +49,8 @@ struct ExtSchedule {
     target: Target,
     timer: Timer,
     active: bool,
+    parameters: HashMap<String, Value>,
+    script_revision: Option<i64>,
     last_execution: Option<DateTime<Utc>>,
 }
 
@@ -69,7 +81,7 @@ impl Default for Timer {
 }
 
 impl Schedule {
-    /// Insert into or Replace `Schedule` in schedules table in SQLite database
+    /// Insert or Update `Schedule` in schedules table in SQLite database
     ///
     /// | Name | Type | Comment
     /// :--- | :--- | :---
@@ -80,6 +92,8 @@ impl Schedule {
     /// | timer_cron | TEXT | cron pattern for execution
     /// | timer_ts | TEXT | cron pattern for execution
     /// | active | NUMERIC |
+    /// | parameters | TEXT | json map of script parameter values
+    /// | script_revision | INT | pinned script revision, NULL for latest
     #[allow(dead_code)]
     // FIXME: write test and remove dead_code
     pub async fn insert_into_db(
@@ -95,7 +109,9 @@ impl Schedule {
             Timer::Timestamp(ts) => (None, Some(utc_to_str(ts))),
         };
 
-        let q = r#"REPLACE INTO schedules( id, script_id, target_attributes, target_host_id, timer_cron, timer_ts, active ) VALUES ( ?, ?, ?, ?, ?, ?, ? )"#;
+        // an upsert, REPLACE would delete the row and cascade to the history of the schedule
+        let q = r#"INSERT INTO schedules( id, script_id, target_attributes, target_host_id, timer_cron, timer_ts, active, parameters, script_revision ) VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ? )
+        ON CONFLICT(id) DO UPDATE SET script_id=excluded.script_id, target_attributes=excluded.target_attributes, target_host_id=excluded.target_host_id, timer_cron=excluded.timer_cron, timer_ts=excluded.timer_ts, active=excluded.active, parameters=excluded.parameters, script_revision=excluded.script_revision"#;
         query(q)
             .bind(self.id.to_string())
             .bind(self.script_id.to_string())
@@ -104,10 +120,31 @@ impl Schedule {
             .bind(timer.0)
             .bind(timer.1)
             .bind(self.active)
+            .bind(serde_json::to_string(&self.parameters).unwrap())
+            .bind(self.script_revision)
             .execute(&mut *connection)
             .await
     }
 
+    /// check the parameter values against the declaration of the linked script (revision)
+    ///
+    /// an unknown script is not an error here, the foreign key takes care of that
+    pub async fn validate_parameters(
+        &self,
+        connection: PoolConnection<Sqlite>,
+    ) -> Result<(), String> {
+        let Some(script) = get_script_for_schedule(self, connection).await else {
+            return match self.script_revision {
+                Some(revision) => Err(format!("Script revision {revision} not found")),
+                None => Ok(()),
+            };
+        };
+        script
+            .resolve_parameters(&self.parameters)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
     /// list of attributes as comma-seperated `String`
     pub fn attributes(&self) -> String {
         if let Target::Attributes(attr) = &self.target {
@@ -127,6 +164,28 @@ impl Schedule {
             "".into()
         }
     }
+
+    /// time between two runs of a cron timer, `None` for one-off timestamps
+    pub fn interval(&self, seven_part_cron: bool) -> Option<chrono::Duration> {
+        let Timer::Cron(c) = &self.timer else {
+            return None;
+        };
+        let cron = if seven_part_cron {
+            c.to_string()
+        } else {
+            format!("0 {} *", c)
+        };
+        let upcoming: Vec<DateTime<Utc>> = cron
+            .parse::<cron::Schedule>()
+            .ok()?
+            .upcoming(Utc)
+            .take(2)
+            .collect();
+        match upcoming[..] {
+            [first, second] => Some(second - first),
+            _ => None,
+        }
+    }
 }
 
 impl From<SqliteRow> for Schedule {
@@ -150,6 +209,8 @@ impl From<SqliteRow> for Schedule {
             target,
             timer,
             active: s.get::<bool, _>("active"),
+            parameters: serde_json::from_str(&s.get::<String, _>("parameters")).unwrap_or_default(),
+            script_revision: s.get::<Option<i64>, _>("script_revision"),
         }
     }
 }
@@ -180,6 +241,8 @@ pub async fn get_schedules_api(
             target: sched.target.clone(),
             timer: sched.timer.clone(),
             active: sched.active,
+            parameters: sched.parameters.clone(),
+            script_revision: sched.script_revision,
         })
     }
     debug!("{:?}", sched_vec);
@@ -227,6 +290,8 @@ pub async fn get_host_schedules_api(
             target: sched.target.clone(),
             timer: sched.timer.clone(),
             active: sched.active,
+            parameters: sched.parameters.clone(),
+            script_revision: sched.script_revision,
         })
     }
     debug!("{:?}", sched_vec);
@@ -269,6 +334,12 @@ pub async fn post_schedules_api(
     Json(payload): Json<Schedule>,
 ) -> Response {
     debug!("{:?}", payload);
+    if let Err(e) = payload
+        .validate_parameters(pool.acquire().await.unwrap())
+        .await
+    {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
     let id = payload.id.to_string();
     let Ok(res) = payload.insert_into_db(pool.acquire().await.unwrap()).await else {
         return (
@@ -297,6 +368,12 @@ pub async fn post_host_schedules_api(
 ) -> Response {
     payload.target = Target::HostId(host_id);
     debug!("{:?}", payload);
+    if let Err(e) = payload
+        .validate_parameters(pool.acquire().await.unwrap())
+        .await
+    {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
     let id = payload.id;
     let Ok(res) = payload.insert_into_db(pool.acquire().await.unwrap()).await else {
         return (
@@ -367,9 +444,18 @@ pub async fn count_rows(connection: PoolConnection<Sqlite>) -> Result<i64, sqlx:
 mod tests {
     use super::*;
     use crate::{
+        alert::{get_alert_rules_from_db, AlertCondition, AlertRule, Severity},
         db::{create_database, init_database},
+        execution::get_executions_from_db,
+        fixtures,
         host::Host,
+        metric::{downsample, RAW_RETENTION},
+        parser::OutputParser,
+        revision::save_script,
+        script::{ParameterKind, Script, ScriptParameter},
     };
+    use serde_json::json;
+    use std::collections::BTreeMap;
     use tracing_subscriber::{
         fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
     };
@@ -386,7 +472,7 @@ mod tests {
 
         init_database(&pool, None).await.unwrap();
         let schedules = get_schedules_from_db(None, pool.acquire().await.unwrap()).await;
-        assert_eq!(schedules.len(), 5);
+        assert_eq!(schedules.len(), 8);
 
         let mut schedule = Schedule {
             script_id: schedules[0].script_id,
@@ -404,7 +490,7 @@ mod tests {
             .await;
         assert_eq!(i2.unwrap().rows_affected(), 1);
         let schedules = count_rows(pool.acquire().await.unwrap()).await.unwrap();
-        assert_eq!(schedules, 7);
+        assert_eq!(schedules, 10);
 
         let err_schedules =
             get_schedules_from_db(Some("this-doesnt-work"), pool.acquire().await.unwrap()).await;
@@ -432,7 +518,7 @@ mod tests {
         .await;
         assert_eq!(single_del, axum::http::StatusCode::OK);
         let schedules = count_rows(pool.acquire().await.unwrap()).await.unwrap();
-        assert_eq!(schedules, 6);
+        assert_eq!(schedules, 9);
 
         let del_fail =
             delete_schedules_from_db(Some("this-doesnt-work"), pool.acquire().await.unwrap()).await;
@@ -442,6 +528,119 @@ mod tests {
         assert_eq!(del, axum::http::StatusCode::OK);
         let schedules = count_rows(pool.acquire().await.unwrap()).await.unwrap();
         assert_eq!(schedules, 0);
+
+        let mut timed = Schedule {
+            timer: Timer::Cron("*/15 * * * *".into()),
+            ..Default::default()
+        };
+        assert_eq!(timed.interval(false), Some(chrono::Duration::minutes(15)));
+        timed.timer = Timer::Cron("0 0 */2 * * * *".into());
+        assert_eq!(timed.interval(true), Some(chrono::Duration::hours(2)));
+        timed.timer = Timer::Cron("not a cron".into());
+        assert_eq!(timed.interval(false), None);
+        assert_eq!(Schedule::default().interval(false), None);
+    }
+
+    #[tokio::test]
+    async fn test_repost_keeps_history() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host = fixtures::host("web-1", &[], &pool).await;
+        let mut script = Script {
+            id: Uuid::new_v4(),
+            name: "pending_updates".into(),
+            script_content: "echo updates=3".into(),
+            output_regex: ".*".into(),
+            parser: OutputParser::KeyValue,
+            parameters: vec![ScriptParameter {
+                name: "min".into(),
+                kind: ParameterKind::Integer,
+                default: Some(json!(0)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let sched = fixtures::schedule(script.clone(), &[], &pool).await;
+        let _run = fixtures::run(&host, sched.id, "updates=3", Utc::now(), &pool).await;
+        let rule = AlertRule {
+            id: Uuid::new_v4(),
+            name: "failing".into(),
+            condition: AlertCondition::ConsecutiveFailures { count: 2 },
+            severity: Severity::Warning,
+            labels: BTreeMap::new(),
+            for_secs: 0,
+            sched_id: Some(sched.id),
+            attributes: vec![],
+            active: true,
+            created: Utc::now(),
+        };
+        let _r = rule.insert_into_db(pool.acquire().await.unwrap()).await;
+        script.script_content = "echo updates=4".into();
+        assert_eq!(
+            save_script(script, "a@test.int", "", &pool).await.unwrap(),
+            2
+        );
+
+        // pin the first revision
+        let api_pin = post_schedules_api(
+            Claims::default(),
+            axum::extract::State(pool.clone()),
+            Json(Schedule {
+                script_revision: Some(1),
+                ..sched.clone()
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_pin.status(), StatusCode::CREATED);
+        let filter = format!("id='{}'", sched.id);
+        let stored = get_schedules_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(stored[0].script_revision, Some(1));
+
+        let filter = format!("sched_id='{}'", sched.id);
+        let executions = get_executions_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(executions.len(), 1);
+        let rules = get_alert_rules_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(rules.len(), 1);
+        let metrics: i64 = query("SELECT COUNT(*) FROM metrics WHERE sched_id = ?")
+            .bind(sched.id.to_string())
+            .fetch_one(&mut *pool.acquire().await.unwrap())
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(metrics, 1);
+
+        // the downsampled series outlives a change of the parameters
+        let later = Utc::now() + RAW_RETENTION + chrono::Duration::hours(2);
+        downsample(later, chrono::Duration::days(730), &pool)
+            .await
+            .unwrap();
+        let api_params = post_schedules_api(
+            Claims::default(),
+            axum::extract::State(pool.clone()),
+            Json(Schedule {
+                script_revision: Some(1),
+                parameters: HashMap::from([("min".to_string(), json!(1))]),
+                ..sched.clone()
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_params.status(), StatusCode::CREATED);
+        let hourly: i64 =
+            query("SELECT COUNT(*) FROM metrics WHERE sched_id = ? AND resolution > 0")
+                .bind(sched.id.to_string())
+                .fetch_one(&mut *pool.acquire().await.unwrap())
+                .await
+                .unwrap()
+                .get(0);
+        assert_eq!(hourly, 1);
     }
 
     #[tokio::test]
@@ -1,22 +1,24 @@
-use std::time::Duration;
+use std::{collections::HashMap, fmt::Display, time::Duration};
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::{
     pool::PoolConnection,
     query,
-    sqlite::{SqliteQueryResult, SqliteRow},
+    sqlite::{SqliteConnection, SqliteQueryResult, SqliteRow},
     Row, Sqlite, SqlitePool,
 };
 use tracing::{debug, error};
 use uuid::Uuid;
 
-use crate::jwt::Claims;
+use crate::{jwt::Claims, parser::OutputParser, reboot::RebootStep, revision::save_script};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
 pub struct Script {
@@ -24,15 +26,190 @@ pub struct Script {
     pub id: Uuid,
     pub name: String,
     pub version: String,
+    /// evaluated against the output, named capture groups are stored on the execution
     pub output_regex: String,
+    /// an output not matching `output_regex` fails the execution
+    #[serde(default)]
+    pub fail_on_no_match: bool,
+    /// turns the output into typed fields on the execution
+    #[serde(default)]
+    pub parser: OutputParser,
     pub labels: Vec<String>,
     pub timeout: Duration,
     pub script_content: String,
+    #[serde(default)]
+    pub parameters: Vec<ScriptParameter>,
+    #[serde(default)]
+    pub interpreter: Interpreter,
+    /// environment variables for the script process, values may contain placeholders
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// user the agent should run the script as
+    #[serde(default)]
+    pub run_as: Option<String>,
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    /// reboot the host after a successful run and wait for it to come back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reboot: Option<RebootStep>,
+    /// record a drift event with a diff whenever the output differs from the previous run
+    #[serde(default)]
+    pub track_drift: bool,
+    /// latest revision, assigned by the server on save
+    #[serde(default)]
+    pub revision: i64,
+}
+
+/// Interpreter the agent uses to run `script_content`
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpreter {
+    Sh,
+    #[default]
+    Bash,
+    Python3,
+    Pwsh,
+    /// custom shebang line, e.g. `#!/usr/bin/env ruby`
+    Custom(String),
+}
+
+impl Interpreter {
+    /// name of the interpreter binary, as reported by agents
+    pub fn name(&self) -> String {
+        match self {
+            Interpreter::Sh => "sh".into(),
+            Interpreter::Bash => "bash".into(),
+            Interpreter::Python3 => "python3".into(),
+            Interpreter::Pwsh => "pwsh".into(),
+            Interpreter::Custom(shebang) => {
+                let mut parts = shebang.trim_start_matches("#!").split_whitespace();
+                let binary = parts.next().unwrap_or_default();
+                let binary = binary.rsplit('/').next().unwrap_or_default();
+                // "#!/usr/bin/env ruby" -> ruby
+                if binary == "env" {
+                    parts.next().unwrap_or_default().to_string()
+                } else {
+                    binary.to_string()
+                }
+            }
+        }
+    }
+
+    /// check against the interpreters an agent reported
+    ///
+    /// sh and bash always run, agents ran them before reporting interpreters and minimal
+    /// agents may only report sh
+    pub fn is_supported_by(&self, interpreters: &[String]) -> bool {
+        let name = self.name();
+        name == "sh" || name == "bash" || interpreters.contains(&name)
+    }
+}
+
+/// Resource limits the agent should apply to the script process, a hint only
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ResourceLimits {
+    #[serde(default)]
+    pub cpu_seconds: Option<u64>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+}
+
+/// Declared parameter of a script, usable as `{{ params.<name> }}` inside the script
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ScriptParameter {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: ParameterKind,
+    /// parameters without default are required
+    #[serde(default)]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterKind {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl ParameterKind {
+    fn accepts(&self, value: &Value) -> bool {
+        match self {
+            ParameterKind::String => value.is_string(),
+            ParameterKind::Integer => value.is_i64() || value.is_u64(),
+            ParameterKind::Number => value.is_number(),
+            ParameterKind::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParameterError {
+    InvalidName(String),
+    Duplicate(String),
+    Unknown(String),
+    Missing(String),
+    WrongType(String, ParameterKind),
+}
+
+impl Display for ParameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterError::InvalidName(n) => write!(
+                f,
+                "parameter name '{n}' is invalid, only [A-Za-z0-9_] allowed"
+            ),
+            ParameterError::Duplicate(n) => write!(f, "parameter '{n}' is declared twice"),
+            ParameterError::Unknown(n) => write!(f, "parameter '{n}' is not declared by script"),
+            ParameterError::Missing(n) => write!(f, "required parameter '{n}' has no value"),
+            ParameterError::WrongType(n, k) => write!(f, "parameter '{n}' must be of type {k:?}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SettingsError {
+    InvalidEnvironmentName(String),
+    InvalidShebang(String),
+    OutputRegex(String),
+    Reboot(String),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::InvalidEnvironmentName(n) => write!(
+                f,
+                "environment variable name '{n}' is invalid, only [A-Za-z0-9_] allowed"
+            ),
+            SettingsError::InvalidShebang(s) => {
+                write!(f, "custom interpreter '{s}' must be a shebang line (#!...)")
+            }
+            SettingsError::OutputRegex(e) => write!(f, "output_regex is invalid: {e}"),
+            SettingsError::Reboot(e) => write!(f, "reboot step is invalid: {e}"),
+        }
+    }
+}
+
+/// names usable as parameter or environment variable name
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 impl Script {
-    /// Insert into or Replace `Script` in scripts table in SQLite database
+    /// Insert into or Update `Script` in scripts table in SQLite database
     ///
+    /// an upsert instead of `REPLACE`, which would cascade-delete schedules and revisions,
+    /// on a connection so it runs in the transaction that stores the revision
     /// | Name | Type | Comment
     /// :--- | :--- | :---
     /// | id | TEXT | uuid
@@ -42,8 +219,23 @@ impl Script {
     /// | labels | TEXT | script labels
     /// | timeout_in_s | INT | timeout in seconds
     /// | script_content | TEXT | original script
-    pub async fn insert_into_db(self, mut connection: PoolConnection<Sqlite>) -> SqliteQueryResult {
-        let q = r#"REPLACE INTO scripts( id, name, version, output_regex, labels, timeout_in_s, script_content ) VALUES ( ?, ?, ?, ?, ?, ?, ? )"#;
+    /// | parameters | TEXT | json list of declared parameters
+    /// | interpreter | TEXT | json
+    /// | environment | TEXT | json map of environment variables
+    /// | working_dir | TEXT |
+    /// | run_as | TEXT |
+    /// | resource_limits | TEXT | json
+    /// | revision | INT | latest revision of the script
+    /// | fail_on_no_match | NUMERIC | bool
+    /// | parser | TEXT | json
+    /// | reboot | TEXT | json reboot step, NULL without
+    /// | track_drift | NUMERIC | bool
+    pub async fn insert_into_db(
+        self,
+        connection: &mut SqliteConnection,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO scripts( id, name, version, output_regex, labels, timeout_in_s, script_content, parameters, interpreter, environment, working_dir, run_as, resource_limits, revision, fail_on_no_match, parser, reboot, track_drift ) VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )
+        ON CONFLICT(id) DO UPDATE SET name=excluded.name, version=excluded.version, output_regex=excluded.output_regex, labels=excluded.labels, timeout_in_s=excluded.timeout_in_s, script_content=excluded.script_content, parameters=excluded.parameters, interpreter=excluded.interpreter, environment=excluded.environment, working_dir=excluded.working_dir, run_as=excluded.run_as, resource_limits=excluded.resource_limits, revision=excluded.revision, fail_on_no_match=excluded.fail_on_no_match, parser=excluded.parser, reboot=excluded.reboot, track_drift=excluded.track_drift"#;
         query(q)
             .bind(self.id.to_string())
             .bind(self.name)
@@ -52,14 +244,102 @@ impl Script {
             .bind(serde_json::to_string(&self.labels).unwrap())
             .bind(self.timeout.as_secs() as i64)
             .bind(self.script_content)
-            .execute(&mut *connection)
+            .bind(serde_json::to_string(&self.parameters).unwrap())
+            .bind(serde_json::to_string(&self.interpreter).unwrap())
+            .bind(serde_json::to_string(&self.environment).unwrap())
+            .bind(self.working_dir)
+            .bind(self.run_as)
+            .bind(
+                self.resource_limits
+                    .map(|r| serde_json::to_string(&r).unwrap()),
+            )
+            .bind(self.revision)
+            .bind(self.fail_on_no_match)
+            .bind(serde_json::to_string(&self.parser).unwrap())
+            .bind(self.reboot.map(|r| serde_json::to_string(&r).unwrap()))
+            .bind(self.track_drift)
+            .execute(connection)
             .await
-            .unwrap()
     }
     /// return labels as comma-seperated `String`
     pub fn labels(&self) -> String {
         self.labels.join(",")
     }
+
+    /// check parameter declarations and execution settings before saving
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_parameters().map_err(|e| e.to_string())?;
+        self.validate_settings().map_err(|e| e.to_string())
+    }
+
+    /// check environment variable names, custom interpreter, output regex and reboot step
+    pub fn validate_settings(&self) -> Result<(), SettingsError> {
+        if let Some(name) = self.environment.keys().find(|k| !is_valid_name(k)) {
+            return Err(SettingsError::InvalidEnvironmentName(name.clone()));
+        }
+        if let Interpreter::Custom(shebang) = &self.interpreter {
+            if !shebang.starts_with("#!") || self.interpreter.name().is_empty() {
+                return Err(SettingsError::InvalidShebang(shebang.clone()));
+            }
+        }
+        if let Err(e) = Regex::new(&self.output_regex) {
+            return Err(SettingsError::OutputRegex(e.to_string()));
+        }
+        if let Some(reboot) = &self.reboot {
+            reboot.validate().map_err(SettingsError::Reboot)?;
+        }
+        Ok(())
+    }
+
+    /// check parameter declarations (names and default types)
+    pub fn validate_parameters(&self) -> Result<(), ParameterError> {
+        let mut seen = Vec::new();
+        for param in &self.parameters {
+            if !is_valid_name(&param.name) {
+                return Err(ParameterError::InvalidName(param.name.clone()));
+            }
+            if seen.contains(&&param.name) {
+                return Err(ParameterError::Duplicate(param.name.clone()));
+            }
+            seen.push(&param.name);
+            if let Some(default) = &param.default {
+                if !param.kind.accepts(default) {
+                    return Err(ParameterError::WrongType(
+                        param.name.clone(),
+                        param.kind.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// merge supplied values with declared defaults and check them against the declaration
+    pub fn resolve_parameters(
+        &self,
+        supplied: &HashMap<String, Value>,
+    ) -> Result<HashMap<String, Value>, ParameterError> {
+        if let Some(unknown) = supplied
+            .keys()
+            .find(|k| !self.parameters.iter().any(|p| &p.name == *k))
+        {
+            return Err(ParameterError::Unknown(unknown.clone()));
+        }
+        let mut resolved = HashMap::new();
+        for param in &self.parameters {
+            let Some(value) = supplied.get(&param.name).or(param.default.as_ref()) else {
+                return Err(ParameterError::Missing(param.name.clone()));
+            };
+            if !param.kind.accepts(value) {
+                return Err(ParameterError::WrongType(
+                    param.name.clone(),
+                    param.kind.clone(),
+                ));
+            }
+            resolved.insert(param.name.clone(), value.clone());
+        }
+        Ok(resolved)
+    }
 }
 
 impl From<SqliteRow> for Script {
@@ -69,9 +349,28 @@ impl From<SqliteRow> for Script {
             name: s.get::<String, _>("name"),
             version: s.get::<String, _>("version"),
             output_regex: s.get::<String, _>("output_regex"),
+            fail_on_no_match: s
+                .get::<Option<bool>, _>("fail_on_no_match")
+                .unwrap_or_default(),
+            parser: serde_json::from_str(&s.get::<String, _>("parser")).unwrap_or_default(),
             labels: serde_json::from_str(&s.get::<String, _>("labels")).unwrap(),
             timeout: Duration::new(s.get::<i64, _>("timeout_in_s").unsigned_abs(), 0),
             script_content: s.get::<String, _>("script_content"),
+            parameters: serde_json::from_str(&s.get::<String, _>("parameters")).unwrap_or_default(),
+            interpreter: serde_json::from_str(&s.get::<String, _>("interpreter"))
+                .unwrap_or_default(),
+            environment: serde_json::from_str(&s.get::<String, _>("environment"))
+                .unwrap_or_default(),
+            working_dir: s.get::<Option<String>, _>("working_dir"),
+            run_as: s.get::<Option<String>, _>("run_as"),
+            resource_limits: s
+                .get::<Option<String>, _>("resource_limits")
+                .and_then(|r| serde_json::from_str(&r).ok()),
+            reboot: s
+                .get::<Option<String>, _>("reboot")
+                .and_then(|r| serde_json::from_str(&r).ok()),
+            track_drift: s.get::<Option<bool>, _>("track_drift").unwrap_or_default(),
+            revision: s.get::<Option<i64>, _>("revision").unwrap_or_default(),
         }
     }
 }
@@ -111,19 +410,28 @@ pub async fn delete_one_script_api(
     delete_scripts_from_db(Some(&filter), pool.acquire().await.unwrap()).await
 }
 
-/// API to create a new script
+#[derive(Debug, Deserialize, Default)]
+pub struct ChangeNoteParams {
+    change_note: Option<String>,
+}
+
+/// API to create a new script or a new revision of an existing script
 pub async fn post_scripts_api(
-    _claims: Claims,
+    claims: Claims,
+    Query(params): Query<ChangeNoteParams>,
     State(pool): State<SqlitePool>,
     Json(payload): Json<Script>,
-) -> impl IntoResponse {
+) -> Response {
     debug!("{:?}", payload);
+    if let Err(e) = payload.validate() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
     let id = payload.id.to_string();
-    let res = payload.insert_into_db(pool.acquire().await.unwrap()).await;
-    if res.rows_affected() == 1 {
-        (StatusCode::CREATED, Json(id))
-    } else {
-        (StatusCode::BAD_REQUEST, Json("".into()))
+    let author = claims.sub.to_string();
+    let change_note = params.change_note.unwrap_or_default();
+    match save_script(payload, &author, &change_note, &pool).await {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, Json("")).into_response(),
     }
 }
 
@@ -197,23 +505,53 @@ mod tests {
 
         init_database(&pool, None).await.unwrap();
         let scripts = get_scripts_from_db(None, pool.acquire().await.unwrap()).await;
-        assert_eq!(scripts.len(), 4);
+        assert_eq!(scripts.len(), 7);
 
         let mut script = Script::default();
         let i1 = script
             .clone()
-            .insert_into_db(pool.acquire().await.unwrap())
-            .await;
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
         assert_eq!(i1.rows_affected(), 1);
         script.id = Uuid::new_v4();
         let i2 = script
             .clone()
-            .insert_into_db(pool.acquire().await.unwrap())
-            .await;
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
         assert_eq!(i2.rows_affected(), 1);
 
         let scripts = count_rows(pool.acquire().await.unwrap()).await.unwrap();
-        assert_eq!(scripts, 6);
+        assert_eq!(scripts, 9);
+
+        let settings = Script {
+            id: Uuid::new_v4(),
+            interpreter: Interpreter::Custom("#!/usr/bin/env ruby".into()),
+            environment: HashMap::from([("LANG".to_string(), "C".to_string())]),
+            working_dir: Some("/tmp".into()),
+            run_as: Some("nobody".into()),
+            resource_limits: Some(ResourceLimits {
+                cpu_seconds: Some(10),
+                memory_mb: None,
+            }),
+            ..Default::default()
+        };
+        let _i3 = settings
+            .clone()
+            .insert_into_db(&mut pool.acquire().await.unwrap())
+            .await;
+        let stored = get_scripts_from_db(
+            Some(format!("id='{}'", settings.id).as_str()),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        assert_eq!(stored[0], settings);
+        let _del = delete_scripts_from_db(
+            Some(format!("id='{}'", settings.id).as_str()),
+            pool.acquire().await.unwrap(),
+        )
+        .await;
 
         let err_scripts =
             get_scripts_from_db(Some("this-doesnt-work"), pool.acquire().await.unwrap()).await;
@@ -241,7 +579,7 @@ mod tests {
         .await;
         assert_eq!(single_del, axum::http::StatusCode::OK);
         let scripts = count_rows(pool.acquire().await.unwrap()).await.unwrap();
-        assert_eq!(scripts, 5);
+        assert_eq!(scripts, 8);
 
         let del_fail =
             delete_scripts_from_db(Some("this_doesnt_work"), pool.acquire().await.unwrap()).await;
@@ -253,6 +591,114 @@ mod tests {
         assert_eq!(scripts, 0);
     }
 
+    #[test]
+    fn test_parameters() {
+        let script = Script {
+            parameters: vec![
+                ScriptParameter {
+                    name: "threshold".into(),
+                    kind: ParameterKind::Integer,
+                    default: Some(serde_json::json!(90)),
+                    ..Default::default()
+                },
+                ScriptParameter {
+                    name: "path".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(script.validate_parameters(), Ok(()));
+
+        let supplied = HashMap::from([("path".to_string(), serde_json::json!("/var"))]);
+        let resolved = script.resolve_parameters(&supplied).unwrap();
+        assert_eq!(resolved["threshold"], serde_json::json!(90));
+        assert_eq!(resolved["path"], serde_json::json!("/var"));
+
+        assert_eq!(
+            script.resolve_parameters(&HashMap::new()),
+            Err(ParameterError::Missing("path".into()))
+        );
+        let wrong_type = HashMap::from([
+            ("path".to_string(), serde_json::json!("/var")),
+            ("threshold".to_string(), serde_json::json!("high")),
+        ]);
+        assert_eq!(
+            script.resolve_parameters(&wrong_type),
+            Err(ParameterError::WrongType(
+                "threshold".into(),
+                ParameterKind::Integer
+            ))
+        );
+        let unknown = HashMap::from([
+            ("path".to_string(), serde_json::json!("/var")),
+            ("other".to_string(), serde_json::json!(1)),
+        ]);
+        assert_eq!(
+            script.resolve_parameters(&unknown),
+            Err(ParameterError::Unknown("other".into()))
+        );
+
+        let mut invalid = script.clone();
+        invalid.parameters[1].name = "not valid".into();
+        assert_eq!(
+            invalid.validate_parameters(),
+            Err(ParameterError::InvalidName("not valid".into()))
+        );
+        invalid.parameters[1].name = "threshold".into();
+        assert_eq!(
+            invalid.validate_parameters(),
+            Err(ParameterError::Duplicate("threshold".into()))
+        );
+    }
+
+    #[test]
+    fn test_interpreter() {
+        assert_eq!(Interpreter::default(), Interpreter::Bash);
+        assert_eq!(Interpreter::Python3.name(), "python3");
+        assert_eq!(
+            Interpreter::Custom("#!/usr/bin/env ruby".into()).name(),
+            "ruby"
+        );
+        assert_eq!(
+            Interpreter::Custom("#!/usr/bin/perl -w".into()).name(),
+            "perl"
+        );
+
+        assert!(Interpreter::Bash.is_supported_by(&[]));
+        assert!(!Interpreter::Python3.is_supported_by(&[]));
+        let reported = vec!["sh".to_string(), "python3".to_string()];
+        assert!(Interpreter::Python3.is_supported_by(&reported));
+        assert!(!Interpreter::Pwsh.is_supported_by(&reported));
+        // default scripts keep running on agents reporting only sh
+        assert!(Interpreter::Bash.is_supported_by(&["sh".to_string()]));
+
+        let script = Script {
+            interpreter: Interpreter::Custom("ruby".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            script.validate_settings(),
+            Err(SettingsError::InvalidShebang("ruby".into()))
+        );
+        let script = Script {
+            environment: HashMap::from([("1FOO".to_string(), "bar".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            script.validate_settings(),
+            Err(SettingsError::InvalidEnvironmentName("1FOO".into()))
+        );
+        let script = Script {
+            output_regex: "(?P<load>[0-9.]+".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            script.validate_settings(),
+            Err(SettingsError::OutputRegex(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_apis() {
         registry()
@@ -267,6 +713,7 @@ mod tests {
         let new_script = Script::default();
         let api_post = post_scripts_api(
             claims.clone(),
+            axum::extract::Query(ChangeNoteParams::default()),
             axum::extract::State(pool.clone()),
             Json(new_script.clone()),
         )
@@ -274,6 +721,44 @@ mod tests {
         .into_response();
         assert_eq!(api_post.status(), axum::http::StatusCode::CREATED);
 
+        let invalid_script = Script {
+            parameters: vec![ScriptParameter {
+                name: "count".into(),
+                kind: ParameterKind::Integer,
+                default: Some(serde_json::json!("ten")),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let api_post_invalid = post_scripts_api(
+            claims.clone(),
+            axum::extract::Query(ChangeNoteParams::default()),
+            axum::extract::State(pool.clone()),
+            Json(invalid_script),
+        )
+        .await
+        .into_response();
+        assert_eq!(
+            api_post_invalid.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        let api_post_invalid_regex = post_scripts_api(
+            claims.clone(),
+            axum::extract::Query(ChangeNoteParams::default()),
+            axum::extract::State(pool.clone()),
+            Json(Script {
+                output_regex: "[".into(),
+                ..Default::default()
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(
+            api_post_invalid_regex.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+
         let api_get_all = get_scripts_api(claims.clone(), axum::extract::State(pool.clone()))
             .await
             .into_response();
@@ -0,0 +1,510 @@
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fmt::Display,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path as FsPath, PathBuf},
+    sync::RwLock,
+};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    jwt::Claims,
+};
+
+/// Public part of a key the server signs script payloads with
+///
+/// the private key never touches the database, it only lives in the key file
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct PublishedKey {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub algorithm: String,
+    /// base64 encoded public key
+    pub public_key: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+    /// rotated keys stay published, so in-flight payloads and approvals still verify
+    #[serde(default)]
+    pub retired: Option<DateTime<Utc>>,
+}
+
+/// Signature over the canonical json of a payload
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct PayloadSignature {
+    pub key_id: Uuid,
+    pub algorithm: String,
+    /// base64 encoded signature
+    pub signature: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SigningError {
+    NoActiveKey,
+    InvalidKeyFile(String),
+    Io(String),
+    Db(String),
+}
+
+impl Display for SigningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigningError::NoActiveKey => write!(f, "no signing key loaded"),
+            SigningError::InvalidKeyFile(p) => write!(f, "signing key file {p} is invalid"),
+            SigningError::Io(e) => write!(f, "signing key file could not be written: {e}"),
+            SigningError::Db(e) => write!(f, "signing key could not be published: {e}"),
+        }
+    }
+}
+
+const ALGORITHM: &str = "ed25519";
+
+/// Private signing key of the server, read from the key file
+#[derive(Clone)]
+pub struct ServerKey {
+    pub id: Uuid,
+    key: SigningKey,
+    path: PathBuf,
+    /// public keys rotated out, read from the retired keys file next to the key file
+    previous: Vec<PublishedKey>,
+}
+
+/// key used for dispatched scripts, set on startup and on rotation
+static ACTIVE_KEY: RwLock<Option<ServerKey>> = RwLock::new(None);
+
+impl PublishedKey {
+    /// Insert `PublishedKey` into signing_keys table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | algorithm | TEXT | ed25519
+    /// | public_key | TEXT | base64
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | retired | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ"), NULL for the active key
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"INSERT INTO signing_keys( id, algorithm, public_key, created, retired ) VALUES ( ?, ?, ?, ?, ? )"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.algorithm)
+            .bind(self.public_key)
+            .bind(utc_to_str(self.created))
+            .bind(self.retired.map(utc_to_str))
+            .execute(&mut *connection)
+            .await
+    }
+
+    fn from_key(id: Uuid, key: &SigningKey) -> Self {
+        PublishedKey {
+            id,
+            algorithm: ALGORITHM.into(),
+            public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+            created: Utc::now(),
+            retired: None,
+        }
+    }
+
+    fn verifying_key(&self) -> Option<VerifyingKey> {
+        let bytes: [u8; 32] = STANDARD.decode(&self.public_key).ok()?.try_into().ok()?;
+        VerifyingKey::from_bytes(&bytes).ok()
+    }
+}
+
+impl From<SqliteRow> for PublishedKey {
+    fn from(s: SqliteRow) -> Self {
+        PublishedKey {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            algorithm: s.get::<String, _>("algorithm"),
+            public_key: s.get::<String, _>("public_key"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+            retired: s
+                .get::<Option<String>, _>("retired")
+                .as_deref()
+                .map(utc_from_str),
+        }
+    }
+}
+
+impl ServerKey {
+    /// Load the signing key from `path`, a new key is generated if the file does not exist
+    ///
+    /// the public key is published in the signing_keys table, all other keys are retired
+    pub async fn load(path: &FsPath, pool: &SqlitePool) -> Result<ServerKey, SigningError> {
+        let Some(key) = read_key_file(path)? else {
+            info!("{} not found, generating new signing key", path.display());
+            return ServerKey::rotate(path, pool).await;
+        };
+        let previous = read_retired_file(path)?;
+        let id = publish(&key, pool).await?;
+        retire_others(id, pool).await?;
+        Ok(ServerKey {
+            id,
+            key,
+            path: path.to_path_buf(),
+            previous,
+        })
+    }
+
+    /// Replace the key file with a new key, the old public key stays published as retired
+    ///
+    /// the new public key is published before the key file is written, agents would reject
+    /// payloads signed with a key they cannot fetch; it is removed again if writing fails
+    pub async fn rotate(path: &FsPath, pool: &SqlitePool) -> Result<ServerKey, SigningError> {
+        let mut previous = read_retired_file(path)?;
+        if let Some(current) = read_key_file(path)? {
+            let id = publish(&current, pool).await?;
+            previous.push(PublishedKey::from_key(id, &current));
+        }
+        let key = SigningKey::generate(&mut OsRng);
+        let id = publish(&key, pool).await?;
+        let retired = match previous.is_empty() {
+            true => Ok(()),
+            false => write_private_file(
+                &retired_path(path),
+                &serde_json::to_string(&previous).unwrap(),
+            ),
+        };
+        let written =
+            retired.and_then(|_| write_private_file(path, &STANDARD.encode(key.to_bytes())));
+        if let Err(e) = written {
+            let _res = query("DELETE FROM signing_keys WHERE id = ?")
+                .bind(id.to_string())
+                .execute(&mut *pool.acquire().await.unwrap())
+                .await;
+            return Err(e);
+        }
+        retire_others(id, pool).await?;
+        Ok(ServerKey {
+            id,
+            key,
+            path: path.to_path_buf(),
+            previous,
+        })
+    }
+
+    /// public keys of this key and all keys rotated out before it
+    ///
+    /// approvals are only verified against these, never against keys from the database
+    pub fn trusted_keys(&self) -> Vec<PublishedKey> {
+        let mut keys = self.previous.clone();
+        keys.push(PublishedKey::from_key(self.id, &self.key));
+        keys
+    }
+
+    /// Sign the canonical json of `payload`
+    pub fn sign<T: Serialize>(&self, payload: &T) -> PayloadSignature {
+        let signature = self.key.sign(&canonical_json(payload));
+        PayloadSignature {
+            key_id: self.id,
+            algorithm: ALGORITHM.into(),
+            signature: STANDARD.encode(signature.to_bytes()),
+        }
+    }
+}
+
+/// read the seed from the key file at `path`, `None` if there is no key file
+fn read_key_file(path: &FsPath) -> Result<Option<SigningKey>, SigningError> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let seed: [u8; 32] = STANDARD
+        .decode(content.trim())
+        .ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or(SigningError::InvalidKeyFile(path.display().to_string()))?;
+    Ok(Some(SigningKey::from_bytes(&seed)))
+}
+
+/// retired keys file next to the key file at `path`
+fn retired_path(path: &FsPath) -> PathBuf {
+    path.with_extension("retired")
+}
+
+/// public keys rotated out of the key file at `path`, empty if there is no retired keys file
+fn read_retired_file(path: &FsPath) -> Result<Vec<PublishedKey>, SigningError> {
+    let path = retired_path(path);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    serde_json::from_str(&content)
+        .map_err(|_| SigningError::InvalidKeyFile(path.display().to_string()))
+}
+
+/// write `content` to `path`, readable by the owner only
+///
+/// written to a temporary file first, so a failed write does not leave a truncated file
+fn write_private_file(path: &FsPath, content: &str) -> Result<(), SigningError> {
+    let io = |e: std::io::Error| SigningError::Io(e.to_string());
+    let tmp = path.with_extension("tmp");
+    // the mode only applies to new files
+    let _rm = std::fs::remove_file(&tmp);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).map_err(io)?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(io)?;
+    std::fs::rename(&tmp, path).map_err(io)
+}
+
+/// publish the public key of `key` unless it is known, returns its id
+async fn publish(key: &SigningKey, pool: &SqlitePool) -> Result<Uuid, SigningError> {
+    let public = PublishedKey::from_key(Uuid::new_v4(), key);
+    let filter = format!("public_key='{}'", public.public_key);
+    let known = get_keys_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    if let Some(k) = known.first() {
+        return Ok(k.id);
+    }
+    let id = public.id;
+    public
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await
+        .map_err(|e| SigningError::Db(e.to_string()))?;
+    Ok(id)
+}
+
+/// retire all published keys but `id`
+async fn retire_others(id: Uuid, pool: &SqlitePool) -> Result<(), SigningError> {
+    let q = "UPDATE signing_keys SET retired = ? WHERE retired IS NULL AND id != ?";
+    query(q)
+        .bind(utc_to_str(Utc::now()))
+        .bind(id.to_string())
+        .execute(&mut *pool.acquire().await.unwrap())
+        .await
+        .map_err(|e| SigningError::Db(e.to_string()))?;
+    Ok(())
+}
+
+/// Make `key` the key all payloads are signed with
+pub fn set_active_key(key: ServerKey) {
+    *ACTIVE_KEY.write().unwrap() = Some(key);
+}
+
+/// the key all payloads are signed with, `None` before startup loaded it
+pub fn active_key() -> Option<ServerKey> {
+    ACTIVE_KEY.read().unwrap().clone()
+}
+
+/// canonical json: object keys sorted, no whitespace
+pub fn canonical_json<T: Serialize>(payload: &T) -> Vec<u8> {
+    // serde_json::Map is a BTreeMap, so converting to a Value sorts all keys
+    let value = serde_json::to_value(payload).unwrap_or(Value::Null);
+    serde_json::to_vec(&value).unwrap_or_default()
+}
+
+/// Sign the canonical json of `payload` with the active key
+pub fn sign<T: Serialize>(payload: &T) -> Result<PayloadSignature, SigningError> {
+    let active = active_key().ok_or(SigningError::NoActiveKey)?;
+    Ok(active.sign(payload))
+}
+
+/// Check `signature` of `payload` against the published keys
+pub fn verify<T: Serialize>(
+    payload: &T,
+    signature: &PayloadSignature,
+    keys: &[PublishedKey],
+) -> bool {
+    let Some(key) = keys
+        .iter()
+        .find(|k| k.id == signature.key_id)
+        .and_then(|k| k.verifying_key())
+    else {
+        return false;
+    };
+    let Some(sig) = STANDARD
+        .decode(&signature.signature)
+        .ok()
+        .and_then(|s| Signature::from_slice(&s).ok())
+    else {
+        return false;
+    };
+    key.verify(&canonical_json(payload), &sig).is_ok()
+}
+
+/// API to get all published signing keys, the active key has no `retired` timestamp
+///
+/// no login required, agents fetch the keys to pin them
+pub async fn get_signing_keys_api(State(pool): State<SqlitePool>) -> impl IntoResponse {
+    let key_vec = get_keys_from_db(
+        Some("1=1 ORDER BY created ASC"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    Json(key_vec)
+}
+
+/// API to rotate the signing key
+pub async fn rotate_signing_key_api(
+    claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let Some(active) = active_key() else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "No signing key loaded").into_response();
+    };
+    match ServerKey::rotate(&active.path, &pool).await {
+        Ok(key) => {
+            info!("signing key rotated by {}", claims.sub);
+            let filter = format!("id='{}'", key.id);
+            let published = get_keys_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+            set_active_key(key);
+            (StatusCode::CREATED, Json(published.first().cloned())).into_response()
+        }
+        Err(e) => {
+            warn!("{e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+pub async fn get_keys_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<PublishedKey> {
+    let stmt = if let Some(f) = filter {
+        format!("SELECT * FROM signing_keys WHERE {f}")
+    } else {
+        "SELECT * FROM signing_keys".into()
+    };
+    let keys = match query(&stmt).fetch_all(&mut *connection).await {
+        Ok(d) => d,
+        Err(_) => return Vec::new(),
+    };
+
+    keys.into_iter().map(|s| s.into()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_database, init_database};
+    use serde_json::json;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_signing_keys() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+
+        let key = ServerKey::load(&path, &pool).await.unwrap();
+        // loading the same file again does not publish a second key
+        let key_again = ServerKey::load(&path, &pool).await.unwrap();
+        assert_eq!(key.id, key_again.id);
+        let keys = get_keys_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(keys.len(), 1);
+
+        let payload = json!({"b": 1, "a": {"d": true, "c": "x"}});
+        assert_eq!(
+            canonical_json(&payload),
+            br#"{"a":{"c":"x","d":true},"b":1}"#
+        );
+        let signature = key.sign(&payload);
+        assert!(verify(&payload, &signature, &keys));
+        assert!(!verify(&json!({"b": 2}), &signature, &keys));
+        assert!(!verify(&payload, &signature, &[]));
+
+        let new_key = ServerKey::rotate(&path, &pool).await.unwrap();
+        let keys = get_keys_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.iter().filter(|k| k.retired.is_none()).count(), 1);
+        assert_ne!(new_key.id, signature.key_id);
+        // payloads signed before the rotation still verify
+        assert!(verify(&payload, &signature, &keys));
+        assert!(verify(&payload, &signature, &new_key.trusted_keys()));
+        // the retired key is read back from the retired keys file
+        let reloaded = ServerKey::load(&path, &pool).await.unwrap();
+        let trusted: Vec<Uuid> = reloaded.trusted_keys().iter().map(|k| k.id).collect();
+        assert_eq!(trusted, vec![key.id, new_key.id]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [&path, &retired_path(&path)] {
+                let mode = std::fs::metadata(file).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        // a key file that cannot be written does not publish its key
+        let unwritable = std::env::temp_dir()
+            .join(Uuid::new_v4().to_string())
+            .join("server.key");
+        assert!(ServerKey::rotate(&unwritable, &pool).await.is_err());
+        let keys = get_keys_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(keys.len(), 2);
+        let active: Vec<Uuid> = keys
+            .iter()
+            .filter(|k| k.retired.is_none())
+            .map(|k| k.id)
+            .collect();
+        assert_eq!(active, vec![new_key.id]);
+
+        let broken = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        std::fs::write(&broken, "not a key").unwrap();
+        assert!(ServerKey::load(&broken, &pool).await.is_err());
+        let _rm = std::fs::remove_file(broken);
+        let _rm = std::fs::remove_file(retired_path(&path));
+        let _rm = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let path = std::env::temp_dir().join(format!("{}.key", Uuid::new_v4()));
+        set_active_key(ServerKey::load(&path, &pool).await.unwrap());
+
+        let api_rotate = rotate_signing_key_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_rotate.status(), StatusCode::CREATED);
+
+        let api_get_all = get_signing_keys_api(axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+        let keys = hyper::body::to_bytes(api_get_all.into_body())
+            .await
+            .unwrap();
+        let keys: Vec<PublishedKey> = serde_json::from_slice(&keys).unwrap();
+        assert!(!keys.is_empty());
+        let _rm = std::fs::remove_file(retired_path(&path));
+        let _rm = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,485 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    host::Host,
+    jwt::Claims,
+    maintenance::active_windows_for,
+};
+
+/// Mutes notifications matching all of its selectors between `starts` and `ends`
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Silence {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    #[serde(default)]
+    pub host_id: Option<Uuid>,
+    /// host has all of these attributes
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default)]
+    pub sched_id: Option<Uuid>,
+    /// alert has all of these labels
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default = "Utc::now")]
+    pub starts: DateTime<Utc>,
+    pub ends: DateTime<Utc>,
+    #[serde(default)]
+    pub comment: String,
+    /// set to the logged in user
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+/// What a notification is about, checked against silences and maintenance windows
+#[derive(Debug, Clone)]
+pub struct Subject<'a> {
+    pub host: &'a Host,
+    pub sched_id: Option<Uuid>,
+    pub labels: &'a BTreeMap<String, String>,
+}
+
+impl Silence {
+    /// Insert into or Replace `Silence` in silences table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | host_id | TEXT | uuid, NULL for all hosts
+    /// | attributes | TEXT | json list
+    /// | sched_id | TEXT | uuid, NULL for all schedules
+    /// | labels | TEXT | json map
+    /// | starts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | ends | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | comment | TEXT |
+    /// | created_by | TEXT | email of the user
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"REPLACE INTO silences(id, host_id, attributes, sched_id, labels, starts, ends, comment, created_by, created) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.host_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(self.sched_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&self.labels).unwrap())
+            .bind(utc_to_str(self.starts))
+            .bind(utc_to_str(self.ends))
+            .bind(self.comment)
+            .bind(self.created_by)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.host_id.is_none()
+            && self.attributes.is_empty()
+            && self.sched_id.is_none()
+            && self.labels.is_empty()
+        {
+            return Err("at least one selector is required".into());
+        }
+        if self.ends <= self.starts {
+            return Err("ends must be after starts".into());
+        }
+        Ok(())
+    }
+
+    /// all selectors match `subject`
+    pub fn matches(&self, subject: &Subject) -> bool {
+        self.host_id.is_none_or(|id| id == subject.host.id)
+            && self
+                .attributes
+                .iter()
+                .all(|a| subject.host.selector_attributes().contains(a))
+            && self.sched_id.is_none_or(|id| Some(id) == subject.sched_id)
+            && self
+                .labels
+                .iter()
+                .all(|(k, v)| subject.labels.get(k) == Some(v))
+    }
+}
+
+impl From<SqliteRow> for Silence {
+    fn from(s: SqliteRow) -> Self {
+        let uuid = |column: &str| {
+            s.get::<Option<String>, _>(column)
+                .and_then(|id| id.parse().ok())
+        };
+        Silence {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            host_id: uuid("host_id"),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            sched_id: uuid("sched_id"),
+            labels: serde_json::from_str(&s.get::<String, _>("labels")).unwrap_or_default(),
+            starts: utc_from_str(&s.get::<String, _>("starts")),
+            ends: utc_from_str(&s.get::<String, _>("ends")),
+            comment: s.get::<String, _>("comment"),
+            created_by: s.get::<String, _>("created_by"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+fn active_filter(now: DateTime<Utc>) -> String {
+    let now = utc_to_str(now);
+    format!("starts <= '{now}' AND ends > '{now}'")
+}
+
+/// active silence or maintenance window muting `subject`, `None` if it may be notified
+pub async fn suppressed_by(
+    subject: &Subject<'_>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Option<String> {
+    let silences =
+        get_silences_from_db(Some(&active_filter(now)), pool.acquire().await.unwrap()).await;
+    if let Some(silence) = silences.iter().find(|s| s.matches(subject)) {
+        return Some(format!("silence {}", silence.id));
+    }
+    active_windows_for(subject.host, now, pool)
+        .await
+        .first()
+        .map(|w| format!("maintenance window {}", w.name))
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct SilenceQueryParams {
+    /// only silences active right now
+    active: Option<bool>,
+}
+
+/// API to get silences, latest end first
+pub async fn get_silences_api(
+    _claims: Claims,
+    Query(params): Query<SilenceQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let condition = match params.active {
+        Some(true) => active_filter(Utc::now()),
+        _ => "1=1".into(),
+    };
+    let filter = format!("{condition} ORDER BY ends DESC");
+    let silence_vec = get_silences_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(silence_vec)
+}
+
+/// API to get one silence
+pub async fn get_one_silence_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let silence_vec = get_silences_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(silence_vec)
+}
+
+/// API to create or replace a silence
+pub async fn post_silences_api(
+    claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(mut payload): Json<Silence>,
+) -> Response {
+    if let Err(e) = payload.validate() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+    }
+    payload.created_by = claims.sub.to_string();
+    let id = payload.id.to_string();
+    match payload.insert_into_db(pool.acquire().await.unwrap()).await {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(_) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Host ID or Schedule ID not found",
+        )
+            .into_response(),
+    }
+}
+
+/// API to delete a silence
+pub async fn delete_one_silence_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    delete_silences_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+pub async fn get_silences_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Silence> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM silences WHERE {f}"),
+        None => "SELECT * FROM silences".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Silence::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_silences_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM silences WHERE {f}"),
+        None => "DELETE FROM silences".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        maintenance::MaintenanceWindow,
+    };
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_silences() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        let now = Utc::now();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            attributes: vec!["web".into(), "prod".into()],
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        let other = Host {
+            id: Uuid::new_v4(),
+            attributes: vec!["db".into()],
+            ..Default::default()
+        };
+        let labels = BTreeMap::from([
+            ("alertname".to_string(), "disk full".to_string()),
+            ("team".to_string(), "ops".to_string()),
+        ]);
+        let subject = Subject {
+            host: &host,
+            sched_id: Some(Uuid::new_v4()),
+            labels: &labels,
+        };
+        let other_subject = Subject {
+            host: &other,
+            sched_id: None,
+            labels: &BTreeMap::new(),
+        };
+
+        let silence = Silence {
+            id: Uuid::new_v4(),
+            host_id: None,
+            attributes: vec!["web".into()],
+            sched_id: None,
+            labels: BTreeMap::from([("alertname".to_string(), "disk full".to_string())]),
+            starts: now - Duration::minutes(1),
+            ends: now + Duration::hours(1),
+            comment: "cleanup running".into(),
+            created_by: "".into(),
+            created: now,
+        };
+        assert!(silence.validate().is_ok());
+        assert!(silence.matches(&subject));
+        assert!(!silence.matches(&other_subject));
+        let by_host = Silence {
+            host_id: Some(other.id),
+            attributes: vec![],
+            labels: BTreeMap::new(),
+            ..silence.clone()
+        };
+        assert!(by_host.matches(&other_subject));
+        assert!(!by_host.matches(&subject));
+        let by_sched = Silence {
+            sched_id: subject.sched_id,
+            attributes: vec![],
+            labels: BTreeMap::new(),
+            ..silence.clone()
+        };
+        assert!(by_sched.matches(&subject));
+        assert!(!by_sched.matches(&other_subject));
+        let wrong_label = Silence {
+            labels: BTreeMap::from([("team".to_string(), "dev".to_string())]),
+            ..silence.clone()
+        };
+        assert!(!wrong_label.matches(&subject));
+
+        assert_eq!(suppressed_by(&subject, now, &pool).await, None);
+        let _s = silence
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            suppressed_by(&subject, now, &pool).await,
+            Some(format!("silence {}", silence.id))
+        );
+        // expired
+        assert_eq!(
+            suppressed_by(&subject, now + Duration::hours(2), &pool).await,
+            None
+        );
+        assert_eq!(suppressed_by(&other_subject, now, &pool).await, None);
+
+        // maintenance windows suppress everything of their hosts
+        let window = MaintenanceWindow {
+            id: Uuid::new_v4(),
+            name: "db patching".into(),
+            attributes: vec!["db".into()],
+            starts: now - Duration::minutes(1),
+            ends: now + Duration::hours(1),
+            pause_schedules: false,
+            comment: "".into(),
+            created_by: "".into(),
+            created: now,
+        };
+        let _w = window.insert_into_db(pool.acquire().await.unwrap()).await;
+        assert_eq!(
+            suppressed_by(&other_subject, now, &pool).await,
+            Some("maintenance window db patching".into())
+        );
+
+        let mut invalid = silence.clone();
+        invalid.attributes = vec![];
+        invalid.labels = BTreeMap::new();
+        assert!(invalid.validate().is_err());
+        let mut invalid = silence.clone();
+        invalid.ends = invalid.starts - Duration::seconds(1);
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let now = Utc::now();
+        let silence = Silence {
+            id: Uuid::new_v4(),
+            host_id: None,
+            attributes: vec!["web".into()],
+            sched_id: None,
+            labels: BTreeMap::new(),
+            starts: now - Duration::minutes(1),
+            ends: now + Duration::hours(1),
+            comment: "".into(),
+            created_by: "".into(),
+            created: now,
+        };
+        let api_post = post_silences_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(silence.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let mut unknown_host = silence.clone();
+        unknown_host.id = Uuid::new_v4();
+        unknown_host.host_id = Some(Uuid::new_v4());
+        let api_post_unknown = post_silences_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(unknown_host),
+        )
+        .await;
+        assert_eq!(api_post_unknown.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let mut invalid = silence.clone();
+        invalid.attributes = vec![];
+        let api_post_invalid = post_silences_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(invalid),
+        )
+        .await;
+        assert_eq!(api_post_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get_active = get_silences_api(
+            claims.clone(),
+            axum::extract::Query(SilenceQueryParams { active: Some(true) }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_active.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get_active.into_body())
+            .await
+            .unwrap();
+        let active: Vec<Silence> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].created_by, claims.sub.to_string());
+
+        let api_get_one = get_one_silence_api(
+            claims.clone(),
+            axum::extract::Path(silence.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get_one.status(), StatusCode::OK);
+
+        let api_del = delete_one_silence_api(
+            claims.clone(),
+            axum::extract::Path(silence.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del.status(), StatusCode::OK);
+        assert!(get_silences_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+    }
+}
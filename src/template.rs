@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde_json::Value;
+
+use crate::host::Host;
+
+/// Values available to `{{ scope.name }}` placeholders inside a script
+///
+/// | Scope | Source
+/// :--- | :---
+/// | params | resolved script parameters
+/// | host | `id`, `alias`, `ip` and `attributes` (comma-seperated) of the target host
+/// | facts | facts reported for the target host
+/// | vars | variables of the attributes (groups) the host belongs to
+/// | event | notifications only, `kind`, `title`, `summary`, `severity`, ... of the event
+/// | labels | notifications only, labels of the alert
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    pub params: HashMap<String, Value>,
+    pub host: HashMap<String, String>,
+    pub facts: HashMap<String, String>,
+    pub vars: HashMap<String, String>,
+    pub event: HashMap<String, String>,
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    Unterminated,
+    UnknownPlaceholder(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Unterminated => write!(f, "unterminated placeholder, missing '}}}}'"),
+            TemplateError::UnknownPlaceholder(p) => write!(f, "unknown placeholder '{p}'"),
+        }
+    }
+}
+
+impl TemplateContext {
+    pub fn new(host: &Host, params: HashMap<String, Value>, vars: HashMap<String, String>) -> Self {
+        let host_values = HashMap::from([
+            ("id".to_string(), host.id.to_string()),
+            ("alias".to_string(), host.alias.clone()),
+            ("ip".to_string(), host.ip.clone()),
+            ("attributes".to_string(), host.attributes.join(",")),
+        ]);
+        TemplateContext {
+            params,
+            host: host_values,
+            facts: host.facts.clone(),
+            vars,
+            ..Default::default()
+        }
+    }
+
+    fn lookup(&self, placeholder: &str) -> Option<String> {
+        let (scope, name) = placeholder.split_once('.')?;
+        match scope {
+            "params" => self.params.get(name).map(value_to_string),
+            "host" => self.host.get(name).cloned(),
+            "facts" => self.facts.get(name).cloned(),
+            "vars" => self.vars.get(name).cloned(),
+            "event" => self.event.get(name).cloned(),
+            // labels differ per alert rule, missing ones render empty
+            "labels" => Some(self.labels.get(name).cloned().unwrap_or_default()),
+            _ => None,
+        }
+    }
+}
+
+/// render a json value without quotes around strings
+pub fn value_to_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => "".into(),
+        x => x.to_string(),
+    }
+}
+
+/// scopes of [`TemplateContext`], other `{{ ... }}` are left as they are
+const SCOPES: [&str; 6] = ["params", "host", "facts", "vars", "event", "labels"];
+
+/// `after` (the text following `{{`) starts with a known scope
+fn is_placeholder(after: &str) -> bool {
+    let after = after.trim_start();
+    SCOPES.iter().any(|scope| {
+        after
+            .strip_prefix(scope)
+            .is_some_and(|name| name.starts_with('.'))
+    })
+}
+
+/// Replace all `{{ scope.name }}` placeholders in `template`
+///
+/// unknown names of a known scope are an error, so a script never runs with half of its values
+/// missing; braces without a known scope, like `docker inspect --format '{{.State.Status}}'`,
+/// are kept
+pub fn render(template: &str, context: &TemplateContext) -> Result<String, TemplateError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        if !is_placeholder(after) {
+            rendered.push_str("{{");
+            rest = after;
+            continue;
+        }
+        let end = after.find("}}").ok_or(TemplateError::Unterminated)?;
+        let placeholder = after[..end].trim();
+        let value = context
+            .lookup(placeholder)
+            .ok_or(TemplateError::UnknownPlaceholder(placeholder.to_string()))?;
+        rendered.push_str(&value);
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let host = Host {
+            alias: "web-1".into(),
+            attributes: vec!["linux".into(), "prod".into()],
+            facts: HashMap::from([("os".to_string(), "debian".to_string())]),
+            ..Default::default()
+        };
+        let context = TemplateContext::new(
+            &host,
+            HashMap::from([
+                ("threshold".to_string(), json!(90)),
+                ("path".to_string(), json!("/var")),
+            ]),
+            HashMap::from([("team".to_string(), "ops".to_string())]),
+        );
+        let rendered = render(
+            "df {{ params.path }} # {{params.threshold}} {{ host.alias }} {{ host.attributes }} {{ facts.os }} {{ vars.team }}",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered, "df /var # 90 web-1 linux,prod debian ops");
+
+        assert_eq!(
+            render("no placeholder", &context).unwrap(),
+            "no placeholder"
+        );
+        assert_eq!(
+            render("{{ params.unknown }}", &context).unwrap_err(),
+            TemplateError::UnknownPlaceholder("params.unknown".into())
+        );
+        assert_eq!(
+            render("{{ host.alias", &context).unwrap_err(),
+            TemplateError::Unterminated
+        );
+
+        // literal braces of existing scripts render unchanged
+        for literal in [
+            "docker inspect --format '{{.State.Status}}' web",
+            "kubectl get pods -o go-template='{{range .items}}{{.metadata.name}}{{end}}'",
+            "echo '{{ item }}' '{{' '}}' {{hostname}}",
+        ] {
+            assert_eq!(render(literal, &context).unwrap(), literal);
+        }
+        assert_eq!(
+            render(
+                "docker inspect --format '{{.Name}}' {{ host.alias }}",
+                &context
+            )
+            .unwrap(),
+            "docker inspect --format '{{.Name}}' web-1"
+        );
+
+        let mut context = context;
+        context.event = HashMap::from([("title".to_string(), "disk full".to_string())]);
+        context.labels = HashMap::from([("team".to_string(), "ops".to_string())]);
+        assert_eq!(
+            render(
+                "{{ event.title }} {{ labels.team }}{{ labels.missing }}",
+                &context
+            )
+            .unwrap(),
+            "disk full ops"
+        );
+        assert_eq!(
+            render("{{ event.missing }}", &context).unwrap_err(),
+            TemplateError::UnknownPlaceholder("event.missing".into())
+        );
+    }
+}
@@ -0,0 +1,752 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, query, sqlite::SqliteRow, Row, Sqlite, SqlitePool};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{utc_from_str, utc_to_str},
+    host::get_hosts_from_db,
+    jwt::Claims,
+    package::{get_packages_from_db, PackageFormat},
+    parser::ParseError,
+};
+
+/// Built-in script listing the pending updates of all package managers found on the host,
+/// the output of each package manager starts with a `# format: <format>` line
+pub const UPDATES_SCRIPT: &str = r##"if command -v apt >/dev/null 2>&1; then
+  echo "# format: dpkg"
+  apt list --upgradable 2>/dev/null
+fi
+if command -v dnf >/dev/null 2>&1; then
+  echo "# format: rpm"
+  dnf -q updateinfo list --updates 2>/dev/null
+elif command -v yum >/dev/null 2>&1; then
+  echo "# format: rpm"
+  yum -q updateinfo list updates 2>/dev/null
+fi
+if command -v apk >/dev/null 2>&1; then
+  echo "# format: apk"
+  apk version -l '<' 2>/dev/null
+fi
+if command -v checkupdates >/dev/null 2>&1; then
+  echo "# format: pacman"
+  checkupdates 2>/dev/null
+fi
+if command -v brew >/dev/null 2>&1; then
+  echo "# format: homebrew"
+  brew outdated --verbose 2>/dev/null
+fi
+"##;
+
+/// Pending update as reported by an agent or the updates script
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Update {
+    pub format: PackageFormat,
+    pub name: String,
+    #[serde(default)]
+    pub arch: String,
+    /// taken from the package inventory if empty
+    #[serde(default)]
+    pub installed_version: String,
+    pub available_version: String,
+    #[serde(default)]
+    pub security: bool,
+    /// advisories or repositories of the update, e.g. `RHSA-2024:1234`
+    #[serde(default)]
+    pub advisory: String,
+}
+
+/// Update pending on a host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct PendingUpdate {
+    pub host_id: Uuid,
+    pub format: PackageFormat,
+    pub name: String,
+    pub arch: String,
+    pub installed_version: String,
+    pub available_version: String,
+    pub security: bool,
+    pub advisory: String,
+    /// first report the update was pending in, kept while newer versions become available
+    pub first_seen: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for PendingUpdate {
+    fn from(s: SqliteRow) -> Self {
+        PendingUpdate {
+            host_id: s.get::<String, _>("host_id").parse().unwrap(),
+            format: PackageFormat::from_db(&s.get::<String, _>("format"))
+                .unwrap_or(PackageFormat::Dpkg),
+            name: s.get::<String, _>("name"),
+            arch: s.get::<String, _>("arch"),
+            installed_version: s.get::<String, _>("installed_version"),
+            available_version: s.get::<String, _>("available_version"),
+            security: s.get::<bool, _>("security"),
+            advisory: s.get::<String, _>("advisory"),
+            first_seen: utc_from_str(&s.get::<String, _>("first_seen")),
+        }
+    }
+}
+
+/// split `name-version-release` as printed by apk
+fn split_apk(full: &str) -> Option<(&str, String)> {
+    let mut parts = full.rsplitn(3, '-');
+    let (release, version, name) = (parts.next()?, parts.next()?, parts.next()?);
+    Some((name, format!("{version}-{release}")))
+}
+
+/// split `name-[epoch:]version-release.arch` as printed by dnf, epoch 0 is dropped
+fn split_nevra(nevra: &str) -> Option<(&str, String, &str)> {
+    let (nevr, arch) = nevra.rsplit_once('.')?;
+    let mut parts = nevr.rsplitn(3, '-');
+    let (release, version, name) = (parts.next()?, parts.next()?, parts.next()?);
+    let version = version.strip_prefix("0:").unwrap_or(version);
+    Some((name, format!("{version}-{release}"), arch))
+}
+
+/// parse the output of `UPDATES_SCRIPT`
+///
+/// | Format | Line
+/// :--- | :---
+/// | dpkg | `name/suite,suite version arch [upgradable from: version]` (apt list --upgradable), security if a suite ends with `-security`
+/// | rpm | `advisory type name-epoch:version-release.arch` (dnf updateinfo list), security if the type contains `Sec.`
+/// | apk | `name-version-release < version` (apk version -l '<')
+/// | pacman | `name version -> version` (checkupdates)
+/// | homebrew | `name (version) < version` (brew outdated --verbose)
+///
+/// updates of the same package are merged, e.g. one package fixed by several advisories
+pub fn parse_updates(output: &str) -> Result<Vec<Update>, ParseError> {
+    let mut format = None;
+    let mut updates: Vec<Update> = vec![];
+    for (n, line) in output.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let err = |e: &str| ParseError::Line(n + 1, e.to_string());
+        if let Some(name) = line.strip_prefix("# format:") {
+            format = Some(
+                PackageFormat::from_db(name.trim())
+                    .ok_or(err(&format!("unknown format {}", name.trim())))?,
+            );
+            continue;
+        }
+        let format = format.ok_or(err("missing '# format:' line"))?;
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let update = match format {
+            PackageFormat::Dpkg => {
+                // "Listing..." and warnings of apt
+                if !line.contains('/') || columns.len() < 3 {
+                    continue;
+                }
+                let (name, suites) = columns[0].split_once('/').unwrap_or_default();
+                Update {
+                    format,
+                    name: name.to_string(),
+                    arch: columns[2].to_string(),
+                    installed_version: line
+                        .split_once("upgradable from: ")
+                        .map(|(_, v)| v.trim_end_matches(']').to_string())
+                        .unwrap_or_default(),
+                    available_version: columns[1].to_string(),
+                    security: suites.split(',').any(|s| s.ends_with("-security")),
+                    advisory: suites.to_string(),
+                }
+            }
+            PackageFormat::Rpm => {
+                let [advisory, kind, nevra] = columns[..] else {
+                    return Err(err("expected advisory, type and package"));
+                };
+                let (name, version, arch) =
+                    split_nevra(nevra).ok_or(err("expected name-version-release.arch"))?;
+                Update {
+                    format,
+                    name: name.to_string(),
+                    arch: arch.to_string(),
+                    installed_version: "".into(),
+                    available_version: version,
+                    security: kind.contains("Sec."),
+                    advisory: advisory.to_string(),
+                }
+            }
+            PackageFormat::Apk => {
+                // header of apk version
+                if columns.first() == Some(&"Installed:") {
+                    continue;
+                }
+                let [full, "<", available] = columns[..] else {
+                    return Err(err("expected name-version-release < version"));
+                };
+                let (name, installed) =
+                    split_apk(full).ok_or(err("expected name-version-release"))?;
+                Update {
+                    format,
+                    name: name.to_string(),
+                    arch: "".into(),
+                    installed_version: installed,
+                    available_version: available.to_string(),
+                    security: false,
+                    advisory: "".into(),
+                }
+            }
+            _ => {
+                let (name, installed, available) = match columns[..] {
+                    [name, installed, "->", available] => (name, installed, available),
+                    [name, installed, "<", available] => (name, installed, available),
+                    [name, installed, "!=", available] => (name, installed, available),
+                    _ => return Err(err("expected name version -> version")),
+                };
+                Update {
+                    format,
+                    name: name.to_string(),
+                    arch: "".into(),
+                    installed_version: installed.trim_matches(['(', ')']).to_string(),
+                    available_version: available.to_string(),
+                    security: false,
+                    advisory: "".into(),
+                }
+            }
+        };
+        match updates
+            .iter_mut()
+            .find(|u| u.format == update.format && u.name == update.name && u.arch == update.arch)
+        {
+            Some(existing) => {
+                existing.security |= update.security;
+                if !update.advisory.is_empty() && !existing.advisory.contains(&update.advisory) {
+                    existing.advisory = format!("{}, {}", existing.advisory, update.advisory);
+                }
+            }
+            None => updates.push(update),
+        }
+    }
+    Ok(updates)
+}
+
+/// replace the pending updates of `host_id` for every format in `updates`,
+/// updates pending before keep their `first_seen`
+pub async fn store_updates(
+    host_id: Uuid,
+    updates: Vec<Update>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let mut reported: BTreeMap<PackageFormat, Vec<Update>> = BTreeMap::new();
+    for update in updates {
+        if update.name.is_empty() || update.available_version.is_empty() {
+            warn!("Update without name or version of host {host_id} skipped");
+            continue;
+        }
+        reported.entry(update.format).or_default().push(update);
+    }
+    let filter = format!("host_id='{host_id}'");
+    let installed = get_packages_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let mut tx = pool.begin().await?;
+    for (format, updates) in reported {
+        // mark pending updates of this format, whatever is left marked got installed
+        query("UPDATE pending_updates SET seen = 0 WHERE host_id = ? AND format = ?")
+            .bind(host_id.to_string())
+            .bind(format.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for mut update in updates {
+            if update.installed_version.is_empty() {
+                if let Some(p) = installed.iter().find(|p| {
+                    p.format == format
+                        && p.name == update.name
+                        && (update.arch.is_empty() || p.arch == update.arch)
+                }) {
+                    update.installed_version = p.version.clone();
+                }
+            }
+            let q = r#"INSERT INTO pending_updates(host_id, format, name, arch, installed_version, available_version, security, advisory, first_seen, seen) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+            ON CONFLICT(host_id, format, name, arch) DO UPDATE SET installed_version=excluded.installed_version, available_version=excluded.available_version, security=excluded.security, advisory=excluded.advisory, seen=1"#;
+            query(q)
+                .bind(host_id.to_string())
+                .bind(format.to_string())
+                .bind(&update.name)
+                .bind(&update.arch)
+                .bind(&update.installed_version)
+                .bind(&update.available_version)
+                .bind(update.security)
+                .bind(&update.advisory)
+                .bind(utc_to_str(now))
+                .execute(&mut *tx)
+                .await?;
+        }
+        let resolved =
+            query("DELETE FROM pending_updates WHERE host_id = ? AND format = ? AND seen = 0")
+                .bind(host_id.to_string())
+                .bind(format.to_string())
+                .execute(&mut *tx)
+                .await?;
+        debug!(
+            "Updates of host {host_id}: {} {format} updates no longer pending",
+            resolved.rows_affected()
+        );
+    }
+    tx.commit().await
+}
+
+/// Pending updates of one host
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct HostUpdateSummary {
+    pub host_id: Uuid,
+    pub alias: String,
+    pub pending: usize,
+    pub security: usize,
+    /// first_seen of the oldest pending security update
+    pub oldest_security_update: Option<DateTime<Utc>>,
+    /// newest package update of the inventory
+    pub last_patched: Option<DateTime<Utc>>,
+    pub days_since_last_patch: Option<i64>,
+}
+
+/// Pending updates of all hosts
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FleetUpdateSummary {
+    pub hosts: usize,
+    pub hosts_with_security_updates: usize,
+    pub pending: usize,
+    pub security: usize,
+    /// security update pending the longest
+    pub oldest_security_update: Option<PendingUpdate>,
+    /// hosts with security updates first, then by days since the last patch
+    pub per_host: Vec<HostUpdateSummary>,
+}
+
+/// pending update counts, the oldest unpatched security fix and the days since the last patch
+pub async fn fleet_summary(now: DateTime<Utc>, pool: &SqlitePool) -> FleetUpdateSummary {
+    let hosts = get_hosts_from_db(None, pool.acquire().await.unwrap()).await;
+    let updates = get_updates_from_db(
+        Some("1=1 ORDER BY first_seen"),
+        pool.acquire().await.unwrap(),
+    )
+    .await;
+    let q = "SELECT host_id, MAX(ts) AS ts FROM package_changes WHERE change = 'updated' GROUP BY host_id";
+    let patched: BTreeMap<String, String> = query(q)
+        .map(|row: SqliteRow| (row.get::<String, _>("host_id"), row.get::<String, _>("ts")))
+        .fetch_all(&mut *pool.acquire().await.unwrap())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mut per_host: Vec<HostUpdateSummary> = hosts
+        .iter()
+        .map(|host| {
+            let pending: Vec<&PendingUpdate> =
+                updates.iter().filter(|u| u.host_id == host.id).collect();
+            let security: Vec<&&PendingUpdate> = pending.iter().filter(|u| u.security).collect();
+            let last_patched = patched.get(&host.id.to_string()).map(|ts| utc_from_str(ts));
+            HostUpdateSummary {
+                host_id: host.id,
+                alias: host.alias.clone(),
+                pending: pending.len(),
+                security: security.len(),
+                oldest_security_update: security.first().map(|u| u.first_seen),
+                last_patched,
+                days_since_last_patch: last_patched.map(|ts| (now - ts).num_days()),
+            }
+        })
+        .collect();
+    per_host.sort_by_key(|h| {
+        (
+            h.security == 0,
+            std::cmp::Reverse(h.days_since_last_patch),
+            h.alias.clone(),
+        )
+    });
+    FleetUpdateSummary {
+        hosts: hosts.len(),
+        hosts_with_security_updates: per_host.iter().filter(|h| h.security > 0).count(),
+        pending: updates.len(),
+        security: updates.iter().filter(|u| u.security).count(),
+        oldest_security_update: updates.iter().find(|u| u.security).cloned(),
+        per_host,
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct UpdateQueryParams {
+    host_id: Option<Uuid>,
+    name: Option<String>,
+    security: Option<bool>,
+}
+
+impl UpdateQueryParams {
+    fn to_filter(&self, base: Option<String>) -> String {
+        let mut conditions: Vec<String> = base.into_iter().collect();
+        if let Some(host_id) = self.host_id {
+            conditions.push(format!("host_id = '{host_id}'"));
+        }
+        if let Some(name) = &self.name {
+            conditions.push(format!("name = '{}'", name.replace('\'', "''")));
+        }
+        if let Some(security) = self.security {
+            conditions.push(format!("security = {}", i64::from(security)));
+        }
+        conditions.push("1=1 ORDER BY first_seen, name".into());
+        conditions.join(" AND ")
+    }
+}
+
+/// API to get the pending updates of all hosts, oldest first
+pub async fn get_updates_api(
+    _claims: Claims,
+    Query(params): Query<UpdateQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = params.to_filter(None);
+    let update_vec = get_updates_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(update_vec)
+}
+
+/// API to get the pending updates of a host, oldest first
+pub async fn get_host_updates_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    Query(params): Query<UpdateQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = params.to_filter(Some(format!("host_id = '{id}'")));
+    let update_vec = get_updates_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(update_vec)
+}
+
+/// API to get pending update counts per host and for the fleet
+pub async fn get_update_summary_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    Json(fleet_summary(Utc::now(), &pool).await)
+}
+
+pub async fn get_updates_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<PendingUpdate> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM pending_updates WHERE {f}"),
+        None => "SELECT * FROM pending_updates".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| PendingUpdate::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{create_database, init_database},
+        host::Host,
+        package::{store_inventory, Package},
+    };
+    use axum::http::StatusCode;
+    use chrono::Duration;
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn update(name: &str, available: &str, security: bool) -> Update {
+        Update {
+            format: PackageFormat::Dpkg,
+            name: name.into(),
+            arch: "amd64".into(),
+            installed_version: "".into(),
+            available_version: available.into(),
+            security,
+            advisory: "".into(),
+        }
+    }
+
+    fn package(name: &str, version: &str) -> Package {
+        Package {
+            format: PackageFormat::Dpkg,
+            name: name.into(),
+            version: version.into(),
+            arch: "amd64".into(),
+            source: "".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_updates() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let output = "# format: dpkg\n\
+            Listing...\n\
+            openssl/jammy-updates,jammy-security 3.0.2-0ubuntu1.15 amd64 [upgradable from: 3.0.2-0ubuntu1.14]\n\
+            vim/jammy-updates 2:8.2.3995-1ubuntu2.16 amd64 [upgradable from: 2:8.2.3995-1ubuntu2.15]\n\
+            # format: rpm\n\
+            RHSA-2024:1234 Important/Sec. openssl-libs-1:3.0.7-27.el9.x86_64\n\
+            RHSA-2024:1300 Moderate/Sec.  openssl-libs-1:3.0.7-27.el9.x86_64\n\
+            RHBA-2024:2000 bugfix         tzdata-0:2024a-1.el9.noarch\n\
+            # format: apk\n\
+            Installed:                                Available:\n\
+            musl-1.2.4-r1                           < 1.2.4-r2\n\
+            # format: pacman\n\
+            openssl 3.3.0-1 -> 3.3.1-1\n\
+            # format: homebrew\n\
+            openssl@3 (3.3.0) < 3.3.1\n";
+        let updates = parse_updates(output).unwrap();
+        assert_eq!(updates.len(), 7);
+        assert_eq!(
+            updates[0],
+            Update {
+                format: PackageFormat::Dpkg,
+                name: "openssl".into(),
+                arch: "amd64".into(),
+                installed_version: "3.0.2-0ubuntu1.14".into(),
+                available_version: "3.0.2-0ubuntu1.15".into(),
+                security: true,
+                advisory: "jammy-updates,jammy-security".into(),
+            }
+        );
+        assert!(!updates[1].security);
+        // several advisories for one package are merged
+        assert_eq!(
+            (
+                updates[2].name.as_str(),
+                updates[2].available_version.as_str(),
+                updates[2].arch.as_str(),
+                updates[2].security,
+                updates[2].advisory.as_str()
+            ),
+            (
+                "openssl-libs",
+                "1:3.0.7-27.el9",
+                "x86_64",
+                true,
+                "RHSA-2024:1234, RHSA-2024:1300"
+            )
+        );
+        assert_eq!(updates[3].available_version, "2024a-1.el9");
+        assert!(!updates[3].security);
+        assert_eq!(
+            (
+                updates[4].name.as_str(),
+                updates[4].installed_version.as_str(),
+                updates[4].available_version.as_str()
+            ),
+            ("musl", "1.2.4-r1", "1.2.4-r2")
+        );
+        assert_eq!(updates[5].installed_version, "3.3.0-1");
+        assert_eq!(updates[6].installed_version, "3.3.0");
+        assert_eq!(
+            parse_updates("openssl 3.3.0-1 -> 3.3.1-1").unwrap_err(),
+            ParseError::Line(1, "missing '# format:' line".into())
+        );
+        assert!(parse_updates("# format: pacman\nopenssl 3.3.1-1").is_err());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        let other = Host {
+            id: Uuid::new_v4(),
+            alias: "web-2".into(),
+            ..Default::default()
+        };
+        let other_id = other.id;
+        let _h = other.insert_into_db(pool.acquire().await.unwrap()).await;
+
+        // installed versions are taken from the inventory
+        let now = Utc::now();
+        let _c = store_inventory(
+            host_id,
+            vec![package("openssl", "3.0.2-1"), package("curl", "7.81.0-1")],
+            now - Duration::days(40),
+            &pool,
+        )
+        .await
+        .unwrap();
+        store_updates(
+            host_id,
+            vec![
+                update("openssl", "3.0.2-2", true),
+                update("curl", "7.81.0-2", false),
+                update("", "1.0", false),
+            ],
+            now - Duration::days(10),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let pending = get_updates_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(pending.len(), 2);
+        let openssl = pending.iter().find(|u| u.name == "openssl").unwrap();
+        assert_eq!(openssl.installed_version, "3.0.2-1");
+
+        // a newer version keeps first_seen, installed updates are no longer pending
+        let later = now - Duration::days(5);
+        store_updates(
+            host_id,
+            vec![update("openssl", "3.0.2-3", true)],
+            later,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let pending = get_updates_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].available_version, "3.0.2-3");
+        assert_eq!(
+            pending[0].first_seen.timestamp_millis(),
+            (now - Duration::days(10)).timestamp_millis()
+        );
+        // other formats are not touched
+        let brew = Update {
+            format: PackageFormat::Homebrew,
+            ..update("openssl@3", "3.3.1", false)
+        };
+        store_updates(host_id, vec![brew], later, &pool)
+            .await
+            .unwrap();
+        let pending = get_updates_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(pending.len(), 2);
+
+        // curl got patched 3 days ago
+        let _c = store_inventory(
+            host_id,
+            vec![package("openssl", "3.0.2-1"), package("curl", "7.81.0-2")],
+            now - Duration::days(3),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let summary = fleet_summary(now, &pool).await;
+        assert_eq!(summary.hosts, 2);
+        assert_eq!(summary.hosts_with_security_updates, 1);
+        assert_eq!(summary.pending, 2);
+        assert_eq!(summary.security, 1);
+        assert_eq!(summary.oldest_security_update.unwrap().name, "openssl");
+        assert_eq!(summary.per_host[0].host_id, host_id);
+        assert_eq!(summary.per_host[0].pending, 2);
+        assert_eq!(summary.per_host[0].security, 1);
+        assert_eq!(
+            summary.per_host[0]
+                .oldest_security_update
+                .unwrap()
+                .timestamp_millis(),
+            (now - Duration::days(10)).timestamp_millis()
+        );
+        assert_eq!(summary.per_host[0].days_since_last_patch, Some(3));
+        assert_eq!(summary.per_host[1].host_id, other_id);
+        assert_eq!(summary.per_host[1].pending, 0);
+        assert_eq!(summary.per_host[1].last_patched, None);
+
+        // filters
+        let params = UpdateQueryParams {
+            security: Some(true),
+            ..Default::default()
+        };
+        let filter = params.to_filter(None);
+        let found = get_updates_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert_eq!(found.len(), 1);
+        let params = UpdateQueryParams {
+            name: Some("o'penssl".into()),
+            ..Default::default()
+        };
+        let filter = params.to_filter(None);
+        assert!(
+            get_updates_from_db(Some(&filter), pool.acquire().await.unwrap())
+                .await
+                .is_empty()
+        );
+
+        // deleting the host deletes its pending updates
+        let filter = format!("id='{host_id}'");
+        let _d =
+            crate::host::delete_hosts_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        assert!(get_updates_from_db(None, pool.acquire().await.unwrap())
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let _h = host.insert_into_db(pool.acquire().await.unwrap()).await;
+        store_updates(
+            host_id,
+            vec![update("openssl", "3.0.2", true)],
+            Utc::now(),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let api_updates = get_updates_api(
+            claims.clone(),
+            axum::extract::Query(UpdateQueryParams {
+                security: Some(true),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_updates.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_updates.into_body())
+            .await
+            .unwrap();
+        let found: Vec<PendingUpdate> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(found.len(), 1);
+
+        let api_host = get_host_updates_api(
+            claims.clone(),
+            axum::extract::Path(host_id),
+            axum::extract::Query(UpdateQueryParams::default()),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_host.status(), StatusCode::OK);
+
+        let api_summary =
+            get_update_summary_api(claims.clone(), axum::extract::State(pool.clone()))
+                .await
+                .into_response();
+        assert_eq!(api_summary.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_summary.into_body())
+            .await
+            .unwrap();
+        let summary: FleetUpdateSummary = serde_json::from_slice(&body).unwrap();
+        assert_eq!(summary.security, 1);
+        assert_eq!(summary.per_host[0].alias, "web-1");
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{host::Host, jwt::Claims};
+
+/// Variable for all hosts with a given attribute (group), usable as `{{ vars.<name> }}` in scripts
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Variable {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub attribute: String,
+    pub name: String,
+    pub value: String,
+}
+
+impl Variable {
+    /// Insert into or Replace `Variable` in variables table in SQLite database
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | attribute | TEXT | host attribute (group) the variable belongs to
+    /// | name | TEXT |
+    /// | value | TEXT |
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"REPLACE INTO variables( id, attribute, name, value ) VALUES ( ?, ?, ?, ? )"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.attribute)
+            .bind(self.name)
+            .bind(self.value)
+            .execute(&mut *connection)
+            .await
+    }
+}
+
+impl From<SqliteRow> for Variable {
+    fn from(s: SqliteRow) -> Self {
+        Variable {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            attribute: s.get::<String, _>("attribute"),
+            name: s.get::<String, _>("name"),
+            value: s.get::<String, _>("value"),
+        }
+    }
+}
+
+/// API to get all variables
+pub async fn get_variables_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let variable_vec = get_variables_from_db(None, pool.acquire().await.unwrap()).await;
+    Json(variable_vec)
+}
+
+/// API to create a new variable, replaces an existing one with the same attribute and name
+pub async fn post_variables_api(
+    _claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<Variable>,
+) -> Response {
+    debug!("{:?}", payload);
+    let id = payload.id.to_string();
+    let Ok(res) = payload.insert_into_db(pool.acquire().await.unwrap()).await else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if res.rows_affected() == 1 {
+        (StatusCode::CREATED, Json(id)).into_response()
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+}
+
+/// API to delete one variable
+pub async fn delete_one_variable_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'",);
+    delete_variables_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// All variables of the attributes a host has
+///
+/// attributes are applied in alphabetical order, so for duplicate names the last attribute wins
+pub async fn get_host_variables(
+    host: &Host,
+    connection: PoolConnection<Sqlite>,
+) -> HashMap<String, String> {
+    if host.attributes.is_empty() {
+        return HashMap::new();
+    }
+    let attributes: Vec<String> = host
+        .attributes
+        .iter()
+        .map(|a| format!("'{}'", a.replace('\'', "''")))
+        .collect();
+    let filter = format!(
+        "attribute IN ({}) ORDER BY attribute ASC",
+        attributes.join(",")
+    );
+    get_variables_from_db(Some(&filter), connection)
+        .await
+        .into_iter()
+        .map(|v| (v.name, v.value))
+        .collect()
+}
+
+pub async fn get_variables_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Variable> {
+    let stmt = if let Some(f) = filter {
+        format!("SELECT * FROM variables WHERE {f}")
+    } else {
+        "SELECT * FROM variables".into()
+    };
+    let variables = match query(&stmt).fetch_all(&mut *connection).await {
+        Ok(d) => d,
+        Err(_) => return Vec::new(),
+    };
+
+    variables.into_iter().map(|s| s.into()).collect()
+}
+
+pub async fn delete_variables_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let stmt = if let Some(f) = filter {
+        format!("DELETE FROM variables WHERE {f}")
+    } else {
+        "DELETE FROM variables".into()
+    };
+    let res = query(&stmt).execute(&mut *connection).await;
+    if res.is_err() {
+        StatusCode::FORBIDDEN
+    } else {
+        StatusCode::OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_database, init_database};
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    #[tokio::test]
+    async fn test_variables() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+
+        for (attribute, name, value) in [
+            ("linux", "team", "ops"),
+            ("prod", "team", "sre"),
+            ("prod", "threshold", "90"),
+            ("mac", "team", "desk"),
+        ] {
+            let var = Variable {
+                id: Uuid::new_v4(),
+                attribute: attribute.into(),
+                name: name.into(),
+                value: value.into(),
+            };
+            let res = var.insert_into_db(pool.acquire().await.unwrap()).await;
+            assert_eq!(res.unwrap().rows_affected(), 1);
+        }
+
+        let host = Host {
+            attributes: vec!["prod".into(), "linux".into()],
+            ..Default::default()
+        };
+        let vars = get_host_variables(&host, pool.acquire().await.unwrap()).await;
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars["team"], "sre");
+        assert_eq!(vars["threshold"], "90");
+
+        let no_vars = get_host_variables(&Host::default(), pool.acquire().await.unwrap()).await;
+        assert!(no_vars.is_empty());
+
+        let err_vars =
+            get_variables_from_db(Some("this-doesnt-work"), pool.acquire().await.unwrap()).await;
+        assert_eq!(err_vars.len(), 0);
+
+        let del_fail =
+            delete_variables_from_db(Some("this-doesnt-work"), pool.acquire().await.unwrap()).await;
+        assert_eq!(del_fail, StatusCode::FORBIDDEN);
+        let del = delete_variables_from_db(None, pool.acquire().await.unwrap()).await;
+        assert_eq!(del, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let new_var = Variable {
+            attribute: "linux".into(),
+            name: "team".into(),
+            value: "ops".into(),
+            ..Default::default()
+        };
+        let api_post = post_variables_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            Json(new_var.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let api_get_all = get_variables_api(claims.clone(), axum::extract::State(pool.clone()))
+            .await
+            .into_response();
+        assert_eq!(api_get_all.status(), StatusCode::OK);
+
+        let api_del_one = delete_one_variable_api(
+            claims.clone(),
+            axum::extract::Path(new_var.id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_del_one.status(), StatusCode::OK);
+    }
+}
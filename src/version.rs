@@ -0,0 +1,327 @@
+use std::cmp::Ordering;
+
+use crate::package::PackageFormat;
+
+/// compare two versions of a package the way its package manager does
+///
+/// | Format | Version | Algorithm
+/// :--- | :--- | :---
+/// | dpkg | `[epoch:]upstream[-revision]` | dpkg `verrevcmp`, `~` sorts before everything
+/// | rpm | `[epoch:]version[-release]` | `rpmvercmp`, `~` sorts before and `^` after everything
+/// | apk | `number[.number]*[letter][_suffix[number]]*[-rrelease]` | apk-tools, `_alpha` < `_beta` < `_pre` < `_rc` < none < `_p`
+/// | pacman | `[epoch:]version[-pkgrel]` | `vercmp`, same as rpm
+/// | homebrew | `version` | `rpmvercmp`
+/// | npm, pypi, maven, golang, cargo, gem, nuget | `[v]version[-prerelease][+build]` | `rpmvercmp` of the version, a prerelease sorts before its release
+pub fn compare(format: PackageFormat, a: &str, b: &str) -> Ordering {
+    match format {
+        PackageFormat::Dpkg => {
+            let (ea, ua, ra) = split_evr(a);
+            let (eb, ub, rb) = split_evr(b);
+            ea.cmp(&eb)
+                .then_with(|| verrevcmp(ua, ub))
+                .then_with(|| verrevcmp(ra.unwrap_or_default(), rb.unwrap_or_default()))
+        }
+        PackageFormat::Rpm | PackageFormat::Pacman => {
+            let (ea, va, ra) = split_evr(a);
+            let (eb, vb, rb) = split_evr(b);
+            ea.cmp(&eb)
+                .then_with(|| rpmvercmp(va, vb))
+                .then_with(|| match (ra, rb) {
+                    // a version without release matches all releases
+                    (Some(ra), Some(rb)) => rpmvercmp(ra, rb),
+                    _ => Ordering::Equal,
+                })
+        }
+        PackageFormat::Apk => match (ApkVersion::parse(a), ApkVersion::parse(b)) {
+            (Some(va), Some(vb)) => va.cmp(&vb),
+            _ => rpmvercmp(a, b),
+        },
+        PackageFormat::Homebrew => rpmvercmp(a, b),
+        _ => {
+            let (va, pa) = split_prerelease(a);
+            let (vb, pb) = split_prerelease(b);
+            rpmvercmp(va, vb).then_with(|| match (pa, pb) {
+                (Some(pa), Some(pb)) => rpmvercmp(pa, pb),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+        }
+    }
+}
+
+/// split `[v]version[-prerelease][+build]` of language packages, the build is ignored
+fn split_prerelease(v: &str) -> (&str, Option<&str>) {
+    let v = v.strip_prefix('v').unwrap_or(v);
+    let v = v.split_once('+').map_or(v, |(v, _build)| v);
+    match v.split_once('-') {
+        Some((version, prerelease)) => (version, Some(prerelease)),
+        None => (v, None),
+    }
+}
+
+/// split `[epoch:]version[-release]`, a missing or invalid epoch is 0
+fn split_evr(v: &str) -> (u64, &str, Option<&str>) {
+    let (epoch, rest) = match v.split_once(':') {
+        Some((e, rest)) if e.chars().all(|c| c.is_ascii_digit()) => (e.parse().unwrap_or(0), rest),
+        _ => (0, v),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// weight of a non-digit character in dpkg versions
+fn dpkg_order(c: Option<&u8>) -> i32 {
+    match c {
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(*c),
+        Some(c) => i32::from(*c) + 256,
+        None => 0,
+    }
+}
+
+fn verrevcmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let digit = |s: &[u8], i: usize| s.get(i).is_some_and(u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !digit(a, i)) || (j < b.len() && !digit(b, j)) {
+            let (ac, bc) = (dpkg_order(a.get(i)), dpkg_order(b.get(j)));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while digit(a, i) && digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if digit(a, i) {
+            return Ordering::Greater;
+        }
+        if digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        while a.first().is_some_and(separator) {
+            a = &a[1..];
+        }
+        while b.first().is_some_and(separator) {
+            b = &b[1..];
+        }
+        // tilde sorts before everything, even the end of the version
+        if a.first() == Some(&b'~') || b.first() == Some(&b'~') {
+            if a.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if b.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            (a, b) = (&a[1..], &b[1..]);
+            continue;
+        }
+        // caret sorts after the end of the version but before everything else
+        if a.first() == Some(&b'^') || b.first() == Some(&b'^') {
+            if a.is_empty() {
+                return Ordering::Less;
+            }
+            if b.is_empty() {
+                return Ordering::Greater;
+            }
+            if a.first() != Some(&b'^') {
+                return Ordering::Greater;
+            }
+            if b.first() != Some(&b'^') {
+                return Ordering::Less;
+            }
+            (a, b) = (&a[1..], &b[1..]);
+            continue;
+        }
+        if a.is_empty() || b.is_empty() {
+            break;
+        }
+        let numeric = a[0].is_ascii_digit();
+        let take = |s: &[u8]| {
+            s.iter()
+                .take_while(|c| match numeric {
+                    true => c.is_ascii_digit(),
+                    false => c.is_ascii_alphabetic(),
+                })
+                .count()
+        };
+        let (la, lb) = (take(a), take(b));
+        // numeric segments are newer than alpha segments
+        if lb == 0 {
+            return match numeric {
+                true => Ordering::Greater,
+                false => Ordering::Less,
+            };
+        }
+        let (mut sa, mut sb) = (&a[..la], &b[..lb]);
+        let order = match numeric {
+            true => {
+                while sa.first() == Some(&b'0') {
+                    sa = &sa[1..];
+                }
+                while sb.first() == Some(&b'0') {
+                    sb = &sb[1..];
+                }
+                sa.len().cmp(&sb.len()).then(sa.cmp(sb))
+            }
+            false => sa.cmp(sb),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+        (a, b) = (&a[la..], &b[lb..]);
+    }
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (false, _) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+/// apk version, compared by numbers, letter, suffixes and release in this order
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct ApkVersion {
+    numbers: Vec<u64>,
+    letter: Option<char>,
+    /// (rank, number), a missing suffix ranks 0
+    suffixes: Vec<(i8, u64)>,
+    release: u64,
+}
+
+impl ApkVersion {
+    fn parse(v: &str) -> Option<ApkVersion> {
+        let (v, release) = match v.rsplit_once("-r") {
+            Some((v, r)) => (v, r.parse().ok()?),
+            None => (v, 0),
+        };
+        let mut parts = v.split('_');
+        let mut main = parts.next()?.to_string();
+        let letter = match main.chars().last() {
+            Some(c) if c.is_ascii_alphabetic() => {
+                main.pop();
+                Some(c)
+            }
+            _ => None,
+        };
+        let numbers = main
+            .split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        let mut suffixes = parts
+            .map(|s| {
+                let name = s.trim_end_matches(|c: char| c.is_ascii_digit());
+                let rank = match name {
+                    "alpha" => -4,
+                    "beta" => -3,
+                    "pre" => -2,
+                    "rc" => -1,
+                    "cvs" => 1,
+                    "svn" => 2,
+                    "git" => 3,
+                    "hg" => 4,
+                    "p" => 5,
+                    _ => return None,
+                };
+                Some((rank, s[name.len()..].parse().unwrap_or(0)))
+            })
+            .collect::<Option<Vec<(i8, u64)>>>()?;
+        // a release without suffix sorts after pre-releases and before patches
+        suffixes.push((0, 0));
+        Some(ApkVersion {
+            numbers,
+            letter,
+            suffixes,
+            release,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let dpkg = |a, b| compare(PackageFormat::Dpkg, a, b);
+        assert_eq!(dpkg("1.0", "1.0"), Ordering::Equal);
+        assert_eq!(
+            dpkg("3.0.2-0ubuntu1.14", "3.0.2-0ubuntu1.15"),
+            Ordering::Less
+        );
+        assert_eq!(dpkg("1:1.0", "2.0"), Ordering::Greater);
+        assert_eq!(dpkg("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(dpkg("1.0~rc1-1", "1.0~~-1"), Ordering::Greater);
+        assert_eq!(dpkg("1.0a", "1.0-"), Ordering::Greater);
+        assert_eq!(dpkg("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(dpkg("1.0+dfsg-1", "1.0-1"), Ordering::Greater);
+        assert_eq!(dpkg("3.0.11-1~deb12u2", "3.0.11-1"), Ordering::Less);
+        assert_eq!(dpkg("2.36-9+deb12u4", "2.36-9+deb12u10"), Ordering::Less);
+
+        let rpm = |a, b| compare(PackageFormat::Rpm, a, b);
+        assert_eq!(rpm("1:3.0.7-27.el9", "1:3.0.7-27.el9"), Ordering::Equal);
+        assert_eq!(rpm("1:3.0.7-25.el9", "1:3.0.7-27.el9"), Ordering::Less);
+        assert_eq!(rpm("3.0.7-27.el9", "1:3.0.1-1.el9"), Ordering::Less);
+        assert_eq!(rpm("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(rpm("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(rpm("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(rpm("1.0^git1", "1.0"), Ordering::Greater);
+        assert_eq!(rpm("1.0^git1", "1.0.1"), Ordering::Less);
+        assert_eq!(rpm("2.a", "2.1"), Ordering::Less);
+        assert_eq!(rpm("1.010", "1.9"), Ordering::Greater);
+        assert_eq!(rpm("5.14.0", "5.14.0-427.el9"), Ordering::Equal);
+
+        let apk = |a, b| compare(PackageFormat::Apk, a, b);
+        assert_eq!(apk("1.2.4-r1", "1.2.4-r2"), Ordering::Less);
+        assert_eq!(apk("1.2.4_rc1-r0", "1.2.4-r0"), Ordering::Less);
+        assert_eq!(apk("1.2.4_p1-r0", "1.2.4-r0"), Ordering::Greater);
+        assert_eq!(apk("1.2.4_alpha2", "1.2.4_beta1"), Ordering::Less);
+        assert_eq!(apk("1.2.4a-r0", "1.2.4-r0"), Ordering::Greater);
+        assert_eq!(apk("1.2.10-r0", "1.2.9-r5"), Ordering::Greater);
+        assert_eq!(apk("3.1.4-r5", "3.1.4-r5"), Ordering::Equal);
+
+        let pacman = |a, b| compare(PackageFormat::Pacman, a, b);
+        assert_eq!(pacman("3.3.0-1", "3.3.1-1"), Ordering::Less);
+        assert_eq!(pacman("1:1.0-1", "2.0-1"), Ordering::Greater);
+
+        let npm = |a, b| compare(PackageFormat::Npm, a, b);
+        assert_eq!(npm("4.17.20", "4.17.21"), Ordering::Less);
+        assert_eq!(npm("1.0.0-rc.1", "1.0.0"), Ordering::Less);
+        assert_eq!(npm("1.0.0-alpha", "1.0.0-beta"), Ordering::Less);
+        assert_eq!(npm("1.10.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(npm("1.0.0+build.5", "1.0.0"), Ordering::Equal);
+        let golang = |a, b| compare(PackageFormat::Golang, a, b);
+        assert_eq!(golang("v0.17.0", "0.17.0"), Ordering::Equal);
+        assert_eq!(golang("v0.0.0-20230101-abcdef", "0.1.0"), Ordering::Less);
+    }
+}
@@ -0,0 +1,770 @@
+use std::fmt::Display;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    pool::PoolConnection,
+    query,
+    sqlite::{SqliteQueryResult, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    advisory::Vulnerability,
+    db::{utc_from_str, utc_to_str},
+    host::{get_hosts_from_db, Host},
+    jwt::Claims,
+    notification::{notify, NotificationDelivery, NotificationEvent},
+};
+
+/// Time before the expiry of a waiver its expiring notification is sent
+pub const WAIVER_EXPIRY_WARNING: Duration = Duration::days(7);
+
+/// Accepted risk of vulnerable packages matching all of its scopes until `expires`
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Waiver {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    /// CVE or advisory id
+    #[serde(default)]
+    pub cve: Option<String>,
+    #[serde(default)]
+    pub package: Option<String>,
+    #[serde(default)]
+    pub host_id: Option<Uuid>,
+    /// host has all of these attributes
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    pub justification: String,
+    /// person accepting the risk, must not be the logged in user
+    pub approver: String,
+    pub expires: DateTime<Utc>,
+    /// set to the logged in user
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+impl Waiver {
+    /// Insert into or Replace `Waiver` in waivers table in SQLite database,
+    /// the expiry notifications are sent again for the new expiry
+    ///
+    /// | Name | Type | Comment
+    /// :--- | :--- | :---
+    /// | id | TEXT | uuid
+    /// | cve | TEXT | CVE or advisory id, NULL for all
+    /// | package | TEXT | NULL for all packages
+    /// | host_id | TEXT | uuid, NULL for all hosts
+    /// | attributes | TEXT | json list
+    /// | justification | TEXT |
+    /// | approver | TEXT |
+    /// | expires | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | created_by | TEXT | email of the user
+    /// | created | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+    /// | notified | TEXT | last expiry notification, empty, expiring or expired
+    pub async fn insert_into_db(
+        self,
+        mut connection: PoolConnection<Sqlite>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let q = r#"REPLACE INTO waivers(id, cve, package, host_id, attributes, justification, approver, expires, created_by, created, notified) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, '')"#;
+        query(q)
+            .bind(self.id.to_string())
+            .bind(self.cve)
+            .bind(self.package)
+            .bind(self.host_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&self.attributes).unwrap())
+            .bind(self.justification)
+            .bind(self.approver)
+            .bind(utc_to_str(self.expires))
+            .bind(self.created_by)
+            .bind(utc_to_str(self.created))
+            .execute(&mut *connection)
+            .await
+    }
+
+    fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if self.cve.is_none()
+            && self.package.is_none()
+            && self.host_id.is_none()
+            && self.attributes.is_empty()
+        {
+            return Err("at least one of cve, package, host_id or attributes is required".into());
+        }
+        if self.justification.trim().is_empty() {
+            return Err("justification must not be empty".into());
+        }
+        if self.approver.trim().is_empty() {
+            return Err("approver must not be empty".into());
+        }
+        if self.approver == self.created_by {
+            return Err("approver must be a different user than the creator".into());
+        }
+        if self.expires <= now {
+            return Err("expires must be in the future".into());
+        }
+        Ok(())
+    }
+
+    /// all scopes match the vulnerable package on `host`
+    pub fn matches(&self, host: &Host, vulnerability: &Vulnerability) -> bool {
+        self.cve
+            .as_ref()
+            .is_none_or(|cve| *cve == vulnerability.cve || *cve == vulnerability.advisory_id)
+            && self
+                .package
+                .as_ref()
+                .is_none_or(|p| *p == vulnerability.package)
+            && self.host_id.is_none_or(|id| id == host.id)
+            && self.attributes.iter().all(|a| host.attributes.contains(a))
+    }
+
+    /// human readable scopes, e.g. `CVE-2024-0727 in openssl on hosts with prod`
+    pub fn scope(&self) -> String {
+        let mut scope = vec![self.cve.clone().unwrap_or("all vulnerabilities".into())];
+        if let Some(package) = &self.package {
+            scope.push(format!("in {package}"));
+        }
+        if let Some(host_id) = self.host_id {
+            scope.push(format!("on host {host_id}"));
+        }
+        if !self.attributes.is_empty() {
+            scope.push(format!("on hosts with {}", self.attributes.join(", ")));
+        }
+        scope.join(" ")
+    }
+}
+
+impl From<SqliteRow> for Waiver {
+    fn from(s: SqliteRow) -> Self {
+        Waiver {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            cve: s.get::<Option<String>, _>("cve"),
+            package: s.get::<Option<String>, _>("package"),
+            host_id: s
+                .get::<Option<String>, _>("host_id")
+                .and_then(|id| id.parse().ok()),
+            attributes: serde_json::from_str(&s.get::<String, _>("attributes")).unwrap_or_default(),
+            justification: s.get::<String, _>("justification"),
+            approver: s.get::<String, _>("approver"),
+            expires: utc_from_str(&s.get::<String, _>("expires")),
+            created_by: s.get::<String, _>("created_by"),
+            created: utc_from_str(&s.get::<String, _>("created")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum WaiverAction {
+    Created,
+    Updated,
+    Deleted,
+    Expiring,
+    Expired,
+}
+
+impl Display for WaiverAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            WaiverAction::Created => "created",
+            WaiverAction::Updated => "updated",
+            WaiverAction::Deleted => "deleted",
+            WaiverAction::Expiring => "expiring",
+            WaiverAction::Expired => "expired",
+        };
+        write!(f, "{action}")
+    }
+}
+
+impl WaiverAction {
+    fn from_db(s: &str) -> WaiverAction {
+        match s {
+            "created" => WaiverAction::Created,
+            "updated" => WaiverAction::Updated,
+            "deleted" => WaiverAction::Deleted,
+            "expiring" => WaiverAction::Expiring,
+            _ => WaiverAction::Expired,
+        }
+    }
+}
+
+/// Change of a waiver, kept after the waiver is deleted
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct WaiverAudit {
+    pub id: Uuid,
+    pub waiver_id: Uuid,
+    pub action: WaiverAction,
+    /// email of the user, `unpatched-server` for expiry
+    pub user: String,
+    /// waiver after the change, before for deletions
+    pub waiver: Waiver,
+    pub ts: DateTime<Utc>,
+}
+
+impl From<SqliteRow> for WaiverAudit {
+    fn from(s: SqliteRow) -> Self {
+        WaiverAudit {
+            id: s.get::<String, _>("id").parse().unwrap(),
+            waiver_id: s.get::<String, _>("waiver_id").parse().unwrap(),
+            action: WaiverAction::from_db(&s.get::<String, _>("action")),
+            user: s.get::<String, _>("user"),
+            waiver: serde_json::from_str(&s.get::<String, _>("waiver")).unwrap(),
+            ts: utc_from_str(&s.get::<String, _>("ts")),
+        }
+    }
+}
+
+/// add `action` of `user` on `waiver` to the audit log
+///
+/// | Name | Type | Comment
+/// :--- | :--- | :---
+/// | id | TEXT | uuid
+/// | waiver_id | TEXT | uuid
+/// | action | TEXT | created, updated, deleted, expiring or expired
+/// | user | TEXT | email of the user
+/// | waiver | TEXT | json of the waiver
+/// | ts | TEXT | as rfc3339 string ("YYYY-MM-DDTHH:MM:SS.sssZ")
+async fn audit(
+    waiver: &Waiver,
+    action: WaiverAction,
+    user: &str,
+    now: DateTime<Utc>,
+    mut connection: PoolConnection<Sqlite>,
+) {
+    let q = r#"INSERT INTO waiver_audit(id, waiver_id, action, user, waiver, ts) VALUES(?, ?, ?, ?, ?, ?)"#;
+    let res = query(q)
+        .bind(Uuid::new_v4().to_string())
+        .bind(waiver.id.to_string())
+        .bind(action.to_string())
+        .bind(user)
+        .bind(serde_json::to_string(waiver).unwrap())
+        .bind(utc_to_str(now))
+        .execute(&mut *connection)
+        .await;
+    if let Err(e) = res {
+        warn!("Audit of waiver {} could not be stored: {e}", waiver.id);
+    }
+}
+
+/// create or replace `waiver` in the name of `user`
+pub async fn save_waiver(
+    mut waiver: Waiver,
+    user: &str,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<Uuid, String> {
+    waiver.created_by = user.to_string();
+    waiver.validate(now)?;
+    let filter = format!("id='{}'", waiver.id);
+    let existing = get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let action = match existing.is_empty() {
+        true => WaiverAction::Created,
+        false => WaiverAction::Updated,
+    };
+    let id = waiver.id;
+    waiver
+        .clone()
+        .insert_into_db(pool.acquire().await.unwrap())
+        .await
+        .map_err(|_| "Host ID not found".to_string())?;
+    audit(&waiver, action, user, now, pool.acquire().await.unwrap()).await;
+    Ok(id)
+}
+
+/// waivers not expired at `now`
+pub async fn active_waivers(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<Waiver> {
+    let filter = format!("expires > '{}'", utc_to_str(now));
+    get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await
+}
+
+/// notify once about waivers expiring within `WAIVER_EXPIRY_WARNING` and once about expired ones
+pub async fn check_expiry(now: DateTime<Utc>, pool: &SqlitePool) -> Vec<NotificationDelivery> {
+    let warning = utc_to_str(now + WAIVER_EXPIRY_WARNING);
+    let filter = format!(
+        "(expires <= '{warning}' AND notified = '') OR (expires <= '{}' AND notified != 'expired')",
+        utc_to_str(now)
+    );
+    let waivers = get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let mut deliveries = vec![];
+    for waiver in waivers {
+        let expired = waiver.expires <= now;
+        let action = match expired {
+            true => WaiverAction::Expired,
+            false => WaiverAction::Expiring,
+        };
+        let res = query("UPDATE waivers SET notified = ? WHERE id = ?")
+            .bind(action.to_string())
+            .bind(waiver.id.to_string())
+            .execute(&mut *pool.acquire().await.unwrap())
+            .await;
+        if let Err(e) = res {
+            warn!("Expiry of waiver {} could not be stored: {e}", waiver.id);
+            continue;
+        }
+        audit(
+            &waiver,
+            action,
+            "unpatched-server",
+            now,
+            pool.acquire().await.unwrap(),
+        )
+        .await;
+        let host = match waiver.host_id {
+            Some(id) => {
+                get_hosts_from_db(Some(&format!("id='{id}'")), pool.acquire().await.unwrap())
+                    .await
+                    .pop()
+                    .unwrap_or_default()
+            }
+            None => Host::default(),
+        };
+        let event = NotificationEvent::waiver(&waiver, expired, host, now);
+        deliveries.extend(notify(event, pool.clone()).await);
+    }
+    debug!("Waiver expiry sent {} notifications", deliveries.len());
+    deliveries
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct WaiverQueryParams {
+    /// only waivers not expired yet
+    active: Option<bool>,
+    host_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AuditQueryParams {
+    waiver_id: Option<Uuid>,
+    since: Option<DateTime<Utc>>,
+}
+
+/// API to get waivers, next expiry first
+pub async fn get_waivers_api(
+    _claims: Claims,
+    Query(params): Query<WaiverQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec![];
+    if params.active == Some(true) {
+        conditions.push(format!("expires > '{}'", utc_to_str(Utc::now())));
+    }
+    if let Some(host_id) = params.host_id {
+        conditions.push(format!("host_id = '{host_id}'"));
+    }
+    conditions.push("1=1 ORDER BY expires".into());
+    let filter = conditions.join(" AND ");
+    let waiver_vec = get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(waiver_vec)
+}
+
+/// API to get one waiver
+pub async fn get_one_waiver_api(
+    _claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let waiver_vec = get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(waiver_vec)
+}
+
+/// API to create or replace a waiver
+pub async fn post_waivers_api(
+    claims: Claims,
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<Waiver>,
+) -> Response {
+    match save_waiver(payload, claims.sub.as_ref(), Utc::now(), &pool).await {
+        Ok(id) => (StatusCode::CREATED, Json(id.to_string())).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
+/// API to delete a waiver, its audit log is kept
+pub async fn delete_one_waiver_api(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let filter = format!("id='{id}'");
+    let existing = get_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    let status = delete_waivers_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    if let (StatusCode::OK, Some(waiver)) = (status, existing.first()) {
+        let conn = pool.acquire().await.unwrap();
+        audit(
+            waiver,
+            WaiverAction::Deleted,
+            claims.sub.as_ref(),
+            Utc::now(),
+            conn,
+        )
+        .await;
+    }
+    status
+}
+
+/// API to get the audit log of waivers, newest first
+pub async fn get_waiver_audit_api(
+    _claims: Claims,
+    Query(params): Query<AuditQueryParams>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let mut conditions = vec![];
+    if let Some(waiver_id) = params.waiver_id {
+        conditions.push(format!("waiver_id = '{waiver_id}'"));
+    }
+    if let Some(since) = params.since {
+        conditions.push(format!("ts >= '{}'", utc_to_str(since)));
+    }
+    conditions.push("1=1 ORDER BY ts DESC".into());
+    let filter = conditions.join(" AND ");
+    let audit_vec = get_waiver_audit_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+    Json(audit_vec)
+}
+
+pub async fn get_waivers_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<Waiver> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM waivers WHERE {f}"),
+        None => "SELECT * FROM waivers".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| Waiver::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+pub async fn delete_waivers_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> StatusCode {
+    let q = match filter {
+        Some(f) => format!("DELETE FROM waivers WHERE {f}"),
+        None => "DELETE FROM waivers".into(),
+    };
+    match query(&q).execute(&mut *connection).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
+
+pub async fn get_waiver_audit_from_db(
+    filter: Option<&str>,
+    mut connection: PoolConnection<Sqlite>,
+) -> Vec<WaiverAudit> {
+    let q = match filter {
+        Some(f) => format!("SELECT * FROM waiver_audit WHERE {f}"),
+        None => "SELECT * FROM waiver_audit".into(),
+    };
+    query(&q)
+        .map(|row: SqliteRow| WaiverAudit::from(row))
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        advisory::{host_vulnerabilities, store_advisories, Advisory, AdvisoryFormat, Affected},
+        alert::Severity,
+        db::{create_database, init_database},
+        notification::{tests::http_stand_in, ChannelConfig, EventKind, NotificationChannel},
+        package::{store_inventory, Package, PackageFormat},
+    };
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
+    };
+
+    fn waiver(cve: &str, expires: DateTime<Utc>) -> Waiver {
+        Waiver {
+            id: Uuid::new_v4(),
+            cve: Some(cve.into()),
+            package: None,
+            host_id: None,
+            attributes: vec![],
+            justification: "not reachable, service is internal".into(),
+            approver: "ciso@example.com".into(),
+            expires,
+            created_by: "".into(),
+            created: Utc::now(),
+        }
+    }
+
+    async fn advisory(id: &str, name: &str, pool: &SqlitePool) {
+        let advisory = Advisory {
+            id: id.into(),
+            aliases: vec![],
+            summary: "".into(),
+            severity: crate::advisory::Severity::High,
+            cvss: None,
+            published: None,
+            modified: None,
+            affected: vec![Affected {
+                source: AdvisoryFormat::Osv,
+                format: PackageFormat::Dpkg,
+                distro: "".into(),
+                release: "".into(),
+                name: name.into(),
+                introduced: "".into(),
+                fixed: Some("2.0".into()),
+                last_affected: None,
+            }],
+        };
+        let _s = store_advisories(vec![advisory], Utc::now(), pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_waivers() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        init_database(&pool, None).await.unwrap();
+        let now = Utc::now();
+        let user = "admin@example.com";
+
+        // validation
+        let no_scope = Waiver {
+            cve: None,
+            ..waiver("CVE-1", now + Duration::days(30))
+        };
+        assert!(save_waiver(no_scope, user, now, &pool).await.is_err());
+        let self_approved = Waiver {
+            approver: user.into(),
+            ..waiver("CVE-1", now + Duration::days(30))
+        };
+        assert_eq!(
+            save_waiver(self_approved, user, now, &pool).await,
+            Err("approver must be a different user than the creator".into())
+        );
+        let expired = waiver("CVE-1", now - Duration::days(1));
+        assert!(save_waiver(expired, user, now, &pool).await.is_err());
+        let unknown_host = Waiver {
+            host_id: Some(Uuid::new_v4()),
+            ..waiver("CVE-1", now + Duration::days(30))
+        };
+        assert!(save_waiver(unknown_host, user, now, &pool).await.is_err());
+
+        // waived vulnerabilities stay listed with the waiver
+        let host = Host {
+            id: Uuid::new_v4(),
+            alias: "web-1".into(),
+            attributes: vec!["prod".into()],
+            ..Default::default()
+        };
+        let _h = host
+            .clone()
+            .insert_into_db(pool.acquire().await.unwrap())
+            .await;
+        advisory("CVE-2024-1", "openssl", &pool).await;
+        advisory("CVE-2024-2", "curl", &pool).await;
+        let package = |name: &str| Package {
+            format: PackageFormat::Dpkg,
+            name: name.into(),
+            version: "1.0".into(),
+            arch: "amd64".into(),
+            source: "".into(),
+        };
+        let _c = store_inventory(
+            host.id,
+            vec![package("openssl"), package("curl")],
+            now,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let by_group = Waiver {
+            attributes: vec!["prod".into()],
+            package: Some("openssl".into()),
+            ..waiver("CVE-2024-1", now + Duration::days(30))
+        };
+        let id = save_waiver(by_group.clone(), user, now, &pool)
+            .await
+            .unwrap();
+        let vulnerabilities = host_vulnerabilities(&host, &pool).await;
+        assert_eq!(vulnerabilities.len(), 2);
+        let openssl = vulnerabilities
+            .iter()
+            .find(|v| v.package == "openssl")
+            .unwrap();
+        assert_eq!(openssl.waiver_id, Some(id));
+        let curl = vulnerabilities
+            .iter()
+            .find(|v| v.package == "curl")
+            .unwrap();
+        assert_eq!(curl.waiver_id, None);
+        let staging = Host {
+            attributes: vec!["staging".into()],
+            ..host.clone()
+        };
+        assert!(!by_group.matches(&staging, openssl));
+
+        // expiring within the warning period is notified once, expired once more
+        let (base, captured) = http_stand_in().await;
+        let channel = NotificationChannel {
+            id: Uuid::new_v4(),
+            name: "security".into(),
+            config: ChannelConfig::Slack {
+                url: format!("{base}/security"),
+            },
+            title_template: "{{ event.title }}".into(),
+            body_template: "{{ event.summary }}".into(),
+            min_severity: Severity::Info,
+            events: vec![EventKind::WaiverExpiring, EventKind::WaiverExpired],
+            active: true,
+            created: Utc::now(),
+        };
+        let _c = channel.insert_into_db(pool.acquire().await.unwrap()).await;
+        let soon = Waiver {
+            host_id: Some(host.id),
+            ..waiver("CVE-2024-2", now + Duration::days(3))
+        };
+        let soon_id = save_waiver(soon.clone(), user, now, &pool).await.unwrap();
+        let deliveries = check_expiry(now, &pool).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, EventKind::WaiverExpiring);
+        let body = String::from_utf8(captured.lock().unwrap()[0].3.to_vec()).unwrap();
+        assert!(body.contains("waiver for CVE-2024-2 on host"), "{body}");
+        assert!(body.contains("approved by ciso@example.com"), "{body}");
+        assert!(check_expiry(now, &pool).await.is_empty());
+        let later = now + Duration::days(4);
+        let deliveries = check_expiry(later, &pool).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, EventKind::WaiverExpired);
+        assert!(check_expiry(later, &pool).await.is_empty());
+        assert_eq!(active_waivers(later, &pool).await.len(), 1);
+
+        // extending the waiver notifies again for the new expiry
+        let extended = Waiver {
+            expires: later + Duration::days(2),
+            ..soon
+        };
+        let _id = save_waiver(extended, user, later, &pool).await.unwrap();
+        let deliveries = check_expiry(later, &pool).await;
+        assert_eq!(deliveries[0].event, EventKind::WaiverExpiring);
+
+        // every change is audited
+        let filter = format!("waiver_id = '{soon_id}' ORDER BY ts");
+        let log = get_waiver_audit_from_db(Some(&filter), pool.acquire().await.unwrap()).await;
+        let actions: Vec<WaiverAction> = log.iter().map(|a| a.action.clone()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                WaiverAction::Created,
+                WaiverAction::Expiring,
+                WaiverAction::Expired,
+                WaiverAction::Updated,
+                WaiverAction::Expiring,
+            ]
+        );
+        assert_eq!(log[0].user, user);
+        assert_eq!(log[1].user, "unpatched-server");
+        assert_eq!(
+            log[3].waiver.expires.timestamp(),
+            (later + Duration::days(2)).timestamp()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apis() {
+        registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into()))
+            .with(fmt::layer())
+            .try_init()
+            .unwrap_or(());
+
+        let pool = create_database("sqlite::memory:").await.unwrap();
+        let claims: Claims = Claims::default();
+        init_database(&pool, None).await.unwrap();
+
+        let payload = waiver("CVE-2024-1", Utc::now() + Duration::days(30));
+        let id = payload.id;
+        let api_post = post_waivers_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(payload.clone()),
+        )
+        .await;
+        assert_eq!(api_post.status(), StatusCode::CREATED);
+
+        let api_invalid = post_waivers_api(
+            claims.clone(),
+            axum::extract::State(pool.clone()),
+            axum::extract::Json(Waiver {
+                justification: " ".into(),
+                ..payload
+            }),
+        )
+        .await;
+        assert_eq!(api_invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let api_get = get_waivers_api(
+            claims.clone(),
+            axum::extract::Query(WaiverQueryParams {
+                active: Some(true),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_get.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_get.into_body()).await.unwrap();
+        let waivers: Vec<Waiver> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(waivers.len(), 1);
+        assert_eq!(waivers[0].created_by, claims.sub.to_string());
+
+        let api_one = get_one_waiver_api(
+            claims.clone(),
+            axum::extract::Path(id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_one.status(), StatusCode::OK);
+
+        let api_delete = delete_one_waiver_api(
+            claims.clone(),
+            axum::extract::Path(id),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_delete.status(), StatusCode::OK);
+
+        let api_audit = get_waiver_audit_api(
+            claims.clone(),
+            axum::extract::Query(AuditQueryParams {
+                waiver_id: Some(id),
+                ..Default::default()
+            }),
+            axum::extract::State(pool.clone()),
+        )
+        .await
+        .into_response();
+        assert_eq!(api_audit.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(api_audit.into_body()).await.unwrap();
+        let log: Vec<WaiverAudit> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].action, WaiverAction::Deleted);
+    }
+}
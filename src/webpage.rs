@@ -50,19 +50,17 @@ pub async fn web_page(uri: Uri) -> Response {
             let maybe_file = crate::WEBPAGE.get_file(path);
             match maybe_file {
                 Some(file) => {
-                    return (StatusCode::OK, header, file.contents_utf8().unwrap()).into_response();
-                }
-                None => {
-                    return (
-                        StatusCode::NOT_FOUND,
-                        header,
-                        crate::WEBPAGE
-                            .get_file("404.html")
-                            .map(|e| e.contents_utf8().unwrap_or_default())
-                            .unwrap_or("404"),
-                    )
-                        .into_response()
+                    (StatusCode::OK, header, file.contents_utf8().unwrap()).into_response()
                 }
+                None => (
+                    StatusCode::NOT_FOUND,
+                    header,
+                    crate::WEBPAGE
+                        .get_file("404.html")
+                        .map(|e| e.contents_utf8().unwrap_or_default())
+                        .unwrap_or("404"),
+                )
+                    .into_response(),
             }
         }
     }